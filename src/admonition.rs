@@ -0,0 +1,141 @@
+use crate::code;
+use crate::shortcode::parse_tag;
+use crate::util::escape;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// The kinds of admonition, as named in `:::note` and `> [!NOTE]`
+const KINDS: [&str; 7] = [
+  "note",
+  "info",
+  "tip",
+  "important",
+  "warning",
+  "caution",
+  "danger",
+];
+
+/// Styles for admonitions, which themes override by restyling the
+/// `admonition` class and its kinds or setting `--admonition-color`
+pub const STYLE: &str = "\
+.admonition { border-left: 4px solid var(--admonition-color, #4078c0); \
+padding: 0 1em; margin: 1em 0; }
+.admonition-title { font-weight: bold; color: var(--admonition-color, #4078c0); }
+.admonition.tip { --admonition-color: #2da44e; }
+.admonition.important { --admonition-color: #8250df; }
+.admonition.warning { --admonition-color: #bf8700; }
+.admonition.caution, .admonition.danger { --admonition-color: #cf222e; }
+";
+
+enum Frame {
+  Admonition,
+  Container,
+}
+
+/// Expand `:::note` containers and `> [!NOTE]` blockquotes into admonitions
+///
+/// Admonitions are titled with their kind, or with a `title="TITLE"` of the
+/// container or the text after the marker of the blockquote.
+pub fn expand(input: &str) -> Result<String> {
+  let mut output = String::with_capacity(input.len());
+  let mut frames: Vec<Frame> = vec![];
+  let mut quoted = false;
+  let mut lines = code::lines(input).peekable();
+
+  while let Some((code, line)) = lines.next() {
+    if code {
+      output.push_str(line);
+      continue;
+    }
+    let trimmed = line.trim();
+
+    // Only the first line of a blockquote can make it an admonition
+    let starts_quote = !quoted && trimmed.starts_with('>');
+    quoted = trimmed.starts_with('>');
+    if let Some((kind, title)) = callout(trimmed).filter(|_| starts_quote) {
+      let mut content = String::new();
+      while let Some((_, line)) =
+        lines.next_if(|(code, line)| !code && line.trim().starts_with('>'))
+      {
+        let line = line.trim_start();
+        let line = line.strip_prefix('>').unwrap_or(line);
+        content.push_str(line.strip_prefix(' ').unwrap_or(line));
+      }
+      quoted = false;
+      output.push_str(&open(&kind, title));
+      output.push_str(&content);
+      output.push_str("\n</div>\n\n");
+      continue;
+    }
+
+    let directive = match trimmed.strip_prefix(":::") {
+      Some(directive) => directive.trim(),
+      None => {
+        output.push_str(line);
+        continue;
+      }
+    };
+    if directive.is_empty() {
+      match frames.pop() {
+        Some(Frame::Admonition) => output.push_str("\n</div>\n\n"),
+        _ => output.push_str(line),
+      }
+      continue;
+    }
+    // Other containers are left to their own steps, whatever their syntax
+    let name = directive.split_whitespace().next().unwrap_or("");
+    match KINDS.contains(&name) {
+      true => {
+        let (name, args) = parse_tag(directive)?;
+        let title = match args.get("title") {
+          Some(Value::String(title)) => Some(title.as_str()),
+          _ => None,
+        };
+        output.push_str(&open(&name, title));
+        frames.push(Frame::Admonition);
+      }
+      false => {
+        output.push_str(line);
+        frames.push(Frame::Container);
+      }
+    }
+  }
+
+  if frames
+    .iter()
+    .any(|frame| matches!(frame, Frame::Admonition))
+  {
+    return Err(anyhow!("Unterminated admonition"));
+  }
+  Ok(output)
+}
+
+/// Whether some HTML has admonitions, and so needs their styles
+pub fn has_admonition(html: &str) -> bool {
+  html.contains("<div class=\"admonition ")
+}
+
+/// The kind and any title of a `> [!NOTE] Title` line
+fn callout(line: &str) -> Option<(String, Option<&str>)> {
+  let marker = line.strip_prefix('>')?.trim_start().strip_prefix("[!")?;
+  let (kind, title) = marker.split_once(']')?;
+  let kind = kind.to_lowercase();
+  let title = Some(title.trim()).filter(|title| !title.is_empty());
+  KINDS.contains(&kind.as_str()).then_some((kind, title))
+}
+
+/// The opening tags and title of an admonition
+fn open(kind: &str, title: Option<&str>) -> String {
+  let title = match title {
+    Some(title) => escape(title),
+    None => {
+      let mut chars = kind.chars();
+      let first = chars.next().map(|c| c.to_uppercase().to_string());
+      first.unwrap_or_default() + chars.as_str()
+    }
+  };
+  format!(
+    "<div class=\"admonition {}\">\n<p class=\"admonition-title\">{}</p>\n\n",
+    kind, title
+  )
+}
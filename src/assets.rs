@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The directory assets are written to, relative to the document
+pub const DIR: &str = "assets";
+
+/// Files emitted alongside a document, named by a hash of their contents
+#[derive(Clone, Default)]
+pub struct Assets {
+  /// The fingerprinted path and contents of each asset, by name
+  files: BTreeMap<String, (String, Vec<u8>)>,
+}
+
+impl Assets {
+  /// Load asset files given on the command line
+  pub fn load(paths: &[String]) -> Result<Assets> {
+    let mut assets = Assets::default();
+    for path in paths {
+      let name = Path::new(path)
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid asset '{}'", path))?
+        .to_string_lossy();
+      assets.add(&name, fs::read(path)?);
+    }
+    Ok(assets)
+  }
+
+  /// Add an asset, returning its fingerprinted path
+  pub fn add(&mut self, name: &str, contents: Vec<u8>) -> String {
+    let path = fingerprint(name, &contents);
+    self.files.insert(name.into(), (path.clone(), contents));
+    path
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.files.is_empty()
+  }
+
+  /// The fingerprinted paths of each asset, for templates
+  pub fn to_json(&self) -> Value {
+    let paths = self
+      .files
+      .iter()
+      .map(|(name, (path, _))| (name.clone(), Value::String(path.clone())))
+      .collect::<Map<_, _>>();
+    Value::Object(paths)
+  }
+
+  /// The path and contents of each asset
+  pub fn files(&self) -> impl Iterator<Item = (&str, &[u8])> {
+    self
+      .files
+      .values()
+      .map(|(path, contents)| (path.as_str(), contents.as_slice()))
+  }
+}
+
+/// A hex encoded 64 bit FNV-1a hash of some contents
+pub fn hash(contents: &[u8]) -> String {
+  let mut hash = 0xcbf29ce484222325u64;
+  for byte in contents {
+    hash ^= *byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  format!("{:016x}", hash)
+}
+
+/// The path of an asset with a hash of its contents in the file name
+fn fingerprint(name: &str, contents: &[u8]) -> String {
+  let hash = hash(contents);
+  match name.rsplit_once('.') {
+    Some((stem, ext)) => format!("{}/{}.{}.{}", DIR, stem, &hash[..10], ext),
+    None => format!("{}/{}.{}", DIR, name, &hash[..10]),
+  }
+}
+
+/// The media type of an asset, from its extension
+pub fn content_type(path: &str) -> &'static str {
+  match path.rsplit_once('.').map(|(_, ext)| ext) {
+    Some("css") => "text/css",
+    Some("js") => "text/javascript",
+    Some("svg") => "image/svg+xml",
+    Some("png") => "image/png",
+    Some("jpg") | Some("jpeg") => "image/jpeg",
+    Some("woff2") => "font/woff2",
+    Some("html") => "text/html",
+    Some("ico") => "image/x-icon",
+    Some("webmanifest") => "application/manifest+json",
+    _ => "application/octet-stream",
+  }
+}
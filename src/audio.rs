@@ -0,0 +1,89 @@
+use crate::diagram::pipe_bytes;
+use crate::{document_name, input_dir, options, Params};
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{Event, Parser, Tag};
+use std::path::Path;
+
+/// The kinds of audio file a note can be read aloud in
+const EXTENSIONS: [&str; 5] = ["mp3", "ogg", "opus", "m4a", "wav"];
+
+/// A text to speech program, reading text on standard input and writing audio
+/// to standard output, such as `--tts "piper --output-raw"`
+pub struct Speech {
+  command: Vec<String>,
+  format: String,
+}
+
+impl Speech {
+  pub fn new(
+    command: Option<String>,
+    format: Option<String>,
+  ) -> Result<Speech> {
+    let command =
+      command.ok_or_else(|| anyhow!("--format audio needs a --tts program"))?;
+    let command = command.split_whitespace().map(String::from).collect();
+    let format = format.unwrap_or_else(|| "mp3".into());
+    if !EXTENSIONS.contains(&format.as_str()) {
+      return Err(anyhow!("Unknown audio format '{}'", format));
+    }
+    Ok(Speech { command, format })
+  }
+
+  /// The extension of the audio files written
+  pub fn format(&self) -> &str {
+    &self.format
+  }
+
+  /// Read a note's text aloud, in the note's directory
+  pub fn read(&self, text: &str, dir: &Path) -> Result<Vec<u8>> {
+    match self.command.split_first() {
+      Some((program, args)) => {
+        pipe_bytes(program, args, dir, text.as_bytes().to_vec())
+      }
+      None => Err(anyhow!("Invalid text to speech program ''")),
+    }
+  }
+}
+
+/// The text of some markdown as it is read aloud
+///
+/// Code blocks and HTML are left out, and headings and list items end with a
+/// pause.
+pub fn text(markdown: &str) -> String {
+  let mut text = String::new();
+  let mut code = false;
+  for event in Parser::new_ext(markdown, options()) {
+    match event {
+      Event::Start(Tag::CodeBlock(_)) => code = true,
+      Event::End(Tag::CodeBlock(_)) => code = false,
+      Event::Text(words) | Event::Code(words) if !code => text.push_str(&words),
+      Event::SoftBreak => text.push(' '),
+      Event::HardBreak => text.push('\n'),
+      Event::End(Tag::Heading(..)) | Event::End(Tag::Item) => {
+        if !text.trim_end().ends_with(['.', '!', '?', ':']) {
+          text.push('.');
+        }
+        text.push_str("\n\n");
+      }
+      Event::End(Tag::Paragraph) | Event::End(Tag::TableRow) => {
+        text.push_str("\n\n")
+      }
+      _ => {}
+    }
+  }
+  text.trim().to_string() + "\n"
+}
+
+/// The audio of a document written beside it, for templates to play
+pub fn beside(params: &Params) -> Option<String> {
+  let dir = match &params.output {
+    Some(output) => Path::new(output).parent().unwrap_or(Path::new("")),
+    None => input_dir(params),
+  };
+  let name = document_name(params);
+  let stem = Path::new(&name).file_stem()?.to_string_lossy().into_owned();
+  EXTENSIONS
+    .iter()
+    .map(|ext| format!("{}.{}", stem, ext))
+    .find(|file| dir.join(file).is_file())
+}
@@ -0,0 +1,143 @@
+use crate::frontmatter;
+use crate::listing;
+use crate::options;
+use crate::paths;
+use crate::people::relative;
+use crate::publish::Schedule;
+use crate::util::escape;
+use crate::vfs::Source;
+use crate::wikilink::WikiLinks;
+use anyhow::Result;
+use pulldown_cmark::{Event, Parser, Tag};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A note linking to another
+struct Link {
+  path: PathBuf,
+  title: String,
+}
+
+/// The notes under a directory that link to each of its notes
+///
+/// Every note is scanned for links before any is rendered, so that each page
+/// can list the notes linking to it. Links are relative links to other notes,
+/// as `.md` or `.html`, and wiki links when they are enabled. Notes that are
+/// not yet published link to nothing.
+pub struct Backlinks {
+  /// The notes linking to each note, by its path
+  links: BTreeMap<String, Vec<Link>>,
+}
+
+impl Backlinks {
+  pub fn collect(
+    source: &dyn Source,
+    root: &Path,
+    schedule: &Schedule,
+    wikilinks: bool,
+  ) -> Result<Backlinks> {
+    let mut links: BTreeMap<String, Vec<Link>> = BTreeMap::new();
+    let mut wikis: BTreeMap<PathBuf, WikiLinks> = BTreeMap::new();
+    for path in source.files(root)? {
+      let is_markdown =
+        path.extension().and_then(|ext| ext.to_str()) == Some("md");
+      if !is_markdown {
+        continue;
+      }
+      let text = source.read_to_string(&path)?;
+      let (meta, body) = frontmatter::split(&text);
+      if !schedule.is_published(&meta)? {
+        continue;
+      }
+      let dir = path.parent().unwrap_or(Path::new(""));
+      let mut targets = targets(body)
+        .into_iter()
+        .map(|target| paths::key(&dir.join(target)))
+        .collect::<Vec<_>>();
+      if wikilinks {
+        if !wikis.contains_key(dir) {
+          wikis.insert(dir.to_path_buf(), WikiLinks::collect(source, dir)?);
+        }
+        let wiki = &wikis[dir];
+        let found = wiki.targets(body).into_iter();
+        targets.extend(found.map(|target| paths::key(&dir.join(target))));
+      }
+      targets.sort();
+      targets.dedup();
+
+      let title =
+        listing::title(&meta, body).unwrap_or_else(|| listing::stem(&path));
+      for target in targets {
+        if target == paths::key(&path) {
+          continue;
+        }
+        links.entry(target).or_default().push(Link {
+          path: path.clone(),
+          title: title.clone(),
+        });
+      }
+    }
+    Ok(Backlinks { links })
+  }
+
+  /// The notes linking to a document, as `title` and `href`
+  pub fn to(&self, document: &Path) -> Vec<Value> {
+    let here = document.parent().unwrap_or(Path::new(""));
+    let links = self.links.get(&paths::key(document));
+    links
+      .into_iter()
+      .flatten()
+      .map(|link| {
+        json!({
+          "title": link.title,
+          "href": relative(here, &link.path.with_extension("html")),
+        })
+      })
+      .collect()
+  }
+}
+
+/// HTML listing the notes linking to a document, if any do
+pub fn html(backlinks: &[Value]) -> Option<String> {
+  if backlinks.is_empty() {
+    return None;
+  }
+  let mut html = String::from("<section class=\"backlinks\">\n");
+  html.push_str("<h2>Linked from</h2>\n<ul>\n");
+  for link in backlinks {
+    let field = |key| link.get(key).and_then(Value::as_str).unwrap_or("");
+    html.push_str(&format!(
+      "<li><a href=\"{}\">{}</a></li>\n",
+      escape(field("href")),
+      escape(field("title"))
+    ));
+  }
+  html.push_str("</ul>\n</section>\n");
+  Some(html)
+}
+
+/// The notes that the relative links of some markdown point at
+fn targets(body: &str) -> Vec<PathBuf> {
+  let mut targets = vec![];
+  for event in Parser::new_ext(body, options()) {
+    let dest = match event {
+      Event::Start(Tag::Link(_, dest, _)) => dest,
+      _ => continue,
+    };
+    let end = dest.find(['#', '?']).unwrap_or(dest.len());
+    let path = &dest[..end];
+    if paths::is_external(path) {
+      continue;
+    }
+    let stem = paths::strip_extension(path, "md")
+      .or_else(|| paths::strip_extension(path, "html"));
+    match stem {
+      Some(stem) if !stem.is_empty() => {
+        targets.push(PathBuf::from(format!("{}.md", paths::slashes(stem))))
+      }
+      _ => {}
+    }
+  }
+  targets
+}
@@ -0,0 +1,46 @@
+use crate::assets::hash;
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Rendered diagrams and the output of code blocks, kept in `.notebook/cache`
+/// beside a document so that unchanged blocks are not rendered again
+///
+/// Entries are keyed by a hash of the kind of block, such as its language,
+/// and its text.
+#[derive(Clone)]
+pub struct Cache {
+  dir: Option<PathBuf>,
+}
+
+impl Cache {
+  /// The cache of a document's directory, or no cache when it is disabled
+  pub fn new(dir: &Path, enabled: bool) -> Cache {
+    let dir = enabled.then(|| dir.join(".notebook").join("cache"));
+    Cache { dir }
+  }
+
+  /// The rendering of a block, from the cache or rendered and cached
+  ///
+  /// Blocks that fail to render are not cached, so they are tried again.
+  pub fn get<F>(&self, kind: &str, text: &str, render: F) -> Result<String>
+  where
+    F: FnOnce() -> Result<String>,
+  {
+    let dir = match &self.dir {
+      Some(dir) => dir,
+      None => return render(),
+    };
+    let key = hash(format!("{}\0{}", kind, text).as_bytes());
+    let path = dir.join(format!("{}.html", key));
+    if let Ok(rendered) = fs::read_to_string(&path) {
+      return Ok(rendered);
+    }
+    let rendered = render()?;
+    // A cache that can't be written only means rendering again next time
+    if fs::create_dir_all(dir).is_ok() {
+      fs::write(&path, &rendered).ok();
+    }
+    Ok(rendered)
+  }
+}
@@ -0,0 +1,123 @@
+use crate::assets;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use warp::http::header::{
+  CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use warp::http::{HeaderMap, HeaderValue, Method, Response, StatusCode};
+use warp::hyper::Body;
+use warp::Reply;
+
+/// `Cache-Control` policies for each kind of route in serve mode
+///
+/// Documents are revalidated on every request, so that edits show up
+/// immediately, while fingerprinted assets never change and are cached for
+/// as long as possible.
+#[derive(Clone)]
+pub struct Policies {
+  pub document: String,
+  pub asset: String,
+  pub file: String,
+  pub revision: String,
+}
+
+impl Policies {
+  /// The default policies, overridden by any `ROUTE=POLICY` rules
+  pub fn parse(rules: &[String]) -> Result<Policies> {
+    let mut policies = Policies {
+      document: "no-cache".into(),
+      asset: "public, max-age=31536000, immutable".into(),
+      file: "no-cache".into(),
+      revision: "no-cache".into(),
+    };
+    for rule in rules {
+      let (route, policy) = rule
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid cache rule '{}'", rule))?;
+      let policy = policy.trim().to_string();
+      if HeaderValue::from_str(&policy).is_err() {
+        return Err(anyhow!("Invalid cache policy '{}'", policy));
+      }
+      match route.trim() {
+        "document" => policies.document = policy,
+        "asset" => policies.asset = policy,
+        "file" => policies.file = policy,
+        "revision" => policies.revision = policy,
+        route => return Err(anyhow!("Unknown route '{}'", route)),
+      }
+    }
+    Ok(policies)
+  }
+}
+
+/// A response with a strong `ETag` derived from its content
+pub fn reply(
+  body: Vec<u8>,
+  content_type: &str,
+  policy: &str,
+) -> warp::reply::Response {
+  let etag = format!("\"{}\"", assets::hash(&body));
+  let mut response = Response::new(Body::from(body));
+  let headers = response.headers_mut();
+  let value = |value: &str| {
+    value
+      .parse()
+      .unwrap_or_else(|_| HeaderValue::from_static(""))
+  };
+  headers.insert(CONTENT_TYPE, value(content_type));
+  headers.insert(CACHE_CONTROL, value(policy));
+  headers.insert(ETAG, value(&etag));
+  response
+}
+
+/// A weak `ETag` of a file from its modification time and size, so that it
+/// needn't be read to be compared
+pub fn file_tag(path: &Path) -> Option<String> {
+  let meta = fs::metadata(path).ok()?;
+  let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+  Some(format!("W/\"{:x}-{:x}\"", modified.as_nanos(), meta.len()))
+}
+
+/// A `304 Not Modified` response in place of a successful one, when a `GET`
+/// request's `If-None-Match` header names its `ETag`
+///
+/// Files served from disk also answer `If-Modified-Since` with their
+/// modification times, before this is asked.
+pub fn conditional(
+  method: Method,
+  headers: HeaderMap,
+  reply: impl Reply,
+) -> warp::reply::Response {
+  let response = reply.into_response();
+  let cached = matches!(method, Method::GET | Method::HEAD)
+    && response.status() == StatusCode::OK
+    && match (headers.get(IF_NONE_MATCH), response.headers().get(ETAG)) {
+      (Some(wanted), Some(etag)) => matches(wanted, etag),
+      _ => false,
+    };
+  if !cached {
+    return response;
+  }
+  let mut not_modified = Response::new(Body::empty());
+  *not_modified.status_mut() = StatusCode::NOT_MODIFIED;
+  for name in [ETAG, CACHE_CONTROL, LAST_MODIFIED] {
+    if let Some(value) = response.headers().get(&name) {
+      not_modified.headers_mut().insert(name, value.clone());
+    }
+  }
+  not_modified
+}
+
+/// Whether an `If-None-Match` header names an `ETag`, ignoring whether
+/// either is weak
+fn matches(wanted: &HeaderValue, etag: &HeaderValue) -> bool {
+  let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+  let etag = strip(etag.to_str().unwrap_or(""));
+  wanted
+    .to_str()
+    .unwrap_or("")
+    .split(',')
+    .any(|tag| tag.trim() == "*" || strip(tag) == etag)
+}
@@ -0,0 +1,216 @@
+use crate::assets::hash;
+use crate::code;
+use crate::frontmatter;
+use crate::listing;
+use crate::publish::Schedule;
+use crate::util::{escape, today};
+use crate::vfs::Source;
+use anyhow::Result;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// The path the calendar is served at
+pub const PATH: &str = "calendar.ics";
+
+const OPEN: &str = "{^event:";
+const CLOSE: &str = "}";
+
+/// Front matter keys holding dates that belong in the calendar
+const KEYS: [&str; 5] = ["event", "due", "deadline", "review", "expires"];
+
+/// A dated item found in a note
+pub struct Event {
+  /// A date, `YYYY-MM-DD`, or a local time, `YYYY-MM-DDTHH:MM`
+  date: String,
+  summary: String,
+  path: PathBuf,
+}
+
+/// Collect the events of the notes under a directory, or of a single note
+///
+/// Events are the dates of a note's `event`, `due`, `deadline`, `review` and
+/// `expires` front matter, and inline `{^event:DATE summary}` annotations.
+/// Notes not yet published are left out.
+pub fn events(
+  source: &dyn Source,
+  root: &Path,
+  schedule: &Schedule,
+) -> Result<Vec<Event>> {
+  let files = match root.extension().and_then(|ext| ext.to_str()) {
+    Some("md") => vec![root.to_path_buf()],
+    _ => source.files(root)?,
+  };
+
+  let mut events = vec![];
+  for path in files {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+      continue;
+    }
+    let text = source.read_to_string(&path)?;
+    let (meta, body) = frontmatter::split(&text);
+    if !schedule.is_published(&meta)? {
+      continue;
+    }
+    let title = listing::title(&meta, body).unwrap_or_else(|| {
+      let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+      stem.unwrap_or_default().into_owned()
+    });
+
+    for key in KEYS {
+      if let Some(date) = meta.get(key).and_then(Value::as_str).and_then(parse)
+      {
+        let summary = match key {
+          "event" => title.clone(),
+          _ => format!("{} ({})", title, key),
+        };
+        events.push(Event {
+          date,
+          summary,
+          path: path.clone(),
+        });
+      }
+    }
+
+    for (date, summary) in annotations(body) {
+      events.push(Event {
+        date,
+        summary,
+        path: path.clone(),
+      });
+    }
+  }
+  Ok(events)
+}
+
+/// Validate a date or local time, dropping any seconds or time zone
+fn parse(text: &str) -> Option<String> {
+  let text = text.trim();
+  let is_date = |date: &str| {
+    date.len() == 10
+      && date.char_indices().all(|(i, c)| match i {
+        4 | 7 => c == '-',
+        _ => c.is_ascii_digit(),
+      })
+  };
+  let date = text.get(..10).filter(|date| is_date(date))?;
+  let time = text
+    .get(10..)
+    .and_then(|rest| rest.strip_prefix('T').or_else(|| rest.strip_prefix(' ')))
+    .and_then(|time| time.get(..5))
+    .filter(|time| {
+      time.char_indices().all(|(i, c)| match i {
+        2 => c == ':',
+        _ => c.is_ascii_digit(),
+      })
+    });
+  match time {
+    Some(time) => Some(format!("{}T{}", date, time)),
+    None => Some(date.into()),
+  }
+}
+
+/// Find the `{^event:DATE summary}` annotations outside of code
+fn annotations(input: &str) -> Vec<(String, String)> {
+  let mut found = vec![];
+  expand_with(input, |date, summary| {
+    found.push((date.to_string(), summary.to_string()));
+    String::new()
+  });
+  found
+}
+
+/// Replace event annotations outside of code with the date and summary they
+/// mark
+pub fn expand(input: &str) -> String {
+  expand_with(input, |date, summary| {
+    format!(
+      "<span class=\"event\"><time datetime=\"{}\">{}</time> {}</span>",
+      escape(date),
+      escape(&date.replace('T', " ")),
+      escape(summary)
+    )
+  })
+}
+
+fn expand_with<F>(input: &str, mut replace: F) -> String
+where
+  F: FnMut(&str, &str) -> String,
+{
+  code::map_prose(input, |text| {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+      let annotation = &rest[start + OPEN.len()..];
+      let end = match annotation.find(CLOSE) {
+        Some(end) => end,
+        None => break,
+      };
+      let (date, summary) = annotation[..end]
+        .trim()
+        .split_once(' ')
+        .unwrap_or((annotation[..end].trim(), ""));
+      output.push_str(&rest[..start]);
+      match parse(date) {
+        Some(date) => output.push_str(&replace(&date, summary.trim())),
+        None => output.push_str(&rest[start..start + OPEN.len() + end + 1]),
+      }
+      rest = &annotation[end + CLOSE.len()..];
+    }
+    output.push_str(rest);
+    output
+  })
+}
+
+/// An iCalendar feed of events
+pub fn render(events: &[Event]) -> String {
+  let stamp = format!("{}T000000Z", today().replace('-', ""));
+  let mut lines = vec![
+    "BEGIN:VCALENDAR".to_string(),
+    "VERSION:2.0".into(),
+    "PRODID:-//notebook//calendar//EN".into(),
+  ];
+  for event in events {
+    let path = event.path.to_string_lossy().replace('\\', "/");
+    let path = path.trim_start_matches("./");
+    let uid =
+      hash(format!("{}\n{}\n{}", path, event.date, event.summary).as_bytes());
+    let date = event.date.replace(['-', ':'], "");
+    lines.push("BEGIN:VEVENT".into());
+    lines.push(format!("UID:{}@notebook", uid));
+    lines.push(format!("DTSTAMP:{}", stamp));
+    match date.contains('T') {
+      true => lines.push(format!("DTSTART:{}00", date)),
+      false => lines.push(format!("DTSTART;VALUE=DATE:{}", date)),
+    }
+    lines.push(format!("SUMMARY:{}", text(&event.summary)));
+    lines.push(format!("DESCRIPTION:{}", text(path)));
+    lines.push("END:VEVENT".into());
+  }
+  lines.push("END:VCALENDAR".into());
+  lines.iter().map(|line| fold(line)).collect()
+}
+
+/// Escape an iCalendar text value
+fn text(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace(';', "\\;")
+    .replace(',', "\\,")
+    .replace('\n', "\\n")
+}
+
+/// Fold a content line into lines of at most 75 octets
+fn fold(line: &str) -> String {
+  let mut folded = String::new();
+  let mut length = 0;
+  for c in line.chars() {
+    if length + c.len_utf8() > 75 {
+      folded.push_str("\r\n ");
+      length = 1;
+    }
+    folded.push(c);
+    length += c.len_utf8();
+  }
+  folded.push_str("\r\n");
+  folded
+}
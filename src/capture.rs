@@ -0,0 +1,144 @@
+use crate::util::{date, same_secret};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Route that snippets are posted to, under `/api`
+pub const PATH: &str = "capture";
+
+/// Appends snippets posted to the web server to an inbox or the daily note
+///
+/// Snippets are added as list items stamped with the time, in UTC, to a note
+/// under the notebook, or to the `YYYY-MM-DD.md` note of the day when the
+/// target is `daily`. Notes are created when they don't exist yet.
+#[derive(Clone)]
+pub struct Capture {
+  target: String,
+  token: String,
+  lock: Arc<Mutex<()>>,
+}
+
+impl Capture {
+  pub fn new(target: &str, token: Option<String>) -> Result<Capture> {
+    let token = match token {
+      Some(token) if !token.is_empty() => token,
+      _ => return Err(anyhow!("--capture needs a --capture-token")),
+    };
+    let relative = Path::new(target);
+    let escapes = relative.is_absolute()
+      || relative.components().any(|c| c.as_os_str() == "..");
+    if target != "daily" && (escapes || !target.ends_with(".md")) {
+      return Err(anyhow!("Invalid capture note '{}'", target));
+    }
+    Ok(Capture {
+      target: target.into(),
+      token,
+      lock: Arc::new(Mutex::new(())),
+    })
+  }
+
+  /// Whether a request's `Authorization` header gives the bearer token
+  pub fn authorized(&self, authorization: Option<&str>) -> bool {
+    let token = authorization.and_then(|header| header.strip_prefix("Bearer "));
+    token.is_some_and(|token| {
+      same_secret(token.trim().as_bytes(), self.token.as_bytes())
+    })
+  }
+
+  /// Append a snippet to the note, returning the note's path
+  ///
+  /// The body is the snippet as plain text, or JSON with its `text` and an
+  /// optional `url` it came from, which must be an `http` or `https` URL that
+  /// can be written as an autolink.
+  pub fn append(&self, dir: &Path, body: &[u8], json: bool) -> Result<PathBuf> {
+    let (text, url) = match json {
+      true => {
+        let body: Value = serde_json::from_slice(body)?;
+        let field = |key| body.get(key).and_then(Value::as_str);
+        let text = field("text").unwrap_or_default().to_string();
+        (text, field("url").map(String::from))
+      }
+      false => (String::from_utf8(body.to_vec())?, None),
+    };
+    if let Some(url) = url.as_deref().filter(|url| !is_autolink(url)) {
+      return Err(anyhow!("Invalid capture url '{}'", url));
+    }
+    let text = text.trim();
+    if text.is_empty() && url.is_none() {
+      return Err(anyhow!("Nothing to capture"));
+    }
+
+    let seconds = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs())
+      .unwrap_or(0) as i64;
+    let day = date(seconds.div_euclid(86400));
+    let time = format!(
+      "{:02}:{:02}",
+      seconds.rem_euclid(86400) / 3600,
+      seconds.rem_euclid(3600) / 60
+    );
+    let (path, heading, stamp) = match self.target.as_str() {
+      "daily" => (dir.join(format!("{}.md", day)), day.clone(), time),
+      target => (
+        dir.join(target),
+        "Inbox".into(),
+        format!("{} {}", day, time),
+      ),
+    };
+
+    // Later lines of a snippet stay within its list item
+    let mut item = format!("- {}", stamp);
+    for (i, line) in text.lines().enumerate() {
+      match i {
+        0 => item.push(' '),
+        _ => item.push_str("\n  "),
+      }
+      item.push_str(line);
+    }
+    if let Some(url) = url {
+      item.push_str(&format!(" <{}>", url));
+    }
+    item.push('\n');
+
+    let _lock = self.lock.lock().expect("Capture lock");
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    let existing = fs::read_to_string(&path).ok();
+    let mut file = fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&path)?;
+    match existing {
+      None => write!(file, "# {}\n\n", heading)?,
+      Some(text) if !text.is_empty() && !text.ends_with('\n') => {
+        writeln!(file)?
+      }
+      Some(_) => {}
+    }
+    file.write_all(item.as_bytes())?;
+    Ok(path)
+  }
+}
+
+/// Whether a URL is `http` or `https` and can be written in markdown as an
+/// autolink, `<URL>`, without ending it early or spilling into the note
+fn is_autolink(url: &str) -> bool {
+  let web = match url.split_once("://") {
+    Some((scheme, rest)) => {
+      !rest.is_empty()
+        && (scheme.eq_ignore_ascii_case("http")
+          || scheme.eq_ignore_ascii_case("https"))
+    }
+    None => false,
+  };
+  web
+    && !url
+      .chars()
+      .any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>')
+}
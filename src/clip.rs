@@ -0,0 +1,695 @@
+use crate::links::{self, resolve, unescape};
+use crate::util::{decode_base64, heading, slugify, today};
+use anyhow::{anyhow, Result};
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{ElementContentHandlers, HtmlRewriter, Selector, Settings};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Marks the start and end of a page's main content while converting it
+const START: char = '\u{e000}';
+const END: char = '\u{e001}';
+
+/// Options shared by the capture commands
+fn options() -> getopts::Options {
+  let mut opts = getopts::Options::new();
+  opts.optopt("d", "dir", "directory to file the note in", "DIR");
+  opts
+}
+
+/// Save a web page as a note, `notebook clip URL`
+pub fn clip(args: &[String]) -> Result<()> {
+  let opts = options();
+  let matches = opts.parse(args)?;
+  let url = match matches.free.first() {
+    Some(url) => url,
+    None => {
+      let brief = "Usage: notebook clip URL [options]";
+      print!("{}", opts.usage(brief));
+      return Ok(());
+    }
+  };
+  if !url.starts_with("https://") && !url.starts_with("http://") {
+    return Err(anyhow!(
+      "Only http and https pages can be clipped '{}'",
+      url
+    ));
+  }
+
+  let output = Command::new("curl")
+    .args(["-sSfL", "--max-time", "30", url])
+    .output()
+    .map_err(|err| anyhow!("Failed to run 'curl': {}", err))?;
+  if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(anyhow!("Failed to fetch '{}': {}", url, stderr.trim()));
+  }
+  let html = String::from_utf8_lossy(&output.stdout);
+
+  let body = markdown(&html, Some(url))?;
+  let page = links::metadata(&html);
+  let field = |key: &str| page.get(key).and_then(Value::as_str);
+  let title = field("title")
+    .map(String::from)
+    .or_else(|| heading(&body).map(String::from))
+    .unwrap_or_else(|| url.clone());
+
+  let mut meta = Map::new();
+  meta.insert("title".into(), title.clone().into());
+  meta.insert("source".into(), url.as_str().into());
+  meta.insert("date".into(), today().into());
+  if let Some(description) = field("description") {
+    meta.insert("description".into(), description.into());
+  }
+  let path = file(matches.opt_str("dir"), &title, meta, &body, &[])?;
+  println!("{}", path.display());
+  Ok(())
+}
+
+/// Save emails as notes, `notebook ingest eml FILE` or the unread emails of a
+/// mailbox, `notebook ingest imap URL`
+pub fn ingest(args: &[String]) -> Result<()> {
+  let mut opts = options();
+  opts.optopt(
+    "",
+    "user",
+    "IMAP user, with the password in NOTEBOOK_IMAP_PASSWORD",
+    "USER",
+  );
+  opts.optopt("", "every", "check the mailbox every N seconds", "N");
+  let matches = opts.parse(args)?;
+  match (
+    matches.free.first().map(String::as_str),
+    matches.free.get(1),
+  ) {
+    (Some("eml"), Some(path)) => {
+      let text =
+        String::from_utf8_lossy(&fs::read(path)?).replace("\r\n", "\n");
+      let path = email(&text, path, matches.opt_str("dir"))?;
+      println!("{}", path.display());
+      Ok(())
+    }
+    (Some("imap"), Some(url)) => imap(url, &matches),
+    (Some(kind), Some(_)) => Err(anyhow!("Unknown ingest type '{}'", kind)),
+    _ => {
+      let brief = "Usage: notebook ingest eml FILE [options]
+       notebook ingest imap URL [options]";
+      print!("{}", opts.usage(brief));
+      Ok(())
+    }
+  }
+}
+
+/// Save an email as a note, with its attachments in a directory beside it
+fn email(text: &str, path: &str, dir: Option<String>) -> Result<PathBuf> {
+  let message = Message::parse(text);
+  let body = message.body()?;
+  let title = message
+    .header("subject")
+    .map(decode_words)
+    .filter(|subject| !subject.trim().is_empty())
+    .unwrap_or_else(|| "Untitled email".into());
+
+  let mut meta = Map::new();
+  meta.insert("title".into(), title.clone().into());
+  if let Some(from) = message.header("from") {
+    meta.insert("from".into(), decode_words(from).into());
+  }
+  let source = match message.header("message-id") {
+    Some(id) => format!(
+      "mid:{}",
+      id.trim().trim_start_matches('<').trim_end_matches('>')
+    ),
+    None => path.into(),
+  };
+  meta.insert("source".into(), source.into());
+  let date = message.header("date").and_then(email_date);
+  meta.insert("date".into(), date.unwrap_or_else(today).into());
+  file(dir, &title, meta, &body, &message.attachments())
+}
+
+/// Save the unread emails of an IMAP mailbox as notes, once or every so often
+///
+/// Mailboxes are read with curl, from URLs such as
+/// `imaps://mail.example.com/INBOX`, with the credentials of `--user` or else
+/// of `~/.netrc`. The password is given to curl on its standard input, never
+/// in its arguments, where other users could see it. An email is marked as
+/// read once its note is written, so each is saved once, and is left unread
+/// when it can't be saved.
+fn imap(url: &str, matches: &getopts::Matches) -> Result<()> {
+  if !url.starts_with("imaps://") && !url.starts_with("imap://") {
+    return Err(anyhow!(
+      "Only imap and imaps mailboxes can be read '{}'",
+      url
+    ));
+  }
+  let url = url.trim_end_matches('/');
+  let auth = Auth(matches.opt_str("user").map(|user| {
+    let password = env::var("NOTEBOOK_IMAP_PASSWORD").unwrap_or_default();
+    format!("{}:{}", user, password)
+  }));
+  let every: Option<u64> = matches.opt_get("every")?;
+  loop {
+    let polled = poll(url, &auth, matches.opt_str("dir"));
+    let every = match every {
+      Some(every) => every,
+      None => return polled,
+    };
+    // A mailbox that can't be reached is tried again next time
+    if let Err(err) = polled {
+      eprintln!("Error: {}", err);
+    }
+    thread::sleep(Duration::from_secs(every));
+  }
+}
+
+/// Save each unread email of a mailbox as a note
+fn poll(url: &str, auth: &Auth, dir: Option<String>) -> Result<()> {
+  let search = curl(url, auth, &["--request", "UID SEARCH UNSEEN"])?;
+  let search = String::from_utf8_lossy(&search);
+  let uids = search
+    .lines()
+    .filter_map(|line| line.strip_prefix("* SEARCH"))
+    .flat_map(str::split_whitespace)
+    .filter(|uid| uid.bytes().all(|byte| byte.is_ascii_digit()));
+  for uid in uids {
+    // curl fetches the whole of a message by `BODY[]`, which marks it as
+    // read, as `BODY.PEEK[]` wouldn't, so its flag is set by the outcome
+    let message = format!("{}/;UID={}", url, uid);
+    let saved = curl(&message, auth, &[]).and_then(|text| {
+      let text = String::from_utf8_lossy(&text).replace("\r\n", "\n");
+      email(&text, &message, dir.clone())
+    });
+    let flag = match saved {
+      Ok(_) => format!("UID STORE {} +FLAGS (\\Seen)", uid),
+      Err(_) => format!("UID STORE {} -FLAGS (\\Seen)", uid),
+    };
+    let stored = curl(url, auth, &["--request", &flag]);
+    let path = saved?;
+    stored?;
+    println!("{}", path.display());
+  }
+  Ok(())
+}
+
+/// The credentials of a mailbox, as `USER:PASSWORD`, or none to read them
+/// from `~/.netrc`
+struct Auth(Option<String>);
+
+impl Auth {
+  /// The credentials as a curl config, to be read from standard input
+  fn config(&self) -> Option<String> {
+    let user = self.0.as_ref()?;
+    let mut quoted = String::with_capacity(user.len());
+    for c in user.chars() {
+      match c {
+        '"' | '\\' => quoted.extend(['\\', c]),
+        '\n' => quoted.push_str("\\n"),
+        '\r' => quoted.push_str("\\r"),
+        '\t' => quoted.push_str("\\t"),
+        c => quoted.push(c),
+      }
+    }
+    Some(format!("user = \"{}\"\n", quoted))
+  }
+}
+
+/// Run curl on a URL, returning what it read
+fn curl(url: &str, auth: &Auth, args: &[&str]) -> Result<Vec<u8>> {
+  let config = auth.config();
+  let mut command = Command::new("curl");
+  command
+    .args(["-sS", "--max-time", "60"])
+    .args(args)
+    .arg(url);
+  match config {
+    Some(_) => command.args(["--config", "-"]).stdin(Stdio::piped()),
+    None => command.arg("--netrc-optional").stdin(Stdio::null()),
+  };
+  let mut child = command
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|err| anyhow!("Failed to run 'curl': {}", err))?;
+  if let (Some(config), Some(mut stdin)) = (config, child.stdin.take()) {
+    stdin.write_all(config.as_bytes())?;
+  }
+  let output = child.wait_with_output()?;
+  if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(anyhow!("Failed to read '{}': {}", url, stderr.trim()));
+  }
+  Ok(output.stdout)
+}
+
+/// Write a note named after its title, without replacing an existing note,
+/// with any attachments in a directory of the same name
+fn file(
+  dir: Option<String>,
+  title: &str,
+  meta: Map<String, Value>,
+  body: &str,
+  attachments: &[(String, Vec<u8>)],
+) -> Result<PathBuf> {
+  let dir = PathBuf::from(dir.unwrap_or_else(|| ".".into()));
+  fs::create_dir_all(&dir)?;
+  let slug = match slugify(title) {
+    slug if slug.is_empty() => "clipping".to_string(),
+    slug => slug,
+  };
+  let path = available(&dir, &slug);
+
+  // Attachments are listed at the end of the note, images shown inline
+  let mut body = body.to_string();
+  if !attachments.is_empty() {
+    let folder = path.with_extension("");
+    fs::create_dir_all(&folder)?;
+    let name = folder.file_name().unwrap_or_default().to_string_lossy();
+    body.push_str("\n## Attachments\n\n");
+    for (file, contents) in attachments {
+      fs::write(folder.join(file), contents)?;
+      let image = file.rsplit_once('.').is_some_and(|(_, ext)| {
+        let ext = ext.to_lowercase();
+        matches!(
+          ext.as_str(),
+          "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp"
+        )
+      });
+      let link = format!("[{}](<{}/{}>)", file, name, file);
+      match image {
+        true => body.push_str(&format!("* !{}\n", link)),
+        false => body.push_str(&format!("* {}\n", link)),
+      }
+    }
+  }
+  let front = serde_yaml::to_string(&Value::Object(meta))?;
+  fs::write(&path, format!("---\n{}---\n\n{}", front, body))?;
+  Ok(path)
+}
+
+/// The path of a new note named from a slug, numbered when the slug is taken
+pub fn available(dir: &Path, slug: &str) -> PathBuf {
+  let mut path = dir.join(format!("{}.md", slug));
+  let mut n = 1;
+  while path.exists() {
+    n += 1;
+    path = dir.join(format!("{}-{}.md", slug, n));
+  }
+  path
+}
+
+/// Convert the readable content of an HTML page to markdown
+///
+/// The page's first `article`, or else its `main` element, is kept when it
+/// has one, and navigation, scripts, forms and the like are dropped. Links and
+/// images are resolved against the page's URL.
+pub fn markdown(html: &str, url: Option<&str>) -> Result<String> {
+  let content = ["article", "main"]
+    .iter()
+    .find(|tag| html.contains(&format!("<{}", tag)));
+
+  let resolve = |href: &str| match url {
+    Some(url) => resolve(url, href),
+    None => href.to_string(),
+  };
+  let around = |before: &'static str, after: &'static str| {
+    ElementContentHandlers::default().element(move |el: &mut Element| {
+      el.before(before, ContentType::Text);
+      el.after(after, ContentType::Text);
+      el.remove_and_keep_content();
+      Ok(())
+    })
+  };
+  let mut handlers = vec![
+    (
+      "head, script, style, noscript, template, nav, header, footer, aside, \
+       form, iframe, svg, button",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        el.remove();
+        Ok(())
+      }),
+    ),
+    ("h1", around("\n\n# ", "\n\n")),
+    ("h2", around("\n\n## ", "\n\n")),
+    ("h3", around("\n\n### ", "\n\n")),
+    ("h4", around("\n\n#### ", "\n\n")),
+    ("h5, h6", around("\n\n##### ", "\n\n")),
+    (
+      "article, main, p, div, section, blockquote, tr, figure",
+      around("\n\n", "\n\n"),
+    ),
+    ("ul, ol", around("\n\n", "\n\n")),
+    ("ul > li", around("\n- ", "")),
+    ("ol > li", around("\n1. ", "")),
+    ("strong, b", around("**", "**")),
+    ("em, i", around("*", "*")),
+    (":not(pre) > code", around("`", "`")),
+    ("pre", around("\n\n```\n", "\n```\n\n")),
+    ("pre > code", around("", "")),
+    ("td, th", around(" ", " ")),
+    (
+      "br",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        el.replace("\n\n", ContentType::Text);
+        Ok(())
+      }),
+    ),
+    (
+      "hr",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        el.replace("\n\n---\n\n", ContentType::Text);
+        Ok(())
+      }),
+    ),
+    (
+      "a",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        let href = el.get_attribute("href").unwrap_or_default();
+        if !href.is_empty()
+          && !href.starts_with('#')
+          && !href.starts_with("javascript:")
+        {
+          el.before("[", ContentType::Text);
+          el.after(&format!("]({})", resolve(&href)), ContentType::Text);
+        }
+        el.remove_and_keep_content();
+        Ok(())
+      }),
+    ),
+    (
+      "img",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        let alt = el.get_attribute("alt").unwrap_or_default();
+        match el.get_attribute("src") {
+          Some(src) => el.replace(
+            &format!("![{}]({})", alt, resolve(&src)),
+            ContentType::Text,
+          ),
+          None => el.remove(),
+        }
+        Ok(())
+      }),
+    ),
+  ];
+  if let Some(tag) = content {
+    let mut found = false;
+    let marker =
+      ElementContentHandlers::default().element(move |el: &mut Element| {
+        if !found {
+          found = true;
+          el.before(&START.to_string(), ContentType::Text);
+          el.after(&END.to_string(), ContentType::Text);
+        }
+        Ok(())
+      });
+    handlers.insert(0, (tag, marker));
+  }
+
+  let mut settings = Settings::new();
+  for (selector, handler) in handlers {
+    let selector = selector.parse::<Selector>()?;
+    settings =
+      settings.append_element_content_handler((Cow::Owned(selector), handler));
+  }
+  let mut output = vec![];
+  let mut rewriter =
+    HtmlRewriter::new(settings, |chunk: &[u8]| output.extend_from_slice(chunk));
+  rewriter.write(html.as_bytes())?;
+  rewriter.end()?;
+  let output = String::from_utf8(output)?;
+
+  let output = match (output.find(START), output.find(END)) {
+    (Some(start), Some(end)) if start < end => {
+      &output[start + START.len_utf8()..end]
+    }
+    _ => output.as_str(),
+  };
+  Ok(tidy(&unescape(output)))
+}
+
+/// Trim the lines of converted markdown and collapse runs of blank lines,
+/// leaving code blocks as they are
+fn tidy(markdown: &str) -> String {
+  let mut output = String::new();
+  let mut fenced = false;
+  let mut blank = true;
+  for line in markdown.lines() {
+    if line.trim() == "```" {
+      fenced = !fenced;
+    }
+    // Whitespace from the layout of the HTML is collapsed outside of code
+    let line = match fenced {
+      true => line.trim_end().to_string(),
+      false => line.split_whitespace().collect::<Vec<_>>().join(" "),
+    };
+    if line.is_empty() && !fenced {
+      if !blank {
+        output.push('\n');
+      }
+      blank = true;
+      continue;
+    }
+    output.push_str(&line);
+    output.push('\n');
+    blank = false;
+  }
+  output.trim_end().to_string() + "\n"
+}
+
+/// A MIME message or part, its headers and body
+struct Message<'a> {
+  headers: Vec<(String, String)>,
+  body: &'a str,
+}
+
+impl<'a> Message<'a> {
+  fn parse(text: &'a str) -> Message<'a> {
+    let (head, body) = match text.find("\n\n") {
+      Some(end) => (&text[..end], &text[end + 2..]),
+      None => (text, ""),
+    };
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in head.lines() {
+      // Folded header lines continue the previous header
+      if line.starts_with([' ', '\t']) {
+        if let Some((_, value)) = headers.last_mut() {
+          value.push(' ');
+          value.push_str(line.trim());
+        }
+        continue;
+      }
+      if let Some((name, value)) = line.split_once(':') {
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+      }
+    }
+    Message { headers, body }
+  }
+
+  fn header(&self, name: &str) -> Option<&str> {
+    let header = self.headers.iter().find(|(header, _)| header == name);
+    header.map(|(_, value)| value.as_str())
+  }
+
+  /// A parameter of a header, such as the `boundary` of a `Content-Type`
+  fn param(&self, header: &str, name: &str) -> Option<String> {
+    self.header(header)?.split(';').skip(1).find_map(|param| {
+      let (key, value) = param.split_once('=')?;
+      match key.trim().eq_ignore_ascii_case(name) {
+        true => Some(value.trim().trim_matches('"').to_string()),
+        false => None,
+      }
+    })
+  }
+
+  fn content_type(&self) -> String {
+    let content_type = self.header("content-type").unwrap_or("text/plain");
+    let content_type = content_type.split(';').next().unwrap_or("");
+    content_type.trim().to_lowercase()
+  }
+
+  /// The text of the message as markdown, preferring a plain text part
+  fn body(&self) -> Result<String> {
+    let parts = self.parts();
+    let plain = parts
+      .iter()
+      .find(|part| part.content_type() == "text/plain");
+    let html = parts.iter().find(|part| part.content_type() == "text/html");
+    match (plain, html) {
+      (Some(part), _) => Ok(part.decoded().trim().to_string() + "\n"),
+      (None, Some(part)) => markdown(&part.decoded(), None),
+      (None, None) => Err(anyhow!("Email has no text or HTML body")),
+    }
+  }
+
+  /// The leaf parts of a message, itself when it is not multipart
+  fn parts(&self) -> Vec<Message<'a>> {
+    let boundary = match self.param("content-type", "boundary") {
+      Some(boundary) if self.content_type().starts_with("multipart/") => {
+        format!("--{}", boundary)
+      }
+      _ => {
+        return vec![Message {
+          headers: self.headers.clone(),
+          body: self.body,
+        }]
+      }
+    };
+    let mut parts = vec![];
+    for part in self.body.split(boundary.as_str()).skip(1) {
+      // The closing boundary is followed by `--`
+      if part.starts_with("--") {
+        break;
+      }
+      let part = part.strip_prefix('\n').unwrap_or(part);
+      parts.extend(Message::parse(part).parts());
+    }
+    parts
+  }
+
+  /// The files attached to a message, by their file names
+  fn attachments(&self) -> Vec<(String, Vec<u8>)> {
+    let mut attachments: Vec<(String, Vec<u8>)> = vec![];
+    for part in self.parts() {
+      let attached = part.header("content-disposition").is_some_and(|value| {
+        value.trim().to_lowercase().starts_with("attachment")
+      });
+      let name = part
+        .param("content-disposition", "filename")
+        .or_else(|| part.param("content-type", "name"));
+      let number = attachments.len() + 1;
+      let name = match name {
+        Some(name) => decode_words(&name),
+        None if attached => format!("attachment-{}", number),
+        None => continue,
+      };
+      // Only the file name, so that attachments stay in their directory
+      let name = name.rsplit(['/', '\\']).next().unwrap_or("").trim();
+      let name = match name {
+        "" | "." | ".." => format!("attachment-{}", number),
+        name if attachments.iter().any(|(file, _)| file == name) => {
+          format!("{}-{}", number, name)
+        }
+        name => name.to_string(),
+      };
+      attachments.push((name, part.bytes()));
+    }
+    attachments
+  }
+
+  /// The body with its transfer encoding removed
+  fn bytes(&self) -> Vec<u8> {
+    let encoding = self.header("content-transfer-encoding").unwrap_or("");
+    match encoding.trim().to_lowercase().as_str() {
+      "base64" => decode_base64(self.body),
+      "quoted-printable" => quoted_printable(self.body, false),
+      _ => self.body.as_bytes().to_vec(),
+    }
+  }
+
+  /// The body as text, with its transfer encoding removed
+  fn decoded(&self) -> String {
+    String::from_utf8_lossy(&self.bytes()).into_owned()
+  }
+}
+
+/// Decode quoted-printable text, with underscores as spaces in headers
+fn quoted_printable(text: &str, header: bool) -> Vec<u8> {
+  let mut bytes = vec![];
+  let input = text.as_bytes();
+  let mut i = 0;
+  while i < input.len() {
+    match input[i] {
+      b'=' if input.get(i + 1) == Some(&b'\n') => i += 2,
+      b'=' => {
+        let hex = std::str::from_utf8(input.get(i + 1..i + 3).unwrap_or(&[]))
+          .ok()
+          .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+          Some(byte) => {
+            bytes.push(byte);
+            i += 3;
+          }
+          None => {
+            bytes.push(b'=');
+            i += 1;
+          }
+        }
+      }
+      b'_' if header => {
+        bytes.push(b' ');
+        i += 1;
+      }
+      byte => {
+        bytes.push(byte);
+        i += 1;
+      }
+    }
+  }
+  bytes
+}
+
+/// Decode the `=?charset?B?...?=` encoded words of a header
+fn decode_words(value: &str) -> String {
+  let mut output = String::new();
+  let mut rest = value;
+  while let Some(start) = rest.find("=?") {
+    let word = &rest[start + 2..];
+    let mut fields = word.splitn(3, '?');
+    let (_, encoding, text) =
+      match (fields.next(), fields.next(), fields.next()) {
+        (Some(charset), Some(encoding), Some(text)) => {
+          (charset, encoding, text)
+        }
+        _ => break,
+      };
+    let end = match text.find("?=") {
+      Some(end) => end,
+      None => break,
+    };
+    let bytes = match encoding {
+      "B" | "b" => decode_base64(&text[..end]),
+      "Q" | "q" => quoted_printable(&text[..end], true),
+      _ => break,
+    };
+    // Whitespace between adjacent encoded words is not part of the text
+    let before = &rest[..start];
+    let adjacent = before.trim().is_empty() && !output.is_empty();
+    if !adjacent {
+      output.push_str(before);
+    }
+    output.push_str(&String::from_utf8_lossy(&bytes));
+    let consumed = rest.len() - text.len() + end + 2;
+    rest = &rest[consumed..];
+  }
+  output.push_str(rest);
+  output
+}
+
+/// The date of an email's `Date` header, such as `Tue, 5 Mar 2024 10:00:00`
+fn email_date(value: &str) -> Option<String> {
+  const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct",
+    "nov", "dec",
+  ];
+  let words = value
+    .split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|word| !word.is_empty())
+    .collect::<Vec<_>>();
+  let i = words.iter().position(|word| {
+    MONTHS.contains(&word.to_lowercase().get(..3).unwrap_or(""))
+  })?;
+  let month = MONTHS
+    .iter()
+    .position(|month| Some(*month) == words[i].to_lowercase().get(..3))?
+    + 1;
+  let day: u32 = words.get(i.checked_sub(1)?)?.parse().ok()?;
+  let year: u32 = words.get(i + 1)?.parse().ok()?;
+  Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
@@ -0,0 +1,330 @@
+/// Lines of a document, each with whether it is code, within a fenced code
+/// block, its fences included, or an indented code block
+pub fn lines(input: &str) -> impl Iterator<Item = (bool, &str)> {
+  let mut blocks = Blocks::default();
+  input
+    .split_inclusive('\n')
+    .map(move |line| (blocks.is_code(line), line))
+}
+
+/// A document with some function applied to its text outside of code blocks
+/// and code spans, a piece at a time within each line
+pub fn map_prose(input: &str, mut f: impl FnMut(&str) -> String) -> String {
+  let mut output = String::with_capacity(input.len());
+  for (code, line) in lines(input) {
+    match code {
+      true => output.push_str(line),
+      false => output.push_str(&map_line(line, &mut f)),
+    }
+  }
+  output
+}
+
+/// A document with some function applied to its text outside of code, link
+/// destinations and HTML tags, for replacing words that would break a URL or
+/// an attribute
+pub fn map_text(input: &str, mut f: impl FnMut(&str) -> String) -> String {
+  map_prose(input, |text| {
+    let mut output = String::with_capacity(text.len());
+    for (markup, piece) in markup(text) {
+      match markup {
+        true => output.push_str(piece),
+        false => output.push_str(&f(piece)),
+      }
+    }
+    output
+  })
+}
+
+/// A line with some function applied to its text outside of code spans
+pub fn map_line(line: &str, mut f: impl FnMut(&str) -> String) -> String {
+  let mut output = String::with_capacity(line.len());
+  for (code, piece) in spans(line) {
+    match code {
+      true => output.push_str(piece),
+      false => output.push_str(&f(piece)),
+    }
+  }
+  output
+}
+
+/// A document with its code blocks and code spans set aside behind
+/// placeholders, along with the code they stand for
+///
+/// This is for syntax that may span lines of prose, such as paired
+/// shortcodes, which `map_prose` would see a line at a time. The code is put
+/// back with `restore`.
+pub fn set_aside(input: &str) -> (String, Vec<String>) {
+  let mut output = String::with_capacity(input.len());
+  let mut code = vec![];
+  let mut hide = |text: &str, output: &mut String| {
+    output.push_str(&format!("{}{}{}", HIDDEN.0, code.len(), HIDDEN.1));
+    code.push(text.to_string());
+  };
+  for (fenced, line) in lines(input) {
+    let (text, end) = match line.strip_suffix('\n') {
+      Some(text) => (text, "\n"),
+      None => (line, ""),
+    };
+    match fenced {
+      true => hide(text, &mut output),
+      false => {
+        for (span, piece) in spans(text) {
+          match span {
+            true => hide(piece, &mut output),
+            false => output.push_str(piece),
+          }
+        }
+      }
+    }
+    output.push_str(end);
+  }
+  (output, code)
+}
+
+/// Put back the code set aside from a document by `set_aside`
+pub fn restore(text: &str, code: &[String]) -> String {
+  let mut output = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find(HIDDEN.0) {
+    output.push_str(&rest[..start]);
+    let after = &rest[start + HIDDEN.0.len_utf8()..];
+    let placeholder = after.split_once(HIDDEN.1).and_then(|(index, tail)| {
+      Some((code.get(index.parse::<usize>().ok()?)?, tail))
+    });
+    match placeholder {
+      Some((code, tail)) => {
+        output.push_str(code);
+        rest = tail;
+      }
+      None => {
+        output.push(HIDDEN.0);
+        rest = after;
+      }
+    }
+  }
+  output.push_str(rest);
+  output
+}
+
+/// The characters around the placeholders of code set aside, from Unicode's
+/// private use area, which text doesn't use
+const HIDDEN: (char, char) = ('\u{e000}', '\u{e001}');
+
+/// The pieces of a line, each with whether it is a code span
+fn spans(line: &str) -> Vec<(bool, &str)> {
+  let mut pieces = vec![];
+  let mut prose = 0;
+  let mut i = 0;
+  while let Some(found) = line[i..].find('`') {
+    let start = i + found;
+    let length = ticks(&line[start..]);
+    i = start + length;
+    if line[..start].ends_with('\\') {
+      continue;
+    }
+    // A span ends at the next run of as many backticks, else the run is text
+    let mut j = i;
+    while let Some(found) = line[j..].find('`') {
+      let end = j + found;
+      let closing = ticks(&line[end..]);
+      j = end + closing;
+      if closing == length {
+        pieces.push((false, &line[prose..start]));
+        pieces.push((true, &line[start..j]));
+        prose = j;
+        i = j;
+        break;
+      }
+    }
+  }
+  pieces.push((false, &line[prose..]));
+  pieces
+}
+
+/// The pieces of some text, each with whether it is a link's `](destination)`
+/// or an HTML tag
+pub fn markup(text: &str) -> Vec<(bool, &str)> {
+  let mut pieces = vec![];
+  let mut prose = 0;
+  let mut i = 0;
+  while let Some(found) = text[i..].find(['<', ']']) {
+    let start = i + found;
+    let rest = &text[start..];
+    let end = match rest.starts_with("](") {
+      true => rest.find(')').map(|end| end + 1),
+      false => {
+        let tag = rest[1..].starts_with(|c: char| {
+          c.is_ascii_alphabetic() || c == '/' || c == '!'
+        });
+        rest.find('>').filter(|_| tag).map(|end| end + 1)
+      }
+    };
+    match end {
+      Some(end) => {
+        pieces.push((false, &text[prose..start]));
+        pieces.push((true, &rest[..end]));
+        prose = start + end;
+        i = prose;
+      }
+      None => i = start + 1,
+    }
+  }
+  pieces.push((false, &text[prose..]));
+  pieces
+}
+
+/// Tracks the code blocks of a document, a line at a time
+#[derive(Default)]
+pub struct Blocks {
+  /// The character and length of the fence of an open fenced block
+  fence: Option<(char, usize)>,
+  /// Whether the last line was in a paragraph, which indented lines continue
+  paragraph: bool,
+  /// Whether the last line that wasn't blank was indented code
+  indented: bool,
+  /// Whether the lines are within a list, whose items indent their content
+  list: bool,
+}
+
+impl Blocks {
+  /// Whether the next line of the document is code
+  pub fn is_code(&mut self, line: &str) -> bool {
+    if let Some((mark, length)) = self.fence {
+      let closes = fence(line).is_some_and(|(closing, count, info)| {
+        closing == mark && count >= length && info.trim().is_empty()
+      });
+      if closes {
+        self.fence = None;
+      }
+      return true;
+    }
+
+    if line.trim().is_empty() {
+      self.paragraph = false;
+      return false;
+    }
+    let indent = indentation(line);
+    if indent >= 4 && (self.indented || !self.paragraph && !self.list) {
+      self.indented = true;
+      return true;
+    }
+    self.indented = false;
+
+    if let Some((mark, length, _)) = fence(line) {
+      self.fence = Some((mark, length));
+      self.paragraph = false;
+      return true;
+    }
+
+    let trimmed = line.trim_start();
+    if indent < 4 && is_list_item(trimmed) {
+      self.list = true;
+    } else if indent == 0 {
+      self.list = false;
+    }
+    self.paragraph = !trimmed.starts_with('#');
+    false
+  }
+
+  /// Whether the lines so far leave a fenced code block open
+  pub fn is_fenced(&self) -> bool {
+    self.fence.is_some()
+  }
+}
+
+/// The character, length and info string of a code fence
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+  if indentation(line) > 3 {
+    return None;
+  }
+  let trimmed = line.trim_start();
+  let mark = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+  let length = trimmed.chars().take_while(|c| *c == mark).count();
+  let info = &trimmed[length..];
+  // Backtick fences can't have backticks in their info string
+  let valid = length >= 3 && !(mark == '`' && info.contains('`'));
+  valid.then_some((mark, length, info))
+}
+
+/// The number of backticks at the start of some text
+fn ticks(text: &str) -> usize {
+  text.bytes().take_while(|byte| *byte == b'`').count()
+}
+
+/// The columns of the indentation of a line, with tab stops every four
+fn indentation(line: &str) -> usize {
+  let mut columns = 0;
+  for c in line.chars() {
+    match c {
+      ' ' => columns += 1,
+      '\t' => columns += 4 - columns % 4,
+      _ => break,
+    }
+  }
+  columns
+}
+
+fn is_list_item(line: &str) -> bool {
+  let marker = line.starts_with(['-', '*', '+']) && line[1..].starts_with(' ');
+  let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+  let numbered = (1..10).contains(&digits)
+    && line[digits..].starts_with(['.', ')'])
+    && line[digits + 1..].starts_with(' ');
+  marker || numbered
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn code(input: &str) -> Vec<bool> {
+    lines(input).map(|(code, _)| code).collect()
+  }
+
+  #[test]
+  fn fences_close_only_with_as_long_a_fence_of_their_kind() {
+    let input = "text\n````md\n```\n~~~\n````\ntext\n";
+    assert_eq!(code(input), [false, true, true, true, true, false]);
+  }
+
+  #[test]
+  fn indented_code_follows_a_blank_line_outside_lists() {
+    let input = "para\n    more\n\n    code\n\n    code\n- item\n\n    item\n";
+    assert_eq!(
+      code(input),
+      [false, false, false, true, false, true, false, false, false]
+    );
+  }
+
+  #[test]
+  fn code_set_aside_is_restored() {
+    let input = "a `b` c\n```\nd\n```\ne \u{e000}9\u{e001}\n";
+    let (text, code) = set_aside(input);
+    assert_eq!(
+      text,
+      "a \u{e000}0\u{e001} c\n\u{e000}1\u{e001}\n\u{e000}2\u{e001}\n\
+       \u{e000}3\u{e001}\ne \u{e000}9\u{e001}\n"
+    );
+    assert_eq!(restore(&text, &code), input);
+  }
+
+  #[test]
+  fn code_spans_are_left_alone() {
+    let upper = |text: &str| text.to_uppercase();
+    assert_eq!(
+      map_line("a `b` c ``d ` e`` f", upper),
+      "A `b` C ``d ` e`` F"
+    );
+    assert_eq!(map_line("a ``b` c", upper), "A ``B` C");
+  }
+
+  #[test]
+  fn link_destinations_and_tags_are_left_alone() {
+    let upper = |text: &str| text.to_uppercase();
+    assert_eq!(
+      map_text("[a](b.md) <i title=\"c\">d</i> e < f `g`\n", upper),
+      "[A](b.md) <i title=\"c\">D</i> E < F `g`\n"
+    );
+  }
+}
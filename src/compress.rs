@@ -0,0 +1,589 @@
+use crate::util::crc32;
+use anyhow::{anyhow, Result};
+use warp::http::header::{
+  HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+  ETAG, VARY,
+};
+use warp::http::{HeaderMap, Response, StatusCode};
+use warp::hyper::body::{to_bytes, Body};
+
+/// Responses smaller than this aren't worth compressing
+const MINIMUM: usize = 1024;
+
+/// The furthest back a match is looked for, the most deflate allows
+const WINDOW: usize = 32 * 1024;
+
+/// The most earlier positions of the same three bytes tried for a match
+const CHAIN: usize = 64;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// A response compressed with gzip, when the request accepts it and it is
+/// text worth compressing, such as HTML with large inline SVGs
+///
+/// Brotli isn't offered, there being no encoder for it here, and browsers
+/// that accept it all accept gzip too.
+pub async fn respond(
+  headers: HeaderMap,
+  response: Response<Body>,
+) -> Response<Body> {
+  let accepted = headers
+    .get(ACCEPT_ENCODING)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(accepts_gzip);
+  let compressible = response.status() == StatusCode::OK
+    && !response.headers().contains_key(CONTENT_ENCODING)
+    && response
+      .headers()
+      .get(CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .is_some_and(is_text);
+  if !accepted || !compressible {
+    return response;
+  }
+
+  let (mut parts, body) = response.into_parts();
+  let body = match to_bytes(body).await {
+    Ok(body) => body,
+    Err(_) => return Response::from_parts(parts, Body::empty()),
+  };
+  parts
+    .headers
+    .append(VARY, HeaderValue::from_static("accept-encoding"));
+  if body.len() < MINIMUM {
+    return Response::from_parts(parts, Body::from(body));
+  }
+
+  // The compressed bytes differ, but mean the same as the uncompressed
+  if let Some(etag) = parts.headers.get(ETAG).and_then(|v| v.to_str().ok()) {
+    if !etag.starts_with("W/") {
+      if let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag)) {
+        parts.headers.insert(ETAG, weak);
+      }
+    }
+  }
+  parts.headers.remove(CONTENT_LENGTH);
+  parts
+    .headers
+    .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+  Response::from_parts(parts, Body::from(gzip(&body)))
+}
+
+/// Whether an `Accept-Encoding` header accepts gzip, not refused with `q=0`
+fn accepts_gzip(header: &str) -> bool {
+  header.split(',').any(|coding| {
+    let mut parts = coding.split(';').map(str::trim);
+    let name = parts.next().unwrap_or("");
+    let refused = parts.any(|param| {
+      param
+        .strip_prefix("q=")
+        .and_then(|q| q.parse::<f32>().ok())
+        .is_some_and(|q| q == 0.0)
+    });
+    matches!(name, "gzip" | "x-gzip" | "*") && !refused
+  })
+}
+
+/// Whether a media type is text, which compresses well, rather than an image
+/// or font that is compressed already
+fn is_text(content_type: &str) -> bool {
+  let kind = content_type.split(';').next().unwrap_or("").trim();
+  kind.starts_with("text/") && kind != "text/event-stream"
+    || matches!(
+      kind,
+      "application/json"
+        | "application/javascript"
+        | "application/xml"
+        | "application/atom+xml"
+        | "application/manifest+json"
+        | "image/svg+xml"
+    )
+}
+
+/// Compress some bytes as a gzip stream
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+  let mut output = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+  output.extend(deflate(data));
+  output.extend_from_slice(&crc32(data).to_le_bytes());
+  output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+  output
+}
+
+/// Compress some bytes as a single deflate block, of the fixed Huffman codes
+/// and repeats found within the window
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+  let mut bits = Bits::default();
+  // The final block, with fixed codes
+  bits.write(1, 1);
+  bits.write(1, 2);
+
+  // The last position of each hash of three bytes, and the position before
+  // each with the same hash
+  let mut head = vec![usize::MAX; 1 << 15];
+  let mut previous = vec![usize::MAX; WINDOW];
+  let hash = |i: usize| {
+    let key = (data[i] as usize) << 10
+      ^ (data[i + 1] as usize) << 5
+      ^ data[i + 2] as usize;
+    key & 0x7fff
+  };
+  let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+    if i + MIN_MATCH <= data.len() {
+      let key = hash(i);
+      previous[i % WINDOW] = head[key];
+      head[key] = i;
+    }
+  };
+
+  let mut i = 0;
+  while i < data.len() {
+    let (mut length, mut distance) = (0, 0);
+    if i + MIN_MATCH <= data.len() {
+      let mut candidate = head[hash(i)];
+      let mut tries = 0;
+      while candidate != usize::MAX && i - candidate <= WINDOW && tries < CHAIN
+      {
+        let longest = (data.len() - i).min(MAX_MATCH);
+        let found = (0..longest)
+          .take_while(|&n| data[candidate + n] == data[i + n])
+          .count();
+        if found > length {
+          length = found;
+          distance = i - candidate;
+          if found == longest {
+            break;
+          }
+        }
+        let next = previous[candidate % WINDOW];
+        if next == usize::MAX || next >= candidate {
+          break;
+        }
+        candidate = next;
+        tries += 1;
+      }
+    }
+
+    if length >= MIN_MATCH {
+      bits.length(length);
+      bits.distance(distance);
+      for position in i..i + length {
+        insert(position, &mut head, &mut previous);
+      }
+      i += length;
+    } else {
+      bits.literal(data[i] as u16);
+      insert(i, &mut head, &mut previous);
+      i += 1;
+    }
+  }
+  bits.literal(256);
+  bits.finish()
+}
+
+/// The first lengths of each length code, from 257, and its extra bits
+const LENGTHS: [(u16, u8); 29] = [
+  (3, 0),
+  (4, 0),
+  (5, 0),
+  (6, 0),
+  (7, 0),
+  (8, 0),
+  (9, 0),
+  (10, 0),
+  (11, 1),
+  (13, 1),
+  (15, 1),
+  (17, 1),
+  (19, 2),
+  (23, 2),
+  (27, 2),
+  (31, 2),
+  (35, 3),
+  (43, 3),
+  (51, 3),
+  (59, 3),
+  (67, 4),
+  (83, 4),
+  (99, 4),
+  (115, 4),
+  (131, 5),
+  (163, 5),
+  (195, 5),
+  (227, 5),
+  (258, 0),
+];
+
+/// The first distances of each distance code, and its extra bits
+const DISTANCES: [(u16, u8); 30] = [
+  (1, 0),
+  (2, 0),
+  (3, 0),
+  (4, 0),
+  (5, 1),
+  (7, 1),
+  (9, 2),
+  (13, 2),
+  (17, 3),
+  (25, 3),
+  (33, 4),
+  (49, 4),
+  (65, 5),
+  (97, 5),
+  (129, 6),
+  (193, 6),
+  (257, 7),
+  (385, 7),
+  (513, 8),
+  (769, 8),
+  (1025, 9),
+  (1537, 9),
+  (2049, 10),
+  (3073, 10),
+  (4097, 11),
+  (6145, 11),
+  (8193, 12),
+  (12289, 12),
+  (16385, 13),
+  (24577, 13),
+];
+
+/// Bits written from the least significant bit of each byte, as deflate
+/// packs them
+#[derive(Default)]
+struct Bits {
+  bytes: Vec<u8>,
+  buffer: u32,
+  count: u8,
+}
+
+impl Bits {
+  /// Write the lowest bits of a value, least significant first
+  fn write(&mut self, value: u32, count: u8) {
+    self.buffer |= value << self.count;
+    self.count += count;
+    while self.count >= 8 {
+      self.bytes.push(self.buffer as u8);
+      self.buffer >>= 8;
+      self.count -= 8;
+    }
+  }
+
+  /// Write a Huffman code, which is packed most significant bit first
+  fn code(&mut self, code: u32, count: u8) {
+    let reversed = code.reverse_bits() >> (32 - count);
+    self.write(reversed, count);
+  }
+
+  /// Write a literal byte, or the end of the block, in the fixed codes
+  fn literal(&mut self, value: u16) {
+    let value = value as u32;
+    match value {
+      0..=143 => self.code(0x30 + value, 8),
+      144..=255 => self.code(0x190 + value - 144, 9),
+      256..=279 => self.code(value - 256, 7),
+      _ => self.code(0xc0 + value - 280, 8),
+    }
+  }
+
+  fn length(&mut self, length: usize) {
+    let index = LENGTHS
+      .iter()
+      .rposition(|&(base, _)| base as usize <= length)
+      .unwrap_or(0);
+    let (base, extra) = LENGTHS[index];
+    self.literal(257 + index as u16);
+    self.write(length as u32 - base as u32, extra);
+  }
+
+  fn distance(&mut self, distance: usize) {
+    let index = DISTANCES
+      .iter()
+      .rposition(|&(base, _)| base as usize <= distance)
+      .unwrap_or(0);
+    let (base, extra) = DISTANCES[index];
+    self.code(index as u32, 5);
+    self.write(distance as u32 - base as u32, extra);
+  }
+
+  fn finish(mut self) -> Vec<u8> {
+    if self.count > 0 {
+      self.bytes.push(self.buffer as u8);
+    }
+    self.bytes
+  }
+}
+
+/// The order the lengths of the code length codes are given in, in a block
+/// with dynamic codes
+const CODE_LENGTH_ORDER: [usize; 19] = [
+  16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompress a raw deflate stream, of stored blocks or blocks with fixed or
+/// dynamic codes, as the entries of zip archives are, failing once it grows
+/// past a limit rather than filling memory
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+  let mut input = Input {
+    bytes: data,
+    at: 0,
+    bit: 0,
+    limit,
+  };
+  let mut output = vec![];
+  loop {
+    let last = input.bits(1)? == 1;
+    match input.bits(2)? {
+      0 => input.stored(&mut output)?,
+      1 => {
+        let mut lengths = [8; 288];
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        let literals = Huffman::new(&lengths);
+        let distances = Huffman::new(&[5; 30]);
+        input.codes(&literals, &distances, &mut output)?;
+      }
+      2 => {
+        let (literals, distances) = input.dynamic()?;
+        input.codes(&literals, &distances, &mut output)?;
+      }
+      _ => return Err(invalid()),
+    }
+    if last {
+      return Ok(output);
+    }
+  }
+}
+
+fn invalid() -> anyhow::Error {
+  anyhow!("Invalid deflate stream")
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the
+/// symbols in the order of their codes
+struct Huffman {
+  counts: [u16; 16],
+  symbols: Vec<u16>,
+}
+
+impl Huffman {
+  /// The code of the lengths of the codes of each symbol, with `0` for
+  /// symbols that aren't used
+  fn new(lengths: &[u8]) -> Huffman {
+    let mut counts = [0; 16];
+    for &length in lengths {
+      counts[length as usize] += 1;
+    }
+    counts[0] = 0;
+    let mut symbols = (0..lengths.len() as u16)
+      .filter(|&symbol| lengths[symbol as usize] != 0)
+      .collect::<Vec<_>>();
+    symbols.sort_by_key(|&symbol| lengths[symbol as usize]);
+    Huffman { counts, symbols }
+  }
+}
+
+/// Bits read from the least significant bit of each byte, as deflate packs
+/// them
+struct Input<'a> {
+  bytes: &'a [u8],
+  at: usize,
+  bit: u8,
+  /// The most bytes the output may have
+  limit: usize,
+}
+
+impl<'a> Input<'a> {
+  /// Read a value of some bits, least significant first
+  fn bits(&mut self, count: u8) -> Result<u32> {
+    let mut value = 0;
+    for i in 0..count {
+      let byte = *self.bytes.get(self.at).ok_or_else(invalid)?;
+      value |= ((byte >> self.bit) as u32 & 1) << i;
+      self.bit += 1;
+      if self.bit == 8 {
+        self.bit = 0;
+        self.at += 1;
+      }
+    }
+    Ok(value)
+  }
+
+  /// Read a symbol of a Huffman code, whose codes are packed most
+  /// significant bit first
+  fn symbol(&mut self, huffman: &Huffman) -> Result<u16> {
+    let (mut code, mut first, mut index) = (0, 0, 0);
+    for &count in &huffman.counts[1..] {
+      code |= self.bits(1)? as i32;
+      let count = count as i32;
+      if code - first < count {
+        return Ok(huffman.symbols[(index + code - first) as usize]);
+      }
+      index += count;
+      first = (first + count) << 1;
+      code <<= 1;
+    }
+    Err(invalid())
+  }
+
+  /// Copy a stored block, which starts at the next byte
+  fn stored(&mut self, output: &mut Vec<u8>) -> Result<()> {
+    if self.bit > 0 {
+      self.bit = 0;
+      self.at += 1;
+    }
+    let header = self.bytes.get(self.at..self.at + 4).ok_or_else(invalid)?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+      return Err(invalid());
+    }
+    let start = self.at + 4;
+    let end = start + length as usize;
+    self.reserve(output, length as usize)?;
+    output.extend_from_slice(self.bytes.get(start..end).ok_or_else(invalid)?);
+    self.at = end;
+    Ok(())
+  }
+
+  /// Fail when some more bytes would take the output past its limit
+  fn reserve(&self, output: &[u8], more: usize) -> Result<()> {
+    match output.len() + more > self.limit {
+      true => Err(anyhow!("Deflate stream is over {} bytes", self.limit)),
+      false => Ok(()),
+    }
+  }
+
+  /// Read the codes of a block with dynamic codes, which are themselves
+  /// coded by the lengths of their codes
+  fn dynamic(&mut self) -> Result<(Huffman, Huffman)> {
+    let literals = self.bits(5)? as usize + 257;
+    let distances = self.bits(5)? as usize + 1;
+    let code_lengths = self.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+      return Err(invalid());
+    }
+    let mut lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+      lengths[symbol] = self.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+      let (length, repeat) = match self.symbol(&code)? {
+        symbol @ 0..=15 => (symbol as u8, 1),
+        16 => {
+          let previous = *lengths.last().ok_or_else(invalid)?;
+          (previous, 3 + self.bits(2)?)
+        }
+        17 => (0, 3 + self.bits(3)?),
+        _ => (0, 11 + self.bits(7)?),
+      };
+      lengths.extend((0..repeat).map(|_| length));
+    }
+    if lengths.len() > literals + distances || lengths[256] == 0 {
+      return Err(invalid());
+    }
+    Ok((
+      Huffman::new(&lengths[..literals]),
+      Huffman::new(&lengths[literals..]),
+    ))
+  }
+
+  /// Decode the literals and repeats of a block, until its end
+  fn codes(
+    &mut self,
+    literals: &Huffman,
+    distances: &Huffman,
+    output: &mut Vec<u8>,
+  ) -> Result<()> {
+    loop {
+      let symbol = self.symbol(literals)? as usize;
+      if symbol < 256 {
+        self.reserve(output, 1)?;
+        output.push(symbol as u8);
+        continue;
+      }
+      if symbol == 256 {
+        return Ok(());
+      }
+      let (base, extra) = *LENGTHS.get(symbol - 257).ok_or_else(invalid)?;
+      let length = base as usize + self.bits(extra)? as usize;
+      let symbol = self.symbol(distances)? as usize;
+      let (base, extra) = *DISTANCES.get(symbol).ok_or_else(invalid)?;
+      let distance = base as usize + self.bits(extra)? as usize;
+      if distance > output.len() {
+        return Err(invalid());
+      }
+      self.reserve(output, length)?;
+      let start = output.len() - distance;
+      for i in 0..length {
+        output.push(output[start + i]);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn gzip_matches_what_zlib_reads_back() {
+    // As `zlib.decompress(data, -15)` reads back "hello, hello, hello", with
+    // the second and third "hello" as one back reference
+    let deflated = [203, 72, 205, 201, 201, 215, 81, 64, 166, 0];
+    assert_eq!(deflate(b"hello, hello, hello"), deflated);
+
+    let data = gzip(b"hello, hello, hello");
+    assert_eq!(data[..10], [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+    assert_eq!(data[10..20], deflated);
+    assert_eq!(data[20..24], 0x09caa19fu32.to_le_bytes());
+    assert_eq!(data[24..], 19u32.to_le_bytes());
+  }
+
+  #[test]
+  fn only_text_for_clients_accepting_gzip_is_compressed() {
+    assert!(accepts_gzip("deflate, gzip;q=0.5"));
+    assert!(!accepts_gzip("gzip;q=0, br"));
+    assert!(is_text("text/html; charset=utf-8"));
+    assert!(!is_text("image/png"));
+  }
+
+  #[test]
+  fn inflate_reverses_deflate() {
+    let text = "a note, a note, a longer note and a note again\n".repeat(50);
+    assert_eq!(
+      inflate(&deflate(text.as_bytes()), usize::MAX).unwrap(),
+      text.as_bytes()
+    );
+    assert_eq!(inflate(&deflate(b""), usize::MAX).unwrap(), b"");
+  }
+
+  #[test]
+  fn inflate_reads_stored_and_dynamic_blocks() {
+    // A stored block, as compressors write incompressible data
+    let stored = [1, 5, 0, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+    assert_eq!(inflate(&stored, usize::MAX).unwrap(), b"hello");
+    // As `zlib` compresses it, with codes of its own
+    let dynamic = [
+      0x15, 0xc7, 0x41, 0x11, 0x00, 0x20, 0x00, 0xc3, 0x30, 0x2b, 0xb3, 0xd6,
+      0xf6, 0xf0, 0x6f, 0x01, 0xc8, 0x2f, 0xe8, 0x62, 0x14, 0xca, 0x81, 0xdc,
+      0xab, 0xf1, 0x5d,
+    ];
+    assert_eq!(
+      inflate(&dynamic, usize::MAX).unwrap(),
+      b"abb ca accabbaeaacb a abcaaaaa"
+    );
+    assert!(inflate(&[0xff], usize::MAX).is_err());
+  }
+
+  #[test]
+  fn inflate_stops_at_its_limit() {
+    let text = "a".repeat(10_000);
+    let data = deflate(text.as_bytes());
+    assert_eq!(inflate(&data, 10_000).unwrap(), text.as_bytes());
+    assert!(inflate(&data, 9_999).is_err());
+  }
+}
@@ -0,0 +1,131 @@
+use crate::code;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Variables that `:::if` blocks are evaluated against
+pub struct Conditions {
+  variables: BTreeMap<String, String>,
+}
+
+enum Frame {
+  /// A conditional block, with whether its current branch is included
+  If { included: bool, taken: bool },
+  /// Any other `:::` container, which is passed through untouched
+  Container,
+}
+
+impl Conditions {
+  /// Create conditions from the scalar values in a document's front matter
+  pub fn new(meta: &Value) -> Conditions {
+    let mut variables = BTreeMap::new();
+    if let Value::Object(meta) = meta {
+      for (key, value) in meta {
+        let value = match value {
+          Value::String(value) => value.clone(),
+          Value::Bool(_) | Value::Number(_) => value.to_string(),
+          _ => continue,
+        };
+        variables.insert(key.clone(), value);
+      }
+    }
+    Conditions { variables }
+  }
+
+  pub fn set(&mut self, key: &str, value: &str) {
+    self.variables.insert(key.into(), value.into());
+  }
+
+  /// Evaluate one of `key=a,b`, `key!=a,b`, `key` or `!key`
+  fn eval(&self, condition: &str) -> bool {
+    let condition = condition.trim();
+    if let Some(i) = condition.find("!=") {
+      return !self.matches(&condition[..i], &condition[i + 2..]);
+    }
+    if let Some(i) = condition.find('=') {
+      return self.matches(&condition[..i], &condition[i + 1..]);
+    }
+    if let Some(key) = condition.strip_prefix('!') {
+      return !self.truthy(key);
+    }
+    self.truthy(condition)
+  }
+
+  fn matches(&self, key: &str, values: &str) -> bool {
+    match self.variables.get(key.trim()) {
+      Some(value) => values.split(',').any(|v| v.trim() == value),
+      None => false,
+    }
+  }
+
+  fn truthy(&self, key: &str) -> bool {
+    match self.variables.get(key.trim()) {
+      Some(value) => !matches!(value.as_str(), "" | "false" | "0"),
+      None => false,
+    }
+  }
+
+  /// Remove the excluded branches of `:::if` / `:::else` blocks
+  pub fn apply(&self, input: &str) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut frames: Vec<Frame> = vec![];
+
+    for (code, line) in code::lines(input) {
+      let included = frames.iter().all(|frame| match frame {
+        Frame::If { included, .. } => *included,
+        Frame::Container => true,
+      });
+
+      let directive = match line.trim().strip_prefix(":::") {
+        Some(directive) if !code => directive.trim(),
+        _ => {
+          if included {
+            output.push_str(line);
+          }
+          continue;
+        }
+      };
+
+      if let Some(condition) = directive.strip_prefix("if ") {
+        let included = self.eval(condition);
+        frames.push(Frame::If {
+          included,
+          taken: included,
+        });
+        continue;
+      }
+
+      match (directive, frames.last_mut()) {
+        ("else", Some(Frame::If { included, taken })) => {
+          *included = !*taken;
+          *taken = true;
+          continue;
+        }
+        ("", Some(Frame::If { .. })) | ("endif", Some(Frame::If { .. })) => {
+          frames.pop();
+          continue;
+        }
+        ("", Some(Frame::Container)) => {
+          frames.pop();
+        }
+        ("", None) => {}
+        ("else", _) | ("endif", _) => {
+          return Err(anyhow!(
+            "Unexpected ':::{}' outside of ':::if'",
+            directive
+          ))
+        }
+        _ => frames.push(Frame::Container),
+      }
+
+      if included {
+        output.push_str(line);
+      }
+    }
+
+    if frames.iter().any(|frame| matches!(frame, Frame::If { .. })) {
+      return Err(anyhow!("Unterminated ':::if' block"));
+    }
+    Ok(output)
+  }
+}
@@ -0,0 +1,114 @@
+use crate::paths;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// The file name of a notebook's configuration
+pub const FILE: &str = "notebook.toml";
+
+/// Options whose values are paths, found from the configuration's directory
+const PATHS: [&str; 15] = [
+  "output",
+  "archive",
+  "template",
+  "layouts",
+  "strings",
+  "data",
+  "glossary",
+  "prelude",
+  "shortcodes",
+  "hyphenation",
+  "asset",
+  "assets",
+  "icon",
+  "snapshot",
+  "vault",
+];
+
+/// The configuration of the input's directory, or of the nearest directory
+/// above it
+pub fn find(input: &str) -> Option<PathBuf> {
+  let input = Path::new(input);
+  let start = match input.is_dir() {
+    true => input,
+    false => input.parent().unwrap_or(Path::new("")),
+  };
+  let start = match start.as_os_str().is_empty() {
+    true => Path::new("."),
+    false => start,
+  };
+  let start = paths::canonicalize(start)?;
+  start
+    .ancestors()
+    .map(|dir| dir.join(FILE))
+    .find(|path| path.is_file())
+}
+
+/// Command line arguments for the options of a configuration that aren't
+/// given on the command line, which overrides them, or unused by what it asks
+/// for
+///
+/// Keys are the long names of options, with `-` or `_` between words. Flags
+/// are set with `true`, options given more than once with an array, and
+/// `NAME=VALUE` options, such as `filter` or `runner`, with a table.
+pub fn args(
+  path: &Path,
+  given: &getopts::Matches,
+  unused: &[&str],
+) -> Result<Vec<String>> {
+  let text = fs::read_to_string(path)?;
+  let table = toml::from_str::<toml::Table>(&text)
+    .map_err(|err| anyhow!("{} in '{}'", err, path.display()))?;
+  let dir = path.parent().unwrap_or(Path::new(""));
+
+  let mut args = vec![];
+  for (key, value) in table {
+    let name = key.replace('_', "-");
+    if !given.opt_defined(&name) {
+      return Err(anyhow!("Unknown option '{}' in '{}'", key, path.display()));
+    }
+    if given.opt_present(&name) || unused.contains(&name.as_str()) {
+      continue;
+    }
+    let invalid = || anyhow!("Invalid '{}' in '{}'", key, path.display());
+    let values = match value {
+      Value::Array(values) => values,
+      Value::Table(table) => {
+        let mut values = vec![];
+        for (name, value) in table {
+          let value = scalar(value).ok_or_else(invalid)?;
+          values.push(Value::String(format!("{}={}", name, value)));
+        }
+        values
+      }
+      value => vec![value],
+    };
+    for value in values {
+      match value {
+        Value::Boolean(true) => args.push(format!("--{}", name)),
+        Value::Boolean(false) => {}
+        value => {
+          let value = scalar(value).ok_or_else(invalid)?;
+          let value = match PATHS.contains(&name.as_str()) {
+            true => dir.join(value).to_string_lossy().into_owned(),
+            false => value,
+          };
+          args.push(format!("--{}={}", name, value));
+        }
+      }
+    }
+  }
+  Ok(args)
+}
+
+/// The text of a string, number or date value
+fn scalar(value: Value) -> Option<String> {
+  match value {
+    Value::String(text) => Some(text),
+    Value::Integer(n) => Some(n.to_string()),
+    Value::Float(n) => Some(n.to_string()),
+    Value::Datetime(date) => Some(date.to_string()),
+    _ => None,
+  }
+}
@@ -0,0 +1,31 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// Load data files into a map keyed by each file's stem
+pub fn load(paths: &[String]) -> Result<Value> {
+  let mut data = Map::new();
+  for path in paths {
+    let path = Path::new(path);
+    let name = match path.file_stem() {
+      Some(stem) => stem.to_string_lossy().to_string(),
+      None => return Err(anyhow!("Invalid data file '{}'", path.display())),
+    };
+    data.insert(name, parse(path)?);
+  }
+  Ok(Value::Object(data))
+}
+
+/// Parse a JSON, TOML or YAML file based on its extension
+pub fn parse(path: &Path) -> Result<Value> {
+  let text = fs::read_to_string(path)?;
+  let extension = path.extension().and_then(|ext| ext.to_str());
+  let value = match extension {
+    Some("json") => serde_json::from_str(&text)?,
+    Some("toml") => toml::from_str(&text)?,
+    Some("yaml") | Some("yml") => serde_yaml::from_str(&text)?,
+    _ => return Err(anyhow!("Unknown data format '{}'", path.display())),
+  };
+  Ok(value)
+}
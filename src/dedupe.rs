@@ -0,0 +1,205 @@
+use crate::frontmatter;
+use crate::util::markdown_files;
+use anyhow::{anyhow, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// The number of words in each shingle
+const SHINGLE: usize = 4;
+
+/// The number of hashes in each note's signature
+const HASHES: u64 = 128;
+
+/// The least likelihood of a pair of notes as similar as the threshold being
+/// compared, by the bands its signatures are split into
+const RECALL: f64 = 0.99;
+
+/// A note's shingles, and the smallest of their hashes under each seed
+struct Note {
+  path: PathBuf,
+  shingles: HashSet<u64>,
+  signature: Vec<u64>,
+}
+
+impl Note {
+  fn new(path: PathBuf, text: &str) -> Note {
+    let words = text
+      .split(|c: char| !c.is_alphanumeric())
+      .filter(|word| !word.is_empty())
+      .map(str::to_lowercase)
+      .collect::<Vec<_>>();
+    // Notes shorter than a shingle are a single shingle of every word
+    let shingles = words
+      .windows(SHINGLE.min(words.len()).max(1))
+      .map(|shingle| hash(0, shingle))
+      .collect::<HashSet<_>>();
+    let signature = (1..=HASHES)
+      .map(|seed| {
+        let hashes = shingles.iter().map(|shingle| hash(seed, shingle));
+        hashes.min().unwrap_or(u64::MAX)
+      })
+      .collect();
+    Note {
+      path,
+      shingles,
+      signature,
+    }
+  }
+
+  /// The share of all shingles two notes have in common
+  fn similarity(&self, other: &Note) -> f64 {
+    let shared = self.shingles.intersection(&other.shingles).count();
+    let all = self.shingles.union(&other.shingles).count();
+    match all {
+      0 => 0.0,
+      all => shared as f64 / all as f64,
+    }
+  }
+}
+
+/// The number of hashes in each band of a signature, the most that still
+/// finds pairs of notes as similar as a threshold with the recall wanted
+///
+/// A pair with a similarity `s` has all of a band's `r` hashes in common with
+/// a likelihood of `s^r`, and so some band of `b` in common with `1 - (1 -
+/// s^r)^b`.
+fn rows(threshold: f64) -> usize {
+  let divisors = (0..).map(|power| 1 << power);
+  let divisors = divisors.take_while(|rows| *rows <= HASHES as usize);
+  divisors
+    .filter(|rows| {
+      let bands = (HASHES as usize / rows) as i32;
+      1.0 - (1.0 - threshold.powi(*rows as i32)).powi(bands) >= RECALL
+    })
+    .last()
+    .unwrap_or(1)
+}
+
+/// The pairs of notes with a band of their signatures in common, each with
+/// the earlier note first
+fn candidates(notes: &[Note], rows: usize) -> HashSet<(usize, usize)> {
+  let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+  for (i, note) in notes.iter().enumerate() {
+    for (band, hashes) in note.signature.chunks(rows).enumerate() {
+      buckets.entry((band, hash(0, hashes))).or_default().push(i);
+    }
+  }
+  let mut pairs = HashSet::new();
+  for bucket in buckets.values() {
+    for (n, a) in bucket.iter().enumerate() {
+      pairs.extend(bucket[n + 1..].iter().map(|b| (*a, *b)));
+    }
+  }
+  pairs
+}
+
+fn hash<T: Hash + ?Sized>(seed: u64, value: &T) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  seed.hash(&mut hasher);
+  value.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// Report pairs of notes with much of their text in common
+pub fn dedupe(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optopt(
+    "",
+    "threshold",
+    "least similarity reported, from 0 to 1, defaults to 0.5",
+    "N",
+  );
+  let matches = opts.parse(args)?;
+  if matches.free.is_empty() {
+    let brief = "Usage: notebook dedupe PATH... [--threshold N]";
+    print!("{}", opts.usage(brief));
+    return Ok(());
+  }
+  let threshold: f64 = matches.opt_get_default("threshold", 0.5)?;
+  if !(0.0..=1.0).contains(&threshold) {
+    return Err(anyhow!("Invalid threshold '{}'", threshold));
+  }
+
+  let mut paths: Vec<PathBuf> = vec![];
+  for path in &matches.free {
+    let path = Path::new(path);
+    match path.is_dir() {
+      true => paths.extend(markdown_files(path)?),
+      false => paths.push(path.into()),
+    }
+  }
+  let mut notes = vec![];
+  for path in paths {
+    let input = fs::read_to_string(&path)?;
+    let (_, body) = frontmatter::split(&input);
+    notes.push(Note::new(path, body));
+  }
+
+  // Notes sharing a band of their signatures are the likely pairs, which are
+  // then compared in full, rather than comparing every pair of notes
+  let mut pairs = vec![];
+  for (i, j) in candidates(&notes, rows(threshold)) {
+    let (a, b) = (&notes[i], &notes[j]);
+    let similarity = a.similarity(b);
+    if similarity >= threshold {
+      pairs.push((similarity, a, b));
+    }
+  }
+  pairs.sort_by(|a, b| {
+    let paths =
+      |(_, a, b): &(f64, &Note, &Note)| (a.path.clone(), b.path.clone());
+    b.0.total_cmp(&a.0).then_with(|| paths(a).cmp(&paths(b)))
+  });
+  for (similarity, a, b) in pairs {
+    println!(
+      "{:.0}% {} {}",
+      similarity * 100.0,
+      a.path.display(),
+      b.path.display()
+    );
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn words(range: std::ops::Range<usize>) -> String {
+    range.map(|n| format!("word{} ", n)).collect()
+  }
+
+  #[test]
+  fn similarity_is_the_share_of_shingles_in_common() {
+    let a = Note::new(PathBuf::from("a.md"), &words(0..40));
+    let b = Note::new(PathBuf::from("b.md"), &words(0..40).to_uppercase());
+    let c = Note::new(PathBuf::from("c.md"), &words(100..140));
+    assert_eq!(a.similarity(&b), 1.0);
+    assert_eq!(a.similarity(&c), 0.0);
+    // 37 shingles each, 27 of them shared
+    let d = Note::new(PathBuf::from("d.md"), &words(10..50));
+    assert_eq!(a.similarity(&d), 27.0 / 47.0);
+  }
+
+  #[test]
+  fn bands_find_similar_notes_and_not_others() {
+    let notes = [
+      Note::new(PathBuf::from("a.md"), &words(0..40)),
+      Note::new(PathBuf::from("b.md"), &words(100..140)),
+      Note::new(PathBuf::from("c.md"), &words(0..38)),
+    ];
+    let pairs = candidates(&notes, rows(0.8));
+    assert!(pairs.contains(&(0, 2)));
+    assert!(!pairs.contains(&(0, 1)) && !pairs.contains(&(1, 2)));
+  }
+
+  #[test]
+  fn bands_are_wider_for_higher_thresholds() {
+    assert!(rows(0.5) < rows(0.9));
+    assert_eq!(HASHES as usize % rows(0.8), 0);
+    assert_eq!(rows(0.01), 1);
+  }
+}
@@ -0,0 +1,275 @@
+use crate::assets::hash;
+use crate::cache::Cache;
+use crate::util::escape;
+use crate::wavedrom;
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// The script that draws ` ```mermaid ` blocks in the browser
+pub const MERMAID: &str =
+  "https://cdn.jsdelivr.net/npm/mermaid@10/dist/mermaid.min.js";
+
+/// The programs that draw diagrams with external tools
+///
+/// Mermaid diagrams are drawn in the browser unless a program, such as
+/// `mmdc`, is given to draw them. Filters are commands given the text of
+/// fenced code blocks of a language on standard input, whose output is used
+/// as HTML in their place, and take precedence over the built in renderers.
+#[derive(Clone)]
+pub struct Programs {
+  dot: String,
+  mermaid: Option<String>,
+  filters: BTreeMap<String, Vec<String>>,
+}
+
+impl Programs {
+  /// The programs along with any `LANG=COMMAND` filters
+  pub fn new(
+    dot: Option<String>,
+    mermaid: Option<String>,
+    filters: &[String],
+  ) -> Result<Programs> {
+    let mut commands = BTreeMap::new();
+    for filter in filters {
+      match filter.split_once('=') {
+        Some((lang, command)) if !command.trim().is_empty() => commands.insert(
+          lang.trim().into(),
+          command.split_whitespace().map(String::from).collect(),
+        ),
+        _ => return Err(anyhow!("Invalid filter '{}'", filter)),
+      };
+    }
+    Ok(Programs {
+      dot: dot.unwrap_or_else(|| "dot".into()),
+      mermaid,
+      filters: commands,
+    })
+  }
+}
+
+/// Renders the text of a fenced code block to SVG or HTML
+pub type Renderer = fn(&str) -> Result<String>;
+
+/// Returns the renderer for a fenced code block language, if there is one
+pub fn renderer(lang: &str) -> Option<Renderer> {
+  match lang {
+    "smiles" => Some(smiles),
+    "wavedrom" => Some(wavedrom::render),
+    _ => None,
+  }
+}
+
+/// Fenced code block languages drawn with a Graphviz program
+const GRAPHVIZ: [&str; 2] = ["dot", "graphviz"];
+
+/// Render a graph in the DOT language with a Graphviz program, such as `dot`
+/// or `neato`
+fn graphviz(program: &str, text: &str) -> Result<String> {
+  let output = pipe(program, &["-Tsvg"], text)?;
+  Ok(inline_svg(&output).to_string())
+}
+
+/// Render a Mermaid diagram with the Mermaid CLI, or leave it to be drawn by
+/// the browser
+fn mermaid(program: Option<&str>, text: &str) -> Result<String> {
+  let program = match program {
+    Some(program) => program,
+    None => {
+      return Ok(format!("<pre class=\"mermaid\">{}</pre>\n", escape(text)))
+    }
+  };
+  // The CLI reads and writes files rather than standard input and output
+  let stem =
+    env::temp_dir().join(format!("notebook-{}", hash(text.as_bytes())));
+  let (input, output) =
+    (stem.with_extension("mmd"), stem.with_extension("svg"));
+  fs::write(&input, text)?;
+  let result = Command::new(program)
+    .arg("-i")
+    .arg(&input)
+    .arg("-o")
+    .arg(&output)
+    .arg("--quiet")
+    .output();
+  fs::remove_file(&input).ok();
+  let result =
+    result.map_err(|err| anyhow!("Failed to run '{}': {}", program, err))?;
+  if !result.status.success() {
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    return Err(anyhow!("'{}' failed: {}", program, stderr.trim()));
+  }
+  let svg = fs::read_to_string(&output)?;
+  fs::remove_file(&output).ok();
+  Ok(inline_svg(&svg).to_string())
+}
+
+/// Whether a document has Mermaid diagrams left to be drawn by the browser
+pub fn has_mermaid(events: &[Event]) -> bool {
+  events.iter().any(|event| {
+    matches!(event, Event::Html(html) if html.starts_with("<pre class=\"mermaid\">"))
+  })
+}
+
+/// Render a SMILES string to a 2D structure with Open Babel
+fn smiles(text: &str) -> Result<String> {
+  let mut svg = String::new();
+  for line in text.lines().filter(|line| !line.trim().is_empty()) {
+    let output = pipe("obabel", &["-ismi", "-osvg"], line.trim())?;
+    svg.push_str(inline_svg(&output));
+  }
+  Ok(svg)
+}
+
+/// Strip any XML declaration or doctype preceding an SVG document
+pub fn inline_svg(svg: &str) -> &str {
+  match svg.find("<svg") {
+    Some(start) => &svg[start..],
+    None => svg,
+  }
+}
+
+/// Run a command with some text as its standard input, returning its output
+pub fn pipe<S>(program: &str, args: &[S], input: &str) -> Result<String>
+where
+  S: AsRef<OsStr>,
+{
+  let mut child = Command::new(program)
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|err| anyhow!("Failed to run '{}': {}", program, err))?;
+
+  child
+    .stdin
+    .take()
+    .expect("Piped standard input")
+    .write_all(input.as_bytes())?;
+
+  let output = child.wait_with_output()?;
+  if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(anyhow!("'{}' failed: {}", program, stderr.trim()));
+  }
+  Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Run a command in a directory with some bytes as its standard input,
+/// returning its output as bytes
+pub fn pipe_bytes<S>(
+  program: &str,
+  args: &[S],
+  dir: &Path,
+  input: Vec<u8>,
+) -> Result<Vec<u8>>
+where
+  S: AsRef<OsStr>,
+{
+  let mut child = Command::new(program)
+    .args(args)
+    .current_dir(dir)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|err| anyhow!("Failed to run '{}': {}", program, err))?;
+
+  // Written from another thread, so that a full output pipe can't block
+  let mut stdin = child.stdin.take().expect("Piped standard input");
+  let writer = thread::spawn(move || stdin.write_all(&input));
+  let output = child.wait_with_output()?;
+  writer.join().ok();
+  if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(anyhow!("'{}' failed: {}", program, stderr.trim()));
+  }
+  Ok(output.stdout)
+}
+
+/// Transforms fenced code blocks with a known renderer into diagrams
+pub struct DiagramTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  cache: &'a Cache,
+  programs: &'a Programs,
+}
+
+impl<'a, T> DiagramTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T, cache: &'a Cache, programs: &'a Programs) -> Self {
+    DiagramTransformer {
+      iter,
+      cache,
+      programs,
+    }
+  }
+}
+
+impl<'a, T> Iterator for DiagramTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+    let lang = match event {
+      Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+        if self.programs.filters.contains_key(lang.as_ref())
+          || GRAPHVIZ.contains(&lang.as_ref())
+          || lang.as_ref() == "mermaid"
+          || renderer(lang).is_some() =>
+      {
+        lang.to_string()
+      }
+      _ => return Some(event),
+    };
+
+    let mut text = String::new();
+    for event in &mut self.iter {
+      match event {
+        Event::Text(chunk) => text.push_str(&chunk),
+        _ => break,
+      }
+    }
+
+    // Like Pikchr, errors are displayed in the output document
+    let programs = self.programs;
+    let rendered = match lang.as_str() {
+      lang if programs.filters.contains_key(lang) => {
+        let command = &programs.filters[lang];
+        let kind = format!("filter {}", command.join(" "));
+        self
+          .cache
+          .get(&kind, &text, || pipe(&command[0], &command[1..], &text))
+      }
+      // Diagrams drawn by the browser are not worth caching
+      "mermaid" if programs.mermaid.is_none() => mermaid(None, &text),
+      "mermaid" => self
+        .cache
+        .get(&lang, &text, || mermaid(programs.mermaid.as_deref(), &text)),
+      _ => self.cache.get(&lang, &text, || match renderer(&lang) {
+        Some(render) => render(&text),
+        None => graphviz(&programs.dot, &text),
+      }),
+    };
+    let event = match rendered {
+      Ok(svg) => Event::Html(svg.into()),
+      Err(err) => Event::Text(err.to_string().into()),
+    };
+    Some(event)
+  }
+}
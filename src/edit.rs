@@ -0,0 +1,227 @@
+use crate::assets::hash;
+use crate::fixture::diff;
+use crate::guard::Guard;
+use crate::history::{self, History};
+use crate::util::{encode_path, escape};
+use crate::vfs::Symlinks;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Route of the editor of the web server
+pub const PATH: &str = "edit";
+
+/// The outcome of saving a note
+pub enum Saved {
+  Saved,
+  /// The note changed since the edit began, to the text given
+  Conflict(String),
+}
+
+/// Edits the notes of a notebook from the browser, at `/edit/NOTE.md`
+///
+/// Each edit carries the version of the note it began from, a hash of its
+/// text, and is refused when the note has changed since, as when it is
+/// edited on two devices, rather than overwriting the other change. Edits
+/// are saved as drafts while they are written, and the text each save
+/// replaces is kept in the note's history.
+///
+/// Notes are confined to the served directory, as rendered notes are, with
+/// symlinks followed only as `--symlinks` allows.
+#[derive(Clone)]
+pub struct Editor {
+  guard: Guard,
+  lock: Arc<Mutex<()>>,
+}
+
+impl Editor {
+  pub fn new(symlinks: Symlinks) -> Editor {
+    Editor {
+      guard: Guard::new(&[], symlinks),
+      lock: Arc::new(Mutex::new(())),
+    }
+  }
+
+  /// The editor of a note, as an HTML page, of its draft when asked for
+  pub fn page(&self, dir: &Path, note: &str, draft: bool) -> Result<String> {
+    let current = fs::read_to_string(self.path(dir, note)?).unwrap_or_default();
+    let history = History::new(dir);
+    let saved = history.draft(note).filter(|saved| *saved != current);
+    let mut html = head(note);
+    html.push_str(&format!("<h1>{}</h1>\n", escape(note)));
+    html.push_str(&format!(
+      "<p><a href=\"/{}/{}\">History</a></p>\n",
+      history::PATH,
+      escape(&encode_path(note))
+    ));
+    let text = match (saved, draft) {
+      (Some(saved), true) => saved,
+      (Some(_), false) => {
+        html.push_str(&format!(
+          "<p class=\"draft\">This note has an unsaved draft. <a \
+           href=\"/{}/{}?draft=1\">Continue the draft</a></p>\n",
+          PATH,
+          escape(&encode_path(note))
+        ));
+        current.clone()
+      }
+      (None, _) => current.clone(),
+    };
+    html.push_str(&form(note, &text, &version(&current)));
+    html.push_str(&autosave(note));
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+  }
+
+  /// The history of a note, as an HTML page
+  pub fn history(&self, dir: &Path, note: &str) -> Result<String> {
+    let current = fs::read_to_string(self.path(dir, note)?).unwrap_or_default();
+    let history = History::new(dir);
+    Ok(history.page(note, &current, &version(&current)))
+  }
+
+  /// Save a kept version of a note over it, if the note is still at the
+  /// version the restore began from, returning the version's text
+  pub fn restore(
+    &self,
+    dir: &Path,
+    note: &str,
+    stamp: &str,
+    from: &str,
+  ) -> Result<(String, Saved)> {
+    self.path(dir, note)?;
+    let text = History::new(dir).version(note, stamp)?;
+    let saved = self.save(dir, note, &text, from)?;
+    Ok((text, saved))
+  }
+
+  /// Keep the draft of an edit of a note, until it is saved
+  pub fn save_draft(&self, dir: &Path, note: &str, text: &str) -> Result<()> {
+    self.path(dir, note)?;
+    History::new(dir).save_draft(note, &text.replace("\r\n", "\n"))
+  }
+
+  /// Save a note's text, if it is still at the version the edit began from
+  pub fn save(
+    &self,
+    dir: &Path,
+    note: &str,
+    text: &str,
+    from: &str,
+  ) -> Result<Saved> {
+    let path = self.path(dir, note)?;
+    let _lock = self.lock.lock().expect("Editor lock");
+    let current = fs::read_to_string(&path).unwrap_or_default();
+    if version(&current) != from {
+      return Ok(Saved::Conflict(current));
+    }
+    // Browsers send the lines of text areas ending with `\r\n`
+    let text = text.replace("\r\n", "\n");
+    let history = History::new(dir);
+    if path.exists() && current != text {
+      history.keep(note, &current)?;
+    }
+    fs::write(&path, text)?;
+    history.discard_draft(note);
+    Ok(Saved::Saved)
+  }
+
+  /// The path of a note under a directory, refusing any other file
+  fn path(&self, dir: &Path, note: &str) -> Result<PathBuf> {
+    self
+      .guard
+      .named_note(dir, note)
+      .filter(|_| note.ends_with(".md"))
+      .ok_or_else(|| anyhow!("Invalid note '{}'", note))
+  }
+}
+
+/// Whether a request posted by a browser came from a page of the server, by
+/// its `Origin` header or else its `Referer`, as browsers send the `--auth`
+/// credentials with forms posted to it from any site
+///
+/// Requests with neither aren't sent from the pages of other sites.
+pub fn same_origin(
+  host: Option<&str>,
+  origin: Option<&str>,
+  referer: Option<&str>,
+) -> bool {
+  let source = match origin.or(referer) {
+    Some(source) => source,
+    None => return true,
+  };
+  let authority = source
+    .split_once("://")
+    .and_then(|(_, rest)| rest.split('/').next());
+  match (authority, host) {
+    (Some(authority), Some(host)) => authority.eq_ignore_ascii_case(host),
+    _ => false,
+  }
+}
+
+/// The version of a note's text
+pub fn version(text: &str) -> String {
+  hash(text.as_bytes())
+}
+
+/// A page helping to merge an edit refused for a newer version of its note,
+/// with the changes between them and the edit, to be saved over the newer
+/// version once merged
+pub fn conflict(note: &str, text: &str, current: &str) -> String {
+  let text = text.replace("\r\n", "\n");
+  let mut html = head(note);
+  html.push_str(&format!("<h1>{} changed</h1>\n", escape(note)));
+  html.push_str(
+    "<p class=\"conflict\">The note was saved elsewhere since this edit \
+     began. The changes from the saved note to this edit are below, with \
+     <code>-</code> for lines only in the saved note and <code>+</code> for \
+     lines only in this edit. Merge them into the edit and save it again to \
+     replace the saved note.</p>\n",
+  );
+  html.push_str(&format!(
+    "<pre class=\"diff\">{}</pre>\n",
+    escape(&diff(current, &text))
+  ));
+  html.push_str(&form(note, &text, &version(current)));
+  html.push_str("</body>\n</html>\n");
+  html
+}
+
+fn head(note: &str) -> String {
+  format!(
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Edit \
+     {}</title>\n<style>textarea {{ width: 100%; height: 70vh; }}</style>\n\
+     </head>\n<body>\n",
+    escape(note)
+  )
+}
+
+/// A form saving a note, based on a version of it
+fn form(note: &str, text: &str, version: &str) -> String {
+  format!(
+    "<form method=\"post\" action=\"/{}/{}\">\n<input type=\"hidden\" \
+     name=\"version\" value=\"{}\">\n<textarea name=\"text\">{}</textarea>\n\
+     <button>Save</button>\n</form>\n",
+    PATH,
+    escape(&encode_path(note)),
+    version,
+    escape(text)
+  )
+}
+
+/// A script saving the text of the editor as a draft, every few seconds
+/// while it changes
+fn autosave(note: &str) -> String {
+  let url = Value::String(format!("/{}/{}", history::PATH, encode_path(note)));
+  format!(
+    "<script>\n(function () {{\n  var text = \
+     document.querySelector('textarea[name=text]');\n  var saved = \
+     text.value;\n  setInterval(function () {{\n    if (text.value === saved) \
+     return;\n    saved = text.value;\n    fetch({}, {{ method: 'POST', body: \
+     new URLSearchParams({{ draft: saved }}) }});\n  }}, 5000);\n}})();\n\
+     </script>\n",
+    url.to_string().replace("</", "<\\/")
+  )
+}
@@ -0,0 +1,311 @@
+use crate::code;
+use crate::events::Events;
+use crate::frontmatter;
+use crate::headings::strip_id;
+use crate::listing;
+use crate::people::relative;
+use crate::util::{escape, slugify};
+use crate::vfs::Source;
+use anyhow::{anyhow, Result};
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+const OPEN: &str = "![[";
+const CLOSE: &str = "]]";
+
+/// Embeds of other notes, `![[note]]`, or of one of their sections,
+/// `![[note#Heading]]`, or blocks, `![[note#^id]]`
+///
+/// Blocks are paragraphs or list items ending with a ` ^id` marker. Notes are
+/// found by their path from the embedding note or from the root of the
+/// notebook, with or without `.md`, or by their file name anywhere in the
+/// notebook, nearest the embedding note first.
+pub struct Embeds<'a> {
+  source: &'a dyn Source,
+  root: &'a Path,
+  events: &'a Events,
+  files: Vec<PathBuf>,
+}
+
+impl<'a> Embeds<'a> {
+  pub fn new(
+    source: &'a dyn Source,
+    root: &'a Path,
+    events: &'a Events,
+  ) -> Embeds<'a> {
+    Embeds {
+      source,
+      root,
+      events,
+      files: vec![],
+    }
+  }
+
+  /// Replace embeds outside of code with the fragments they name, and
+  /// remove block markers
+  ///
+  /// Embeds of notes, sections or blocks that aren't found are left marked as
+  /// missing, with a warning.
+  pub fn expand(&mut self, document: &Path, input: &str) -> Result<String> {
+    if input.contains(OPEN) {
+      self.files = self.source.files(self.root)?;
+    }
+    self.expand_with(document, input, &mut vec![absolute(document)])
+  }
+
+  fn expand_with(
+    &self,
+    document: &Path,
+    input: &str,
+    stack: &mut Vec<PathBuf>,
+  ) -> Result<String> {
+    let mut error = None;
+    let output = code::map_prose(input, |text| {
+      let mut output = String::with_capacity(text.len());
+      let mut rest = text;
+      while let Some(start) = rest.find(OPEN).filter(|_| error.is_none()) {
+        let reference = &rest[start + OPEN.len()..];
+        let end = match reference.find(CLOSE) {
+          Some(end) => end,
+          None => break,
+        };
+        output.push_str(&rest[..start]);
+        let target = reference[..end].trim();
+        match self.embed(document, target, stack) {
+          Ok(html) => output.push_str(&html),
+          Err(err) => error = Some(err),
+        }
+        rest = &reference[end + CLOSE.len()..];
+      }
+      output.push_str(&strip_markers(rest));
+      output
+    });
+    match error {
+      Some(error) => Err(error),
+      None => Ok(output),
+    }
+  }
+
+  /// HTML wrapping an embedded fragment and linking to where it came from
+  fn embed(
+    &self,
+    document: &Path,
+    target: &str,
+    stack: &mut Vec<PathBuf>,
+  ) -> Result<String> {
+    let (note, fragment) = match target.split_once('#') {
+      Some((note, fragment)) => (note.trim(), Some(fragment.trim())),
+      None => (target, None),
+    };
+    let path = match self.find(document, note) {
+      Some(path) => path,
+      None => return Ok(self.missing(target, "note")),
+    };
+    if stack.contains(&absolute(&path)) {
+      stack.push(absolute(&path));
+      let current = absolute(Path::new(""));
+      let cycle = stack.iter().map(|path| relative(&current, path));
+      return Err(anyhow!(
+        "Embed cycle: {}",
+        cycle.collect::<Vec<_>>().join(" -> ")
+      ));
+    }
+
+    let text = self.source.read_to_string(&path)?;
+    let (meta, body) = frontmatter::split(&text);
+    let (content, anchor, label) = match fragment {
+      Some(id) if id.starts_with('^') => match block(body, &id[1..]) {
+        Some(content) => (content, None, None),
+        None => return Ok(self.missing(target, "block")),
+      },
+      Some(heading) => match section(body, heading) {
+        Some((content, anchor)) => (content, Some(anchor), Some(heading)),
+        None => return Ok(self.missing(target, "section")),
+      },
+      None => (body.to_string(), None, None),
+    };
+    stack.push(absolute(&path));
+    let content = self.expand_with(&path, &content, stack)?;
+    stack.pop();
+
+    let title =
+      listing::title(&meta, body).unwrap_or_else(|| listing::stem(&path));
+    let here = absolute(document.parent().unwrap_or(Path::new("")));
+    let mut href = relative(&here, &absolute(&path.with_extension("html")));
+    if let Some(anchor) = anchor {
+      href.push('#');
+      href.push_str(&anchor);
+    }
+    let source = match label {
+      Some(heading) => format!("{} › {}", title, strip_id(heading)),
+      None => title,
+    };
+    Ok(format!(
+      "\n\n<div class=\"embed\">\n\n{}\n\n<p class=\"embed-source\">\
+       <a href=\"{}\">{}</a></p>\n</div>\n\n",
+      content.trim(),
+      escape(&href),
+      escape(&source)
+    ))
+  }
+
+  /// A placeholder for an embed that isn't found, warning of it
+  fn missing(&self, target: &str, kind: &str) -> String {
+    self
+      .events
+      .warn(&format!("unknown {} embedded with '![[{}]]'", kind, target));
+    format!(
+      "<span class=\"embed missing\">![[{}]]</span>",
+      escape(target)
+    )
+  }
+
+  /// The path of an embedded note
+  fn find(&self, document: &Path, note: &str) -> Option<PathBuf> {
+    let here = document.parent().unwrap_or(Path::new(""));
+    let name = match note.ends_with(".md") {
+      true => note.to_string(),
+      false => format!("{}.md", note),
+    };
+    // Paths may lead out of the notebook's directory with `..`
+    for path in [here.join(&name), self.root.join(&name)] {
+      let path = normal(&path);
+      if self.source.read(&path).is_ok() {
+        return Some(path);
+      }
+    }
+
+    let file_name = Path::new(&name).file_name()?;
+    let here = absolute(here);
+    let shared = |file: &PathBuf| {
+      let file = absolute(file);
+      let pairs = here.components().zip(file.components());
+      pairs.take_while(|(a, b)| a == b).count()
+    };
+    let files = self.files.iter();
+    let found = files.filter(|file| file.file_name() == Some(file_name));
+    found.rev().max_by_key(|file| shared(file)).cloned()
+  }
+}
+
+/// A path without `.` components, or `..` components after a directory
+fn normal(path: &Path) -> PathBuf {
+  let mut normal = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir
+        if matches!(
+          normal.components().next_back(),
+          Some(Component::Normal(_))
+        ) =>
+      {
+        normal.pop();
+      }
+      component => normal.push(component),
+    }
+  }
+  normal
+}
+
+/// A path from the root of the filesystem, for comparing and linking paths
+fn absolute(path: &Path) -> PathBuf {
+  normal(&env::current_dir().unwrap_or_default().join(path))
+}
+
+/// The level of an ATX heading line and its text
+fn heading(line: &str) -> Option<(usize, &str)> {
+  let level = line.chars().take_while(|c| *c == '#').count();
+  let text = line[level..].strip_prefix(' ')?;
+  (1..=6).contains(&level).then_some((level, text.trim()))
+}
+
+/// The id of a heading, explicit with `{#id}` or from its text
+fn heading_id(text: &str) -> String {
+  let trimmed = text.trim_end();
+  match trimmed.rfind("{#") {
+    Some(start) if trimmed.ends_with('}') => {
+      trimmed[start + 2..trimmed.len() - 1].to_string()
+    }
+    _ => slugify(text),
+  }
+}
+
+/// A section of some markdown, from the heading with some text or id up to the
+/// next heading of the same or a higher level, with the heading's id
+fn section(body: &str, name: &str) -> Option<(String, String)> {
+  let mut found: Option<(usize, String)> = None;
+  let mut content = String::new();
+
+  for (code, line) in code::lines(body) {
+    let heading = heading(line).filter(|_| !code);
+    match (&found, heading) {
+      (None, Some((level, text))) => {
+        let id = heading_id(text);
+        let matches =
+          strip_id(text).eq_ignore_ascii_case(strip_id(name)) || id == name;
+        if matches {
+          found = Some((level, id));
+        }
+      }
+      (Some((level, _)), Some((other, _))) if other <= *level => break,
+      _ => {}
+    }
+    if found.is_some() {
+      content.push_str(line);
+    }
+  }
+  found.map(|(_, id)| (content, id))
+}
+
+/// The paragraph or list item ending with a block marker
+fn block(body: &str, id: &str) -> Option<String> {
+  let marker = format!(" ^{}", id);
+  let mut paragraph: Vec<&str> = vec![];
+
+  for (code, line) in code::lines(body) {
+    let trimmed = line.trim_start();
+    if line.trim().is_empty() || code {
+      paragraph.clear();
+      continue;
+    }
+    paragraph.push(line);
+    if !line.trim_end().ends_with(&marker) {
+      continue;
+    }
+    let is_item = trimmed.starts_with("- ")
+      || trimmed.starts_with("* ")
+      || trimmed.split_once(". ").is_some_and(|(number, _)| {
+        !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+      });
+    let lines = match is_item {
+      true => &paragraph[paragraph.len() - 1..],
+      false => &paragraph[..],
+    };
+    return Some(strip_markers(&lines.concat()));
+  }
+  None
+}
+
+/// Remove ` ^id` block markers from the ends of lines
+fn strip_markers(input: &str) -> String {
+  let mut output = String::with_capacity(input.len());
+  for line in input.split_inclusive('\n') {
+    let (text, end) = match line.strip_suffix('\n') {
+      Some(text) => (text, "\n"),
+      None => (line, ""),
+    };
+    let stripped = text.trim_end().rsplit_once(" ^").filter(|(_, id)| {
+      !id.is_empty()
+        && id
+          .chars()
+          .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    });
+    match stripped {
+      Some((text, _)) => output.push_str(text),
+      None => output.push_str(text),
+    }
+    output.push_str(end);
+  }
+  output
+}
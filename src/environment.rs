@@ -0,0 +1,205 @@
+use crate::code::{self, lines};
+use crate::shortcode::parse_tag;
+use crate::util::{escape, slugify};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const OPEN: &str = "{@";
+const CLOSE: &str = "}";
+
+/// Environments captioned below their content, and listed with a marker line
+const CAPTIONED: [(&str, &str); 2] =
+  [("figure", "{^figures}"), ("table", "{^tables}")];
+
+/// Numbered environments, declared with `:::theorem id=NAME title="TITLE"`
+#[derive(Clone)]
+pub struct Environments {
+  labels: BTreeMap<String, String>,
+}
+
+impl Environments {
+  /// The built in environments along with any `NAME=LABEL` definitions
+  pub fn new(definitions: &[String]) -> Result<Environments> {
+    let mut labels = BTreeMap::new();
+    labels.insert("example".into(), "Example".into());
+    labels.insert("theorem".into(), "Theorem".into());
+    labels.insert("exercise".into(), "Exercise".into());
+    labels.insert("figure".into(), "Figure".into());
+    labels.insert("table".into(), "Table".into());
+    for definition in definitions {
+      match definition.find('=') {
+        Some(i) => labels.insert(
+          definition[..i].trim().into(),
+          definition[i + 1..].trim().into(),
+        ),
+        None => return Err(anyhow!("Invalid environment '{}'", definition)),
+      };
+    }
+    Ok(Environments { labels })
+  }
+
+  /// Number each environment and resolve `{@id}` references to them
+  ///
+  /// Environments are numbered per kind, and per chapter when the document
+  /// has more than one level one heading. Figures and tables have their title
+  /// as a caption, and `{^figures}` or `{^tables}` lines are replaced with a
+  /// list of them.
+  pub fn apply(&self, input: &str) -> Result<String> {
+    let chapters = lines(input)
+      .filter(|(code, line)| !code && is_chapter(line))
+      .count();
+
+    let mut output = String::with_capacity(input.len());
+    let mut numbers: BTreeMap<String, String> = BTreeMap::new();
+    let mut counters: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut captions: Vec<(&str, String, String, String)> = vec![];
+    let mut chapter = 0;
+    // Each open container, with the HTML closing it if it is an environment
+    let mut frames: Vec<Option<String>> = vec![];
+
+    for (code, line) in lines(input) {
+      if !code && chapters > 1 && is_chapter(line) {
+        chapter += 1;
+        counters.clear();
+      }
+
+      let directive = match line.trim().strip_prefix(":::") {
+        Some(directive) if !code => directive.trim(),
+        _ => {
+          output.push_str(line);
+          continue;
+        }
+      };
+
+      if directive.is_empty() {
+        match frames.pop() {
+          Some(Some(close)) => output.push_str(&close),
+          _ => output.push_str(line),
+        }
+        continue;
+      }
+
+      // Other directives, such as admonitions, are left to their own steps
+      let name = directive.split_whitespace().next().unwrap_or("");
+      let (kind, label) = match self.labels.get_key_value(name) {
+        Some(label) => label,
+        None => {
+          frames.push(None);
+          output.push_str(line);
+          continue;
+        }
+      };
+      let (_, args) = parse_tag(directive)?;
+
+      let count = counters.entry(kind).or_insert(0);
+      *count += 1;
+      let number = match chapters > 1 {
+        true => format!("{} {}.{}", label, chapter, count),
+        false => format!("{} {}", label, count),
+      };
+
+      let id = match args.get("id") {
+        Some(Value::String(id)) => id.clone(),
+        _ => slugify(&number),
+      };
+      numbers.insert(id.clone(), number.clone());
+      let title = match args.get("title") {
+        Some(Value::String(title)) => escape(title),
+        _ => "".into(),
+      };
+
+      if CAPTIONED.iter().any(|(captioned, _)| captioned == kind) {
+        captions.push((kind, id.clone(), number.clone(), title.clone()));
+        frames.push(Some(format!(
+          "\n<figcaption><strong>{}</strong> {}</figcaption>\n</figure>\n",
+          escape(&number),
+          title
+        )));
+        output.push_str(&format!(
+          "<figure class=\"environment {}\" id=\"{}\">\n\n",
+          kind,
+          escape(&id)
+        ));
+        continue;
+      }
+
+      let title = match title.is_empty() {
+        true => title,
+        false => format!(" ({})", title),
+      };
+      frames.push(Some("\n</div>\n".into()));
+      output.push_str(&format!(
+        "<div class=\"environment {}\" id=\"{}\">\n\
+         <p class=\"environment-title\"><strong>{}</strong>{}</p>\n\n",
+        kind,
+        escape(&id),
+        escape(&number),
+        title
+      ));
+    }
+
+    if !captions.is_empty() {
+      output = output
+        .split_inclusive('\n')
+        .map(|line| {
+          let kind = CAPTIONED
+            .iter()
+            .find(|(_, marker)| line.trim() == *marker)
+            .map(|(kind, _)| kind);
+          match kind {
+            Some(kind) => list(kind, &captions),
+            None => line.into(),
+          }
+        })
+        .collect();
+    }
+
+    Ok(resolve(&output, &numbers))
+  }
+}
+
+/// HTML listing the captioned environments of a kind
+fn list(kind: &str, captions: &[(&str, String, String, String)]) -> String {
+  let mut list = format!("<ul class=\"list-of-{}s\">\n", kind);
+  for (_, id, number, title) in captions.iter().filter(|(k, ..)| *k == kind) {
+    list.push_str(&format!(
+      "<li><a href=\"#{}\">{}</a> {}</li>\n",
+      escape(id),
+      escape(number),
+      title
+    ));
+  }
+  list.push_str("</ul>\n");
+  list
+}
+
+fn is_chapter(line: &str) -> bool {
+  line.starts_with("# ") || line.trim_end() == "#"
+}
+
+/// Replace `{@id}` references with links to the numbered environment,
+/// outside of code and leaving any to unknown ids as they are
+fn resolve(input: &str, numbers: &BTreeMap<String, String>) -> String {
+  code::map_prose(input, |text| {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+      let reference = &rest[start + OPEN.len()..];
+      let end = match reference.find(CLOSE) {
+        Some(end) => end,
+        None => break,
+      };
+      let id = reference[..end].trim();
+      let after = &reference[end + CLOSE.len()..];
+      output.push_str(&rest[..start]);
+      match numbers.get(id) {
+        Some(number) => output.push_str(&format!("[{}](#{})", number, id)),
+        None => output.push_str(&rest[start..rest.len() - after.len()]),
+      }
+      rest = after;
+    }
+    output.push_str(rest);
+    output
+  })
+}
@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Where the lines of events are written
+pub type Sink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Reports what builds do, as text for people or as `json` events, one object
+/// a line, for programs
+///
+/// Events are written to standard error, unless another sink is given, with
+/// an `event` of `started`, `finished` or `skipped` for each file, `warning`
+/// for each warning, `info` for anything else done, such as a snapshot
+/// written or a webmention sent, and `totals` once the output is written.
+/// Clones report to the same sink and share their counts, as the renders of a
+/// directory's notes do.
+#[derive(Clone)]
+pub struct Events {
+  json: bool,
+  sink: Sink,
+  counts: Arc<Mutex<Counts>>,
+}
+
+#[derive(Default)]
+struct Counts {
+  files: usize,
+  skipped: usize,
+  warnings: usize,
+  /// The file being written, which warnings are about
+  file: Option<String>,
+}
+
+impl Default for Events {
+  fn default() -> Events {
+    Events {
+      json: false,
+      sink: Arc::new(|line| eprintln!("{}", line)),
+      counts: Default::default(),
+    }
+  }
+}
+
+impl Events {
+  /// Events of a format, `text` or `json`
+  pub fn new(format: &str) -> Result<Events> {
+    let json = match format {
+      "text" => false,
+      "json" => true,
+      format => return Err(anyhow!("Unknown progress format '{}'", format)),
+    };
+    Ok(Events {
+      json,
+      ..Default::default()
+    })
+  }
+
+  /// The same events, written to another sink
+  pub fn with_sink(self, sink: Sink) -> Events {
+    Events { sink, ..self }
+  }
+
+  fn emit(&self, event: Value) {
+    (self.sink)(&event.to_string());
+  }
+
+  fn counts(&self) -> std::sync::MutexGuard<'_, Counts> {
+    self.counts.lock().expect("Events lock")
+  }
+
+  /// A file begins to be written
+  pub fn started(&self, file: &str) {
+    self.counts().file = Some(file.to_string());
+    if self.json {
+      self.emit(json!({ "event": "started", "file": file }));
+    }
+  }
+
+  /// A file has been written to an output
+  pub fn finished(&self, file: &str, output: &Path) {
+    let mut counts = self.counts();
+    counts.files += 1;
+    counts.file = None;
+    drop(counts);
+    if self.json {
+      let output = output.to_string_lossy();
+      self.emit(json!({ "event": "finished", "file": file, "output": output }));
+    }
+  }
+
+  /// A file is left out of the output
+  pub fn skipped(&self, file: &str, reason: &str) {
+    let mut counts = self.counts();
+    counts.skipped += 1;
+    counts.file = None;
+    drop(counts);
+    match self.json {
+      true => {
+        self.emit(json!({ "event": "skipped", "file": file, "reason": reason }))
+      }
+      false => {
+        (self.sink)(&format!("Skipping '{}', which is {}", file, reason))
+      }
+    }
+  }
+
+  /// Something that doesn't stop the output being written, but should be
+  /// fixed
+  pub fn warn(&self, message: &str) {
+    let mut counts = self.counts();
+    counts.warnings += 1;
+    let file = counts.file.clone();
+    drop(counts);
+    match self.json {
+      true => self
+        .emit(json!({ "event": "warning", "file": file, "message": message })),
+      false => (self.sink)(&format!("Warning: {}", message)),
+    }
+  }
+
+  /// Something done along with the output, worth knowing but not a problem
+  pub fn info(&self, message: &str) {
+    match self.json {
+      true => {
+        let file = self.counts().file.clone();
+        self.emit(json!({ "event": "info", "file": file, "message": message }));
+      }
+      false => (self.sink)(message),
+    }
+  }
+
+  /// The output has been written, or failed to be
+  pub fn totals(&self, started: Instant, error: Option<&anyhow::Error>) {
+    let counts = std::mem::take(&mut *self.counts());
+    if self.json {
+      self.emit(json!({
+        "event": "totals",
+        "files": counts.files,
+        "skipped": counts.skipped,
+        "warnings": counts.warnings,
+        "milliseconds": started.elapsed().as_millis() as u64,
+        "error": error.map(|err| err.to_string()),
+      }));
+    }
+  }
+}
@@ -0,0 +1,235 @@
+use crate::cache::Cache;
+use crate::util::escape;
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The commands fenced code blocks marked `run` are executed with, by language
+///
+/// The code of a block is given to its command on standard input, in the
+/// document's directory.
+#[derive(Clone)]
+pub struct Runners {
+  commands: BTreeMap<String, Vec<String>>,
+  timeout: Duration,
+}
+
+impl Runners {
+  /// The built in commands along with any `LANG=COMMAND` definitions
+  pub fn new(definitions: &[String], timeout: u64) -> Result<Runners> {
+    let mut commands = BTreeMap::new();
+    for (lang, command) in [
+      ("sh", "sh"),
+      ("bash", "bash"),
+      ("python", "python3"),
+      ("ruby", "ruby"),
+      ("js", "node"),
+      ("javascript", "node"),
+    ] {
+      commands.insert(lang.to_string(), vec![command.to_string()]);
+    }
+    for definition in definitions {
+      match definition.split_once('=') {
+        Some((lang, command)) if !command.trim().is_empty() => commands.insert(
+          lang.trim().into(),
+          command.split_whitespace().map(String::from).collect(),
+        ),
+        _ => return Err(anyhow!("Invalid runner '{}'", definition)),
+      };
+    }
+    Ok(Runners {
+      commands,
+      timeout: Duration::from_secs(timeout),
+    })
+  }
+
+  /// The command that runs a language, as written
+  fn command(&self, lang: &str) -> String {
+    self
+      .commands
+      .get(lang)
+      .map(|command| command.join(" "))
+      .unwrap_or_default()
+  }
+
+  /// Run some code, returning its standard output and standard error
+  fn run(
+    &self,
+    lang: &str,
+    code: &str,
+    dir: &Path,
+  ) -> Result<(String, String)> {
+    let command = self
+      .commands
+      .get(lang)
+      .ok_or_else(|| anyhow!("No command to run '{}'", lang))?;
+    let mut child = Command::new(&command[0])
+      .args(&command[1..])
+      .current_dir(dir)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .map_err(|err| anyhow!("Failed to run '{}': {}", command[0], err))?;
+
+    // Output is read as it is written, so that a full pipe can't block
+    let stdout = read(child.stdout.take());
+    let stderr = read(child.stderr.take());
+    if let Some(mut stdin) = child.stdin.take() {
+      // Programs may exit without reading all of their input
+      stdin.write_all(code.as_bytes()).ok();
+    }
+
+    if !wait(&mut child, self.timeout)? {
+      child.kill().ok();
+      child.wait()?;
+      return Err(anyhow!(
+        "'{}' timed out after {} seconds",
+        command[0],
+        self.timeout.as_secs()
+      ));
+    }
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    Ok((stdout, stderr))
+  }
+}
+
+fn read<R>(pipe: Option<R>) -> thread::JoinHandle<String>
+where
+  R: Read + Send + 'static,
+{
+  thread::spawn(move || {
+    let mut bytes = vec![];
+    if let Some(mut pipe) = pipe {
+      pipe.read_to_end(&mut bytes).ok();
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+  })
+}
+
+/// Wait for a process to exit, returning false if it is still running after
+/// the timeout
+fn wait(child: &mut Child, timeout: Duration) -> Result<bool> {
+  let start = Instant::now();
+  while start.elapsed() < timeout {
+    if child.try_wait()?.is_some() {
+      return Ok(true);
+    }
+    thread::sleep(Duration::from_millis(10));
+  }
+  Ok(child.try_wait()?.is_some())
+}
+
+/// The language of a fenced code block marked to be run, as in `sh run`
+fn runnable(info: &str) -> Option<&str> {
+  let mut words = info.split_whitespace();
+  let lang = words.next()?;
+  words.any(|word| word == "run").then_some(lang)
+}
+
+/// Runs fenced code blocks marked `run`, adding their output below them
+pub struct ExecuteTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  runners: Option<&'a Runners>,
+  dir: PathBuf,
+  cache: &'a Cache,
+  buffer: VecDeque<Event<'a>>,
+}
+
+impl<'a, T> ExecuteTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  /// Code is only run when there are runners, from `--execute`
+  pub fn new(
+    iter: T,
+    runners: Option<&'a Runners>,
+    dir: &Path,
+    cache: &'a Cache,
+  ) -> Self {
+    ExecuteTransformer {
+      iter,
+      runners,
+      dir: dir.into(),
+      cache,
+      buffer: VecDeque::new(),
+    }
+  }
+}
+
+impl<'a, T> Iterator for ExecuteTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(event) = self.buffer.pop_front() {
+      return Some(event);
+    }
+    let event = self.iter.next()?;
+    let (runners, lang) = match (&event, self.runners) {
+      (
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))),
+        Some(runners),
+      ) => match runnable(info) {
+        Some(lang) => (runners, lang.to_string()),
+        None => return Some(event),
+      },
+      _ => return Some(event),
+    };
+
+    // The code is shown as it is, followed by its output
+    let mut code = String::new();
+    for event in &mut self.iter {
+      let end = matches!(event, Event::End(Tag::CodeBlock(_)));
+      if let Event::Text(text) = &event {
+        code.push_str(text);
+      }
+      self.buffer.push_back(event);
+      if end {
+        break;
+      }
+    }
+    // Output is cached by the command that ran the code as well as the code
+    let kind = format!("run {}", runners.command(&lang));
+    let output = self.cache.get(&kind, &code, || {
+      let (stdout, stderr) = runners.run(&lang, &code, &self.dir)?;
+      let mut html = String::new();
+      if !stdout.is_empty() {
+        html.push_str(&format!(
+          "<pre class=\"output\"><samp>{}</samp></pre>\n",
+          escape(&stdout)
+        ));
+      }
+      if !stderr.is_empty() {
+        html.push_str(&format!(
+          "<pre class=\"output stderr\"><samp>{}</samp></pre>\n",
+          escape(&stderr)
+        ));
+      }
+      Ok(html)
+    });
+    let html = match output {
+      Ok(html) => html,
+      // Like diagrams, errors are displayed in the output document
+      Err(err) => format!(
+        "<pre class=\"output error\"><samp>{}</samp></pre>\n",
+        escape(&err.to_string())
+      ),
+    };
+    if !html.is_empty() {
+      self.buffer.push_back(Event::Html(html.into()));
+    }
+    Some(event)
+  }
+}
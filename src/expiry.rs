@@ -0,0 +1,89 @@
+use crate::frontmatter;
+use crate::util::{escape, markdown_files, today};
+use anyhow::Result;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The state of a note with `expires` or `review` dates in its front matter
+pub enum Status {
+  Current,
+  /// The note's `review` date has passed
+  ReviewDue(String),
+  /// The note's `expires` date has passed
+  Expired(String),
+}
+
+/// Check a note's front matter dates against today
+pub fn status(meta: &Value, today: &str) -> Status {
+  let date = |key: &str| {
+    let value = meta.get(key)?.as_str()?;
+    // Dates may include a time, which is ignored
+    let date = value.get(..10).unwrap_or(value);
+    Some(date.to_string()).filter(|date| date.as_str() <= today)
+  };
+  if let Some(date) = date("expires") {
+    return Status::Expired(date);
+  }
+  if let Some(date) = date("review") {
+    return Status::ReviewDue(date);
+  }
+  Status::Current
+}
+
+/// HTML for a banner warning that a note is stale, if it is
+pub fn banner(meta: &Value) -> Option<String> {
+  let (class, message) = match status(meta, &today()) {
+    Status::Current => return None,
+    Status::Expired(date) => {
+      ("expired", format!("This note expired on {}", date))
+    }
+    Status::ReviewDue(date) => (
+      "review-due",
+      format!("This note was due for review on {}", date),
+    ),
+  };
+  Some(format!(
+    "<div class=\"stale-banner {}\">{}</div>\n",
+    class,
+    escape(&message)
+  ))
+}
+
+/// Report notes with dates in their front matter that have passed
+pub fn stats(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optflag("", "stale", "list expired notes and notes due for review");
+  let matches = opts.parse(args)?;
+
+  if !matches.opt_present("stale") || matches.free.is_empty() {
+    let brief = "Usage: notebook stats --stale PATH...";
+    print!("{}", opts.usage(brief));
+    return Ok(());
+  }
+
+  let mut paths: Vec<PathBuf> = vec![];
+  for path in &matches.free {
+    let path = Path::new(path);
+    match path.is_dir() {
+      true => paths.extend(markdown_files(path)?),
+      false => paths.push(path.into()),
+    }
+  }
+
+  let today = today();
+  for path in paths {
+    let input = fs::read_to_string(&path)?;
+    let (meta, _) = frontmatter::split(&input);
+    match status(&meta, &today) {
+      Status::Current => {}
+      Status::Expired(date) => {
+        println!("{}: expired on {}", path.display(), date)
+      }
+      Status::ReviewDue(date) => {
+        println!("{}: review due since {}", path.display(), date)
+      }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,109 @@
+use crate::util::markdown_files;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+/// Lines of context shown around each change in a diff
+const CONTEXT: usize = 3;
+
+/// Render each `NAME.md` fixture in a directory and compare it to `NAME.html`
+///
+/// With `update` set the golden files are written instead of compared.
+pub fn run<F>(dir: &str, update: bool, render: F) -> Result<()>
+where
+  F: Fn(&Path) -> Result<String>,
+{
+  let mut failures = 0;
+  let fixtures = markdown_files(Path::new(dir))?;
+  for fixture in &fixtures {
+    let golden = fixture.with_extension("html");
+    let actual = render(fixture)?;
+
+    if update {
+      fs::write(&golden, &actual)?;
+      println!("updated {}", golden.display());
+      continue;
+    }
+
+    let expected = match fs::read_to_string(&golden) {
+      Ok(expected) => expected,
+      Err(_) => {
+        failures += 1;
+        println!("FAIL {}: missing {}", fixture.display(), golden.display());
+        continue;
+      }
+    };
+    if expected == actual {
+      println!("ok {}", fixture.display());
+      continue;
+    }
+
+    failures += 1;
+    println!("FAIL {}", fixture.display());
+    print!("{}", diff(&expected, &actual));
+  }
+
+  match failures {
+    0 => Ok(()),
+    _ => Err(anyhow!(
+      "{} of {} fixtures failed",
+      failures,
+      fixtures.len()
+    )),
+  }
+}
+
+/// A line based diff of two texts, showing changes with some context
+pub fn diff(expected: &str, actual: &str) -> String {
+  let a = expected.lines().collect::<Vec<_>>();
+  let b = actual.lines().collect::<Vec<_>>();
+
+  // Longest common subsequence lengths of every pair of suffixes
+  let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+  for i in (0..a.len()).rev() {
+    for j in (0..b.len()).rev() {
+      lengths[i][j] = match a[i] == b[j] {
+        true => lengths[i + 1][j + 1] + 1,
+        false => lengths[i + 1][j].max(lengths[i][j + 1]),
+      };
+    }
+  }
+
+  let mut lines: Vec<(char, &str)> = vec![];
+  let (mut i, mut j) = (0, 0);
+  while i < a.len() || j < b.len() {
+    if i < a.len() && j < b.len() && a[i] == b[j] {
+      lines.push((' ', a[i]));
+      i += 1;
+      j += 1;
+    } else if i < a.len()
+      && (j == b.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+    {
+      lines.push(('-', a[i]));
+      i += 1;
+    } else {
+      lines.push(('+', b[j]));
+      j += 1;
+    }
+  }
+
+  let changed = |index: usize| {
+    let start = index.saturating_sub(CONTEXT);
+    let end = (index + CONTEXT + 1).min(lines.len());
+    lines[start..end].iter().any(|(kind, _)| *kind != ' ')
+  };
+  let mut output = String::new();
+  let mut skipped = false;
+  for (index, (kind, line)) in lines.iter().enumerate() {
+    if !changed(index) {
+      skipped = true;
+      continue;
+    }
+    if skipped {
+      output.push_str("  ...\n");
+      skipped = false;
+    }
+    output.push_str(&format!("{} {}\n", kind, line));
+  }
+  output
+}
@@ -0,0 +1,160 @@
+use crate::frontmatter;
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{html, CodeBlockKind, Event, Parser, Tag};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Parse the `Q:` / `A:` pairs of a flashcard block
+///
+/// Questions and answers may span several lines, continuing until the next
+/// `Q:` or `A:` line.
+pub fn parse(text: &str) -> Vec<(String, String)> {
+  let mut cards: Vec<(String, String)> = vec![];
+  let mut answering = false;
+  for line in text.lines() {
+    if let Some(question) = line.strip_prefix("Q:") {
+      cards.push((question.trim().into(), String::new()));
+      answering = false;
+      continue;
+    }
+    let card = match cards.last_mut() {
+      Some(card) => card,
+      None => continue,
+    };
+    let text = match line.strip_prefix("A:") {
+      Some(answer) => {
+        answering = true;
+        answer.trim()
+      }
+      None => line,
+    };
+    let part = if answering { &mut card.1 } else { &mut card.0 };
+    if !part.is_empty() {
+      part.push('\n');
+    }
+    part.push_str(text);
+  }
+  cards
+}
+
+fn markdown(text: &str) -> String {
+  let mut rendered = String::new();
+  html::push_html(&mut rendered, Parser::new(text));
+  rendered.trim().into()
+}
+
+/// Transforms flashcard fenced code blocks into cards that flip when clicked
+pub struct FlashcardTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  open: bool,
+}
+
+impl<'a, T> FlashcardTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  /// Cards are rendered already turned over when `open` is set
+  pub fn new(iter: T, open: bool) -> Self {
+    FlashcardTransformer { iter, open }
+  }
+}
+
+impl<'a, T> Iterator for FlashcardTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+    match event {
+      Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+        if lang.as_ref() == "flashcard" => {}
+      _ => return Some(event),
+    }
+
+    let mut text = String::new();
+    for event in &mut self.iter {
+      match event {
+        Event::Text(chunk) => text.push_str(&chunk),
+        _ => break,
+      }
+    }
+
+    let open = if self.open { " open" } else { "" };
+    let mut output = String::from("<div class=\"flashcards\">\n");
+    for (question, answer) in parse(&text) {
+      output.push_str(&format!(
+        "<details class=\"flashcard\"{}>\n<summary>{}</summary>\n{}\n</details>\n",
+        open,
+        markdown(&question),
+        markdown(&answer)
+      ));
+    }
+    output.push_str("</div>\n");
+    Some(Event::Html(output.into()))
+  }
+}
+
+/// Collect the flashcards of some notes into an Anki importable deck
+pub fn export(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optopt("", "anki", "write a tab separated deck", "FILE");
+  let matches = opts.parse(args)?;
+
+  let deck = match matches.opt_str("anki") {
+    Some(deck) => deck,
+    None => {
+      let brief = "Usage: notebook export --anki DECK.tsv FILE...";
+      print!("{}", opts.usage(brief));
+      return Ok(());
+    }
+  };
+  if !deck.ends_with(".tsv") && !deck.ends_with(".txt") {
+    return Err(anyhow!("Decks are exported as tab separated '.tsv' files"));
+  }
+
+  let mut output = fs::File::create(&deck)?;
+  for path in &matches.free {
+    let input = fs::read_to_string(path)?;
+    let (_, input) = frontmatter::split(&input);
+    let tag = Path::new(path)
+      .file_stem()
+      .map(|stem| stem.to_string_lossy().replace(' ', "_"))
+      .unwrap_or_default();
+
+    let mut fenced = None;
+    for event in Parser::new(input) {
+      match event {
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang)))
+          if lang.as_ref() == "flashcard" =>
+        {
+          fenced = Some(String::new())
+        }
+        Event::Text(text) => {
+          if let Some(fenced) = &mut fenced {
+            fenced.push_str(&text);
+          }
+        }
+        Event::End(Tag::CodeBlock(_)) => {
+          for (question, answer) in parse(&fenced.take().unwrap_or_default()) {
+            let field = |text: &str| markdown(text).replace(['\t', '\n'], " ");
+            writeln!(
+              output,
+              "{}\t{}\t{}",
+              field(&question),
+              field(&answer),
+              tag
+            )?;
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+  Ok(())
+}
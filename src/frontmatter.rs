@@ -0,0 +1,28 @@
+use serde_json::{Map, Value};
+
+/// Split YAML front matter, delimited by `---` lines, from a document
+///
+/// A document whose front matter isn't a YAML mapping, such as one starting
+/// with a thematic break, is all body.
+pub fn split(input: &str) -> (Value, &str) {
+  let empty = Value::Object(Map::new());
+  let rest = match input.strip_prefix("---") {
+    Some(rest) if rest.starts_with('\n') || rest.starts_with("\r\n") => rest,
+    _ => return (empty, input),
+  };
+
+  let mut offset = 0;
+  for line in rest.split_inclusive('\n') {
+    let end = offset + line.len();
+    if offset > 0 && matches!(line.trim_end(), "---" | "...") {
+      return match serde_yaml::from_str(&rest[..offset]) {
+        Ok(Value::Null) => (empty, &rest[end..]),
+        Ok(meta @ Value::Object(_)) => (meta, &rest[end..]),
+        _ => (empty, input),
+      };
+    }
+    offset = end;
+  }
+
+  (empty, input)
+}
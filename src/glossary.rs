@@ -0,0 +1,176 @@
+use crate::data;
+use crate::util::{escape, slugify};
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{html, Event, Parser, Tag};
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+
+/// File name of the generated glossary page
+pub const PAGE: &str = "glossary.html";
+
+/// Terms and their definitions, loaded from a JSON, TOML or YAML map
+#[derive(Clone)]
+pub struct Glossary {
+  terms: Vec<(String, String)>,
+}
+
+impl Glossary {
+  pub fn load(path: &str) -> Result<Glossary> {
+    let terms = match data::parse(Path::new(path))? {
+      Value::Object(terms) => terms,
+      _ => return Err(anyhow!("Glossary '{}' must be a map of terms", path)),
+    };
+    let mut terms = terms
+      .into_iter()
+      .map(|(term, definition)| match definition {
+        Value::String(definition) => (term, definition),
+        definition => (term, definition.to_string()),
+      })
+      .collect::<Vec<_>>();
+    terms.sort_by_key(|(term, _)| term.to_lowercase());
+    Ok(Glossary { terms })
+  }
+
+  /// Markdown for a page listing every term and its definition
+  pub fn page(&self) -> String {
+    let mut page = String::from("# Glossary\n\n<dl>\n");
+    for (term, definition) in &self.terms {
+      let mut rendered = String::new();
+      html::push_html(&mut rendered, Parser::new(definition));
+      page.push_str(&format!(
+        "<dt id=\"{}\">{}</dt>\n<dd>{}</dd>\n",
+        slugify(term),
+        escape(term),
+        rendered.trim()
+      ));
+    }
+    page.push_str("</dl>\n");
+    page
+  }
+
+  /// Find the earliest term in some text that has not been linked yet
+  fn find(
+    &self,
+    text: &str,
+    used: &HashSet<usize>,
+  ) -> Option<(usize, usize, usize)> {
+    let mut boundary = true;
+    for (start, c) in text.char_indices() {
+      let is_word = c.is_alphanumeric();
+      if boundary && is_word {
+        let mut best: Option<(usize, usize)> = None;
+        for (index, (term, _)) in self.terms.iter().enumerate() {
+          let end = start + term.len();
+          let candidate = match text.get(start..end) {
+            Some(candidate) => candidate,
+            None => continue,
+          };
+          let ends_word = text[end..]
+            .chars()
+            .next()
+            .is_none_or(|c| !c.is_alphanumeric());
+          let longest = best.is_none_or(|(_, len)| term.len() > len);
+          if !used.contains(&index)
+            && ends_word
+            && longest
+            && candidate.eq_ignore_ascii_case(term)
+          {
+            best = Some((index, term.len()));
+          }
+        }
+        if let Some((index, len)) = best {
+          return Some((index, start, start + len));
+        }
+      }
+      boundary = !is_word;
+    }
+    None
+  }
+}
+
+/// Links the first occurrence of each glossary term to its definition
+pub struct GlossaryTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  glossary: &'a Glossary,
+  used: HashSet<usize>,
+  buffer: VecDeque<Event<'a>>,
+  skip: usize,
+}
+
+impl<'a, T> GlossaryTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T, glossary: &'a Glossary) -> Self {
+    GlossaryTransformer {
+      iter,
+      glossary,
+      used: HashSet::new(),
+      buffer: VecDeque::new(),
+      skip: 0,
+    }
+  }
+}
+
+impl<'a, T> Iterator for GlossaryTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(event) = self.buffer.pop_front() {
+      return Some(event);
+    }
+
+    let event = self.iter.next()?;
+    let text = match event {
+      // Terms are not linked inside of headings, links or code
+      Event::Start(Tag::Heading(_))
+      | Event::Start(Tag::Link(..))
+      | Event::Start(Tag::Image(..))
+      | Event::Start(Tag::CodeBlock(_)) => {
+        self.skip += 1;
+        return Some(event);
+      }
+      Event::End(Tag::Heading(_))
+      | Event::End(Tag::Link(..))
+      | Event::End(Tag::Image(..))
+      | Event::End(Tag::CodeBlock(_)) => {
+        self.skip -= 1;
+        return Some(event);
+      }
+      Event::Text(ref text) if self.skip == 0 => text.to_string(),
+      _ => return Some(event),
+    };
+
+    let mut rest = text.as_str();
+    while let Some((index, start, end)) = self.glossary.find(rest, &self.used) {
+      let (term, definition) = &self.glossary.terms[index];
+      self.used.insert(index);
+      if start > 0 {
+        self
+          .buffer
+          .push_back(Event::Text(rest[..start].to_string().into()));
+      }
+      let link = format!(
+        "<a class=\"glossary-term\" href=\"{}#{}\" title=\"{}\">{}</a>",
+        PAGE,
+        slugify(term),
+        escape(definition),
+        escape(&rest[start..end])
+      );
+      self.buffer.push_back(Event::Html(link.into()));
+      rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+      self.buffer.push_back(Event::Text(rest.to_string().into()));
+    }
+
+    self.buffer.pop_front()
+  }
+}
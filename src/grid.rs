@@ -0,0 +1,109 @@
+use crate::code;
+use crate::shortcode::parse_tag;
+use crate::util::escape;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// Styles for layout containers, which themes override by restyling the
+/// `layout`, `columns`, `grid` and `cell` classes or setting `--gap`
+pub const STYLE: &str = "\
+.layout { display: grid; gap: var(--gap, 1rem); }
+.layout.columns { grid-auto-flow: column; grid-auto-columns: minmax(0, 1fr); }
+.layout.grid { grid-template-columns: repeat(var(--cols, 2), minmax(0, 1fr)); }
+.layout > .cell > :first-child { margin-top: 0; }
+.layout > .cell > :last-child { margin-bottom: 0; }
+@media (max-width: 40em) {
+  .layout.columns, .layout.grid { grid-auto-flow: row; \
+grid-template-columns: minmax(0, 1fr); }
+}
+";
+
+enum Frame {
+  Layout(String),
+  Cell,
+  Container,
+}
+
+/// Expand `:::columns` and `:::grid cols=3` containers into CSS grids
+///
+/// Each `:::column` or `:::cell` container inside a layout is one of its
+/// cells. Layouts take a `gap`, and a `class` for themes to style.
+pub fn expand(input: &str) -> Result<String> {
+  let mut output = String::with_capacity(input.len());
+  let mut frames: Vec<Frame> = vec![];
+
+  for (code, line) in code::lines(input) {
+    let directive = match line.trim().strip_prefix(":::") {
+      Some(directive) if !code => directive.trim(),
+      _ => {
+        output.push_str(line);
+        continue;
+      }
+    };
+
+    if directive.is_empty() {
+      match frames.pop() {
+        Some(Frame::Layout(_)) | Some(Frame::Cell) => {
+          output.push_str("\n</div>\n\n")
+        }
+        _ => output.push_str(line),
+      }
+      continue;
+    }
+
+    let name = directive.split_whitespace().next().unwrap_or("");
+    let in_layout = matches!(frames.last(), Some(Frame::Layout(_)));
+    match name {
+      "columns" | "grid" => {
+        let (name, args) = parse_tag(directive)?;
+        output.push_str(&open(&name, &args)?);
+        frames.push(Frame::Layout(name));
+      }
+      "column" | "cell" if in_layout => {
+        output.push_str("<div class=\"cell\">\n\n");
+        frames.push(Frame::Cell);
+      }
+      _ => {
+        output.push_str(line);
+        frames.push(Frame::Container);
+      }
+    }
+  }
+
+  for frame in frames {
+    if let Frame::Layout(name) = frame {
+      return Err(anyhow!("Unterminated ':::{}' block", name));
+    }
+  }
+  Ok(output)
+}
+
+/// Whether some HTML has layout containers, and so needs their styles
+pub fn has_layout(html: &str) -> bool {
+  html.contains("<div class=\"layout ")
+}
+
+/// The opening tag of a layout
+fn open(name: &str, args: &serde_json::Map<String, Value>) -> Result<String> {
+  let arg = |key| args.get(key).and_then(Value::as_str);
+  let mut style = vec![];
+  if name == "grid" {
+    let cols = arg("cols").unwrap_or("2");
+    match cols.parse::<usize>() {
+      Ok(cols) if cols > 0 => style.push(format!("--cols: {}", cols)),
+      _ => return Err(anyhow!("Invalid number of columns '{}'", cols)),
+    }
+  }
+  if let Some(gap) = arg("gap") {
+    style.push(format!("--gap: {}", gap));
+  }
+  let class = match arg("class") {
+    Some(class) => format!("layout {} {}", name, class),
+    None => format!("layout {}", name),
+  };
+  let style = match style.is_empty() {
+    true => "".into(),
+    false => format!(" style=\"{}\"", escape(&style.join("; "))),
+  };
+  Ok(format!("<div class=\"{}\"{}>\n\n", escape(&class), style))
+}
@@ -0,0 +1,190 @@
+use crate::vfs::Symlinks;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Extensions of the files served in serve mode, unless others are given
+/// with `--assets-extension`
+pub const EXTENSIONS: [&str; 28] = [
+  "html",
+  "css",
+  "js",
+  "mjs",
+  "json",
+  "map",
+  "xml",
+  "txt",
+  "pdf",
+  "svg",
+  "png",
+  "jpg",
+  "jpeg",
+  "gif",
+  "webp",
+  "avif",
+  "ico",
+  "webmanifest",
+  "woff",
+  "woff2",
+  "ttf",
+  "otf",
+  "mp3",
+  "ogg",
+  "wav",
+  "m4a",
+  "mp4",
+  "webm",
+];
+
+/// The files of a directory that may be served to anyone who can reach the
+/// server
+///
+/// Paths are confined to the directory, with any `..` refused and symlinks
+/// followed only while they stay within it, or not at all unless they are
+/// followed in builds. Hidden files and directories, such as `.env` or
+/// `.git`, are never served, and other files only with an allowed extension.
+#[derive(Clone)]
+pub struct Guard {
+  extensions: Vec<String>,
+  symlinks: Symlinks,
+}
+
+impl Guard {
+  /// A guard allowing some extensions, or the default ones if none are given
+  pub fn new(extensions: &[String], symlinks: Symlinks) -> Guard {
+    let extensions = match extensions.is_empty() {
+      true => EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+      false => extensions
+        .iter()
+        .flat_map(|ext| ext.split(','))
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect(),
+    };
+    Guard {
+      extensions,
+      symlinks,
+    }
+  }
+
+  /// Whether the file at a request's path, still percent encoded, within a
+  /// directory may be served
+  pub fn allows(&self, root: &Path, path: &str) -> bool {
+    let relative = match relative(path) {
+      Some(relative) => relative,
+      None => return false,
+    };
+    let allowed = relative
+      .extension()
+      .map(|ext| ext.to_string_lossy().to_lowercase())
+      .is_some_and(|ext| self.extensions.contains(&ext));
+    allowed && self.resolves(root, &relative)
+  }
+
+  /// The path of a note within a directory at a request's path, still
+  /// percent encoded, if it may be rendered, as from a git revision when it
+  /// may no longer be on disk
+  pub fn note(&self, root: &Path, path: &str) -> Option<PathBuf> {
+    self.named_note(root, &decode(path)?)
+  }
+
+  /// The path of a note within a directory by its name, decoded already, if
+  /// it may be rendered or written, as when it is edited
+  ///
+  /// A note not yet on disk must be created within the directory, by the
+  /// symlinks allowed.
+  pub fn named_note(&self, root: &Path, name: &str) -> Option<PathBuf> {
+    let relative = confine(name)?;
+    let markdown = relative
+      .extension()
+      .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+    let on_disk = root.join(&relative).exists();
+    let allowed = markdown
+      && match on_disk {
+        true => self.resolves(root, &relative),
+        false => !self.linked(root, &relative) && self.creates(root, &relative),
+      };
+    allowed.then(|| root.join(relative))
+  }
+
+  /// Whether a path within a directory leads to a file within it, by the
+  /// symlinks allowed
+  fn resolves(&self, root: &Path, relative: &Path) -> bool {
+    if self.linked(root, relative) {
+      return false;
+    }
+    // The file must resolve within the directory, whatever links lead to it
+    match (
+      fs::canonicalize(root),
+      fs::canonicalize(root.join(relative)),
+    ) {
+      (Ok(root), Ok(file)) => file.starts_with(root),
+      _ => false,
+    }
+  }
+
+  /// Whether a file not yet on disk would be created within a directory, by
+  /// the directory nearest it that is
+  fn creates(&self, root: &Path, relative: &Path) -> bool {
+    let mut dir = relative.to_path_buf();
+    while !root.join(&dir).exists() {
+      if !dir.pop() {
+        return false;
+      }
+    }
+    self.resolves(root, &dir)
+  }
+
+  /// Whether a path within a directory passes through a symlink, when they
+  /// aren't followed
+  fn linked(&self, root: &Path, relative: &Path) -> bool {
+    if self.symlinks == Symlinks::Follow {
+      return false;
+    }
+    let mut path = root.to_path_buf();
+    relative.components().any(|component| {
+      path.push(component);
+      fs::symlink_metadata(&path)
+        .is_ok_and(|meta| meta.file_type().is_symlink())
+    })
+  }
+}
+
+/// The path of a request, still percent encoded, if it stays within its
+/// directory and names nothing hidden
+fn relative(path: &str) -> Option<PathBuf> {
+  confine(&decode(path)?)
+}
+
+/// A decoded path, if it stays within its directory and names nothing hidden
+fn confine(name: &str) -> Option<PathBuf> {
+  if name.contains(['\0', '\\']) {
+    return None;
+  }
+  let path = PathBuf::from(name);
+  let confined = path.components().all(|component| match component {
+    Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+    _ => false,
+  });
+  confined.then_some(path)
+}
+
+/// A percent encoded path, if it decodes to UTF-8 without a `NUL` or `\`
+pub fn decode(path: &str) -> Option<String> {
+  let mut bytes = vec![];
+  let mut rest = path.as_bytes();
+  while let Some((&byte, tail)) = rest.split_first() {
+    rest = tail;
+    if byte != b'%' {
+      bytes.push(byte);
+      continue;
+    }
+    let hex = std::str::from_utf8(rest.get(..2)?).ok()?;
+    bytes.push(u8::from_str_radix(hex, 16).ok()?);
+    rest = &rest[2..];
+  }
+  let path = String::from_utf8(bytes).ok()?;
+  match path.contains(['\0', '\\']) {
+    true => None,
+    false => Some(path),
+  }
+}
@@ -0,0 +1,85 @@
+use crate::util::escape;
+use serde_json::Value;
+
+/// Tags that features need in the `<head>` of a document
+///
+/// Each tag is included once, however many features ask for it, and the
+/// whole set is given to templates as `head_extra`.
+#[derive(Default)]
+pub struct Head {
+  tags: Vec<String>,
+}
+
+impl Head {
+  fn push(&mut self, tag: String) {
+    if !self.tags.contains(&tag) {
+      self.tags.push(tag);
+    }
+  }
+
+  /// A script file, run once the document has been parsed
+  pub fn script(&mut self, src: &str) {
+    self.push(format!("<script src=\"{}\" defer></script>", escape(src)));
+  }
+
+  /// An inline script, run once the document has been parsed
+  pub fn inline_script(&mut self, code: &str) {
+    self.push(format!(
+      "<script>\ndocument.addEventListener(\"DOMContentLoaded\", function () \
+       {{\n{}}});\n</script>",
+      code
+    ));
+  }
+
+  /// A stylesheet file
+  pub fn style(&mut self, href: &str) {
+    self.push(format!(
+      "<link rel=\"stylesheet\" href=\"{}\">",
+      escape(href)
+    ));
+  }
+
+  /// An inline stylesheet
+  pub fn inline_style(&mut self, css: &str) {
+    self.push(format!("<style>\n{}</style>", css));
+  }
+
+  /// A resource to fetch early, such as a font
+  pub fn preload(&mut self, href: &str, kind: &str) {
+    self.push(format!(
+      "<link rel=\"preload\" href=\"{}\" as=\"{}\">",
+      escape(href),
+      escape(kind)
+    ));
+  }
+
+  /// An icon for the document, such as a favicon
+  pub fn icon(&mut self, rel: &str, href: &str, kind: &str, sizes: &str) {
+    self.push(format!(
+      "<link rel=\"{}\" href=\"{}\" type=\"{}\" sizes=\"{}\">",
+      escape(rel),
+      escape(href),
+      escape(kind),
+      escape(sizes)
+    ));
+  }
+
+  /// A web app manifest
+  pub fn manifest(&mut self, href: &str) {
+    self.push(format!("<link rel=\"manifest\" href=\"{}\">", escape(href)));
+  }
+
+  /// Structured data describing the document
+  pub fn json_ld(&mut self, data: &Value) {
+    // A closing script tag inside a string must not end the script early
+    let data = data.to_string().replace("</", "<\\/");
+    self.push(format!(
+      "<script type=\"application/ld+json\">{}</script>",
+      data
+    ));
+  }
+
+  pub fn to_html(&self) -> String {
+    self.tags.iter().map(|tag| format!("{}\n", tag)).collect()
+  }
+}
@@ -0,0 +1,321 @@
+use crate::events::Events;
+use crate::util::{escape, slugify};
+use anyhow::Result;
+use pulldown_cmark::{html, Event, Tag};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// A heading of a document, for its table of contents
+pub struct Heading {
+  pub level: u32,
+  pub id: String,
+  pub text: String,
+}
+
+/// Gives every heading an id, either explicit with `{#id}` or from its text
+///
+/// Generated ids are made unique within the document by appending a number.
+pub struct HeadingTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  /// Each heading, in document order
+  pub headings: Vec<Heading>,
+  used: HashSet<String>,
+}
+
+impl<'a, T> HeadingTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T) -> Self {
+    HeadingTransformer {
+      iter,
+      headings: vec![],
+      used: HashSet::new(),
+    }
+  }
+}
+
+/// Remove a trailing `{#id}` from some heading text
+pub fn strip_id(text: &str) -> &str {
+  let trimmed = text.trim_end();
+  match trimmed.rfind("{#") {
+    Some(start) if trimmed.ends_with('}') => trimmed[..start].trim_end(),
+    _ => text,
+  }
+}
+
+/// Strip a trailing `{#id}` from the text of a heading, returning the id
+fn explicit_id(events: &mut Vec<Event>) -> Option<String> {
+  let mut text = String::new();
+  let mut count = 0;
+  for event in events.iter().rev() {
+    match event {
+      Event::Text(chunk) => text.insert_str(0, chunk),
+      _ => break,
+    }
+    count += 1;
+  }
+
+  let trimmed = text.trim_end();
+  let start = trimmed.rfind("{#")?;
+  let id = trimmed[start + 2..].strip_suffix('}')?.trim();
+  if id.is_empty() || id.contains(char::is_whitespace) {
+    return None;
+  }
+  let id = id.to_string();
+
+  events.truncate(events.len() - count);
+  let rest = text[..start].trim_end();
+  if !rest.is_empty() {
+    events.push(Event::Text(rest.to_string().into()));
+  }
+  Some(id)
+}
+
+impl<'a, T> Iterator for HeadingTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+    let level = match event {
+      Event::Start(Tag::Heading(level)) => level,
+      _ => return Some(event),
+    };
+
+    let mut events = vec![];
+    for event in &mut self.iter {
+      if let Event::End(Tag::Heading(_)) = event {
+        break;
+      }
+      events.push(event);
+    }
+
+    let explicit = explicit_id(&mut events);
+    let text = events
+      .iter()
+      .filter_map(|event| match event {
+        Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+        _ => None,
+      })
+      .collect::<String>();
+    let id = match explicit {
+      Some(id) => id,
+      None => {
+        let slug = slugify(&text);
+        let mut id = slug.clone();
+        let mut n = 1;
+        while self.used.contains(&id) {
+          id = format!("{}-{}", slug, n);
+          n += 1;
+        }
+        id
+      }
+    };
+    self.used.insert(id.clone());
+    self.headings.push(Heading {
+      level,
+      id: id.clone(),
+      text: text.trim().to_string(),
+    });
+
+    let mut content = String::new();
+    html::push_html(&mut content, events.into_iter());
+    Some(Event::Html(
+      format!(
+        "<h{} id=\"{}\">{}</h{}>\n",
+        level,
+        escape(&id),
+        content,
+        level
+      )
+      .into(),
+    ))
+  }
+}
+
+/// The content of a document split at its headings, outside of other blocks,
+/// as `heading`, `level`, `id` and `html` without the heading itself
+///
+/// Any content before the first heading is a section of level 0 without a
+/// heading.
+pub fn sections(events: &[Event], headings: &[Heading]) -> Value {
+  let mut sections = vec![];
+  let mut current: (Option<&Heading>, Vec<Event>) = (None, vec![]);
+  let mut remaining = headings.iter().peekable();
+  let mut depth = 0;
+
+  let mut push = |(heading, events): (Option<&Heading>, Vec<Event>)| {
+    if heading.is_none() && events.is_empty() {
+      return;
+    }
+    let mut content = String::new();
+    html::push_html(&mut content, events.into_iter());
+    sections.push(json!({
+      "heading": heading.map(|heading| &heading.text),
+      "level": heading.map(|heading| heading.level).unwrap_or(0),
+      "id": heading.map(|heading| &heading.id),
+      "html": content,
+    }));
+  };
+  for event in events {
+    match event {
+      Event::Start(_) => depth += 1,
+      Event::End(_) => depth -= 1,
+      _ => {}
+    }
+    // Headings are rendered as HTML by the time the document is split
+    let next = remaining.peek().copied().filter(|heading| match event {
+      Event::Html(html) => html.starts_with(&format!(
+        "<h{} id=\"{}\">",
+        heading.level,
+        escape(&heading.id)
+      )),
+      _ => false,
+    });
+    if let Some(heading) = next {
+      remaining.next();
+      // Headings inside other blocks stay in their section
+      if depth == 0 {
+        push(std::mem::replace(&mut current, (Some(heading), vec![])));
+        continue;
+      }
+    }
+    current.1.push(event.clone());
+  }
+  push(current);
+  Value::Array(sections)
+}
+
+/// The headings of a document nested under the headings above them, each
+/// with its `level`, `id`, `text` and `children`
+pub fn toc(headings: &[Heading]) -> Value {
+  // Headings are nested under the nearest heading of a lower level
+  fn nest(headings: &[Heading], at: &mut usize, level: u32) -> Vec<Value> {
+    let mut nodes = vec![];
+    while let Some(heading) = headings.get(*at) {
+      if heading.level <= level {
+        break;
+      }
+      *at += 1;
+      let children = nest(headings, at, heading.level);
+      nodes.push(json!({
+        "level": heading.level,
+        "id": heading.id,
+        "text": heading.text,
+        "children": children,
+      }));
+    }
+    nodes
+  }
+  let mut at = 0;
+  let mut nodes = vec![];
+  while at < headings.len() {
+    nodes.extend(nest(headings, &mut at, 0));
+  }
+  Value::Array(nodes)
+}
+
+/// HTML for a nested list of links to the headings of a document
+fn toc_html(toc: &Value) -> String {
+  fn list(nodes: &[Value], html: &mut String) {
+    html.push_str("<ul>\n");
+    for node in nodes {
+      let text = |key: &str| node.get(key).and_then(Value::as_str);
+      html.push_str(&format!(
+        "<li><a href=\"#{}\">{}</a>",
+        escape(text("id").unwrap_or("")),
+        escape(text("text").unwrap_or(""))
+      ));
+      match node.get("children").and_then(Value::as_array) {
+        Some(children) if !children.is_empty() => {
+          html.push('\n');
+          list(children, html);
+        }
+        _ => {}
+      }
+      html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+  }
+  let mut html = String::from("<nav class=\"toc\">\n");
+  list(toc.as_array().map(Vec::as_slice).unwrap_or(&[]), &mut html);
+  html.push_str("</nav>\n");
+  html
+}
+
+/// Replace paragraphs of only `[TOC]` with a table of contents
+pub fn expand_toc<'a>(events: Vec<Event<'a>>, toc: &Value) -> Vec<Event<'a>> {
+  let mut expanded = Vec::with_capacity(events.len());
+  let mut at = 0;
+  while at < events.len() {
+    if let Event::Start(Tag::Paragraph) = events[at] {
+      let end = events[at..]
+        .iter()
+        .position(|event| matches!(event, Event::End(Tag::Paragraph)));
+      if let Some(end) = end {
+        let text = events[at + 1..at + end]
+          .iter()
+          .map(|event| match event {
+            Event::Text(text) => Some(text.as_ref()),
+            _ => None,
+          })
+          .collect::<Option<String>>();
+        if text.as_deref().map(str::trim) == Some("[TOC]") {
+          expanded.push(Event::Html(toc_html(toc).into()));
+          at += end + 1;
+          continue;
+        }
+      }
+    }
+    expanded.push(events[at].clone());
+    at += 1;
+  }
+  expanded
+}
+
+/// Warn about heading ids that existed in a document's previous build
+///
+/// The ids of each build are recorded in `.notebook/headings.json` beside the
+/// document, so that renamed headings breaking inbound links are noticed.
+pub fn check(
+  dir: &Path,
+  document: &str,
+  ids: &[String],
+  events: &Events,
+) -> Result<()> {
+  let path = dir.join(".notebook").join("headings.json");
+  let mut manifest = match fs::read_to_string(&path) {
+    Ok(text) => match serde_json::from_str(&text)? {
+      Value::Object(manifest) => manifest,
+      _ => Map::new(),
+    },
+    Err(_) => Map::new(),
+  };
+
+  if let Some(Value::Array(previous)) = manifest.get(document) {
+    for id in previous.iter().filter_map(Value::as_str) {
+      if !ids.iter().any(|other| other == id) {
+        events.warn(&format!(
+          "heading id '{}' no longer exists in {}",
+          id, document
+        ));
+      }
+    }
+  }
+
+  let ids = ids.iter().cloned().map(Value::String).collect();
+  manifest.insert(document.into(), Value::Array(ids));
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  fs::write(&path, serde_json::to_string_pretty(&manifest)?)?;
+  Ok(())
+}
@@ -0,0 +1,148 @@
+use crate::edit;
+use crate::fixture::diff;
+use crate::util::{date, encode_path, escape};
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Route of the history of the notes edited in the web server
+pub const PATH: &str = "history";
+
+/// The most versions of a note kept, after which the oldest are dropped
+const LIMIT: usize = 50;
+
+/// The file of the draft of a note, among its versions
+const DRAFT: &str = "draft.md";
+
+/// Earlier versions of the notes edited in the browser, and drafts of edits
+/// not yet saved, kept in `.notebook/history` beside the notes
+///
+/// Each save keeps the text it replaces, as `NOTE.md/STAMP.md` with the time
+/// of the save in milliseconds, so that a bad save can be undone without the
+/// notebook being kept in version control.
+pub struct History {
+  dir: PathBuf,
+}
+
+impl History {
+  pub fn new(dir: &Path) -> History {
+    History {
+      dir: dir.join(".notebook").join(PATH),
+    }
+  }
+
+  /// Keep a version of a note, dropping the oldest beyond the limit
+  pub fn keep(&self, note: &str, text: &str) -> Result<()> {
+    let dir = self.dir.join(note);
+    fs::create_dir_all(&dir)?;
+    let millis = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_millis())
+      .unwrap_or(0);
+    fs::write(dir.join(format!("{:015}.md", millis)), text)?;
+    for stamp in self.versions(note).iter().skip(LIMIT) {
+      fs::remove_file(dir.join(format!("{}.md", stamp)))?;
+    }
+    Ok(())
+  }
+
+  /// The stamps of the kept versions of a note, newest first
+  pub fn versions(&self, note: &str) -> Vec<String> {
+    let entries = match fs::read_dir(self.dir.join(note)) {
+      Ok(entries) => entries,
+      Err(_) => return vec![],
+    };
+    let mut stamps = entries
+      .filter_map(|entry| entry.ok())
+      .filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let stamp = name.strip_suffix(".md")?.to_string();
+        is_stamp(&stamp).then_some(stamp)
+      })
+      .collect::<Vec<_>>();
+    stamps.sort_by(|a, b| b.cmp(a));
+    stamps
+  }
+
+  /// The text of a kept version of a note
+  pub fn version(&self, note: &str, stamp: &str) -> Result<String> {
+    if !is_stamp(stamp) {
+      return Err(anyhow!("Invalid version '{}'", stamp));
+    }
+    let path = self.dir.join(note).join(format!("{}.md", stamp));
+    fs::read_to_string(path)
+      .map_err(|_| anyhow!("Unknown version '{}' of '{}'", stamp, note))
+  }
+
+  /// The draft of an edit of a note, if there is one
+  pub fn draft(&self, note: &str) -> Option<String> {
+    fs::read_to_string(self.dir.join(note).join(DRAFT)).ok()
+  }
+
+  pub fn save_draft(&self, note: &str, text: &str) -> Result<()> {
+    let dir = self.dir.join(note);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(DRAFT), text)?;
+    Ok(())
+  }
+
+  /// Drop the draft of a note, once its edit is saved
+  pub fn discard_draft(&self, note: &str) {
+    fs::remove_file(self.dir.join(note).join(DRAFT)).ok();
+  }
+
+  /// A page of the kept versions of a note, each with its changes from the
+  /// note's current text and a form restoring it
+  pub fn page(&self, note: &str, current: &str, version: &str) -> String {
+    let mut html = format!(
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>\
+       History of {}</title>\n</head>\n<body>\n<h1>History of {}</h1>\n",
+      escape(note),
+      escape(note)
+    );
+    html.push_str(&format!(
+      "<p><a href=\"/{}/{}\">Edit the note</a></p>\n",
+      edit::PATH,
+      escape(&encode_path(note))
+    ));
+    let stamps = self.versions(note);
+    if stamps.is_empty() {
+      html.push_str("<p class=\"history empty\">No earlier versions</p>\n");
+    }
+    for stamp in stamps {
+      let text = self.version(note, &stamp).unwrap_or_default();
+      html.push_str(&format!(
+        "<details class=\"version\">\n<summary>{}</summary>\n\
+         <pre class=\"diff\">{}</pre>\n<form method=\"post\" \
+         action=\"/{}/{}\">\n<input type=\"hidden\" name=\"restore\" \
+         value=\"{}\">\n<input type=\"hidden\" name=\"version\" value=\"{}\">\n\
+         <button>Restore</button>\n</form>\n</details>\n",
+        time(&stamp),
+        escape(&diff(current, &text)),
+        PATH,
+        escape(&encode_path(note)),
+        stamp,
+        version
+      ));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+  }
+}
+
+fn is_stamp(stamp: &str) -> bool {
+  !stamp.is_empty() && stamp.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// The UTC time of a stamp, as `YYYY-MM-DD HH:MM:SS`
+fn time(stamp: &str) -> String {
+  let seconds = stamp.parse::<i64>().unwrap_or(0) / 1000;
+  format!(
+    "{} {:02}:{:02}:{:02} UTC",
+    date(seconds.div_euclid(86400)),
+    seconds.rem_euclid(86400) / 3600,
+    seconds.rem_euclid(3600) / 60,
+    seconds.rem_euclid(60)
+  )
+}
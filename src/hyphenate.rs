@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{Event, Tag};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const SOFT_HYPHEN: char = '\u{ad}';
+const LEFT_MIN: usize = 2;
+const RIGHT_MIN: usize = 3;
+
+/// Hyphenation patterns for Liang's algorithm, as used by TeX
+pub struct Patterns {
+  patterns: HashMap<String, Vec<u8>>,
+  longest: usize,
+}
+
+impl Patterns {
+  /// Load the `hyph-LANG.pat.txt` patterns file for a language from a directory
+  ///
+  /// These are the files distributed by the hyph-utf8 project, with one
+  /// pattern per line.
+  pub fn load(dir: &str, lang: &str) -> Result<Patterns> {
+    let lang = lang.to_lowercase().replace('_', "-");
+    let candidates =
+      [lang.clone(), lang.split('-').next().unwrap_or("").into()];
+    let path = candidates
+      .iter()
+      .map(|lang| Path::new(dir).join(format!("hyph-{}.pat.txt", lang)))
+      .find(|path| path.exists())
+      .ok_or_else(|| anyhow!("No hyphenation patterns for '{}'", lang))?;
+    Ok(Patterns::parse(&fs::read_to_string(path)?))
+  }
+
+  fn parse(text: &str) -> Patterns {
+    let mut patterns = HashMap::new();
+    let mut longest = 0;
+    for pattern in text.split_whitespace() {
+      let mut letters = String::new();
+      let mut values = vec![0];
+      for c in pattern.chars() {
+        match c.to_digit(10) {
+          Some(value) => *values.last_mut().expect("A value") = value as u8,
+          None => {
+            letters.push(c);
+            values.push(0);
+          }
+        }
+      }
+      longest = longest.max(letters.chars().count());
+      patterns.insert(letters, values);
+    }
+    Patterns { patterns, longest }
+  }
+
+  /// Insert soft hyphens at the permitted break points of a word
+  fn hyphenate(&self, word: &str) -> String {
+    let chars = word.chars().collect::<Vec<_>>();
+    if chars.len() < LEFT_MIN + RIGHT_MIN {
+      return word.into();
+    }
+
+    let dotted = format!(".{}.", word.to_lowercase())
+      .chars()
+      .collect::<Vec<_>>();
+    let mut values = vec![0u8; dotted.len() + 1];
+    for start in 0..dotted.len() {
+      let end = dotted.len().min(start + self.longest);
+      for stop in start + 1..=end {
+        let key = dotted[start..stop].iter().collect::<String>();
+        if let Some(pattern) = self.patterns.get(&key) {
+          for (i, value) in pattern.iter().enumerate() {
+            let slot = &mut values[start + i];
+            *slot = (*slot).max(*value);
+          }
+        }
+      }
+    }
+
+    let mut output = String::with_capacity(word.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+      // The value between letters i - 1 and i, offset by the leading dot
+      let breaks =
+        i >= LEFT_MIN && i <= chars.len() - RIGHT_MIN && values[i + 1] % 2 == 1;
+      if breaks {
+        output.push(SOFT_HYPHEN);
+      }
+      output.push(*c);
+    }
+    output
+  }
+}
+
+/// Inserts soft hyphens into the words of text outside of headings and code
+pub struct HyphenateTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  patterns: Patterns,
+  skip: usize,
+}
+
+impl<'a, T> HyphenateTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T, patterns: Patterns) -> Self {
+    HyphenateTransformer {
+      iter,
+      patterns,
+      skip: 0,
+    }
+  }
+}
+
+impl<'a, T> Iterator for HyphenateTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+    let text = match event {
+      Event::Start(Tag::Heading(_)) | Event::Start(Tag::CodeBlock(_)) => {
+        self.skip += 1;
+        return Some(event);
+      }
+      Event::End(Tag::Heading(_)) | Event::End(Tag::CodeBlock(_)) => {
+        self.skip -= 1;
+        return Some(event);
+      }
+      Event::Text(ref text) if self.skip == 0 => text,
+      _ => return Some(event),
+    };
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text.as_ref();
+    while let Some(start) = rest.find(char::is_alphabetic) {
+      output.push_str(&rest[..start]);
+      rest = &rest[start..];
+      let end = rest
+        .find(|c: char| !c.is_alphabetic())
+        .unwrap_or(rest.len());
+      output.push_str(&self.patterns.hyphenate(&rest[..end]));
+      rest = &rest[end..];
+    }
+    output.push_str(rest);
+    Some(Event::Text(output.into()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn words_break_where_their_patterns_allow() {
+    let patterns =
+      Patterns::parse("hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n");
+    assert_eq!(patterns.hyphenate("Hyphenation"), "Hy\u{ad}phen\u{ad}ation");
+    // Too short to break, with two letters before and three after
+    assert_eq!(patterns.hyphenate("hyph"), "hyph");
+  }
+}
@@ -0,0 +1,383 @@
+use crate::assets::Assets;
+use crate::compress::{deflate, inflate};
+use crate::head::Head;
+use crate::util::crc32;
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Sizes of the images of `favicon.ico`
+const FAVICON: [u32; 3] = [16, 32, 48];
+
+/// Size of the touch icon, as iOS uses
+const TOUCH: u32 = 180;
+
+/// Sizes of the icons of the web app manifest, which browsers need to install
+/// it
+const MANIFEST: [u32; 2] = [192, 512];
+
+/// The image a notebook's favicon, touch icon and app icons are made from
+///
+/// PNG images must be square and at least as large as the largest icon, 512
+/// pixels, and are scaled down to each icon's size once, when loaded. SVG
+/// images are used as they are, at any size.
+#[derive(Clone)]
+pub enum Icon {
+  /// PNG images of each size of icon
+  Png(BTreeMap<u32, Vec<u8>>),
+  Svg(Vec<u8>),
+}
+
+impl Icon {
+  pub fn load(path: &str) -> Result<Icon> {
+    let contents = fs::read(path)?;
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+      Some("svg") => Ok(Icon::Svg(contents)),
+      Some("png") => {
+        let (width, height, pixels) = decode(&contents)
+          .map_err(|err| anyhow!("Invalid PNG icon '{}': {}", path, err))?;
+        let largest = MANIFEST[MANIFEST.len() - 1];
+        if width != height || width < largest {
+          return Err(anyhow!(
+            "PNG icons must be square and at least {} pixels '{}'",
+            largest,
+            path
+          ));
+        }
+        let sizes = FAVICON.iter().chain(&[TOUCH]).chain(&MANIFEST);
+        let pngs = sizes
+          .map(|to| (*to, encode(*to, &scale(&pixels, width, *to))))
+          .collect();
+        Ok(Icon::Png(pngs))
+      }
+      _ => Err(anyhow!("Icons must be PNG or SVG images '{}'", path)),
+    }
+  }
+
+  /// Add the icon files and web app manifest to a document's assets, and tags
+  /// linking to them to its head
+  pub fn add(&self, name: &str, assets: &mut Assets, head: &mut Head) {
+    let icons = match self {
+      Icon::Png(pngs) => {
+        let png = |size: u32| pngs[&size].clone();
+        let favicon = ico(&FAVICON.map(|size| (size, png(size))));
+        let favicon = assets.add("favicon.ico", favicon);
+        let sizes = FAVICON.map(|size| format!("{}x{}", size, size)).join(" ");
+        head.icon("icon", &favicon, "image/x-icon", &sizes);
+        let sizes = format!("{}x{}", TOUCH, TOUCH);
+        let touch = assets.add("apple-touch-icon.png", png(TOUCH));
+        head.icon("apple-touch-icon", &touch, "image/png", &sizes);
+        MANIFEST
+          .iter()
+          .map(|size| {
+            let icon = assets.add(&format!("icon-{}.png", size), png(*size));
+            (icon, "image/png", format!("{}x{}", size, size))
+          })
+          .collect::<Vec<_>>()
+      }
+      Icon::Svg(contents) => {
+        let icon = assets.add("icon.svg", contents.clone());
+        head.icon("icon", &icon, "image/svg+xml", "any");
+        vec![(icon, "image/svg+xml", "any".to_string())]
+      }
+    };
+
+    // The manifest is beside the icons, so links to them by their file names
+    let icons = icons
+      .iter()
+      .map(|(icon, kind, sizes)| {
+        let src = icon.rsplit('/').next().unwrap_or(icon);
+        json!({"src": src, "type": kind, "sizes": sizes})
+      })
+      .collect::<Vec<_>>();
+    let manifest = json!({
+      "name": name,
+      "short_name": name,
+      "start_url": "../",
+      "display": "standalone",
+      "icons": icons,
+    });
+    let manifest = serde_json::to_vec_pretty(&manifest).expect("Manifest");
+    head.manifest(&assets.add("manifest.webmanifest", manifest));
+  }
+}
+
+/// The width, height and RGBA pixels of a PNG image
+///
+/// Images of any colour type are read, at 8 or 16 bits a sample, or fewer for
+/// grey and palette images, but not interlaced ones.
+fn decode(png: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+  let mut rest = png.strip_prefix(PNG).ok_or_else(|| anyhow!("no header"))?;
+  let (mut header, mut palette, mut alpha, mut data) =
+    (None, vec![], vec![], vec![]);
+  while rest.len() >= 12 {
+    let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+    let length = length as usize;
+    let chunk = rest
+      .get(8..8 + length)
+      .ok_or_else(|| anyhow!("truncated"))?;
+    match &rest[4..8] {
+      b"IHDR" => header = Some(chunk.to_vec()),
+      b"PLTE" => palette = chunk.to_vec(),
+      b"tRNS" => alpha = chunk.to_vec(),
+      b"IDAT" => data.extend_from_slice(chunk),
+      b"IEND" => break,
+      _ => {}
+    }
+    rest = rest.get(12 + length..).unwrap_or_default();
+  }
+  let header = header.filter(|header| header.len() == 13);
+  let header = header.ok_or_else(|| anyhow!("no IHDR chunk"))?;
+  let number = |at: usize| {
+    u32::from_be_bytes([
+      header[at],
+      header[at + 1],
+      header[at + 2],
+      header[at + 3],
+    ])
+  };
+  let (width, height) = (number(0), number(4));
+  let (depth, colour, interlaced) = (header[8], header[9], header[12]);
+  if interlaced != 0 {
+    return Err(anyhow!("interlaced images aren't supported"));
+  }
+  let channels = match colour {
+    0 | 3 => 1,
+    2 => 3,
+    4 => 2,
+    6 => 4,
+    _ => return Err(anyhow!("unknown colour type {}", colour)),
+  };
+  let supported = match colour {
+    0 => matches!(depth, 1 | 2 | 4 | 8 | 16),
+    3 => matches!(depth, 1 | 2 | 4 | 8),
+    _ => matches!(depth, 8 | 16),
+  };
+  if !supported {
+    return Err(anyhow!("unsupported bit depth {}", depth));
+  }
+
+  // The zlib stream of the image data, without its header and checksum
+  let bits = channels * depth as usize;
+  let stride = (width as usize * bits).div_ceil(8);
+  let bpp = bits.div_ceil(8);
+  let size = (stride + 1).saturating_mul(height as usize);
+  let raw = inflate(data.get(2..).unwrap_or_default(), size)?;
+  if raw.len() < (stride + 1) * height as usize {
+    return Err(anyhow!("truncated image data"));
+  }
+  let mut previous = vec![0u8; stride];
+  let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+  for line in raw.chunks_exact(stride + 1).take(height as usize) {
+    let row = unfilter(line[0], &line[1..], &previous, bpp)?;
+    for x in 0..width as usize {
+      let sample = |channel: usize| match depth {
+        16 => row[(x * channels + channel) * 2],
+        8 => row[x * channels + channel],
+        _ => {
+          let at = x * depth as usize;
+          let shift = 8 - depth as usize - at % 8;
+          let value = (row[at / 8] >> shift) & ((1 << depth) - 1);
+          match colour {
+            3 => value,
+            _ => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+          }
+        }
+      };
+      let rgba = match colour {
+        0 => [sample(0), sample(0), sample(0), 255],
+        2 => [sample(0), sample(1), sample(2), 255],
+        3 => {
+          let index = sample(0) as usize;
+          let rgb = palette
+            .get(index * 3..index * 3 + 3)
+            .ok_or_else(|| anyhow!("palette index out of range"))?;
+          let a = alpha.get(index).copied().unwrap_or(255);
+          [rgb[0], rgb[1], rgb[2], a]
+        }
+        4 => [sample(0), sample(0), sample(0), sample(1)],
+        _ => [sample(0), sample(1), sample(2), sample(3)],
+      };
+      pixels.extend_from_slice(&rgba);
+    }
+    previous = row;
+  }
+  Ok((width, height, pixels))
+}
+
+/// A row of image data with its filter undone, given the row before it
+fn unfilter(
+  filter: u8,
+  line: &[u8],
+  previous: &[u8],
+  bpp: usize,
+) -> Result<Vec<u8>> {
+  let mut row = line.to_vec();
+  for i in 0..row.len() {
+    let up = previous[i];
+    let (left, corner) = match i >= bpp {
+      true => (row[i - bpp], previous[i - bpp]),
+      false => (0, 0),
+    };
+    let predicted = match filter {
+      0 => 0,
+      1 => left,
+      2 => up,
+      3 => ((left as u16 + up as u16) / 2) as u8,
+      4 => paeth(left, up, corner),
+      _ => return Err(anyhow!("unknown filter {}", filter)),
+    };
+    row[i] = row[i].wrapping_add(predicted);
+  }
+  Ok(row)
+}
+
+fn paeth(left: u8, up: u8, corner: u8) -> u8 {
+  let estimate = left as i16 + up as i16 - corner as i16;
+  let distance = |value: u8| (estimate - value as i16).abs();
+  match (distance(left), distance(up), distance(corner)) {
+    (a, b, c) if a <= b && a <= c => left,
+    (_, b, c) if b <= c => up,
+    _ => corner,
+  }
+}
+
+/// The RGBA pixels of a square image scaled down from one size to another,
+/// each the average of the source pixels it covers, weighted by their alpha
+fn scale(pixels: &[u8], from: u32, to: u32) -> Vec<u8> {
+  let weights = weights(from as usize, to as usize);
+  let mut scaled = Vec::with_capacity(to as usize * to as usize * 4);
+  for rows in &weights {
+    for columns in &weights {
+      let mut sum = [0f64; 4];
+      for (y, row_weight) in rows {
+        for (x, column_weight) in columns {
+          let at = (y * from as usize + x) * 4;
+          let weight = row_weight * column_weight;
+          let alpha = pixels[at + 3] as f64 * weight;
+          for channel in 0..3 {
+            sum[channel] += pixels[at + channel] as f64 * alpha;
+          }
+          sum[3] += alpha;
+        }
+      }
+      let colour = |channel: usize| match sum[3] > 0.0 {
+        true => (sum[channel] / sum[3]).round() as u8,
+        false => 0,
+      };
+      scaled.extend_from_slice(&[
+        colour(0),
+        colour(1),
+        colour(2),
+        sum[3].round().min(255.0) as u8,
+      ]);
+    }
+  }
+  scaled
+}
+
+/// The source pixels each pixel of a row scaled from one size to another
+/// covers, with the share of it each covers
+fn weights(from: usize, to: usize) -> Vec<Vec<(usize, f64)>> {
+  let ratio = from as f64 / to as f64;
+  (0..to)
+    .map(|i| {
+      let (start, end) = (i as f64 * ratio, (i + 1) as f64 * ratio);
+      (start.floor() as usize..(end.ceil() as usize).min(from))
+        .map(|j| {
+          let covered = end.min(j as f64 + 1.0) - start.max(j as f64);
+          (j, covered / ratio)
+        })
+        .collect()
+    })
+    .collect()
+}
+
+/// A PNG image of square RGBA pixels
+fn encode(size: u32, pixels: &[u8]) -> Vec<u8> {
+  let mut raw = Vec::with_capacity(pixels.len() + size as usize);
+  for row in pixels.chunks_exact(size as usize * 4) {
+    raw.push(0);
+    raw.extend_from_slice(row);
+  }
+  let mut zlib = vec![0x78, 0x01];
+  zlib.extend(deflate(&raw));
+  zlib.extend(adler32(&raw).to_be_bytes());
+
+  let mut header = vec![];
+  header.extend(size.to_be_bytes());
+  header.extend(size.to_be_bytes());
+  header.extend([8, 6, 0, 0, 0]);
+  let mut png = PNG.to_vec();
+  for (kind, data) in [(b"IHDR", header), (b"IDAT", zlib), (b"IEND", vec![])] {
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(&data);
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend_from_slice(&chunk);
+    png.extend(crc32(&chunk).to_be_bytes());
+  }
+  png
+}
+
+/// The Adler-32 checksum of some bytes, as zlib streams end with
+fn adler32(data: &[u8]) -> u32 {
+  let (mut a, mut b) = (1u32, 0u32);
+  for byte in data {
+    a = (a + *byte as u32) % 65521;
+    b = (b + a) % 65521;
+  }
+  b << 16 | a
+}
+
+/// An ICO file holding PNG images, by their sizes
+fn ico(images: &[(u32, Vec<u8>)]) -> Vec<u8> {
+  let count = u16::try_from(images.len()).expect("Icon count");
+  let mut ico = vec![0, 0, 1, 0];
+  ico.extend(count.to_le_bytes());
+  let mut offset = 6 + 16 * images.len() as u32;
+  for (size, png) in images {
+    // Sizes of 256 pixels or more are written as zero
+    let dimension = if *size >= 256 { 0 } else { *size as u8 };
+    ico.extend([dimension, dimension, 0, 0]);
+    ico.extend(1u16.to_le_bytes());
+    ico.extend(32u16.to_le_bytes());
+    ico.extend((png.len() as u32).to_le_bytes());
+    ico.extend(offset.to_le_bytes());
+    offset += png.len() as u32;
+  }
+  for (_, png) in images {
+    ico.extend(png);
+  }
+  ico
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn png_images_read_back_as_written() {
+    let pixels = (0..3 * 3 * 4).map(|n| (n * 7) as u8).collect::<Vec<_>>();
+    assert_eq!(decode(&encode(3, &pixels)).unwrap(), (3, 3, pixels));
+    assert!(decode(b"not a png").is_err());
+  }
+
+  #[test]
+  fn scaled_pixels_average_those_they_cover_by_alpha() {
+    // Opaque red above transparent blue, and an opaque grey square
+    let pixels = [255, 0, 0, 255, 255, 0, 0, 255, 0, 0, 255, 0, 0, 0, 255, 0];
+    assert_eq!(scale(&pixels, 2, 1), [255, 0, 0, 128]);
+    let grey = [100, 100, 100, 255].repeat(16);
+    assert_eq!(scale(&grey, 4, 2), [100, 100, 100, 255].repeat(4));
+  }
+
+  #[test]
+  fn adler32_matches_its_known_value() {
+    assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+  }
+}
@@ -0,0 +1,101 @@
+use crate::code;
+use crate::util::{escape, slugify};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+const OPEN: &str = "{^index:";
+const CLOSE: &str = "}";
+const MARKER: &str = "{^index}";
+
+/// Terms annotated with `{^index:term}`, with the anchors of each occurrence
+#[derive(Default)]
+pub struct Index {
+  entries: BTreeMap<String, (String, Vec<String>)>,
+}
+
+impl Index {
+  /// Replace index annotations outside of code with anchors, collecting their
+  /// terms
+  ///
+  /// A `{^index}` line is replaced with an alphabetical index section linking
+  /// back to every occurrence of each term.
+  pub fn collect(input: &str) -> (Index, String) {
+    let mut index = Index::default();
+    let mut output = code::map_prose(input, |text| {
+      let mut output = String::with_capacity(text.len());
+      let mut rest = text;
+      while let Some(start) = rest.find(OPEN) {
+        let term = &rest[start + OPEN.len()..];
+        let end = match term.find(CLOSE) {
+          Some(end) => end,
+          None => break,
+        };
+        output.push_str(&rest[..start]);
+        output.push_str(&index.insert(term[..end].trim()));
+        rest = &term[end + CLOSE.len()..];
+      }
+      output.push_str(rest);
+      output
+    });
+
+    if !index.entries.is_empty() {
+      let section = index.section();
+      output = code::map_prose(&output, |text| match text.trim() == MARKER {
+        true => section.clone(),
+        false => text.to_string(),
+      });
+    }
+
+    (index, output)
+  }
+
+  fn insert(&mut self, term: &str) -> String {
+    let key = term.to_lowercase();
+    let (_, anchors) = self
+      .entries
+      .entry(key)
+      .or_insert_with(|| (term.into(), vec![]));
+    let id = format!("index-{}-{}", slugify(term), anchors.len() + 1);
+    let anchor = format!("<a class=\"index-term\" id=\"{}\"></a>", id);
+    anchors.push(id);
+    anchor
+  }
+
+  /// Markdown for an index section, grouped by the first letter of each term
+  fn section(&self) -> String {
+    let mut section = String::from("<dl class=\"index\">\n");
+    let mut letter = None;
+    for (key, (term, anchors)) in &self.entries {
+      let first = key.chars().next().map(|c| c.to_ascii_uppercase());
+      if first != letter {
+        letter = first;
+        section.push_str(&format!(
+          "<dt class=\"index-letter\">{}</dt>\n",
+          first.unwrap_or(' ')
+        ));
+      }
+      let links = anchors
+        .iter()
+        .enumerate()
+        .map(|(i, id)| format!("<a href=\"#{}\">{}</a>", id, i + 1))
+        .collect::<Vec<_>>();
+      section.push_str(&format!(
+        "<dd>{}, {}</dd>\n",
+        escape(term),
+        links.join(", ")
+      ));
+    }
+    section.push_str("</dl>\n");
+    section
+  }
+
+  /// Index entries for templates, as a list of terms and their anchors
+  pub fn to_json(&self) -> Value {
+    let entries = self
+      .entries
+      .values()
+      .map(|(term, anchors)| json!({ "term": term, "anchors": anchors }))
+      .collect::<Vec<_>>();
+    Value::Array(entries)
+  }
+}
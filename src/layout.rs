@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Base templates that other templates extend, overriding their blocks
+///
+/// A layout marks its blocks with partial blocks such as
+/// `{{#> content}}default{{/content}}`, and a template extends it with
+/// `{{#> base}}{{#*inline "content"}}...{{/inline}}{{/base}}`.
+#[derive(Clone)]
+pub struct Layouts {
+  templates: BTreeMap<String, String>,
+}
+
+impl Layouts {
+  /// Load the built in layouts along with any `.hbs` files in a directory
+  ///
+  /// Besides `base`, there is a built in layout for each page type.
+  pub fn load(dir: Option<&str>) -> Result<Layouts> {
+    let mut templates = BTreeMap::new();
+    templates.insert("base".into(), include_str!("template.hbs").into());
+    templates
+      .insert("recipe".into(), include_str!("layouts/recipe.hbs").into());
+    templates.insert("book".into(), include_str!("layouts/book.hbs").into());
+    templates
+      .insert("meeting".into(), include_str!("layouts/meeting.hbs").into());
+
+    if let Some(dir) = dir {
+      for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("hbs") {
+          continue;
+        }
+        if let Some(name) = path.file_stem() {
+          let name = name.to_string_lossy().to_string();
+          templates.insert(name, fs::read_to_string(&path)?);
+        }
+      }
+    }
+
+    Ok(Layouts { templates })
+  }
+
+  /// Register every layout as a partial
+  pub fn register(&self, registry: &mut handlebars::Handlebars) -> Result<()> {
+    for (name, template) in &self.templates {
+      registry.register_partial(name, template)?;
+    }
+    Ok(())
+  }
+
+  /// The template of a layout, for documents that choose one in front matter
+  pub fn get(&self, name: &str) -> Result<&str> {
+    match self.templates.get(name) {
+      Some(template) => Ok(template),
+      None => Err(anyhow!("Unknown layout '{}'", name)),
+    }
+  }
+}
@@ -0,0 +1,772 @@
+//! Renders markdown notes to HTML
+//!
+//! Documents are rendered with [`render_html`], given [`Params`], which the
+//! command line builds from its options and programs may start from
+//! [`Params::default`]. The events of a document pass through the
+//! transformers of [`transform`], each of which is public in its module.
+//! Programs using the library add their own steps to the pipeline by
+//! implementing [`EventTransformer`] and listing them in
+//! [`Params::transformers`].
+
+pub mod admonition;
+pub mod assets;
+pub mod audio;
+pub mod backlinks;
+pub mod cache;
+pub mod calendar;
+pub mod code;
+pub mod compress;
+pub mod conditional;
+pub mod data;
+pub mod diagram;
+pub mod embed;
+pub mod environment;
+pub mod events;
+pub mod execute;
+pub mod expiry;
+pub mod fixture;
+pub mod flashcard;
+pub mod frontmatter;
+pub mod glossary;
+pub mod grid;
+pub mod head;
+pub mod headings;
+pub mod hyphenate;
+pub mod icons;
+pub mod index;
+pub mod layout;
+pub mod links;
+pub mod listing;
+pub mod macros;
+pub mod math;
+pub mod offline;
+pub mod output;
+pub mod pagetype;
+pub mod paths;
+pub mod people;
+pub mod print;
+pub mod properties;
+pub mod publish;
+pub mod query;
+pub mod rewrite;
+pub mod secrets;
+pub mod shortcode;
+pub mod site;
+pub mod snippet;
+pub mod standalone;
+pub mod strings;
+pub mod suggest;
+pub mod summary;
+pub mod table;
+pub mod tags;
+pub mod track;
+pub mod transformer;
+pub mod typography;
+pub mod unicode;
+pub mod util;
+pub mod vfs;
+pub mod wavedrom;
+pub mod webmention;
+pub mod wikilink;
+
+use anyhow::{anyhow, Result};
+use pikchr::{Pikchr, PikchrFlags};
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag};
+use serde_json::json;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use transformer::{EventTransformer, Events};
+
+/// Options controlling how documents are rendered
+#[derive(Clone)]
+pub struct Params {
+  pub input: String,
+  /// The directory of the whole notebook, when it is known, that embeds are
+  /// found within rather than only beside the input
+  pub root: Option<PathBuf>,
+  pub source: Arc<dyn vfs::Source>,
+  pub output: Option<String>,
+  pub template: String,
+  /// The file the template was read from, if not the built in template
+  pub template_path: Option<String>,
+  pub layouts: layout::Layouts,
+  pub strings: strings::Strings,
+  /// The language of documents without a `lang` in their front matter
+  pub lang: String,
+  pub order: listing::Order,
+  /// Whether the document is rendered with the rest of its directory, linking
+  /// to the pages of other notes rather than their markdown
+  pub site: bool,
+  /// The page of a paginated listing to render, counting from one
+  pub page: usize,
+  pub rewrites: Vec<rewrite::Rule>,
+  pub data: serde_json::Value,
+  pub shortcodes: shortcode::Shortcodes,
+  pub macros: macros::Macros,
+  pub profile: Option<String>,
+  pub glossary: Option<glossary::Glossary>,
+  pub environments: environment::Environments,
+  pub print: bool,
+  pub sortable: bool,
+  pub assets: assets::Assets,
+  pub icon: Option<icons::Icon>,
+  /// Whether a service worker caches the output for offline reading
+  pub offline: bool,
+  /// Commands to run code blocks marked `run` with, when running them
+  pub execute: Option<execute::Runners>,
+  /// Whether rendered diagrams and code output are cached between renders
+  pub cache: bool,
+  /// Whether `$...$` and `$$...$$` math is rendered as MathML
+  pub math: bool,
+  /// A command drawing math as SVG, shown to browsers without MathML
+  pub math_fallback: Option<String>,
+  /// The programs `dot`, `mermaid` and filtered blocks are drawn with
+  pub diagrams: diagram::Programs,
+  /// Whether notes mentioning a note without linking to it are found
+  pub unlinked_mentions: bool,
+  /// The front matter shown in a table at the top of notes
+  pub properties: Option<properties::Properties>,
+  /// Further steps of the rendering pipeline, run in order
+  pub transformers: Vec<Arc<dyn EventTransformer>>,
+  /// Whether built in assets are inlined, when there is nowhere to write them
+  pub inline_assets: bool,
+  /// Whether local files are inlined, for a document without dependencies
+  pub standalone: bool,
+  pub typography: Option<typography::Language>,
+  pub hyphenation: Option<String>,
+  pub unicode: unicode::Unicode,
+  pub secrets: Option<secrets::Scanner>,
+  pub schedule: publish::Schedule,
+  /// A JSON endpoint of the mentions of a document, for templates
+  pub mentions: Option<String>,
+  /// What writes summaries for notes without one
+  pub summarizer: Option<summary::Summarizer>,
+  /// Whether `[[Page Name]]` links to the notes beside a document
+  pub wikilinks: bool,
+  /// Whether `#tag` tags in the text of notes are linked and indexed
+  pub inline_tags: bool,
+  /// The notes linking to each note, collected before a directory is built
+  pub backlinks: Option<Arc<backlinks::Backlinks>>,
+  /// The snippets of the notes, collected once for a build of a directory
+  pub snippets: Option<snippet::Cache>,
+  /// The people of the notes, collected once for a build of a directory
+  pub people: Option<people::Cache>,
+  /// Whether pages end with the notes linking to them
+  pub linked_from: bool,
+  /// The markdown extensions documents are parsed with
+  pub extensions: Options,
+  /// Where warnings and progress are reported
+  pub events: events::Events,
+}
+
+/// Params rendering a document with the built in template, layouts, strings
+/// and shortcodes, read from the filesystem with nothing else enabled, to be
+/// changed as in `Params { input: "note.md".into(), ..Params::default() }`
+impl Default for Params {
+  fn default() -> Params {
+    Params {
+      input: String::new(),
+      root: None,
+      source: Arc::new(vfs::Filesystem::default()),
+      output: None,
+      template: include_str!("template.hbs").into(),
+      template_path: None,
+      layouts: layout::Layouts::load(None).expect("Built in layouts"),
+      strings: strings::Strings::load(&[]).expect("Built in strings"),
+      lang: "en".into(),
+      order: listing::Order::new(None, None, None).expect("Default order"),
+      site: false,
+      page: 1,
+      rewrites: vec![],
+      data: json!({}),
+      shortcodes: shortcode::Shortcodes::load(None)
+        .expect("Built in shortcodes"),
+      macros: macros::Macros::default(),
+      profile: None,
+      glossary: None,
+      environments: environment::Environments::new(&[])
+        .expect("Built in environments"),
+      print: false,
+      sortable: false,
+      assets: assets::Assets::default(),
+      icon: None,
+      offline: false,
+      execute: None,
+      cache: true,
+      math: false,
+      math_fallback: None,
+      diagrams: diagram::Programs::new(None, None, &[])
+        .expect("Default programs"),
+      unlinked_mentions: false,
+      properties: None,
+      transformers: vec![],
+      inline_assets: true,
+      standalone: false,
+      typography: None,
+      hyphenation: None,
+      unicode: unicode::Unicode::default(),
+      secrets: None,
+      schedule: publish::Schedule::new(None).expect("Current schedule"),
+      mentions: None,
+      summarizer: None,
+      wikilinks: false,
+      inline_tags: false,
+      backlinks: None,
+      snippets: None,
+      people: None,
+      linked_from: false,
+      extensions: options(),
+      events: events::Events::default(),
+    }
+  }
+}
+
+/// Insert some HTML before a closing tag of a document, such as `</body>`
+pub fn inject(mut html: Vec<u8>, end: &str, snippet: &str) -> Vec<u8> {
+  let end = end.as_bytes();
+  let at = html
+    .windows(end.len())
+    .rposition(|window| window == end)
+    .unwrap_or(html.len());
+  html.splice(at..at, snippet.bytes());
+  html
+}
+
+/// The file name of the rendered document
+pub fn document_name(params: &Params) -> String {
+  let path = match &params.output {
+    Some(output) => Path::new(output).with_extension("html"),
+    None => Path::new(&params.input).with_extension("html"),
+  };
+  let name = path.file_name().map(|name| name.to_string_lossy());
+  name.unwrap_or_default().into_owned()
+}
+
+/// The directory containing the input document
+pub fn input_dir(params: &Params) -> &Path {
+  Path::new(&params.input)
+    .parent()
+    .filter(|dir| dir != &Path::new(""))
+    .unwrap_or(Path::new("."))
+}
+
+/// Details of a rendered document
+pub struct Document {
+  /// The ids of each heading
+  pub ids: Vec<String>,
+  /// The rendered content, without the template
+  pub content: String,
+  /// Assets referenced by the document
+  pub assets: assets::Assets,
+  /// The number of pages of the document's listing
+  pub pages: usize,
+}
+
+/// The markdown extensions documents are parsed with
+pub fn options() -> Options {
+  let mut options = Options::empty();
+  options.insert(Options::ENABLE_STRIKETHROUGH);
+  options.insert(Options::ENABLE_TABLES);
+  options.insert(Options::ENABLE_FOOTNOTES);
+  options.insert(Options::ENABLE_TASKLISTS);
+  options.insert(Options::ENABLE_SMART_PUNCTUATION);
+  options
+}
+
+/// The markdown extensions named, `tables`, `footnotes`, `strikethrough`,
+/// `tasklists` or `smart-punctuation`, alone or in comma separated lists,
+/// or all of them when none are named
+pub fn extensions(names: &[String]) -> Result<Options> {
+  if names.is_empty() {
+    return Ok(options());
+  }
+  let mut options = Options::empty();
+  for name in names.iter().flat_map(|names| names.split(',')) {
+    options.insert(match name.trim() {
+      "tables" => Options::ENABLE_TABLES,
+      "footnotes" => Options::ENABLE_FOOTNOTES,
+      "strikethrough" => Options::ENABLE_STRIKETHROUGH,
+      "tasklists" => Options::ENABLE_TASKLISTS,
+      "smart-punctuation" => Options::ENABLE_SMART_PUNCTUATION,
+      "" | "none" => Options::empty(),
+      name => return Err(anyhow!("Unknown markdown extension '{}'", name)),
+    });
+  }
+  Ok(options)
+}
+
+/// Render a markdown document, with its front matter, through its template
+pub fn render_html<W>(
+  input: &str,
+  params: &Params,
+  output: &mut W,
+) -> Result<Document>
+where
+  W: io::Write,
+{
+  let (mut meta, source) = frontmatter::split(input);
+  let cache = cache::Cache::new(input_dir(params), params.cache);
+  if let Some(summarizer) = &params.summarizer {
+    summarizer.fill(&mut meta, source, &cache)?;
+  }
+  let path = Path::new(&params.input);
+  let root = params.root.as_deref().unwrap_or(input_dir(params));
+  let input = embed::Embeds::new(&*params.source, root, &params.events)
+    .expand(path, source)?;
+  let snippets = params.snippets.clone().unwrap_or_default();
+  let input =
+    snippets.expand(&*params.source, input_dir(params), path, &input)?;
+
+  let mut conditions = conditional::Conditions::new(&meta);
+  conditions.set("target", "html");
+  if let Some(profile) = &params.profile {
+    conditions.set("profile", profile);
+  }
+  let input = conditions.apply(&input)?;
+  let input = params.macros.expand(&meta, &input);
+  let input = params.shortcodes.expand(&input)?;
+  let input = params.environments.apply(&input)?;
+  let input = grid::expand(&input)?;
+  let input = admonition::expand(&input)?;
+  let (index, input) = index::Index::collect(&input);
+  let input = calendar::expand(&input);
+  let input = query::expand(
+    &*params.source,
+    input_dir(params),
+    path,
+    &params.schedule,
+    params.inline_tags,
+    &input,
+  )?;
+  let input = match params.math {
+    true => {
+      let fallback = params
+        .math_fallback
+        .as_deref()
+        .map(|command| math::Fallback::new(command, &cache));
+      math::expand(&input, fallback.as_ref())?
+    }
+    false => input,
+  };
+  let people = params
+    .people
+    .clone()
+    .unwrap_or_default()
+    .collect(&*params.source, input_dir(params))?;
+  let input = match params.wikilinks {
+    true => wikilink::WikiLinks::collect(&*params.source, input_dir(params))?
+      .link(&input),
+    false => input,
+  };
+  let input = match links::is_link_log(&meta) {
+    true => {
+      let mut cache = links::Cache::load(input_dir(params), &params.events);
+      let input = links::cards(&input, &mut cache);
+      cache.save()?;
+      input
+    }
+    false => input,
+  };
+  let input = match &params.secrets {
+    Some(scanner) => scanner.apply(&params.input, &input, &params.events)?,
+    None => input,
+  };
+  let parser = Parser::new_ext(&input, params.extensions);
+  let parser = people::MentionTransformer::new(parser, &people, path);
+  let events = transform(parser, &meta, params, &cache)?;
+  let heading = extract_heading(&events);
+  let heading = heading.map(|heading| headings::strip_id(&heading).to_string());
+  let mut headings = headings::HeadingTransformer::new(events.into_iter());
+  let events = headings.by_ref().collect::<Vec<_>>();
+  let toc = headings::toc(&headings.headings);
+  let events = headings::expand_toc(events, &toc);
+  let mermaid = diagram::has_mermaid(&events);
+  let sections = headings::sections(&events, &headings.headings);
+
+  let mut content = expiry::banner(&meta).unwrap_or_default();
+  if let Some(properties) = &params.properties {
+    content
+      .push_str(&properties.table(&meta, path, &people).unwrap_or_default());
+  }
+  content.push_str(&people.profile(path).unwrap_or_default());
+  html::push_html(&mut content, events.into_iter());
+  if let Some(backlinks) = people.backlinks(&*params.source, path)? {
+    content.push_str(&backlinks);
+  }
+  let backlinks = match &params.backlinks {
+    Some(backlinks) => backlinks.to(path),
+    None if params.linked_from => backlinks::Backlinks::collect(
+      &*params.source,
+      input_dir(params),
+      &params.schedule,
+      params.wikilinks,
+    )?
+    .to(path),
+    None => vec![],
+  };
+  if params.linked_from {
+    content.push_str(&backlinks::html(&backlinks).unwrap_or_default());
+  }
+
+  // A directory's index page lists the notes beside it
+  let listing = match listing::is_index(&params.input) {
+    true => Some(listing::Listing::collect(
+      &*params.source,
+      input_dir(params),
+      &meta,
+      &params.order,
+      &params.schedule,
+      params.summarizer.as_ref(),
+      &cache,
+    )?),
+    false => None,
+  };
+  if let Some(listing) = &listing {
+    let base = document_name(params);
+    content.push_str(&listing.to_html(params.page, &base));
+  }
+
+  let mut assets = params.assets.clone();
+  let mut head = head::Head::default();
+  // Printed tables have no use for the sorting and filtering controls
+  if params.sortable && !params.print {
+    let script = include_str!("sortable.js");
+    match params.inline_assets {
+      true => head.inline_script(script),
+      false => head.script(&assets.add("sortable.js", script.into())),
+    }
+  }
+  // Mermaid diagrams not drawn by a program are drawn by the browser
+  if mermaid {
+    head.script(diagram::MERMAID);
+    head.inline_script("mermaid.run();\n");
+  }
+  if grid::has_layout(&content) {
+    head.inline_style(grid::STYLE);
+  }
+  if admonition::has_admonition(&content) {
+    head.inline_style(admonition::STYLE);
+  }
+  if math::has_fallback(&content) {
+    head.inline_style(math::STYLE);
+  }
+  if params.hyphenation.is_some() {
+    head.inline_style("p, li, dd { text-align: justify; hyphens: manual; }\n");
+  }
+  let list = |key: &str| {
+    let values = meta.get(key).and_then(|value| value.as_array());
+    let values = values.map(|values| values.iter().filter_map(|v| v.as_str()));
+    values.into_iter().flatten().collect::<Vec<_>>()
+  };
+  for href in list("styles") {
+    head.style(href);
+  }
+  for src in list("scripts") {
+    head.script(src);
+  }
+  for href in list("preload") {
+    let kind = match href.rsplit_once('.').map(|(_, ext)| ext) {
+      Some("woff2") | Some("woff") | Some("ttf") => "font",
+      Some("css") => "style",
+      Some("js") => "script",
+      _ => "image",
+    };
+    head.preload(href, kind);
+  }
+
+  let title = heading
+    .or_else(|| meta.get("title")?.as_str().map(String::from))
+    .unwrap_or("".into());
+  if let Some(icon) = &params.icon {
+    icon.add(&title, &mut assets, &mut head);
+  }
+  if params.offline {
+    head.inline_script(&offline::register());
+  }
+  if let Some(data) = pagetype::json_ld(&meta, &title) {
+    head.json_ld(&data);
+  }
+
+  let unlinked_mentions = match params.unlinked_mentions {
+    true => Some(suggest::unlinked_mentions(
+      &*params.source,
+      input_dir(params),
+      path,
+      &meta,
+      &input,
+    )?),
+    false => None,
+  };
+
+  let lang = meta.get("lang").and_then(|lang| lang.as_str());
+  let lang = lang.unwrap_or(&params.lang).to_string();
+  // Templates shouldn't show the secrets masked in the content
+  let source = match &params.secrets {
+    Some(scanner) => scanner.mask(source),
+    None => source.to_string(),
+  };
+  let context = json!({
+      "title": title,
+      "lang": lang,
+      "content": &content,
+      "toc": toc,
+      "source": source,
+      "sections": sections,
+      "data": params.data,
+      "meta": meta,
+      "index": index.to_json(),
+      "print": params.print,
+      "hyphenate": params.hyphenation.is_some(),
+      "assets": assets.to_json(),
+      "head_extra": head.to_html(),
+      "listing": listing.as_ref().map(|listing| listing.to_json(params.page)),
+      "mentions": params.mentions.as_ref().map(|endpoint| {
+        let url = meta.get("url").and_then(|url| url.as_str());
+        webmention::mentions(endpoint, url, &params.events)
+      }),
+      "unlinked_mentions": unlinked_mentions,
+      "backlinks": backlinks,
+      "audio": audio::beside(params),
+  });
+
+  let mut registry = handlebars::Handlebars::new();
+  params.layouts.register(&mut registry)?;
+  params.strings.register(&mut registry, &lang);
+  // Typed pages use the layout of their type unless they choose another
+  let key = |key: &str| meta.get(key).and_then(|value| value.as_str());
+  let page_type = key("type").filter(|kind| pagetype::TYPES.contains(kind));
+  let template = match key("layout").or(page_type) {
+    Some(layout) => params.layouts.get(layout)?,
+    None => &params.template,
+  };
+  let rendered = registry.render_template(template, &context)?;
+  let rendered = if params.rewrites.is_empty() {
+    rendered
+  } else {
+    rewrite::apply(&rendered, &params.rewrites)?
+  };
+  // Templates without `head_extra` still get the tags features depend on
+  let head = head.to_html();
+  let rendered = match rendered.contains(&head) {
+    true => rendered,
+    false => {
+      String::from_utf8(inject(rendered.into_bytes(), "</head>", &head))?
+    }
+  };
+
+  // Standalone documents have their assets inlined rather than beside them
+  let (rendered, assets) = match params.standalone {
+    true => (
+      standalone::inline(
+        &rendered,
+        &*params.source,
+        input_dir(params),
+        &assets,
+        &params.events,
+      )?,
+      assets::Assets::default(),
+    ),
+    false => (rendered, assets),
+  };
+
+  output.write_all(rendered.as_bytes())?;
+  Ok(Document {
+    ids: headings
+      .headings
+      .into_iter()
+      .map(|heading| heading.id)
+      .collect(),
+    content,
+    assets,
+    pages: listing.map(|listing| listing.pages()).unwrap_or(1),
+  })
+}
+
+/// Apply the transformers of the rendering pipeline to the events of a
+/// document's markdown, before headings are given their ids
+///
+/// Each transformer is an iterator of events that can be composed with others,
+/// or used on its own.
+pub fn transform<'a, I>(
+  events: I,
+  meta: &serde_json::Value,
+  params: &'a Params,
+  cache: &'a cache::Cache,
+) -> Result<Vec<Event<'a>>>
+where
+  I: Iterator<Item = Event<'a>> + 'a,
+{
+  let path = Path::new(&params.input);
+  let parser =
+    PikchrTransformer::new(cache.clone()).transform(Box::new(events));
+  let parser = match params.inline_tags {
+    true => tags::TagTransformer.transform(parser),
+    false => parser,
+  };
+  let parser =
+    diagram::DiagramTransformer::new(parser, cache, &params.diagrams);
+  let parser = table::TableTransformer::new(parser);
+  let parser = flashcard::FlashcardTransformer::new(parser, params.print);
+  let parser =
+    track::TrackTransformer::new(parser, track::note_date(meta, path));
+  let parser = execute::ExecuteTransformer::new(
+    parser,
+    params.execute.as_ref(),
+    input_dir(params),
+    cache,
+  );
+  let mut parser: Events = Box::new(parser);
+  for transformer in &params.transformers {
+    parser = transformer.transform(parser);
+  }
+  let events = match &params.glossary {
+    Some(glossary) => {
+      glossary::GlossaryTransformer::new(parser, glossary).collect::<Vec<_>>()
+    }
+    None => parser.collect::<Vec<_>>(),
+  };
+  let events = match params.site {
+    true => events.into_iter().map(site::link).collect(),
+    false => events,
+  };
+  let events = match params.unicode.is_enabled() {
+    true => {
+      unicode::UnicodeTransformer::new(events.into_iter(), params.unicode)
+        .collect()
+    }
+    false => events,
+  };
+  let events = match params.typography {
+    Some(language) => {
+      typography::TypographyTransformer::new(events.into_iter(), language)
+        .collect()
+    }
+    None => events,
+  };
+  let events = match &params.hyphenation {
+    Some(dir) => {
+      let lang = meta.get("lang").and_then(|lang| lang.as_str());
+      let patterns = hyphenate::Patterns::load(dir, lang.unwrap_or("en-us"))?;
+      hyphenate::HyphenateTransformer::new(events.into_iter(), patterns)
+        .collect()
+    }
+    None => events,
+  };
+  let events = match params.print {
+    true => print::PrintLinkTransformer::new(events.into_iter()).collect(),
+    false => events,
+  };
+  Ok(events)
+}
+
+/// Extract a heading from the markdown input
+pub fn extract_heading(events: &[Event]) -> Option<String> {
+  let mut in_h1 = false;
+  for event in events {
+    let tag = match (in_h1, &event) {
+      (false, Event::Start(tag)) => tag,
+      (true, Event::Text(text)) => return Some(text.to_string()),
+      _ => continue,
+    };
+    let kind = match tag {
+      Tag::Heading(kind) => kind,
+      _ => continue,
+    };
+    in_h1 = *kind == 1;
+  }
+  None
+}
+
+/// Transforms Pikchr fenced code blocks into SVG diagrams
+pub struct PikchrTransformer {
+  cache: cache::Cache,
+}
+
+impl PikchrTransformer {
+  pub fn new(cache: cache::Cache) -> Self {
+    PikchrTransformer { cache }
+  }
+}
+
+impl EventTransformer for PikchrTransformer {
+  fn transform<'a>(&self, events: Events<'a>) -> Events<'a> {
+    Box::new(PikchrEvents {
+      iter: events,
+      cache: self.cache.clone(),
+    })
+  }
+}
+
+struct PikchrEvents<'a> {
+  iter: Events<'a>,
+  cache: cache::Cache,
+}
+
+impl<'a> Iterator for PikchrEvents<'a> {
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+
+    let tag = match event {
+      Event::Start(ref tag) => tag,
+      _ => return Some(event),
+    };
+
+    let kind = match tag {
+      Tag::CodeBlock(kind) => kind,
+      _ => return Some(event),
+    };
+
+    let lang = match kind {
+      CodeBlockKind::Fenced(lang) => lang,
+      _ => return Some(event),
+    };
+
+    if lang.as_ref() != "pikchr" {
+      return Some(event);
+    }
+
+    // The fence's text may come in pieces, or not at all when it is empty
+    let mut text = String::new();
+    for event in &mut self.iter {
+      match event {
+        Event::Text(piece) => text.push_str(&piece),
+        _ => break,
+      }
+    }
+    if text.trim().is_empty() {
+      return Some(Event::Html("".into()));
+    }
+
+    // Display Pikchr syntax errors in the output document
+    let svg = self.cache.get("pikchr", &text, || {
+      match Pikchr::render(&text, None, PikchrFlags::default()) {
+        Ok(svg) => Ok(svg.to_string()),
+        Err(err) => Err(anyhow!("{}", err)),
+      }
+    });
+    let event = match svg {
+      Ok(svg) => Event::Html(svg.into()),
+      Err(err) => Event::Text(err.to_string().into()),
+    };
+
+    Some(event)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn empty_pikchr_fences_draw_nothing() {
+    let cache = cache::Cache::new(Path::new("."), false);
+    let events = Box::new(Parser::new("```pikchr\n```\n\ntext\n"));
+    let events = PikchrTransformer::new(cache).transform(events);
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+    assert_eq!(output, "<p>text</p>\n");
+  }
+}
@@ -0,0 +1,380 @@
+use crate::code;
+use crate::events::Events;
+use crate::frontmatter;
+use crate::publish::Schedule;
+use crate::track::note_date;
+use crate::util::{escape, today};
+use crate::vfs::Source;
+use anyhow::Result;
+use lol_html::html_content::{Element, TextChunk};
+use lol_html::{ElementContentHandlers, HtmlRewriter, Selector, Settings};
+use serde_json::{json, Map, Value};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The path the feed of links is served at
+pub const FEED: &str = "links.xml";
+
+/// The file, in a notebook's `.notebook` directory, of fetched link metadata
+const CACHE: &str = "links.json";
+
+/// Whether a note is a link log, with `type: links` in its front matter
+pub fn is_link_log(meta: &Value) -> bool {
+  meta.get("type").and_then(Value::as_str) == Some("links")
+}
+
+/// The URL and comment of each list item of a link log starting with a URL
+fn items(body: &str) -> Vec<(&str, &str)> {
+  code::lines(body)
+    .filter(|(code, _)| !code)
+    .filter_map(|(_, line)| item(line))
+    .collect()
+}
+
+/// The URL and comment of a list item line starting with a URL
+fn item(line: &str) -> Option<(&str, &str)> {
+  let trimmed = line.trim_start();
+  let rest = ["- ", "* ", "+ "]
+    .iter()
+    .find_map(|marker| trimmed.strip_prefix(marker))?
+    .trim_start();
+  let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+  let url = rest[..end].trim_start_matches('<').trim_end_matches('>');
+  match url.starts_with("https://") || url.starts_with("http://") {
+    true => Some((url, rest[end..].trim())),
+    false => None,
+  }
+}
+
+/// The title, description, image and site name of pages, by URL
+///
+/// Pages are fetched once, with `curl`, and kept in `.notebook/links.json`.
+/// Pages that fail to fetch are remembered without metadata, until they are
+/// removed from the cache.
+pub struct Cache {
+  path: PathBuf,
+  pages: Map<String, Value>,
+  changed: bool,
+  events: Events,
+}
+
+impl Cache {
+  /// Load the cache of a notebook directory, warning of the pages that fail
+  /// to fetch
+  pub fn load(dir: &Path, events: &Events) -> Cache {
+    let path = dir.join(".notebook").join(CACHE);
+    let pages = fs::read_to_string(&path)
+      .ok()
+      .and_then(|text| serde_json::from_str(&text).ok())
+      .unwrap_or_default();
+    Cache {
+      path,
+      pages,
+      changed: false,
+      events: events.clone(),
+    }
+  }
+
+  fn get(&mut self, url: &str) -> &Value {
+    if !self.pages.contains_key(url) {
+      self.pages.insert(url.into(), fetch(url, &self.events));
+      self.changed = true;
+    }
+    &self.pages[url]
+  }
+
+  /// Write any newly fetched metadata back to the cache
+  pub fn save(&self) -> Result<()> {
+    if self.changed {
+      fs::create_dir_all(self.path.parent().unwrap_or(Path::new(".")))?;
+      fs::write(&self.path, serde_json::to_string_pretty(&self.pages)?)?;
+    }
+    Ok(())
+  }
+}
+
+/// Fetch the metadata of a page, empty if it can not be fetched
+fn fetch(url: &str, events: &Events) -> Value {
+  let output = Command::new("curl")
+    .args([
+      "-sSfL",
+      "--max-time",
+      "10",
+      "--max-filesize",
+      "5000000",
+      url,
+    ])
+    .output();
+  match output {
+    Ok(output) if output.status.success() => {
+      metadata(&String::from_utf8_lossy(&output.stdout))
+    }
+    Ok(output) => {
+      let stderr = String::from_utf8_lossy(&output.stderr);
+      events.warn(&format!("failed to fetch '{}': {}", url, stderr.trim()));
+      json!({})
+    }
+    Err(err) => {
+      events.warn(&format!("failed to run 'curl': {}", err));
+      json!({})
+    }
+  }
+}
+
+/// The title and Open Graph metadata of an HTML page
+pub fn metadata(html: &str) -> Value {
+  let found = RefCell::new(Map::new());
+  let title = RefCell::new(String::new());
+  let selector = |selector: &str| {
+    Cow::Owned(selector.parse::<Selector>().expect("Valid selector"))
+  };
+  let meta = ElementContentHandlers::default().element(|el: &mut Element| {
+    let name = el
+      .get_attribute("property")
+      .or_else(|| el.get_attribute("name"));
+    let key = match name.as_deref() {
+      Some("og:title") => "title",
+      Some("og:description") | Some("description") => "description",
+      Some("og:image") => "image",
+      Some("og:site_name") => "site",
+      _ => return Ok(()),
+    };
+    // Open Graph properties take precedence over the page's own description
+    let og = name.is_some_and(|name| name.starts_with("og:"));
+    let content = el.get_attribute("content").unwrap_or_default();
+    let mut found = found.borrow_mut();
+    if !content.trim().is_empty() && (og || !found.contains_key(key)) {
+      found.insert(key.into(), json!(content.trim()));
+    }
+    Ok(())
+  });
+  let text = ElementContentHandlers::default().text(|chunk: &mut TextChunk| {
+    title.borrow_mut().push_str(chunk.as_str());
+    Ok(())
+  });
+
+  let settings = Settings::new()
+    .append_element_content_handler((selector("meta"), meta))
+    .append_element_content_handler((selector("head > title"), text));
+  let mut rewriter = HtmlRewriter::new(settings, |_: &[u8]| {});
+  // Metadata is best effort, from however much of the page could be parsed
+  match rewriter.write(html.as_bytes()) {
+    Ok(()) => drop(rewriter.end()),
+    Err(_) => drop(rewriter),
+  }
+
+  let mut found = found.into_inner();
+  let title = title.into_inner();
+  if !found.contains_key("title") && !title.trim().is_empty() {
+    let title = unescape(&title);
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    found.insert("title".into(), json!(title));
+  }
+  // Relative image URLs can not be resolved once the card is on another site
+  let absolute =
+    |url: &str| url.starts_with("https://") || url.starts_with("http://");
+  let image = found.get("image").and_then(Value::as_str);
+  if !image.is_none_or(absolute) {
+    found.remove("image");
+  }
+  Value::Object(found)
+}
+
+/// Replace the URL starting each list item of a link log with a link card
+pub fn cards(input: &str, cache: &mut Cache) -> String {
+  let mut output = String::with_capacity(input.len());
+  for (code, line) in code::lines(input) {
+    let trimmed = line.trim_start();
+    let (url, comment) = match item(line).filter(|_| !code) {
+      Some(item) => item,
+      None => {
+        output.push_str(line);
+        continue;
+      }
+    };
+    let indent = &line[..line.len() - trimmed.len()];
+    let marker = &trimmed[..2];
+    output.push_str(&format!(
+      "{}{}{}",
+      indent,
+      marker,
+      card(url, cache.get(url))
+    ));
+    if !comment.is_empty() {
+      output.push_str(&format!(" {}", comment));
+    }
+    output.push('\n');
+  }
+  output
+}
+
+/// HTML for a card showing a link's title, site, description and image
+fn card(url: &str, page: &Value) -> String {
+  let field = |key: &str| page.get(key).and_then(Value::as_str);
+  let mut html = format!("<a class=\"link-card\" href=\"{}\">", escape(url));
+  if let Some(image) = field("image") {
+    html.push_str(&format!(
+      "<img class=\"link-image\" src=\"{}\" alt=\"\">",
+      escape(image)
+    ));
+  }
+  html.push_str(&format!(
+    "<span class=\"link-title\">{}</span>",
+    escape(field("title").unwrap_or(url))
+  ));
+  if let Some(site) = field("site") {
+    html.push_str(&format!(
+      "<span class=\"link-site\">{}</span>",
+      escape(site)
+    ));
+  }
+  if let Some(description) = field("description") {
+    html.push_str(&format!(
+      "<span class=\"link-description\">{}</span>",
+      escape(description)
+    ));
+  }
+  html.push_str("</a>");
+  html
+}
+
+/// An Atom feed of the links of every published link log under a directory
+///
+/// Links are dated by the `date` of their note, or its file name, and listed
+/// newest first.
+pub fn feed(
+  source: &dyn Source,
+  root: &Path,
+  schedule: &Schedule,
+  cache: &mut Cache,
+) -> Result<String> {
+  let mut entries = vec![];
+  for path in source.files(root)? {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+      continue;
+    }
+    let text = source.read_to_string(&path)?;
+    let (meta, body) = frontmatter::split(&text);
+    if !is_link_log(&meta) || !schedule.is_published(&meta)? {
+      continue;
+    }
+    let date = note_date(&meta, &path).unwrap_or_else(today);
+    for (url, comment) in items(body) {
+      let page = cache.get(url);
+      let title = page.get("title").and_then(Value::as_str).unwrap_or(url);
+      entries.push((
+        date.clone(),
+        url.to_string(),
+        title.to_string(),
+        comment.to_string(),
+      ));
+    }
+  }
+  entries.sort_by(|a, b| b.0.cmp(&a.0));
+
+  let updated = entries
+    .first()
+    .map(|entry| entry.0.clone())
+    .unwrap_or_else(today);
+  let mut feed = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+  feed.push_str("<title>Links</title>\n");
+  feed.push_str("<id>urn:notebook:links</id>\n");
+  feed.push_str(&format!("<updated>{}T00:00:00Z</updated>\n", updated));
+  for (date, url, title, comment) in entries {
+    feed.push_str("<entry>\n");
+    feed.push_str(&format!("<title>{}</title>\n", escape(&title)));
+    feed.push_str(&format!("<link href=\"{}\"/>\n", escape(&url)));
+    feed.push_str(&format!("<id>{}</id>\n", escape(&url)));
+    feed.push_str(&format!("<updated>{}T00:00:00Z</updated>\n", date));
+    if !comment.is_empty() {
+      feed.push_str(&format!("<summary>{}</summary>\n", escape(&comment)));
+    }
+    feed.push_str("</entry>\n");
+  }
+  feed.push_str("</feed>\n");
+  Ok(feed)
+}
+
+/// Resolve a link on a page against the page's URL
+pub fn resolve(base: &str, href: &str) -> String {
+  if href.contains("://") || href.starts_with("mailto:") {
+    return href.into();
+  }
+  let (scheme, rest) = base.split_once("://").unwrap_or(("https", base));
+  let origin = match rest.find('/') {
+    Some(end) => &rest[..end],
+    None => rest,
+  };
+  if let Some(href) = href.strip_prefix("//") {
+    return format!("{}://{}", scheme, href);
+  }
+  if href.starts_with('/') {
+    return format!("{}://{}{}", scheme, origin, href);
+  }
+  let path = rest[origin.len()..].split(['?', '#']).next().unwrap_or("");
+  let dir = match path.rfind('/') {
+    Some(end) => &path[..=end],
+    None => "/",
+  };
+  format!("{}://{}{}{}", scheme, origin, dir, href)
+}
+
+/// Replace HTML character references with the characters they stand for
+pub fn unescape(text: &str) -> String {
+  let mut output = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find('&') {
+    output.push_str(&rest[..start]);
+    rest = &rest[start..];
+    let end = match rest[..rest.len().min(12)].find(';') {
+      Some(end) => end,
+      None => {
+        output.push('&');
+        rest = &rest[1..];
+        continue;
+      }
+    };
+    let name = &rest[1..end];
+    let c = match name {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      "nbsp" => Some(' '),
+      "ndash" => Some('\u{2013}'),
+      "mdash" => Some('\u{2014}'),
+      "hellip" => Some('\u{2026}'),
+      "lsquo" => Some('\u{2018}'),
+      "rsquo" => Some('\u{2019}'),
+      "ldquo" => Some('\u{201c}'),
+      "rdquo" => Some('\u{201d}'),
+      "copy" => Some('\u{a9}'),
+      _ => match name.strip_prefix('#') {
+        Some(hex) if hex.starts_with(['x', 'X']) => {
+          u32::from_str_radix(&hex[1..], 16)
+            .ok()
+            .and_then(char::from_u32)
+        }
+        Some(decimal) => decimal.parse().ok().and_then(char::from_u32),
+        None => None,
+      },
+    };
+    match c {
+      Some(c) => {
+        output.push(c);
+        rest = &rest[end + 1..];
+      }
+      None => {
+        output.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  output.push_str(rest);
+  output
+}
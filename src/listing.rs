@@ -0,0 +1,401 @@
+use crate::cache::Cache;
+use crate::frontmatter;
+use crate::headings::strip_id;
+use crate::publish::Schedule;
+use crate::summary::{summary, Summarizer};
+use crate::util::{escape, heading};
+use crate::vfs::Source;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The file name of a directory's index page
+pub const INDEX: &str = "_index.md";
+
+/// A note or subdirectory in a directory listing
+pub struct Entry {
+  /// The path of the note, relative to the directory
+  path: PathBuf,
+  title: String,
+  meta: Value,
+  modified: Option<SystemTime>,
+  /// HTML for the summary of the note, if it has one
+  summary: Option<String>,
+  pinned: bool,
+}
+
+impl Entry {
+  fn href(&self) -> String {
+    self
+      .path
+      .with_extension("html")
+      .to_string_lossy()
+      .replace('\\', "/")
+  }
+
+  fn date(&self) -> &str {
+    self.meta.get("date").and_then(Value::as_str).unwrap_or("")
+  }
+
+  fn weight(&self) -> f64 {
+    let weight = self.meta.get("weight").and_then(Value::as_f64);
+    weight.unwrap_or(f64::MAX)
+  }
+
+  /// The name of the group an entry belongs to
+  fn group(&self, group: &str) -> String {
+    match group {
+      "folder" if self.path.components().count() > 1 => {
+        let folder = self.path.components().next();
+        let folder = folder.map(|c| c.as_os_str().to_string_lossy());
+        folder.unwrap_or_default().into_owned()
+      }
+      "year" => self.date().get(..4).unwrap_or("").into(),
+      "letter" => {
+        let first = self.title.chars().next();
+        first.into_iter().flat_map(char::to_uppercase).collect()
+      }
+      _ => String::new(),
+    }
+  }
+}
+
+const SORTS: [&str; 5] = ["name", "title", "date", "weight", "modified"];
+const GROUPS: [&str; 3] = ["folder", "year", "letter"];
+
+/// How listings are sorted and grouped
+///
+/// Sorting is by `name` (the default), `title`, `weight`, `date` or
+/// `modified`, with dates newest first, and a `-` prefix reverses the order.
+/// Listings are grouped by `folder`, `year` or first `letter`, and split into
+/// pages of `page_size` entries.
+#[derive(Clone)]
+pub struct Order {
+  sort: Option<String>,
+  group: Option<String>,
+  page_size: Option<usize>,
+}
+
+impl Order {
+  pub fn new(
+    sort: Option<&str>,
+    group: Option<&str>,
+    page_size: Option<usize>,
+  ) -> Result<Order> {
+    if let Some(sort) = sort {
+      if !SORTS.contains(&sort.trim_start_matches('-')) {
+        return Err(anyhow!("Unknown listing sort '{}'", sort));
+      }
+    }
+    if let Some(group) = group {
+      if !GROUPS.contains(&group) {
+        return Err(anyhow!("Unknown listing group '{}'", group));
+      }
+    }
+    if page_size == Some(0) {
+      return Err(anyhow!("Listing page size must be at least 1"));
+    }
+    Ok(Order {
+      sort: sort.map(String::from),
+      group: group.map(String::from),
+      page_size,
+    })
+  }
+
+  /// This order, overridden by the `sort`, `group` and `paginate` of an index
+  /// page
+  fn with(&self, meta: &Value) -> Result<Order> {
+    let key = |key: &str| meta.get(key).and_then(Value::as_str);
+    // `order` is the original name for `sort`
+    let sort = key("sort").or_else(|| key("order"));
+    let paginate = meta.get("paginate").and_then(Value::as_u64);
+    Order::new(
+      sort.or(self.sort.as_deref()),
+      key("group").or(self.group.as_deref()),
+      paginate.map(|size| size as usize).or(self.page_size),
+    )
+  }
+
+  fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+    let sort = self.sort.as_deref().unwrap_or("name");
+    let ordering = match sort.trim_start_matches('-') {
+      "title" => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+      "date" => b.date().cmp(a.date()),
+      "weight" => a.weight().total_cmp(&b.weight()),
+      "modified" => b.modified.cmp(&a.modified),
+      _ => a.path.cmp(&b.path),
+    };
+    match sort.starts_with('-') {
+      true => ordering.reverse(),
+      false => ordering,
+    }
+  }
+}
+
+/// The notes of a directory, as controlled by the front matter of its index
+///
+/// Notes named in the index's `pinned` list come first, followed by the rest
+/// in the listing's order, leaving out notes that are not yet published. Subdirectories with their own index are listed
+/// alongside the notes, or their notes when grouping by folder.
+pub struct Listing {
+  description: Option<String>,
+  group: Option<String>,
+  page_size: Option<usize>,
+  entries: Vec<Entry>,
+}
+
+impl Listing {
+  pub fn collect(
+    source: &dyn Source,
+    dir: &Path,
+    meta: &Value,
+    order: &Order,
+    schedule: &Schedule,
+    summarizer: Option<&Summarizer>,
+    cache: &Cache,
+  ) -> Result<Listing> {
+    let order = order.with(meta)?;
+    let folders = order.group.as_deref() == Some("folder");
+    let mut entries = vec![];
+    for path in source.files(dir)? {
+      let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+      let depth = relative.components().count();
+      let name = relative.file_name().and_then(|name| name.to_str());
+      let is_markdown =
+        relative.extension().and_then(|ext| ext.to_str()) == Some("md");
+      let listed = match (depth, name) {
+        (1, Some(INDEX)) => false,
+        (1, _) => is_markdown,
+        // Subdirectories are listed by their own index
+        (2, Some(INDEX)) => !folders,
+        (_, Some(INDEX)) => false,
+        _ => folders && is_markdown,
+      };
+      if !listed {
+        continue;
+      }
+
+      let text = source.read_to_string(&path)?;
+      let (mut note, body) = frontmatter::split(&text);
+      if !schedule.is_published(&note)? {
+        continue;
+      }
+      if let Some(summarizer) = summarizer {
+        summarizer.fill(&mut note, body, cache)?;
+      }
+      let title = title(&note, body).unwrap_or_else(|| match depth {
+        1 => stem(&relative),
+        _ => stem(relative.parent().unwrap_or(&relative)),
+      });
+      entries.push(Entry {
+        path: relative,
+        title,
+        summary: summary(&note, body),
+        meta: note,
+        modified: source.modified(&path),
+        pinned: false,
+      });
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.sort_by(|a, b| order.compare(a, b));
+
+    // Groups are kept together, years newest first
+    if let Some(group) = &order.group {
+      entries.sort_by(|a, b| match group.as_str() {
+        "year" => b.group(group).cmp(&a.group(group)),
+        _ => a.group(group).cmp(&b.group(group)),
+      });
+    }
+
+    let pinned = meta.get("pinned").and_then(Value::as_array);
+    let pinned = pinned.into_iter().flatten().filter_map(Value::as_str);
+    for (n, pin) in pinned.enumerate() {
+      let found = entries
+        .iter()
+        .position(|entry| !entry.pinned && entry.path == Path::new(pin));
+      if let Some(i) = found {
+        let mut entry = entries.remove(i);
+        entry.pinned = true;
+        entries.insert(n, entry);
+      }
+    }
+
+    Ok(Listing {
+      description: meta
+        .get("description")
+        .and_then(Value::as_str)
+        .map(String::from),
+      group: order.group,
+      page_size: order.page_size,
+      entries,
+    })
+  }
+
+  /// The number of pages the listing is split into
+  pub fn pages(&self) -> usize {
+    match self.page_size {
+      Some(size) => self.entries.len().div_ceil(size).max(1),
+      None => 1,
+    }
+  }
+
+  /// The entries on a page, counting from one
+  fn page(&self, page: usize) -> &[Entry] {
+    match self.page_size {
+      Some(size) => {
+        let start = ((page - 1) * size).min(self.entries.len());
+        let end = (start + size).min(self.entries.len());
+        &self.entries[start..end]
+      }
+      None => &self.entries,
+    }
+  }
+
+  /// HTML for a page of the listing, linking to its other pages at `base`
+  pub fn to_html(&self, page: usize, base: &str) -> String {
+    let mut html = String::from("<nav class=\"listing\">\n");
+    if let Some(description) = &self.description {
+      html.push_str(&format!(
+        "<p class=\"listing-description\">{}</p>\n",
+        escape(description)
+      ));
+    }
+
+    // Pinned notes are listed before, and outside of, any groups
+    let mut current: Option<Option<String>> = None;
+    for entry in self.page(page) {
+      let group = match entry.pinned {
+        true => None,
+        false => Some(self.group_of(entry)),
+      };
+      if current.as_ref() != Some(&group) {
+        if current.is_some() {
+          html.push_str("</ul>\n");
+        }
+        if let Some(name) = group.as_ref().filter(|name| !name.is_empty()) {
+          html.push_str(&format!(
+            "<h2 class=\"listing-group\">{}</h2>\n",
+            escape(name)
+          ));
+        }
+        html.push_str("<ul>\n");
+        current = Some(group);
+      }
+      let class = if entry.pinned {
+        " class=\"pinned\""
+      } else {
+        ""
+      };
+      let href = escape(&entry.href());
+      html.push_str(&format!(
+        "<li{}><a href=\"{}\">{}</a>",
+        class,
+        href,
+        escape(&entry.title)
+      ));
+      if let Some(summary) = &entry.summary {
+        html.push_str(&format!(
+          "\n<div class=\"summary\">\n{}</div>\n\
+           <a class=\"read-more\" href=\"{}\">Read more</a>",
+          summary, href
+        ));
+      }
+      html.push_str("</li>\n");
+    }
+    if current.is_some() {
+      html.push_str("</ul>\n");
+    }
+
+    let pages = self.pages();
+    if pages > 1 {
+      html.push_str("<p class=\"pagination\">");
+      if page > 1 {
+        html.push_str(&format!(
+          "<a href=\"{}\" rel=\"prev\">Previous</a> ",
+          escape(&page_href(base, page - 1))
+        ));
+      }
+      html.push_str(&format!("Page {} of {}", page, pages));
+      if page < pages {
+        html.push_str(&format!(
+          " <a href=\"{}\" rel=\"next\">Next</a>",
+          escape(&page_href(base, page + 1))
+        ));
+      }
+      html.push_str("</p>\n");
+    }
+    html.push_str("</nav>\n");
+    html
+  }
+
+  fn group_of(&self, entry: &Entry) -> String {
+    match &self.group {
+      Some(group) => entry.group(group),
+      None => String::new(),
+    }
+  }
+
+  pub fn to_json(&self, page: usize) -> Value {
+    let entries = self
+      .page(page)
+      .iter()
+      .map(|entry| {
+        json!({
+          "title": entry.title,
+          "href": entry.href(),
+          "pinned": entry.pinned,
+          "summary": entry.summary,
+          "group": self.group_of(entry),
+          "meta": entry.meta,
+        })
+      })
+      .collect();
+    Value::Array(entries)
+  }
+}
+
+/// The file name of a page of a listing, with later pages numbered
+///
+/// Page two of `index.html` is `index-2.html`.
+pub fn page_href(base: &str, page: usize) -> String {
+  match (page, base.rsplit_once('.')) {
+    (1, _) => base.into(),
+    (_, Some((stem, ext))) => format!("{}-{}.{}", stem, page, ext),
+    (_, None) => format!("{}-{}", base, page),
+  }
+}
+
+/// The number of a later page of a listing from its file name
+pub fn page_number(base: &str, name: &str) -> Option<usize> {
+  let (stem, ext) = base.rsplit_once('.').unwrap_or((base, ""));
+  let rest = name.strip_prefix(stem)?.strip_prefix('-')?;
+  let number = match ext {
+    "" => rest,
+    _ => rest.strip_suffix(ext)?.strip_suffix('.')?,
+  };
+  number.parse().ok().filter(|number| *number > 1)
+}
+
+/// Whether a document is a directory's index page
+pub fn is_index(path: &str) -> bool {
+  Path::new(path).file_name().and_then(|name| name.to_str()) == Some(INDEX)
+}
+
+/// The title of a note from its front matter `title` or `name`, or its heading
+pub fn title(meta: &Value, body: &str) -> Option<String> {
+  let key = |key: &str| meta.get(key).and_then(Value::as_str);
+  key("title")
+    .or_else(|| key("name"))
+    .map(String::from)
+    .or_else(|| heading(body).map(|title| strip_id(title).to_string()))
+}
+
+pub fn stem(path: &Path) -> String {
+  path
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().into_owned())
+    .unwrap_or_default()
+}
@@ -0,0 +1,91 @@
+use crate::code;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+
+const DEFINE: &str = "!define ";
+
+/// Text macros, defined with `!define NAME REPLACEMENT` lines
+#[derive(Clone, Default)]
+pub struct Macros {
+  definitions: BTreeMap<String, String>,
+}
+
+impl Macros {
+  /// Load definitions from a prelude file
+  pub fn load(path: Option<&str>) -> Result<Macros> {
+    let mut macros = Macros::default();
+    if let Some(path) = path {
+      let prelude = fs::read_to_string(path)?;
+      for line in prelude.lines() {
+        macros.define(line);
+      }
+    }
+    Ok(macros)
+  }
+
+  fn define(&mut self, line: &str) -> bool {
+    let definition = match line.strip_prefix(DEFINE) {
+      Some(definition) => definition.trim(),
+      None => return false,
+    };
+    let (name, value) = match definition.find(char::is_whitespace) {
+      Some(i) => (&definition[..i], definition[i..].trim()),
+      None => (definition, ""),
+    };
+    self.definitions.insert(name.into(), value.into());
+    true
+  }
+
+  /// Expand macros throughout a document, outside of code, link destinations
+  /// and HTML tags
+  ///
+  /// Definitions come from the prelude, a `macros` map in the front matter and
+  /// `!define` lines in the document itself, which are removed from the output.
+  pub fn expand(&self, meta: &Value, input: &str) -> String {
+    let mut macros = self.clone();
+    if let Some(Value::Object(definitions)) = meta.get("macros") {
+      for (name, value) in definitions {
+        let value = match value {
+          Value::String(value) => value.clone(),
+          value => value.to_string(),
+        };
+        macros.definitions.insert(name.clone(), value);
+      }
+    }
+
+    let mut lines = vec![];
+    for (code, line) in code::lines(input) {
+      if code || !macros.define(line) {
+        lines.push((code, line));
+      }
+    }
+
+    if macros.definitions.is_empty() {
+      return input.into();
+    }
+
+    let input = lines.into_iter().map(|(_, line)| line).collect::<String>();
+    code::map_text(&input, |text| macros.replace_words(text))
+  }
+
+  fn replace_words(&self, text: &str) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_word) {
+      output.push_str(&rest[..start]);
+      rest = &rest[start..];
+      let end = rest.find(|c| !is_word(c)).unwrap_or(rest.len());
+      let word = &rest[..end];
+      match self.definitions.get(word) {
+        Some(value) => output.push_str(value),
+        None => output.push_str(word),
+      }
+      rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+  }
+}
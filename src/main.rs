@@ -1,36 +1,371 @@
-use anyhow::Result;
-use pikchr::{Pikchr, PikchrFlags};
-use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag};
-use serde_json::json;
+mod api;
+mod caching;
+mod capture;
+mod clip;
+mod config;
+mod dedupe;
+mod edit;
+mod guard;
+mod history;
+mod meetings;
+mod nav;
+mod opml;
+mod pdf;
+mod progress;
+mod refactor;
+mod scaffold;
+mod search;
+mod snapshot;
+mod visits;
+mod watch;
+
+use anyhow::{anyhow, Result};
+use notebook::output::Writer;
+use notebook::*;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::io;
 use std::net;
-use warp::Filter;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::stream::StreamExt;
+use warp::{Filter, Reply};
 
+fn usage(opts: getopts::Options) -> Result<()> {
+  let brief = "Usage: notebook FILE|DIR|- [options]
+       notebook build FILE|DIR|- [options]
+       notebook serve FILE [--addr HOST] [options]
+       notebook watch FILE|DIR [options]
+       notebook clip URL [--dir DIR]
+       notebook ingest eml FILE [--dir DIR]
+       notebook ingest imap URL [--user USER] [--every N] [--dir DIR]
+       notebook export --anki DECK.tsv FILE...
+       notebook stats --stale PATH...
+       notebook dedupe PATH... [--threshold N]
+       notebook refactor split FILE [--by h1|h2]
+       notebook refactor merge A B -o C
+       notebook mv FILE DESTINATION
+       notebook suggest FILE [--dir DIR] [--apply]
+       notebook new [NAME] [--from TEMPLATE.md] [--vars NAME=VALUE]
+       notebook template-test FIXTURES [options]";
+  print!("{}", opts.usage(brief));
+  Ok(())
+}
+
+/// The options of the command line, rendering documents by their `params`
+/// and writing or serving them by the rest
 #[derive(Clone)]
-struct Params {
-  input: String,
-  output: Option<String>,
-  template: String,
+struct Cli {
+  params: Params,
+  /// The directory rendered content is compared with, when it is
+  snapshot: Option<String>,
+  /// Whether changed snapshots are written rather than failing the build
+  update: bool,
+  /// Whether webmentions are sent for the links of written documents
+  webmentions: bool,
+  /// The static JSON API of the notes written, when one is written
+  api: Option<api::Api>,
+  /// The `USER:PASSWORD` that may see notes before they are published
+  auth: Option<String>,
+  cache_control: caching::Policies,
+  /// Where served visits are counted, `memory` or `file`
+  visits: Option<String>,
+  /// Where snippets posted to the web server are appended
+  capture: Option<capture::Capture>,
+  /// The editor of notes in serve mode, when notes may be edited
+  editor: Option<edit::Editor>,
+  /// Whether notes are served as they were at git revisions
+  revisions: bool,
+  /// What finds served notes by their meaning
+  embedder: Option<search::Embedder>,
+  /// The directory of files served beside documents, if not the input's
+  static_dir: Option<String>,
+  /// The path files are served under, such as `static`, if not the root
+  static_prefix: Option<String>,
+  /// The extensions of the files that may be served
+  static_guard: guard::Guard,
 }
 
-fn usage(opts: getopts::Options) -> Result<()> {
-  let brief = format!("Usage: notebook FILE [options]");
-  print!("{}", opts.usage(&brief));
-  Ok(())
+impl Deref for Cli {
+  type Target = Params;
+
+  fn deref(&self) -> &Params {
+    &self.params
+  }
+}
+
+impl DerefMut for Cli {
+  fn deref_mut(&mut self) -> &mut Params {
+    &mut self.params
+  }
 }
 
 fn main() -> Result<()> {
   let args: Vec<String> = env::args().collect();
+  match args.get(1).map(String::as_str) {
+    Some("clip") => return clip::clip(&args[2..]),
+    Some("ingest") => return clip::ingest(&args[2..]),
+    Some("export") => return flashcard::export(&args[2..]),
+    Some("stats") => return expiry::stats(&args[2..]),
+    Some("dedupe") => return dedupe::dedupe(&args[2..]),
+    Some("refactor") => return refactor::refactor(&args[2..]),
+    Some("mv") => return refactor::mv(&args[2..]),
+    Some("suggest") => return suggest::suggest(&args[2..]),
+    Some("new") => return scaffold::new(&args[2..]),
+    _ => {}
+  }
 
   let mut opts = getopts::Options::new();
-  opts.optopt("o", "output", "set output file name", "NAME");
+  opts.optopt("o", "output", "set output file or .zip archive", "NAME");
+  opts.optopt(
+    "",
+    "archive",
+    "read sources from a zip or tar archive",
+    "FILE",
+  );
+  opts.optopt("", "rev", "read sources from a git revision", "REV");
+  opts.optopt(
+    "",
+    "format",
+    "output format, html, pdf, audio, opml, ics, actions, dashboard, tags...",
+    "FORMAT",
+  );
   opts.optopt("t", "template", "template file", "TEMPLATE");
+  opts.optopt("", "layouts", "base templates to extend", "DIR");
+  opts.optopt("", "strings", "template strings for each language", "DIR");
+  opts.optopt(
+    "",
+    "lang",
+    "language of the notebook, defaults to en",
+    "LANG",
+  );
+  opts.optopt("", "sort", "sort listings by name, title, date...", "KEY");
+  opts.optopt(
+    "",
+    "group",
+    "group listings by folder, year or letter",
+    "KEY",
+  );
+  opts.optopt("", "page-size", "split listings into pages", "N");
   opts.optopt("s", "serve", "serve", "HOST");
+  opts.optmulti("d", "data", "load a data file into the template", "FILE");
+  opts.optopt("g", "glossary", "glossary of terms to link", "FILE");
+  opts.optopt("p", "profile", "profile for conditional blocks", "NAME");
+  opts.optopt("", "prelude", "macro definitions file", "FILE");
+  opts.optmulti("", "environment", "numbered environment", "NAME=LABEL");
+  opts.optopt("", "shortcodes", "shortcode partials directory", "DIR");
+  opts.optmulti("", "transform", "apply a built in HTML transform", "NAME");
+  opts.optmulti("", "rewrite", "apply an HTML rewrite rule", "RULE");
+  opts.optopt("", "typography", "typographic conventions to apply", "LANG");
+  opts.optopt("", "hyphenation", "hyphenation patterns directory", "DIR");
+  opts.optmulti("", "unicode", "nfc, glyphs or strip-emoji", "OPTIONS");
+  opts.optopt("", "secrets", "fail, warn or mask on secrets", "MODE");
+  opts.optmulti("", "secret-rule", "secret token prefix", "NAME=PREFIX");
+  opts.optflag("", "sortable", "make tables sortable and filterable");
+  opts.optmulti("", "asset", "fingerprint a file for templates", "FILE");
+  opts.optopt(
+    "",
+    "icon",
+    "make favicons and a manifest from an image",
+    "FILE",
+  );
+  opts.optflag("", "offline", "write a service worker for offline reading");
+  opts.optflag("", "math", "render $ and $$ math as MathML");
+  opts.optopt(
+    "",
+    "math-fallback",
+    "draw --math as SVG for browsers without MathML",
+    "COMMAND",
+  );
+  opts.optflag(
+    "",
+    "unlinked-mentions",
+    "find notes mentioning the document's title without a link",
+  );
+  opts.optopt("", "dot", "graphviz program to draw dot blocks", "PROGRAM");
+  opts.optopt(
+    "",
+    "mermaid",
+    "draw mermaid blocks with a program",
+    "PROGRAM",
+  );
+  opts.optmulti(
+    "",
+    "filter",
+    "command to render code blocks with",
+    "LANG=CMD",
+  );
+  opts.optflagopt(
+    "",
+    "properties",
+    "show front matter in a table, or only FIELDS",
+    "FIELDS",
+  );
+  opts.optflag("", "execute", "run code blocks marked run, showing output");
+  opts.optmulti("", "runner", "command to run code blocks with", "LANG=CMD");
+  opts.optopt(
+    "",
+    "timeout",
+    "seconds code blocks may run, default 10",
+    "N",
+  );
+  opts.optflag("", "no-cache", "render diagrams and run code every time");
+  opts.optflag(
+    "",
+    "standalone",
+    "inline local images, styles and scripts into one file",
+  );
+  opts.optflag("", "print", "optimise the output for printing");
+  opts.optopt(
+    "",
+    "tts",
+    "text to speech program reading notes aloud",
+    "PROGRAM",
+  );
+  opts.optopt("", "audio-format", "mp3, ogg, opus, m4a or wav", "EXT");
+  opts.optopt(
+    "",
+    "pdf-engine",
+    "program converting html to pdf, defaults to weasyprint",
+    "PROGRAM",
+  );
+  opts.optopt("", "paper", "size of pdf pages, defaults to A4", "SIZE");
+  opts.optopt(
+    "",
+    "margin",
+    "margin of pdf pages, defaults to 2cm",
+    "LENGTH",
+  );
+  opts.optopt("", "snapshot", "compare output with snapshots", "DIR");
+  opts.optflag("", "update", "update golden files and snapshots");
+  opts.optmulti(
+    "",
+    "cache-control",
+    "serve policy for a route",
+    "ROUTE=POLICY",
+  );
+  opts.optopt("", "at", "build as of a time, for publish_at", "TIME");
+  opts.optopt(
+    "",
+    "auth",
+    "serve unpublished notes to a user",
+    "USER:PASSWORD",
+  );
+  opts.optflag(
+    "",
+    "webmentions",
+    "send webmentions for links from the front matter url",
+  );
+  opts.optopt(
+    "",
+    "mentions",
+    "fetch mentions for templates from a JSON endpoint",
+    "URL",
+  );
+  opts.optopt(
+    "",
+    "count-visits",
+    "count served pages by day, listed at /stats",
+    "memory|file",
+  );
+  opts.optopt(
+    "",
+    "capture",
+    "append snippets posted to /api/capture to a note",
+    "NOTE|daily",
+  );
+  opts.optopt("", "capture-token", "token that captures need", "TOKEN");
+  opts.optflag("", "edit", "edit notes at /edit/NOTE.md, given --auth");
+  opts.optflag(
+    "",
+    "revisions",
+    "serve notes at git revisions at /rev/REV/NOTE.md, given --auth",
+  );
+  opts.optflag("", "wikilinks", "link [[Page Name]] to the notes beside it");
+  opts.optflag("", "inline-tags", "link and index #tag tags in notes");
+  opts.optflag(
+    "",
+    "linked-from",
+    "end pages with the notes linking to them",
+  );
+  opts.optflag("", "api", "write a static JSON API of the notes to api/");
+  opts.optmulti(
+    "",
+    "extensions",
+    "markdown extensions to enable, defaults to all",
+    "tables,footnotes...",
+  );
+  opts.optopt(
+    "",
+    "embed",
+    "search served notes by meaning with an embedding program",
+    "PROGRAM",
+  );
+  opts.optopt(
+    "",
+    "summarize",
+    "summarize notes without a summary with a program",
+    "PROGRAM",
+  );
+  opts.optflag("", "watch", "write the output again on changes");
+  opts.optopt(
+    "",
+    "assets",
+    "directory of files to serve, defaults to the input's",
+    "DIR",
+  );
+  opts.optopt("", "assets-prefix", "path to serve files under", "PATH");
+  opts.optmulti(
+    "",
+    "assets-extension",
+    "extension of the files that may be served",
+    "EXT",
+  );
+  opts.optopt(
+    "",
+    "symlinks",
+    "follow symlinks within the vault, skip them or error",
+    "follow|skip|error",
+  );
+  opts.optopt(
+    "",
+    "vault",
+    "directory nothing is read from outside of",
+    "DIR",
+  );
+  opts.optopt(
+    "",
+    "progress",
+    "report progress as text or json events",
+    "text|json",
+  );
+  opts.optopt(
+    "",
+    "addr",
+    "address of notebook serve, defaults to 127.0.0.1:8000",
+    "HOST",
+  );
   opts.optflag("h", "help", "print this help menu");
 
-  let matches = opts.parse(&args[1..])?;
+  // Template tests accept every option, to render fixtures like documents
+  let testing = args.get(1).map(String::as_str) == Some("template-test");
+  let mode = match args.get(1).map(String::as_str) {
+    Some("build") => Mode::Build,
+    Some("serve") => Mode::Serve,
+    Some("watch") => Mode::Watch,
+    _ => Mode::Flags,
+  };
+  let mut args = match testing || mode != Mode::Flags {
+    true => args[2..].to_vec(),
+    false => args[1..].to_vec(),
+  };
+  let mut matches = opts.parse(&args)?;
+  let unused = mode.unused();
+  if let Some(name) = unused.iter().find(|name| matches.opt_present(name)) {
+    return Err(anyhow!("--{} is not an option of {}", name, mode.name()));
+  }
 
   let input = if !matches.free.is_empty() {
     matches.free[0].clone()
@@ -38,190 +373,1430 @@ fn main() -> Result<()> {
     return usage(opts);
   };
 
-  let params = Params {
-    input: input,
-    output: matches.opt_str("output"),
-    template: match matches.opt_str("template") {
-      Some(path) => fs::read_to_string(path)?,
-      None => include_str!("template.hbs").into(),
+  // Options of the notebook's configuration that aren't given here
+  let config = config::find(&input);
+  if let Some(path) = &config {
+    let config = config::args(path, &matches, &unused)?;
+    if !config.is_empty() {
+      args.extend(config);
+      matches = opts
+        .parse(&args)
+        .map_err(|err| anyhow!("{} in '{}'", err, path.display()))?;
+    }
+  }
+  let serving = mode == Mode::Serve || matches.opt_present("serve");
+
+  let mut rewrites = vec![];
+  if matches.opt_present("sortable") {
+    rewrites.push(rewrite::Rule::builtin("sortable")?);
+  }
+  for name in matches.opt_strs("transform") {
+    rewrites.push(rewrite::Rule::builtin(&name)?);
+  }
+  for rule in matches.opt_strs("rewrite") {
+    rewrites.push(rule.parse()?);
+  }
+
+  // Embeds are found anywhere in the notebook, its vault or the directory
+  // given, rather than only beside each note
+  let root = match (matches.opt_str("vault"), &config) {
+    (Some(dir), _) => Some(PathBuf::from(dir)),
+    (None, Some(path)) => path.parent().map(PathBuf::from),
+    (None, None) => Some(PathBuf::from(&input)).filter(|dir| dir.is_dir()),
+  };
+
+  let symlinks = match matches.opt_str("symlinks") {
+    Some(policy) => vfs::Symlinks::parse(&policy)?,
+    None => vfs::Symlinks::default(),
+  };
+  let source: Arc<dyn vfs::Source> =
+    match (matches.opt_str("archive"), matches.opt_str("rev")) {
+      (Some(_), Some(_)) => {
+        return Err(anyhow!("--archive and --rev cannot be used together"))
+      }
+      (Some(path), None) => Arc::new(vfs::Memory::archive(Path::new(&path))?),
+      (None, Some(rev)) => Arc::new(vfs::Git::new(&rev)?),
+      (None, None) => {
+        // The notebook's directory, or else the current directory, so that
+        // notes in subdirectories may read the notes around them
+        let vault = match (matches.opt_str("vault"), &config) {
+          (Some(dir), _) => PathBuf::from(dir),
+          (None, Some(path)) => path.parent().unwrap_or(Path::new("")).into(),
+          (None, None) => default_vault(Path::new(&input)),
+        };
+        Arc::new(vfs::Filesystem::new(&vault, symlinks)?)
+      }
+    };
+  // A document from standard input is read once, even when served
+  let source: Arc<dyn vfs::Source> = match input == vfs::STDIN {
+    true => Arc::new(vfs::Stdin::read(source)?),
+    false => source,
+  };
+
+  let params = Cli {
+    params: Params {
+      input,
+      root,
+      source,
+      output: matches.opt_str("output"),
+      template: match matches.opt_str("template") {
+        Some(path) => fs::read_to_string(path)?,
+        None => include_str!("template.hbs").into(),
+      },
+      template_path: matches.opt_str("template"),
+      layouts: layout::Layouts::load(matches.opt_str("layouts").as_deref())?,
+      strings: {
+        let theme = matches.opt_str("layouts");
+        let theme = theme.and_then(|dir| strings::Strings::theme(&dir));
+        let dirs = theme.into_iter().chain(matches.opt_str("strings"));
+        strings::Strings::load(&dirs.collect::<Vec<_>>())?
+      },
+      lang: matches.opt_str("lang").unwrap_or_else(|| "en".into()),
+      order: listing::Order::new(
+        matches.opt_str("sort").as_deref(),
+        matches.opt_str("group").as_deref(),
+        matches.opt_get("page-size")?,
+      )?,
+      site: false,
+      page: 1,
+      rewrites,
+      data: data::load(&matches.opt_strs("data"))?,
+      shortcodes: shortcode::Shortcodes::load(
+        matches.opt_str("shortcodes").as_deref(),
+      )?,
+      macros: macros::Macros::load(matches.opt_str("prelude").as_deref())?,
+      profile: matches.opt_str("profile"),
+      glossary: match matches.opt_str("glossary") {
+        Some(path) => Some(glossary::Glossary::load(&path)?),
+        None => None,
+      },
+      environments: environment::Environments::new(
+        &matches.opt_strs("environment"),
+      )?,
+      print: matches.opt_present("print"),
+      sortable: matches.opt_present("sortable"),
+      assets: assets::Assets::load(&matches.opt_strs("asset"))?,
+      offline: matches.opt_present("offline"),
+      math: matches.opt_present("math"),
+      math_fallback: matches.opt_str("math-fallback"),
+      unlinked_mentions: matches.opt_present("unlinked-mentions"),
+      diagrams: diagram::Programs::new(
+        matches.opt_str("dot"),
+        matches.opt_str("mermaid"),
+        &matches.opt_strs("filter"),
+      )?,
+      properties: match matches.opt_present("properties") {
+        true => Some(properties::Properties::new(
+          &matches.opt_str("properties").unwrap_or_default(),
+        )),
+        false => None,
+      },
+      cache: !matches.opt_present("no-cache"),
+      execute: match matches.opt_present("execute") {
+        true => Some(execute::Runners::new(
+          &matches.opt_strs("runner"),
+          matches.opt_get_default("timeout", 10)?,
+        )?),
+        false => None,
+      },
+      icon: match matches.opt_str("icon") {
+        Some(path) => Some(icons::Icon::load(&path)?),
+        None => None,
+      },
+      inline_assets: !matches.opt_present("output") && !serving
+        || matches.opt_present("standalone"),
+      standalone: matches.opt_present("standalone"),
+      typography: match matches.opt_str("typography") {
+        Some(lang) => Some(typography::Language::parse(&lang)?),
+        None => None,
+      },
+      hyphenation: matches.opt_str("hyphenation"),
+      unicode: unicode::Unicode::parse(&matches.opt_strs("unicode"))?,
+      secrets: match matches.opt_str("secrets") {
+        Some(mode) => Some(secrets::Scanner::new(
+          &mode,
+          &matches.opt_strs("secret-rule"),
+        )?),
+        None => None,
+      },
+      schedule: publish::Schedule::new(matches.opt_str("at").as_deref())?,
+      mentions: matches.opt_str("mentions"),
+      summarizer: match matches.opt_str("summarize") {
+        Some(command) => Some(summary::Summarizer::new(&command)?),
+        None => None,
+      },
+      wikilinks: matches.opt_present("wikilinks"),
+      inline_tags: matches.opt_present("inline-tags"),
+      backlinks: None,
+      snippets: None,
+      people: None,
+      linked_from: matches.opt_present("linked-from"),
+      extensions: extensions(&matches.opt_strs("extensions"))?,
+      transformers: vec![],
+      events: match matches.opt_str("progress") {
+        Some(format) => events::Events::new(&format)?,
+        None => events::Events::default(),
+      },
+    },
+    snapshot: matches.opt_str("snapshot"),
+    update: matches.opt_present("update"),
+    webmentions: matches.opt_present("webmentions"),
+    api: matches.opt_present("api").then(api::Api::default),
+    auth: matches.opt_str("auth"),
+    cache_control: caching::Policies::parse(
+      &matches.opt_strs("cache-control"),
+    )?,
+    visits: matches.opt_str("count-visits"),
+    capture: match matches.opt_str("capture") {
+      Some(target) => Some(capture::Capture::new(
+        &target,
+        matches.opt_str("capture-token"),
+      )?),
+      None => None,
+    },
+    editor: match (matches.opt_present("edit"), matches.opt_present("auth")) {
+      (true, true) => Some(edit::Editor::new(symlinks)),
+      (true, false) => return Err(anyhow!("--edit needs --auth")),
+      (false, _) => None,
+    },
+    revisions: match (
+      matches.opt_present("revisions"),
+      matches.opt_present("auth"),
+    ) {
+      (true, false) => return Err(anyhow!("--revisions needs --auth")),
+      (revisions, _) => revisions,
     },
+    embedder: match matches.opt_str("embed") {
+      Some(command) => Some(search::Embedder::new(&command)?),
+      None => None,
+    },
+    static_dir: matches.opt_str("assets"),
+    static_prefix: matches.opt_str("assets-prefix"),
+    static_guard: guard::Guard::new(
+      &matches.opt_strs("assets-extension"),
+      symlinks,
+    ),
   };
 
-  match matches.opt_str("serve") {
-    Some(host) => web_output(host.parse()?, params),
-    None => file_output(params),
+  match matches.opt_str("format").as_deref() {
+    None | Some("html") => {}
+    Some("pdf") => {
+      let pdf = pdf::Pdf::new(
+        matches.opt_str("pdf-engine"),
+        matches.opt_str("paper"),
+        matches.opt_str("margin"),
+      )?;
+      return pdf_output(params, pdf);
+    }
+    Some("audio") => {
+      let speech = audio::Speech::new(
+        matches.opt_str("tts"),
+        matches.opt_str("audio-format"),
+      )?;
+      return audio_output(params, speech);
+    }
+    Some("opml") => return opml_output(params),
+    Some("ics") => return calendar_output(params),
+    Some("actions") => return actions_output(params),
+    Some("dashboard") => return dashboard_output(params),
+    Some("tags") => return tags_output(params),
+    Some("links") => return links_output(params),
+    Some(format) => return Err(anyhow!("Unknown format '{}'", format)),
+  }
+
+  if testing {
+    return fixture::run(&params.input, params.update, |path| {
+      let mut params = params.clone();
+      params.input = path.to_string_lossy().into();
+      let mut output = vec![];
+      render_html(&fs::read_to_string(path)?, &params, &mut output)?;
+      Ok(String::from_utf8(output)?)
+    });
   }
+
+  let watching = mode == Mode::Watch || matches.opt_present("watch");
+  if watching && params.input == vfs::STDIN {
+    return Err(anyhow!("Watching can not be used with standard input"));
+  }
+  match mode {
+    Mode::Build => write_output(params),
+    Mode::Serve => {
+      let host = matches.opt_str("addr");
+      web_output(host.as_deref().unwrap_or(ADDRESS).parse()?, params)
+    }
+    Mode::Watch => watch_output(params),
+    Mode::Flags => match (matches.opt_str("serve"), watching) {
+      (Some(_), true) => Err(anyhow!("--watch can not be used with --serve")),
+      (Some(host), false) => web_output(host.parse()?, params),
+      (None, true) => watch_output(params),
+      (None, false) => write_output(params),
+    },
+  }
+}
+
+/// The address `notebook serve` listens on unless given another
+const ADDRESS: &str = "127.0.0.1:8000";
+
+/// Options only of serving documents
+const SERVE_OPTIONS: [&str; 11] = [
+  "assets",
+  "assets-prefix",
+  "assets-extension",
+  "auth",
+  "count-visits",
+  "capture",
+  "capture-token",
+  "edit",
+  "revisions",
+  "embed",
+  "cache-control",
+];
+
+/// Options only of writing the output
+const BUILD_OPTIONS: [&str; 11] = [
+  "format",
+  "progress",
+  "tts",
+  "audio-format",
+  "pdf-engine",
+  "paper",
+  "margin",
+  "api",
+  "webmentions",
+  "snapshot",
+  "update",
+];
+
+/// What a command line asks for
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+  /// `notebook build`, writing the output once
+  Build,
+  /// `notebook serve`, serving documents as they are requested
+  Serve,
+  /// `notebook watch`, writing the output again on changes
+  Watch,
+  /// `notebook FILE`, with `--serve` or `--watch` choosing what to do
+  Flags,
 }
 
-fn file_output(params: Params) -> Result<()> {
-  let input = fs::read_to_string(&params.input)?;
-  let mut output: Box<dyn io::Write> = match &params.output {
-    Some(path) => Box::new(fs::File::create(path)?),
-    None => Box::new(io::stdout()),
+impl Mode {
+  fn name(self) -> &'static str {
+    match self {
+      Mode::Build => "notebook build",
+      Mode::Serve => "notebook serve",
+      Mode::Watch => "notebook watch",
+      Mode::Flags => "notebook FILE",
+    }
+  }
+
+  /// The options that have no use in the mode
+  fn unused(self) -> Vec<&'static str> {
+    let modes = ["serve", "watch"];
+    match self {
+      Mode::Build | Mode::Watch => {
+        [&SERVE_OPTIONS[..], &modes, &["addr"]].concat()
+      }
+      Mode::Serve => [&BUILD_OPTIONS[..], &modes].concat(),
+      Mode::Flags => vec!["addr"],
+    }
+  }
+}
+
+/// Write the output, and write it again whenever the document or its template
+/// changes
+fn watch_output(mut params: Cli) -> Result<()> {
+  let input = Path::new(&params.input);
+  let mut watched = match input.is_dir() {
+    true => params.source.files(input)?,
+    false => vec![input.to_path_buf()],
   };
-  render_html(&input, &params.template, &mut output)
+  watched.extend(params.template_path.iter().map(PathBuf::from));
+  let mut watcher = watch::Watcher::new(watched);
+  write_output(params.clone())?;
+  loop {
+    std::thread::sleep(Duration::from_millis(250));
+    if !watcher.changed() {
+      continue;
+    }
+    if let Err(err) = reload_template(&mut params) {
+      eprintln!("Error: {}", err);
+    }
+    // Mistakes while editing are reported without ending the watch
+    match write_output(params.clone()) {
+      Ok(()) => eprintln!("Updated {}", params.input),
+      Err(err) => eprintln!("Error: {}", err),
+    }
+  }
 }
 
-#[tokio::main]
-async fn web_output(addr: net::SocketAddr, params: Params) -> Result<()> {
-  let not_found = || {
-    let body = warp::reply::html("File not found".as_bytes().to_vec());
-    let code = warp::http::StatusCode::NOT_FOUND;
-    warp::reply::with_status(body, code)
+/// The vault of an input without a notebook, the current directory when the
+/// input is within it, or else the input's directory
+fn default_vault(input: &Path) -> PathBuf {
+  let dir = match input.is_dir() {
+    true => input,
+    false => input
+      .parent()
+      .filter(|dir| dir != &Path::new(""))
+      .unwrap_or(Path::new(".")),
+  };
+  let within = match (fs::canonicalize(dir), env::current_dir()) {
+    (Ok(dir), Ok(current)) => dir.starts_with(current),
+    _ => true,
   };
+  match within {
+    true => PathBuf::from("."),
+    false => dir.to_path_buf(),
+  }
+}
 
-  let bad_request = |err: &str| {
-    let body = warp::reply::html(err.as_bytes().to_vec());
-    let code = warp::http::StatusCode::BAD_REQUEST;
-    warp::reply::with_status(body, code)
+/// Read the template again, so that edits to it show without a restart
+fn reload_template(params: &mut Cli) -> Result<()> {
+  if let Some(path) = &params.template_path {
+    params.template = fs::read_to_string(path)?;
+  }
+  Ok(())
+}
+
+/// Write a document, or every document under a directory
+fn write_output(params: Cli) -> Result<()> {
+  let started = Instant::now();
+  let events = params.events.clone();
+  let written = match Path::new(&params.input).is_dir() {
+    true => site_output(params),
+    false => file_output(params),
   };
+  events.totals(started, written.as_ref().err());
+  written
+}
 
-  let document_params = params.clone();
-  let document = move || {
-    let params = &document_params;
-    let input = match fs::read_to_string(&params.input) {
-      Ok(input) => input,
-      Err(_) => return not_found(),
+fn file_output(params: Cli) -> Result<()> {
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
+  document_output(&params, &mut output, &path)?;
+  finish_output(&params, output, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Render every note under a directory into the same place in an output
+/// directory, copying the other files beside them
+fn site_output(mut params: Cli) -> Result<()> {
+  let root = PathBuf::from(&params.input);
+  let mut output = match &params.output {
+    Some(out) => output::Output::directory(out)?,
+    None => return Err(anyhow!("Rendering a directory needs an --output")),
+  };
+  // Every note's links are found before any note is rendered
+  params.backlinks = Some(Arc::new(backlinks::Backlinks::collect(
+    &*params.source,
+    &root,
+    &params.schedule,
+    params.wikilinks,
+  )?));
+  params.snippets = Some(Default::default());
+  params.people = Some(Default::default());
+  let out_dir = PathBuf::from(params.output.as_deref().unwrap_or(""));
+  for file in params.source.files(&root)? {
+    let relative = file.strip_prefix(&root).unwrap_or(&file);
+    // Earlier builds into the notebook itself, and its history, aren't notes
+    if paths::within(&file, &out_dir) || relative.starts_with(".notebook") {
+      continue;
+    }
+    if relative.extension().and_then(|ext| ext.to_str()) != Some("md") {
+      output.write(relative, &params.source.read(&file)?)?;
+      continue;
+    }
+    let mut params = params.clone();
+    params.input = file.to_string_lossy().into();
+    params.site = true;
+    let path = relative.with_extension("html");
+    let out = Path::new(params.output.as_deref().unwrap_or("")).join(&path);
+    params.output = Some(out.to_string_lossy().into());
+    document_output(&params, &mut output, &path)?;
+
+    // Pages a note was once at, as its `aliases`, redirect to it
+    let (meta, _) = frontmatter::split(&params.source.read_to_string(&file)?);
+    for (from, html) in refactor::redirects(&meta, &path) {
+      output.write(&from, html.as_bytes())?;
+    }
+  }
+
+  // Pages for the whole notebook are rendered as if they were notes at its
+  // root
+  let mut params = params.clone();
+  params.input = root.join("index.md").to_string_lossy().into();
+  finish_output(&params, output, Path::new(""))
+}
+
+/// Render a document and everything written alongside it to a path within
+/// the output
+fn document_output(
+  params: &Cli,
+  output: &mut output::Output,
+  path: &Path,
+) -> Result<()> {
+  params.events.started(&params.input);
+  let input = params.source.read_to_string(Path::new(&params.input))?;
+  let (meta, _) = frontmatter::split(&input);
+  if !params.schedule.is_published(&meta)? {
+    params.events.skipped(&params.input, "not yet published");
+    return Ok(());
+  }
+  let mut buffer = vec![];
+  let document = render_html(&input, params, &mut buffer)?;
+  output.write(path, &buffer)?;
+  match params.output {
+    Some(_) => {
+      let dir = path.parent().unwrap_or(Path::new(""));
+      for (asset, contents) in document.assets.files() {
+        output.write(&dir.join(asset), contents)?;
+      }
+    }
+    None if !document.assets.is_empty() => params
+      .events
+      .warn("assets are not written to standard output"),
+    None => {}
+  }
+
+  // Notes of a directory are written to its API by their path within it
+  if let (Some(api), Some(_)) = (&params.api, &params.output) {
+    let (root, page) = match params.site {
+      true => (Path::new(""), path),
+      false => (
+        path.parent().unwrap_or(Path::new("")),
+        Path::new(path.file_name().unwrap_or_default()),
+      ),
+    };
+    let backlinks = match &params.backlinks {
+      Some(backlinks) => backlinks.to(Path::new(&params.input)),
+      None => vec![],
     };
+    let (_, body) = frontmatter::split(&input);
+    let body = match &params.secrets {
+      Some(scanner) => scanner.mask(body),
+      None => body.to_string(),
+    };
+    let (file, note) =
+      api.note(page, &meta, &body, &document.content, backlinks);
+    output.write(&root.join(file), &serde_json::to_vec_pretty(&note)?)?;
+  }
+
+  // Later pages of a paginated listing are written beside the first
+  for page in 2..=document.pages {
+    let name = listing::page_href(&document_name(params), page);
+    match params.output {
+      Some(_) => {
+        let mut params = params.clone();
+        params.page = page;
+        let mut buffer = vec![];
+        render_html(&input, &params, &mut buffer)?;
+        output.write(&path.with_file_name(&name), &buffer)?;
+      }
+      None => params
+        .events
+        .warn(&format!("{} is not written to standard output", name)),
+    }
+  }
 
+  if params.output.is_some() {
+    let (dir, events) = (input_dir(params), &params.events);
+    headings::check(dir, &params.input, &document.ids, events)?;
+  }
+  if params.webmentions {
+    match meta.get("url").and_then(|url| url.as_str()) {
+      Some(url) => {
+        let (dir, events) = (input_dir(params), &params.events);
+        webmention::send(dir, url, &document.content, events)?
+      }
+      None => params
+        .events
+        .warn("webmentions need the note's front matter url"),
+    }
+  }
+  if let Some(dir) = &params.snapshot {
+    let (input, content) = (&params.input, &document.content);
+    snapshot::check(dir, input, content, params.update, &params.events)?;
+  }
+  params.events.finished(&params.input, path);
+  Ok(())
+}
+
+/// Write the pages and files for the whole output into a directory of it,
+/// then finish writing
+fn finish_output(
+  params: &Cli,
+  mut output: output::Output,
+  dir: &Path,
+) -> Result<()> {
+  // The glossary page is written alongside the output document
+  if let (Some(glossary), Some(_)) = (&params.glossary, &params.output) {
     let mut buffer = vec![];
-    match render_html(&input, &params.template, &mut buffer) {
-      Ok(_) => {}
-      Err(err) => return bad_request(&err.to_string()),
-    };
+    render_html(&glossary.page(), params, &mut buffer)?;
+    output.write(&dir.join(glossary::PAGE), &buffer)?;
+  }
 
-    let body = warp::reply::html(buffer);
-    let code = warp::http::StatusCode::OK;
-    warp::reply::with_status(body, code)
-  };
+  match (&params.api, &params.output) {
+    (Some(api), Some(_)) => {
+      let (file, notes) = api.index();
+      output.write(&dir.join(file), &serde_json::to_vec_pretty(&notes)?)?;
+    }
+    (Some(_), None) => params
+      .events
+      .warn("the API is not written to standard output"),
+    (None, _) => {}
+  }
 
-  let fallback_params = params.clone();
-  let fallback = warp::path::tail().map(move |tail: warp::path::Tail| {
-    let tail = tail.as_str();
-    let params = &fallback_params;
-    match (tail, &params.output.as_ref()) {
-      ("", None) => document(),
-      (_, Some(out)) if tail == out.as_str() => document(),
-      _ => not_found(),
+  // The service worker caches everything written before it
+  match (params.offline, &params.output) {
+    (true, Some(_)) => {
+      let (precache, worker) = offline::files(output.written());
+      output.write(&dir.join(offline::PRECACHE), &precache)?;
+      output.write(&dir.join(offline::WORKER), &worker)?;
     }
-  });
+    (true, None) => params
+      .events
+      .warn("the service worker is not written to standard output"),
+    (false, _) => {}
+  }
 
-  let assets = warp::get().and(warp::fs::dir("."));
-  let routes = assets.or(fallback);
+  let manifest = output.finish()?;
+  if params.output.is_some() {
+    let path = input_dir(params).join(".notebook").join("manifest.json");
+    fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+    fs::write(path, serde_json::to_string_pretty(&manifest)?)?;
+  }
+  Ok(())
+}
 
-  warp::serve(routes).run(addr).await;
+/// Write a document as a PDF, converted from its output for printing
+fn pdf_output(mut params: Cli, pdf: pdf::Pdf) -> Result<()> {
+  params.print = true;
+  let input = params.source.read_to_string(Path::new(&params.input))?;
+  let mut html = vec![];
+  render_html(&input, &params, &mut html)?;
+  let html = inject(html, "</head>", &pdf.style());
+  let document = pdf.convert(html, input_dir(&params))?;
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
+  output.write(&path.with_extension("pdf"), &document)?;
+  output.finish()?;
   Ok(())
 }
 
-fn render_html<W>(input: &str, template: &str, output: &mut W) -> Result<()>
-where
-  W: io::Write,
-{
-  let mut options = Options::empty();
-  options.insert(Options::ENABLE_STRIKETHROUGH);
-  options.insert(Options::ENABLE_TABLES);
-  options.insert(Options::ENABLE_FOOTNOTES);
-  options.insert(Options::ENABLE_TASKLISTS);
-  options.insert(Options::ENABLE_SMART_PUNCTUATION);
-
-  let parser = Parser::new_ext(input, options);
-  let parser = PikchrTransformer { iter: parser };
-  let events = parser.into_iter().collect::<Vec<_>>();
-  let heading = extract_heading(&events);
-
-  let mut content = String::new();
-  html::push_html(&mut content, events.into_iter());
-
-  let context = json!({
-      "title": heading.unwrap_or("".into()),
-      "content": content,
-  });
+/// Write a document read aloud, or each of the notes under a directory
+fn audio_output(params: Cli, speech: audio::Speech) -> Result<()> {
+  let root = Path::new(&params.input);
+  if !root.is_dir() {
+    let input = params.source.read_to_string(root)?;
+    let (_, body) = frontmatter::split(&input);
+    let audio = speech.read(&audio::text(body), input_dir(&params))?;
+    let (mut output, path) = output::Output::open(params.output.as_deref())?;
+    output.write(&path.with_extension(speech.format()), &audio)?;
+    output.finish()?;
+    return Ok(());
+  }
+
+  let mut output = match &params.output {
+    Some(out) => output::Output::directory(out)?,
+    None => return Err(anyhow!("Reading a directory aloud needs an --output")),
+  };
+  for file in params.source.files(root)? {
+    let relative = file.strip_prefix(root).unwrap_or(&file);
+    if relative.extension().and_then(|ext| ext.to_str()) != Some("md") {
+      continue;
+    }
+    let input = params.source.read_to_string(&file)?;
+    let (meta, body) = frontmatter::split(&input);
+    if !params.schedule.is_published(&meta)? {
+      continue;
+    }
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let audio = speech.read(&audio::text(body), dir)?;
+    output.write(&relative.with_extension(speech.format()), &audio)?;
+  }
+  output.finish()?;
+  Ok(())
+}
 
-  let registry = handlebars::Handlebars::new();
-  let rendered = registry.render_template(&template, &context)?;
+/// Write an outline of the notes under a directory, or of a single note
+fn opml_output(params: Cli) -> Result<()> {
+  let root = Path::new(&params.input);
+  let nodes = nav::tree(&*params.source, root, &params.schedule)?;
+  let title = root.file_stem().map(|stem| stem.to_string_lossy());
+  let opml = opml::render(&title.unwrap_or_default(), &nodes);
 
-  output.write(rendered.as_bytes())?;
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
+  output.write(&path.with_extension("opml"), opml.as_bytes())?;
+  output.finish()?;
   Ok(())
 }
 
-/// Extract a heading from the markdown input
-fn extract_heading(events: &[Event]) -> Option<String> {
-  let mut in_h1 = false;
-  for event in events {
-    let tag = match (in_h1, &event) {
-      (false, Event::Start(tag)) => tag,
-      (true, Event::Text(text)) => return Some(text.to_string()),
-      _ => continue,
-    };
-    let kind = match tag {
-      Tag::Heading(kind) => kind,
-      _ => continue,
+/// Write a calendar of the dated items in the notes under a directory
+fn calendar_output(params: Cli) -> Result<()> {
+  let events = calendar::events(
+    &*params.source,
+    Path::new(&params.input),
+    &params.schedule,
+  )?;
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
+  let calendar = calendar::render(&events);
+  output.write(&path.with_extension("ics"), calendar.as_bytes())?;
+  output.finish()?;
+  Ok(())
+}
+
+/// Write pages of the action items and decisions of the meeting notes under a
+/// directory, by owner and by project
+fn actions_output(params: Cli) -> Result<()> {
+  let root = Path::new(&params.input);
+  let meetings = meetings::Meetings::collect(&*params.source, root)?;
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
+
+  // The pages are rendered as if they were notes at the root of the notebook
+  let mut params = params.clone();
+  params.input = meetings.document().to_string_lossy().into();
+  for (name, page) in meetings.pages() {
+    let mut buffer = vec![];
+    // The index is the output document, with the other pages beside it
+    let file = match (&params.output, name.as_str()) {
+      (_, meetings::PAGE) => path.clone(),
+      (Some(_), _) => path.with_file_name(&name),
+      (None, _) => {
+        params
+          .events
+          .warn(&format!("{} is not written to standard output", name));
+        continue;
+      }
     };
-    in_h1 = *kind == 1;
+    render_html(&page, &params, &mut buffer)?;
+    output.write(&file, &buffer)?;
   }
-  None
+  output.finish()?;
+  Ok(())
 }
 
-/// Transforms Pikchr fenced code blocks into SVG diagrams
-struct PikchrTransformer<'a, T>
-where
-  T: Iterator<Item = Event<'a>>,
-{
-  iter: T,
+/// Write a dashboard of the metrics tracked in the notes under a directory
+fn dashboard_output(params: Cli) -> Result<()> {
+  let root = Path::new(&params.input);
+  let tracker =
+    track::Tracker::collect(&*params.source, root, &params.schedule)?;
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
+
+  // The dashboard is rendered as if it were a note at the root of the notebook
+  let mut params = params.clone();
+  params.input = root
+    .join(track::PAGE)
+    .with_extension("md")
+    .to_string_lossy()
+    .into();
+  let mut buffer = vec![];
+  render_html(&tracker.page(), &params, &mut buffer)?;
+  output.write(&path, &buffer)?;
+  output.finish()?;
+  Ok(())
 }
 
-impl<'a, T> Iterator for PikchrTransformer<'a, T>
-where
-  T: Iterator<Item = Event<'a>>,
-{
-  type Item = Event<'a>;
+/// Write an index of the tags of the notes under a directory
+fn tags_output(params: Cli) -> Result<()> {
+  let root = Path::new(&params.input);
+  let tags = tags::Tags::collect(
+    &*params.source,
+    root,
+    &params.schedule,
+    params.inline_tags,
+  )?;
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
 
-  fn next(&mut self) -> Option<Self::Item> {
-    let event = match self.iter.next() {
-      Some(event) => event,
-      None => return None,
-    };
+  // The index is rendered as if it were a note at the root of the notebook
+  let mut params = params.clone();
+  params.input = root
+    .join(tags::PAGE)
+    .with_extension("md")
+    .to_string_lossy()
+    .into();
+  let mut buffer = vec![];
+  render_html(&tags.page(), &params, &mut buffer)?;
+  output.write(&path, &buffer)?;
+  output.finish()?;
+  Ok(())
+}
 
-    let tag = match event {
-      Event::Start(ref tag) => tag,
-      _ => return Some(event),
-    };
+/// Write an Atom feed of the links in the link logs under a directory
+fn links_output(params: Cli) -> Result<()> {
+  let root = Path::new(&params.input);
+  let mut cache = links::Cache::load(root, &params.events);
+  let feed = links::feed(&*params.source, root, &params.schedule, &mut cache)?;
+  cache.save()?;
+  let (mut output, path) = output::Output::open(params.output.as_deref())?;
+  output.write(&path.with_extension("xml"), feed.as_bytes())?;
+  output.finish()?;
+  Ok(())
+}
 
-    let kind = match tag {
-      Tag::CodeBlock(kind) => kind,
-      _ => return Some(event),
-    };
+#[tokio::main]
+async fn web_output(addr: net::SocketAddr, params: Cli) -> Result<()> {
+  let not_found = || {
+    let body = warp::reply::html("File not found".as_bytes().to_vec());
+    let code = warp::http::StatusCode::NOT_FOUND;
+    warp::reply::with_status(body, code).into_response()
+  };
 
-    let lang = match kind {
-      CodeBlockKind::Fenced(lang) => lang,
-      _ => return Some(event),
-    };
+  let bad_request = |err: &str| {
+    let body = warp::reply::html(err.as_bytes().to_vec());
+    let code = warp::http::StatusCode::BAD_REQUEST;
+    warp::reply::with_status(body, code).into_response()
+  };
 
-    if lang.as_ref() != "pikchr" {
-      return Some(event);
+  // Unpublished notes ask for a password, when there is one to give
+  let hidden = move |params: &Cli| match params.auth {
+    Some(_) => {
+      let body = warp::reply::html("Unauthorized".as_bytes().to_vec());
+      let code = warp::http::StatusCode::UNAUTHORIZED;
+      let reply = warp::reply::with_status(body, code);
+      let challenge = "Basic realm=\"notebook\"";
+      warp::reply::with_header(reply, "www-authenticate", challenge)
+        .into_response()
     }
+    None => not_found(),
+  };
 
-    let event = self
-      .iter
-      .next()
-      .expect("Fence block to contain a text block");
+  // Browsers reload when the document or its template changes
+  let (changes, _) = tokio::sync::broadcast::channel(16);
+  let mut watched = vec![PathBuf::from(&params.input)];
+  watched.extend(params.template_path.iter().map(PathBuf::from));
+  let mut watcher = watch::Watcher::new(watched);
+  let sender = changes.clone();
+  tokio::spawn(async move {
+    loop {
+      tokio::time::delay_for(Duration::from_millis(250)).await;
+      if watcher.changed() {
+        // There is no one to tell when no browser is listening
+        let _ = sender.send(());
+      }
+    }
+  });
+  let reload = warp::get()
+    .and(warp::path(watch::PATH))
+    .and(warp::path::end())
+    .map(move || {
+      let events = changes
+        .subscribe()
+        .into_stream()
+        .map(|change| change.map(|()| warp::sse::data("reload")));
+      warp::sse::reply(warp::sse::keep_alive().stream(events))
+    });
 
-    self
-      .iter
-      .next()
-      .expect("A start event to be followed by an end event");
+  let progress = progress::Progress::load(input_dir(&params))?;
+  let visits = match &params.visits {
+    Some(mode) => Some(visits::Visits::new(mode, input_dir(&params))?),
+    None => None,
+  };
 
-    let text = match event {
-      Event::Text(text) => text,
-      _ => unreachable!(),
+  // Assets of rendered documents, by their fingerprinted paths
+  let served = output::Memory::default();
+  let store = move |served: &output::Memory, document: Document| {
+    let mut served = served.clone();
+    for (path, contents) in document.assets.files() {
+      served.write(Path::new(path), contents)?;
+    }
+    Ok::<_, anyhow::Error>(())
+  };
+
+  let document_served = served.clone();
+  let document_params = params.clone();
+  let document_progress = progress.clone();
+  let document = move |page: Page, tail: &str, preview: bool| {
+    let mut params = document_params.clone();
+    if let Err(err) = reload_template(&mut params) {
+      return bad_request(&err.to_string());
+    }
+    if preview {
+      params.schedule = params.schedule.preview();
+    }
+    let params = &params;
+    let input = match (page, &params.glossary) {
+      (Page::Glossary, Some(glossary)) => glossary.page(),
+      (Page::Glossary, None) => return not_found(),
+      (Page::Progress, _) => document_progress.page(),
+      (Page::Visits, _) => match &visits {
+        Some(visits) => visits.page(),
+        None => return not_found(),
+      },
+      (Page::Dashboard, _) => {
+        match track::Tracker::collect(
+          &*params.source,
+          input_dir(params),
+          &params.schedule,
+        ) {
+          Ok(tracker) => tracker.page(),
+          Err(err) => return bad_request(&err.to_string()),
+        }
+      }
+      (Page::Tags, _) => {
+        let (source, schedule) = (&*params.source, &params.schedule);
+        let dir = input_dir(params);
+        match tags::Tags::collect(source, dir, schedule, params.inline_tags) {
+          Ok(tags) => tags.page(),
+          Err(err) => return bad_request(&err.to_string()),
+        }
+      }
+      (Page::Search(query), _) => {
+        let cache = cache::Cache::new(input_dir(params), params.cache);
+        match search::page(
+          &*params.source,
+          input_dir(params),
+          &params.schedule,
+          query,
+          params.embedder.as_ref(),
+          &cache,
+        ) {
+          Ok(page) => page,
+          Err(err) => return bad_request(&err.to_string()),
+        }
+      }
+      (Page::Document(_), _) => {
+        match params.source.read_to_string(Path::new(&params.input)) {
+          Ok(input) => input,
+          Err(_) => return not_found(),
+        }
+      }
     };
+    let published = params.schedule.is_published(&frontmatter::split(&input).0);
+    match published {
+      Ok(true) => {}
+      Ok(false) => return hidden(params),
+      Err(err) => return bad_request(&err.to_string()),
+    }
+
+    let mut params = params.clone();
+    if let Page::Document(number) = page {
+      params.page = number;
+    }
+    let mut buffer = vec![];
+    match render_html(&input, &params, &mut buffer) {
+      Ok(document) => {
+        if let Err(err) = store(&document_served, document) {
+          return bad_request(&err.to_string());
+        }
+      }
+      Err(err) => return bad_request(&err.to_string()),
+    }
+    if let Page::Document(_) = page {
+      let script = document_progress.script(&format!("/{}", tail));
+      buffer = inject(buffer, "</body>", &script);
+      buffer = inject(buffer, "</body>", &watch::script());
+      if let Some(visits) = &visits {
+        if let Err(err) = visits.record(tail) {
+          params
+            .events
+            .warn(&format!("failed to count visit: {}", err));
+        }
+      }
+    }
+    caching::reply(buffer, HTML, &params.cache_control.document)
+  };
+
+  let fallback_params = params.clone();
+  let fallback = warp::path::tail()
+    .and(warp::header::optional::<String>("authorization"))
+    .and(warp::query::<BTreeMap<String, String>>())
+    .map(
+      move |tail: warp::path::Tail,
+            authorization: Option<String>,
+            query: BTreeMap<String, String>| {
+        let tail = tail.as_str();
+        let params = &fallback_params;
+        let preview = authorized(params, authorization.as_deref());
+        let document = |page: Page, tail: &str| document(page, tail, preview);
+        if let Some(number) = document_page(params, tail) {
+          return document(Page::Document(number), tail);
+        }
+        match tail {
+          glossary::PAGE => document(Page::Glossary, tail),
+          progress::PATH => document(Page::Progress, tail),
+          visits::PATH => document(Page::Visits, tail),
+          track::PAGE => document(Page::Dashboard, tail),
+          tags::PAGE => document(Page::Tags, tail),
+          search::PATH => {
+            let query = query.get("q").map(String::as_str).unwrap_or_default();
+            document(Page::Search(query), tail)
+          }
+          calendar::PATH => {
+            let source = &*params.source;
+            match calendar::events(source, input_dir(params), &params.schedule)
+            {
+              Ok(events) => caching::reply(
+                calendar::render(&events).into_bytes(),
+                "text/calendar; charset=utf-8",
+                &params.cache_control.document,
+              ),
+              Err(err) => bad_request(&err.to_string()),
+            }
+          }
+          links::FEED => {
+            let root = input_dir(params);
+            let mut cache = links::Cache::load(root, &params.events);
+            let feed =
+              links::feed(&*params.source, root, &params.schedule, &mut cache)
+                .and_then(|feed| cache.save().map(|_| feed));
+            match feed {
+              Ok(feed) => caching::reply(
+                feed.into_bytes(),
+                "application/atom+xml; charset=utf-8",
+                &params.cache_control.document,
+              ),
+              Err(err) => bad_request(&err.to_string()),
+            }
+          }
+          _ => not_found(),
+        }
+      },
+    );
+
+  // Reading positions of the document's pages, from readers who may read
+  // them, given `--auth` when there is one
+  let progress_params = params.clone();
+  let save_progress = warp::post()
+    .and(warp::path(progress::PATH))
+    .and(warp::path::end())
+    .and(warp::header::optional::<String>("authorization"))
+    .and(warp::body::content_length_limit(4 * 1024))
+    .and(warp::body::json())
+    .map(
+      move |authorization: Option<String>, update: serde_json::Value| {
+        let params = &progress_params;
+        if params.auth.is_some()
+          && !authorized(params, authorization.as_deref())
+        {
+          return hidden(params);
+        }
+        let known = update
+          .get("path")
+          .and_then(|path| path.as_str()?.strip_prefix('/'))
+          .is_some_and(|tail| document_page(params, tail).is_some());
+        if !known {
+          return bad_request("Unknown note in progress update");
+        }
+        match progress.update(&update) {
+          Ok(()) => {
+            let body = warp::reply::html(vec![]);
+            let code = warp::http::StatusCode::NO_CONTENT;
+            warp::reply::with_status(body, code).into_response()
+          }
+          Err(err) => bad_request(&err.to_string()),
+        }
+      },
+    );
+
+  // Snippets from shortcuts and bookmarklets, appended to a note
+  let capture_params = params.clone();
+  let capture = warp::post()
+    .and(warp::path("api"))
+    .and(warp::path(capture::PATH))
+    .and(warp::path::end())
+    .and(warp::header::optional::<String>("authorization"))
+    .and(warp::header::optional::<String>("content-type"))
+    .and(warp::body::content_length_limit(64 * 1024))
+    .and(warp::body::bytes())
+    .map(
+      move |authorization: Option<String>,
+            content_type: Option<String>,
+            body: warp::hyper::body::Bytes| {
+        let params = &capture_params;
+        let capture = match &params.capture {
+          Some(capture) => capture,
+          None => return not_found(),
+        };
+        if !capture.authorized(authorization.as_deref()) {
+          let body = warp::reply::html("Unauthorized".as_bytes().to_vec());
+          let code = warp::http::StatusCode::UNAUTHORIZED;
+          return warp::reply::with_status(body, code).into_response();
+        }
+        let json =
+          content_type.is_some_and(|kind| kind.starts_with("application/json"));
+        match capture.append(input_dir(params), &body, json) {
+          Ok(_) => {
+            let body = warp::reply::html(vec![]);
+            let code = warp::http::StatusCode::NO_CONTENT;
+            warp::reply::with_status(body, code).into_response()
+          }
+          Err(err) => bad_request(&err.to_string()),
+        }
+      },
+    );
 
-    // Display Pikchr syntax errors in the output document
-    let event = match Pikchr::render(&text, None, PikchrFlags::default()) {
-      Ok(svg) => Event::Html(svg.to_string().into()),
-      Err(err) => Event::Text(err.to_string().into()),
+  // Notes edited in the browser, refusing saves over newer changes
+  let editing = |params: &Cli, authorization: Option<&str>| {
+    let editor = params.editor.clone()?;
+    let dir = match Path::new(&params.input).is_dir() {
+      true => PathBuf::from(&params.input),
+      false => input_dir(params).to_path_buf(),
     };
+    authorized(params, authorization).then_some((editor, dir))
+  };
+  let editor_params = params.clone();
+  let editor = warp::get()
+    .and(warp::path(edit::PATH))
+    .and(warp::path::tail())
+    .and(warp::header::optional::<String>("authorization"))
+    .and(warp::query::<BTreeMap<String, String>>())
+    .map(
+      move |tail: warp::path::Tail,
+            authorization: Option<String>,
+            query: BTreeMap<String, String>| {
+        let params = &editor_params;
+        let (editor, dir) = match editing(params, authorization.as_deref()) {
+          Some(editing) => editing,
+          None => return hidden(params),
+        };
+        let note = guard::decode(tail.as_str()).unwrap_or_default();
+        let draft = query.contains_key("draft");
+        match editor.page(&dir, &note, draft) {
+          Ok(page) => caching::reply(page.into_bytes(), HTML, "no-store"),
+          Err(err) => bad_request(&err.to_string()),
+        }
+      },
+    );
+  // Edits only from the server's own pages, as browsers send the `--auth`
+  // credentials with forms posted from any site
+  let same_origin = warp::header::optional::<String>("host")
+    .and(warp::header::optional::<String>("origin"))
+    .and(warp::header::optional::<String>("referer"))
+    .map(
+      |host: Option<String>,
+       origin: Option<String>,
+       referer: Option<String>| {
+        edit::same_origin(
+          host.as_deref(),
+          origin.as_deref(),
+          referer.as_deref(),
+        )
+      },
+    );
+  let cross_site = || {
+    let body = warp::reply::html("Cross-site request".as_bytes().to_vec());
+    let code = warp::http::StatusCode::FORBIDDEN;
+    warp::reply::with_status(body, code).into_response()
+  };
+  let save_params = params.clone();
+  let save = warp::post()
+    .and(warp::path(edit::PATH))
+    .and(warp::path::tail())
+    .and(warp::header::optional::<String>("authorization"))
+    .and(same_origin)
+    .and(warp::body::content_length_limit(4 * 1024 * 1024))
+    .and(warp::body::form::<BTreeMap<String, String>>())
+    .map(
+      move |tail: warp::path::Tail,
+            authorization: Option<String>,
+            same_origin: bool,
+            form: BTreeMap<String, String>| {
+        let params = &save_params;
+        let (editor, dir) = match editing(params, authorization.as_deref()) {
+          Some(editing) => editing,
+          None => return hidden(params),
+        };
+        if !same_origin {
+          return cross_site();
+        }
+        let note = guard::decode(tail.as_str()).unwrap_or_default();
+        let field = |key| form.get(key).map(String::as_str).unwrap_or("");
+        match editor.save(&dir, &note, field("text"), field("version")) {
+          Ok(edit::Saved::Saved) => {
+            let body = warp::reply::html(vec![]);
+            let code = warp::http::StatusCode::SEE_OTHER;
+            let reply = warp::reply::with_status(body, code);
+            let location = format!("/{}/{}", edit::PATH, tail.as_str());
+            warp::reply::with_header(reply, "location", location)
+              .into_response()
+          }
+          Ok(edit::Saved::Conflict(current)) => {
+            let page = edit::conflict(&note, field("text"), &current);
+            let body = warp::reply::html(page.into_bytes());
+            let code = warp::http::StatusCode::CONFLICT;
+            warp::reply::with_status(body, code).into_response()
+          }
+          Err(err) => bad_request(&err.to_string()),
+        }
+      },
+    );
+
+  // Earlier versions of edited notes, and drafts of their edits
+  let history_params = params.clone();
+  let history = warp::get()
+    .and(warp::path(history::PATH))
+    .and(warp::path::tail())
+    .and(warp::header::optional::<String>("authorization"))
+    .map(
+      move |tail: warp::path::Tail, authorization: Option<String>| {
+        let params = &history_params;
+        let (editor, dir) = match editing(params, authorization.as_deref()) {
+          Some(editing) => editing,
+          None => return hidden(params),
+        };
+        let note = guard::decode(tail.as_str()).unwrap_or_default();
+        match editor.history(&dir, &note) {
+          Ok(page) => caching::reply(page.into_bytes(), HTML, "no-store"),
+          Err(err) => bad_request(&err.to_string()),
+        }
+      },
+    );
+  let restore_params = params.clone();
+  let restore = warp::post()
+    .and(warp::path(history::PATH))
+    .and(warp::path::tail())
+    .and(warp::header::optional::<String>("authorization"))
+    .and(same_origin)
+    .and(warp::body::content_length_limit(4 * 1024 * 1024))
+    .and(warp::body::form::<BTreeMap<String, String>>())
+    .map(
+      move |tail: warp::path::Tail,
+            authorization: Option<String>,
+            same_origin: bool,
+            form: BTreeMap<String, String>| {
+        let params = &restore_params;
+        let (editor, dir) = match editing(params, authorization.as_deref()) {
+          Some(editing) => editing,
+          None => return hidden(params),
+        };
+        if !same_origin {
+          return cross_site();
+        }
+        let note = guard::decode(tail.as_str()).unwrap_or_default();
+        let field = |key| form.get(key).map(String::as_str).unwrap_or("");
+        if let Some(draft) = form.get("draft") {
+          return match editor.save_draft(&dir, &note, draft) {
+            Ok(()) => {
+              let body = warp::reply::html(vec![]);
+              let code = warp::http::StatusCode::NO_CONTENT;
+              warp::reply::with_status(body, code).into_response()
+            }
+            Err(err) => bad_request(&err.to_string()),
+          };
+        }
+        let stamp = field("restore");
+        match editor.restore(&dir, &note, stamp, field("version")) {
+          Ok((_, edit::Saved::Saved)) => {
+            let body = warp::reply::html(vec![]);
+            let code = warp::http::StatusCode::SEE_OTHER;
+            let reply = warp::reply::with_status(body, code);
+            let location = format!("/{}/{}", edit::PATH, tail.as_str());
+            warp::reply::with_header(reply, "location", location)
+              .into_response()
+          }
+          Ok((restored, edit::Saved::Conflict(current))) => {
+            let page = edit::conflict(&note, &restored, &current);
+            let body = warp::reply::html(page.into_bytes());
+            let code = warp::http::StatusCode::CONFLICT;
+            warp::reply::with_status(body, code).into_response()
+          }
+          Err(err) => bad_request(&err.to_string()),
+        }
+      },
+    );
+
+  // Notes matching a query, for dashboards and scripts
+  let notes_params = params.clone();
+  let notes = warp::get()
+    .and(warp::path("api"))
+    .and(warp::path(query::PATH))
+    .and(warp::path::end())
+    .and(warp::query::<BTreeMap<String, String>>())
+    .map(move |query: BTreeMap<String, String>| {
+      let params = &notes_params;
+      let found = query::Query::from_params(&query).and_then(|query| {
+        let source = &*params.source;
+        let dir = input_dir(params);
+        query::find(source, dir, &params.schedule, params.inline_tags, &query)
+      });
+      match found {
+        Ok(notes) => caching::reply(
+          notes.to_string().into_bytes(),
+          "application/json",
+          &params.cache_control.document,
+        ),
+        Err(err) => bad_request(&err.to_string()),
+      }
+    });
+
+  // Notes as they were at a git revision, at `/rev/<rev>/<path>` from the
+  // directory of served files, for those giving the `--auth` credentials
+  let revision_params = params.clone();
+  let revision_served = served.clone();
+  let revision = warp::get()
+    .and(warp::path("rev"))
+    .and(warp::path::param::<String>())
+    .and(warp::path::tail())
+    .and(warp::header::optional::<String>("authorization"))
+    .map(
+      move |rev: String,
+            tail: warp::path::Tail,
+            authorization: Option<String>| {
+        let mut params = revision_params.clone();
+        if !params.revisions {
+          return not_found();
+        }
+        if !authorized(&params, authorization.as_deref()) {
+          return hidden(&params);
+        }
+        if let Err(err) = reload_template(&mut params) {
+          return bad_request(&err.to_string());
+        }
+        params.schedule = params.schedule.preview();
+        if !tail.as_str().is_empty() {
+          let dir = served_dir(&params);
+          match params.static_guard.note(&dir, tail.as_str()) {
+            Some(path) => params.input = path.to_string_lossy().into_owned(),
+            None => return not_found(),
+          }
+        }
+        params.source = match vfs::Git::new(&rev) {
+          Ok(git) => Arc::new(git),
+          Err(err) => return bad_request(&err.to_string()),
+        };
+        let input = match params.source.read_to_string(Path::new(&params.input))
+        {
+          Ok(input) => input,
+          Err(_) => return not_found(),
+        };
+        let published =
+          params.schedule.is_published(&frontmatter::split(&input).0);
+        match published {
+          Ok(true) => {}
+          Ok(false) => return hidden(&params),
+          Err(err) => return bad_request(&err.to_string()),
+        }
+
+        let mut buffer = vec![];
+        match render_html(&input, &params, &mut buffer) {
+          Ok(document) => {
+            if let Err(err) = store(&revision_served, document) {
+              return bad_request(&err.to_string());
+            }
+          }
+          Err(err) => return bad_request(&err.to_string()),
+        }
+        caching::reply(buffer, HTML, &params.cache_control.revision)
+      },
+    );
+
+  // Assets are found relative to any document, including past revisions
+  let asset_policy = params.cache_control.asset.clone();
+  let generated = warp::get().and(warp::path::tail()).and_then(
+    move |tail: warp::path::Tail| {
+      let served = served.clone();
+      let policy = asset_policy.clone();
+      async move {
+        let (path, contents) = served
+          .ending(tail.as_str())
+          .ok_or_else(warp::reject::not_found)?;
+        let content_type = assets::content_type(&path);
+        let reply = caching::reply(contents, content_type, &policy);
+        Ok::<_, warp::Rejection>(reply)
+      }
+    },
+  );
+
+  // Files beside the document, or of the `--assets` directory, under the
+  // `--assets-prefix` path
+  let file_policy = params.cache_control.file.clone();
+  let dir = served_dir(&params);
+  let prefix = params.static_prefix.as_deref().unwrap_or("");
+  let mut mount = warp::any().boxed();
+  for segment in prefix.split('/').filter(|segment| !segment.is_empty()) {
+    mount = mount.and(warp::path(segment.to_string())).boxed();
+  }
+  let guard = params.static_guard.clone();
+  let root = dir.clone();
+  let confined = warp::path::peek()
+    .and_then(move |path: warp::path::Peek| {
+      let allowed = guard.allows(&root, path.as_str());
+      async move {
+        match allowed {
+          true => Ok(()),
+          false => Err(warp::reject::not_found()),
+        }
+      }
+    })
+    .untuple_one();
+  let assets = warp::get()
+    .and(mount)
+    .and(confined)
+    .and(warp::fs::dir(dir))
+    .map(move |file: warp::fs::File| {
+      let etag = caching::file_tag(file.path()).unwrap_or_default();
+      let reply =
+        warp::reply::with_header(file, "cache-control", file_policy.as_str());
+      warp::reply::with_header(reply, "etag", etag)
+    });
+  let routes = save_progress
+    .or(capture)
+    .or(editor)
+    .or(save)
+    .or(history)
+    .or(restore)
+    .or(notes)
+    .or(reload)
+    .or(generated)
+    .or(revision)
+    .or(assets)
+    .or(fallback);
+  let routes = warp::method()
+    .and(warp::header::headers_cloned())
+    .and(routes)
+    .map(caching::conditional)
+    .and(warp::header::headers_cloned())
+    .and_then(|reply, headers| async move {
+      let response = compress::respond(headers, reply).await;
+      Ok::<_, warp::Rejection>(response)
+    });
+
+  warp::serve(routes).run(addr).await;
+  Ok(())
+}
+
+/// The directory of files served beside the document, or of `--assets`
+fn served_dir(params: &Cli) -> PathBuf {
+  match &params.static_dir {
+    Some(dir) => PathBuf::from(dir),
+    None if Path::new(&params.input).is_dir() => PathBuf::from(&params.input),
+    None => input_dir(params).to_path_buf(),
+  }
+}
+
+/// The number of the page of the document at a request's path, if it is one
+fn document_page(params: &Cli, tail: &str) -> Option<usize> {
+  match (tail, &params.output) {
+    ("", None) => Some(1),
+    (_, None) if tail == document_name(params) => Some(1),
+    (_, Some(out)) if tail == out.as_str() => Some(1),
+    _ => listing::page_number(&document_name(params), tail),
+  }
+}
 
-    Some(event)
+/// Whether a request's `Authorization` header gives the `--auth` credentials
+fn authorized(params: &Cli, authorization: Option<&str>) -> bool {
+  let credentials = authorization
+    .and_then(|header| header.strip_prefix("Basic "))
+    .map(|encoded| util::decode_base64(encoded.trim()));
+  match (&params.auth, credentials) {
+    (Some(auth), Some(credentials)) => {
+      util::same_secret(auth.as_bytes(), &credentials)
+    }
+    _ => false,
   }
 }
+
+/// The media type of rendered documents
+const HTML: &str = "text/html; charset=utf-8";
+
+/// Pages rendered by the web server
+#[derive(Clone, Copy)]
+enum Page<'a> {
+  /// A page of the document, beyond the first for paginated listings
+  Document(usize),
+  Glossary,
+  Progress,
+  Dashboard,
+  Tags,
+  Visits,
+  /// The notes found by a search
+  Search(&'a str),
+}
@@ -0,0 +1,922 @@
+use crate::cache::Cache;
+use crate::code;
+use crate::diagram::pipe;
+use crate::util::encode_base64;
+use anyhow::Result;
+
+/// Styles showing the fallback images of math only to browsers without
+/// MathML, without scripts
+pub const STYLE: &str = "\
+.math-fallback { display: none; }
+@supports not (display: math) {
+  .math-fallback { display: inline-block; vertical-align: middle; }
+  .math-fallback + math { display: none; }
+}
+";
+
+/// A command drawing TeX math as SVG, given it on standard input, for the
+/// browsers without MathML
+///
+/// Display math is given to the command after `\displaystyle`. Drawings are
+/// cached, as diagrams are.
+pub struct Fallback<'a> {
+  command: Vec<&'a str>,
+  cache: &'a Cache,
+}
+
+impl<'a> Fallback<'a> {
+  pub fn new(command: &'a str, cache: &'a Cache) -> Fallback<'a> {
+    Fallback {
+      command: command.split_whitespace().collect(),
+      cache,
+    }
+  }
+
+  /// An image of some math, as an `img` of its SVG
+  fn draw(&self, tex: &str, display: bool) -> Result<String> {
+    let tex = match display {
+      true => format!("\\displaystyle {}", tex.trim()),
+      false => tex.trim().to_string(),
+    };
+    let (program, args) = self.command.split_first().unwrap_or((&"", &[]));
+    let svg = self
+      .cache
+      .get("math-fallback", &tex, || pipe(program, args, &tex))?;
+    Ok(format!(
+      "<img class=\"math-fallback\" alt=\"{}\" \
+       src=\"data:image/svg+xml;base64,{}\">",
+      escape(&tex),
+      encode_base64(svg.as_bytes())
+    ))
+  }
+}
+
+/// Whether some HTML has fallback images of math, and so needs their styles
+pub fn has_fallback(html: &str) -> bool {
+  html.contains("<img class=\"math-fallback\"")
+}
+
+/// Replace `$...$` inline math and `$$...$$` display math in markdown with
+/// MathML, leaving code, link destinations, HTML tags and escaped `\$` dollars
+/// alone, preceded by an image of it when there is a fallback
+pub fn expand(input: &str, fallback: Option<&Fallback>) -> Result<String> {
+  let mut output = String::with_capacity(input.len());
+  let mut text = String::new();
+  for (code, line) in code::lines(input) {
+    if code && !text.is_empty() {
+      output.push_str(&replace(&text, fallback)?);
+      text.clear();
+    }
+    match code {
+      true => output.push_str(line),
+      false => text.push_str(line),
+    }
+  }
+  output.push_str(&replace(&text, fallback)?);
+  Ok(output)
+}
+
+/// Some text with its math replaced, outside of link destinations and tags
+fn replace(text: &str, fallback: Option<&Fallback>) -> Result<String> {
+  let mut output = String::with_capacity(text.len());
+  for (markup, piece) in code::markup(text) {
+    match markup {
+      true => output.push_str(piece),
+      false => output.push_str(&replace_math(piece, fallback)?),
+    }
+  }
+  Ok(output)
+}
+
+fn replace_math(text: &str, fallback: Option<&Fallback>) -> Result<String> {
+  let mut output = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(at) = rest.find(['`', '$', '\\']) {
+    output.push_str(&rest[..at]);
+    let tail = &rest[at..];
+
+    // Escapes are left for markdown to remove
+    if let Some(escaped) = tail.strip_prefix('\\') {
+      let len = escaped.chars().next().map_or(1, |c| 1 + c.len_utf8());
+      output.push_str(&tail[..len]);
+      rest = &tail[len..];
+      continue;
+    }
+
+    if tail.starts_with('`') {
+      let ticks = tail.len() - tail.trim_start_matches('`').len();
+      let len = match tail[ticks..].find(&tail[..ticks]) {
+        Some(end) => end + 2 * ticks,
+        None => ticks,
+      };
+      output.push_str(&tail[..len]);
+      rest = &tail[len..];
+      continue;
+    }
+
+    let image = |tex: &str, display: bool| match fallback {
+      Some(fallback) => fallback.draw(tex, display),
+      None => Ok(String::new()),
+    };
+    if let Some(body) = tail.strip_prefix("$$") {
+      if let Some(end) = body.find("$$") {
+        // Display math is a block of its own, separate from any paragraph
+        let tex = &body[..end];
+        output.push_str(&format!(
+          "\n\n<div class=\"math\">{}{}</div>\n\n",
+          image(tex, true)?,
+          render(tex, true)
+        ));
+        rest = &body[end + 2..];
+        continue;
+      }
+    } else if let Some(end) = inline_end(&tail[1..]) {
+      let tex = &tail[1..end + 1];
+      output.push_str(&image(tex, false)?);
+      output.push_str(&render(tex, false));
+      rest = &tail[end + 2..];
+      continue;
+    }
+    output.push('$');
+    rest = &tail[1..];
+  }
+  output.push_str(rest);
+  Ok(output)
+}
+
+/// The end of inline math, which neither starts nor ends with a space and
+/// is not followed by a digit, so that amounts such as $5 and $10 are not
+/// math
+fn inline_end(body: &str) -> Option<usize> {
+  if body.starts_with(char::is_whitespace) {
+    return None;
+  }
+  let mut previous = ' ';
+  let mut chars = body.char_indices().peekable();
+  while let Some((at, c)) = chars.next() {
+    match c {
+      '\\' => {
+        chars.next();
+      }
+      '\n' if previous == '\n' => return None,
+      // Code spans take precedence over math
+      '`' => return None,
+      '$' if at > 0 && !previous.is_whitespace() => {
+        let next = chars.peek().map(|(_, c)| *c);
+        return match next.is_some_and(|c| c.is_ascii_digit()) {
+          true => None,
+          false => Some(at),
+        };
+      }
+      _ => {}
+    }
+    previous = c;
+  }
+  None
+}
+
+/// MathML for some TeX, keeping the TeX as an annotation
+pub fn render(tex: &str, display: bool) -> String {
+  let mut parser = Tex {
+    chars: tex.chars().collect(),
+    at: 0,
+    display,
+  };
+  let mut items = parser.row();
+  // Unmatched closing braces and the like are shown rather than dropped
+  while parser.at < parser.chars.len() {
+    let c = parser.chars[parser.at];
+    parser.at += 1;
+    let stray = match c {
+      // Line breaks and alignment outside of a table are ignored
+      '\\' => match parser.command() {
+        name if name == "\\" => None,
+        name => Some(format!("\\{}", name)),
+      },
+      '&' => None,
+      c => Some(c.to_string()),
+    };
+    if let Some(stray) = stray {
+      items.push(format!(
+        "<merror><mtext>{}</mtext></merror>",
+        escape(&stray)
+      ));
+    }
+    items.extend(parser.row());
+  }
+  format!(
+    "<math{}><semantics>{}<annotation encoding=\"application/x-tex\">{}\
+     </annotation></semantics></math>",
+    if display { " display=\"block\"" } else { "" },
+    mrow(items),
+    escape(tex.trim())
+  )
+}
+
+/// Escape text for MathML, including any punctuation markdown would treat
+/// as syntax
+fn escape(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| match c.is_ascii_punctuation() {
+      true => format!("&#{};", c as u32),
+      false => c.to_string(),
+    })
+    .collect()
+}
+
+fn mrow(mut items: Vec<String>) -> String {
+  match items.len() {
+    1 => items.remove(0),
+    _ => format!("<mrow>{}</mrow>", items.concat()),
+  }
+}
+
+fn mo(text: &str) -> String {
+  format!("<mo>{}</mo>", escape(text))
+}
+
+fn mi(text: &str) -> String {
+  format!("<mi>{}</mi>", escape(text))
+}
+
+/// A parsed atom, and whether scripts go above and below it in display math
+struct Node {
+  mathml: String,
+  limits: bool,
+}
+
+impl Node {
+  fn new(mathml: String) -> Node {
+    Node {
+      mathml,
+      limits: false,
+    }
+  }
+}
+
+/// A recursive descent parser for a subset of TeX math
+struct Tex {
+  chars: Vec<char>,
+  at: usize,
+  display: bool,
+}
+
+impl Tex {
+  fn peek(&mut self) -> Option<char> {
+    while self.chars.get(self.at).is_some_and(|c| c.is_whitespace()) {
+      self.at += 1;
+    }
+    self.chars.get(self.at).copied()
+  }
+
+  /// Whether the input continues with a command, without consuming it
+  fn at_command(&mut self, name: &str) -> bool {
+    if self.peek() != Some('\\') {
+      return false;
+    }
+    let start = self.at + 1;
+    let end = start + name.chars().count();
+    let matches = self
+      .chars
+      .get(start..end)
+      .is_some_and(|chars| chars.iter().copied().eq(name.chars()));
+    let letters = name.chars().all(|c| c.is_ascii_alphabetic());
+    let continues =
+      self.chars.get(end).is_some_and(|c| c.is_ascii_alphabetic());
+    matches && !(letters && continues)
+  }
+
+  /// The name of the command after a backslash, either letters or a
+  /// single other character
+  fn command(&mut self) -> String {
+    let start = self.at;
+    while self
+      .chars
+      .get(self.at)
+      .is_some_and(|c| c.is_ascii_alphabetic())
+    {
+      self.at += 1;
+    }
+    if self.at == start && self.at < self.chars.len() {
+      self.at += 1;
+    }
+    self.chars[start..self.at].iter().collect()
+  }
+
+  /// The raw text of a braced group, or of the next character
+  fn raw(&mut self) -> String {
+    match self.peek() {
+      Some('{') => {
+        self.at += 1;
+        let start = self.at;
+        let mut depth = 0;
+        while let Some(&c) = self.chars.get(self.at) {
+          match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => break,
+            '}' => depth -= 1,
+            _ => {}
+          }
+          self.at += 1;
+        }
+        let text = self.chars[start..self.at].iter().collect();
+        self.at = (self.at + 1).min(self.chars.len());
+        text
+      }
+      Some(c) => {
+        self.at += 1;
+        c.to_string()
+      }
+      None => String::new(),
+    }
+  }
+
+  /// Atoms with their scripts up to the end of a group, row or cell
+  fn row(&mut self) -> Vec<String> {
+    let mut items = vec![];
+    loop {
+      match self.peek() {
+        None | Some('}') | Some('&') => break,
+        Some('\\')
+          if self.at_command("\\")
+            || self.at_command("right")
+            || self.at_command("end") =>
+        {
+          break
+        }
+        _ => {}
+      }
+      let base = match self.peek() {
+        Some('^') | Some('_') => Node::new("<mrow></mrow>".into()),
+        _ => self.atom(),
+      };
+      items.push(self.scripts(base));
+    }
+    items
+  }
+
+  /// An atom's subscript and superscript, if it has them
+  fn scripts(&mut self, base: Node) -> String {
+    let mut sub = None;
+    let mut sup: Option<String> = None;
+    loop {
+      match self.peek() {
+        Some('_') if sub.is_none() => {
+          self.at += 1;
+          sub = Some(self.argument());
+        }
+        Some('^') if sup.is_none() => {
+          self.at += 1;
+          sup = Some(self.argument());
+        }
+        Some('\'') => {
+          self.at += 1;
+          let prime = mo("\u{2032}");
+          sup = Some(match sup {
+            Some(sup) => format!("<mrow>{}{}</mrow>", sup, prime),
+            None => prime,
+          });
+        }
+        _ => break,
+      }
+    }
+    let (under, over, both) = match base.limits && self.display {
+      true => ("munder", "mover", "munderover"),
+      false => ("msub", "msup", "msubsup"),
+    };
+    match (sub, sup) {
+      (None, None) => base.mathml,
+      (Some(sub), None) => {
+        format!("<{0}>{1}{2}</{0}>", under, base.mathml, sub)
+      }
+      (None, Some(sup)) => {
+        format!("<{0}>{1}{2}</{0}>", over, base.mathml, sup)
+      }
+      (Some(sub), Some(sup)) => {
+        format!("<{0}>{1}{2}{3}</{0}>", both, base.mathml, sub, sup)
+      }
+    }
+  }
+
+  /// The argument of a command or script, a group or a single atom
+  fn argument(&mut self) -> String {
+    match self.peek() {
+      Some('{') => self.atom().mathml,
+      // Only the first digit of a number is an argument, as in `x^23`
+      Some(c) if c.is_ascii_digit() => {
+        self.at += 1;
+        format!("<mn>{}</mn>", c)
+      }
+      Some(_) => self.atom().mathml,
+      None => "<mrow></mrow>".into(),
+    }
+  }
+
+  fn atom(&mut self) -> Node {
+    let c = match self.peek() {
+      Some(c) => c,
+      None => return Node::new("<mrow></mrow>".into()),
+    };
+    self.at += 1;
+    match c {
+      '{' => {
+        let items = self.row();
+        if self.peek() == Some('}') {
+          self.at += 1;
+        }
+        Node::new(format!("<mrow>{}</mrow>", items.concat()))
+      }
+      '\\' => self.control(),
+      c if c.is_ascii_digit() || c == '.' => {
+        let start = self.at - 1;
+        while self
+          .chars
+          .get(self.at)
+          .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+        {
+          self.at += 1;
+        }
+        let number: String = self.chars[start..self.at].iter().collect();
+        Node::new(format!("<mn>{}</mn>", escape(&number)))
+      }
+      c if c.is_alphabetic() => Node::new(mi(&c.to_string())),
+      '-' => Node::new(mo("\u{2212}")),
+      '*' => Node::new(mo("\u{2217}")),
+      c => Node::new(mo(&c.to_string())),
+    }
+  }
+
+  /// The atom of a command, after its backslash
+  fn control(&mut self) -> Node {
+    let name = self.command();
+    if let Some(symbol) = symbol(&name) {
+      return symbol;
+    }
+    let mathml = match name.as_str() {
+      "frac" | "dfrac" | "tfrac" => {
+        let numerator = self.argument();
+        let denominator = self.argument();
+        format!("<mfrac>{}{}</mfrac>", numerator, denominator)
+      }
+      "binom" => {
+        let n = self.argument();
+        let k = self.argument();
+        format!(
+          "<mrow>{}<mfrac linethickness=\"0\">{}{}</mfrac>{}</mrow>",
+          mo("("),
+          n,
+          k,
+          mo(")")
+        )
+      }
+      "sqrt" => match self.peek() {
+        Some('[') => {
+          self.at += 1;
+          let start = self.at;
+          while self.chars.get(self.at).is_some_and(|c| *c != ']') {
+            self.at += 1;
+          }
+          let index: String = self.chars[start..self.at].iter().collect();
+          self.at = (self.at + 1).min(self.chars.len());
+          let radicand = self.argument();
+          let mut index_parser = Tex {
+            chars: index.chars().collect(),
+            at: 0,
+            display: false,
+          };
+          let index = mrow(index_parser.row());
+          format!("<mroot>{}{}</mroot>", radicand, index)
+        }
+        _ => format!("<msqrt>{}</msqrt>", self.argument()),
+      },
+      "text" | "textrm" | "mbox" | "textit" | "textbf" => {
+        format!("<mtext>{}</mtext>", escape(&self.raw()))
+      }
+      "operatorname" => mi(&self.raw()),
+      "mathrm" => {
+        let text = self.raw();
+        format!("<mi mathvariant=\"normal\">{}</mi>", escape(&text))
+      }
+      "mathbf" | "mathbb" | "mathcal" | "mathit" => {
+        let text = self.raw();
+        let styled = text.chars().map(|c| style(&name, c)).collect::<String>();
+        mi(&styled)
+      }
+      "left" => {
+        let open = self.delimiter();
+        let items = self.row();
+        let close = match self.at_command("right") {
+          true => {
+            self.at += "\\right".len();
+            self.delimiter()
+          }
+          false => String::new(),
+        };
+        format!("<mrow>{}{}{}</mrow>", open, items.concat(), close)
+      }
+      "begin" => self.environment(),
+      "hat" | "widehat" => self.accent("^"),
+      "bar" | "overline" => self.accent("\u{af}"),
+      "vec" => self.accent("\u{2192}"),
+      "dot" => self.accent("\u{2d9}"),
+      "ddot" => self.accent("\u{a8}"),
+      "tilde" | "widetilde" => self.accent("~"),
+      "underline" => {
+        let base = self.argument();
+        format!("<munder accentunder=\"true\">{}{}</munder>", base, mo("_"))
+      }
+      "," | ":" | ">" | ";" | " " | "quad" | "qquad" | "!" => {
+        let width = match name.as_str() {
+          "," => "0.1667em",
+          ":" | ">" => "0.2222em",
+          ";" => "0.2778em",
+          " " => "0.25em",
+          "quad" => "1em",
+          "qquad" => "2em",
+          _ => "-0.1667em",
+        };
+        format!("<mspace width=\"{}\"/>", width)
+      }
+      "displaystyle" | "textstyle" | "limits" | "nolimits" => {
+        "<mrow></mrow>".into()
+      }
+      name => format!(
+        "<merror><mtext>{}</mtext></merror>",
+        escape(&format!("\\{}", name))
+      ),
+    };
+    Node::new(mathml)
+  }
+
+  fn accent(&mut self, mark: &str) -> String {
+    let base = self.argument();
+    format!("<mover accent=\"true\">{}{}</mover>", base, mo(mark))
+  }
+
+  /// A stretching delimiter after `\left` or `\right`, nothing for `.`
+  fn delimiter(&mut self) -> String {
+    let delimiter = match self.peek() {
+      Some('\\') => {
+        self.at += 1;
+        let name = self.command();
+        match name.as_str() {
+          "{" | "lbrace" => "{".into(),
+          "}" | "rbrace" => "}".into(),
+          "|" | "Vert" => "\u{2016}".into(),
+          "langle" => "\u{27e8}".into(),
+          "rangle" => "\u{27e9}".into(),
+          "lfloor" => "\u{230a}".into(),
+          "rfloor" => "\u{230b}".into(),
+          "lceil" => "\u{2308}".into(),
+          "rceil" => "\u{2309}".into(),
+          _ => String::new(),
+        }
+      }
+      Some(c) => {
+        self.at += 1;
+        match c {
+          '.' => String::new(),
+          c => c.to_string(),
+        }
+      }
+      None => String::new(),
+    };
+    match delimiter.is_empty() {
+      true => String::new(),
+      false => format!(
+        "<mo fence=\"true\" stretchy=\"true\">{}</mo>",
+        escape(&delimiter)
+      ),
+    }
+  }
+
+  /// A table of rows split by `\\` and cells split by `&`, up to `\end`
+  fn environment(&mut self) -> String {
+    let name = self.raw();
+    let mut rows = vec![];
+    let mut cells = vec![];
+    loop {
+      cells.push(format!("<mtd>{}</mtd>", mrow(self.row())));
+      match self.peek() {
+        Some('&') => self.at += 1,
+        Some('\\') if self.at_command("\\") => {
+          self.at += 2;
+          rows.push(format!("<mtr>{}</mtr>", cells.concat()));
+          cells.clear();
+        }
+        Some('\\') if self.at_command("end") => {
+          self.at += "\\end".len();
+          self.raw();
+          break;
+        }
+        // A closing brace ends the table, as does the end of the input
+        Some('}') => self.at += 1,
+        _ => break,
+      }
+    }
+    // A trailing `\\` leaves an empty last row, which is dropped
+    if cells.len() > 1
+      || cells
+        .first()
+        .is_some_and(|c| c != "<mtd><mrow></mrow></mtd>")
+    {
+      rows.push(format!("<mtr>{}</mtr>", cells.concat()));
+    }
+
+    let (open, close, align) = match name.as_str() {
+      "pmatrix" => ("(", ")", None),
+      "bmatrix" => ("[", "]", None),
+      "Bmatrix" => ("{", "}", None),
+      "vmatrix" => ("|", "|", None),
+      "Vmatrix" => ("\u{2016}", "\u{2016}", None),
+      "cases" => ("{", "", Some("left left")),
+      "aligned" | "align" | "align*" => ("", "", Some("right left")),
+      _ => ("", "", None),
+    };
+    let align = align
+      .map(|align| format!(" columnalign=\"{}\"", align))
+      .unwrap_or_default();
+    let table = format!("<mtable{}>{}</mtable>", align, rows.concat());
+    let fence = |delimiter: &str| match delimiter {
+      "" => String::new(),
+      delimiter => format!(
+        "<mo fence=\"true\" stretchy=\"true\">{}</mo>",
+        escape(delimiter)
+      ),
+    };
+    format!("<mrow>{}{}{}</mrow>", fence(open), table, fence(close))
+  }
+}
+
+/// The atom of a command that stands for a symbol, operator or function
+fn symbol(name: &str) -> Option<Node> {
+  let letter = match name {
+    "alpha" => "\u{3b1}",
+    "beta" => "\u{3b2}",
+    "gamma" => "\u{3b3}",
+    "delta" => "\u{3b4}",
+    "epsilon" => "\u{3f5}",
+    "varepsilon" => "\u{3b5}",
+    "zeta" => "\u{3b6}",
+    "eta" => "\u{3b7}",
+    "theta" => "\u{3b8}",
+    "vartheta" => "\u{3d1}",
+    "iota" => "\u{3b9}",
+    "kappa" => "\u{3ba}",
+    "lambda" => "\u{3bb}",
+    "mu" => "\u{3bc}",
+    "nu" => "\u{3bd}",
+    "xi" => "\u{3be}",
+    "pi" => "\u{3c0}",
+    "varpi" => "\u{3d6}",
+    "rho" => "\u{3c1}",
+    "varrho" => "\u{3f1}",
+    "sigma" => "\u{3c3}",
+    "varsigma" => "\u{3c2}",
+    "tau" => "\u{3c4}",
+    "upsilon" => "\u{3c5}",
+    "phi" => "\u{3d5}",
+    "varphi" => "\u{3c6}",
+    "chi" => "\u{3c7}",
+    "psi" => "\u{3c8}",
+    "omega" => "\u{3c9}",
+    "infty" => "\u{221e}",
+    "partial" => "\u{2202}",
+    "nabla" => "\u{2207}",
+    "emptyset" | "varnothing" => "\u{2205}",
+    "hbar" => "\u{210f}",
+    "ell" => "\u{2113}",
+    "Re" => "\u{211c}",
+    "Im" => "\u{2111}",
+    "aleph" => "\u{2135}",
+    _ => "",
+  };
+  if !letter.is_empty() {
+    return Some(Node::new(mi(letter)));
+  }
+
+  // Upright capital Greek letters
+  let capital = match name {
+    "Gamma" => "\u{393}",
+    "Delta" => "\u{394}",
+    "Theta" => "\u{398}",
+    "Lambda" => "\u{39b}",
+    "Xi" => "\u{39e}",
+    "Pi" => "\u{3a0}",
+    "Sigma" => "\u{3a3}",
+    "Upsilon" => "\u{3a5}",
+    "Phi" => "\u{3a6}",
+    "Psi" => "\u{3a8}",
+    "Omega" => "\u{3a9}",
+    _ => "",
+  };
+  if !capital.is_empty() {
+    let mathml = format!("<mi mathvariant=\"normal\">{}</mi>", capital);
+    return Some(Node::new(mathml));
+  }
+
+  let operator = match name {
+    "pm" => "\u{b1}",
+    "mp" => "\u{2213}",
+    "times" => "\u{d7}",
+    "div" => "\u{f7}",
+    "cdot" => "\u{22c5}",
+    "ast" => "\u{2217}",
+    "star" => "\u{22c6}",
+    "circ" => "\u{2218}",
+    "bullet" => "\u{2219}",
+    "leq" | "le" => "\u{2264}",
+    "geq" | "ge" => "\u{2265}",
+    "neq" | "ne" => "\u{2260}",
+    "approx" => "\u{2248}",
+    "equiv" => "\u{2261}",
+    "sim" => "\u{223c}",
+    "simeq" => "\u{2243}",
+    "cong" => "\u{2245}",
+    "propto" => "\u{221d}",
+    "ll" => "\u{226a}",
+    "gg" => "\u{226b}",
+    "subset" => "\u{2282}",
+    "supset" => "\u{2283}",
+    "subseteq" => "\u{2286}",
+    "supseteq" => "\u{2287}",
+    "in" => "\u{2208}",
+    "notin" => "\u{2209}",
+    "ni" => "\u{220b}",
+    "cup" => "\u{222a}",
+    "cap" => "\u{2229}",
+    "setminus" => "\u{2216}",
+    "forall" => "\u{2200}",
+    "exists" => "\u{2203}",
+    "neg" | "lnot" => "\u{ac}",
+    "land" | "wedge" => "\u{2227}",
+    "lor" | "vee" => "\u{2228}",
+    "to" | "rightarrow" => "\u{2192}",
+    "leftarrow" | "gets" => "\u{2190}",
+    "leftrightarrow" => "\u{2194}",
+    "Rightarrow" => "\u{21d2}",
+    "Leftarrow" => "\u{21d0}",
+    "Leftrightarrow" | "iff" => "\u{21d4}",
+    "implies" => "\u{27f9}",
+    "mapsto" => "\u{21a6}",
+    "uparrow" => "\u{2191}",
+    "downarrow" => "\u{2193}",
+    "ldots" | "dots" => "\u{2026}",
+    "cdots" => "\u{22ef}",
+    "vdots" => "\u{22ee}",
+    "ddots" => "\u{22f1}",
+    "prime" => "\u{2032}",
+    "angle" => "\u{2220}",
+    "perp" => "\u{22a5}",
+    "parallel" => "\u{2225}",
+    "mid" => "\u{2223}",
+    "langle" => "\u{27e8}",
+    "rangle" => "\u{27e9}",
+    "lfloor" => "\u{230a}",
+    "rfloor" => "\u{230b}",
+    "lceil" => "\u{2308}",
+    "rceil" => "\u{2309}",
+    "oplus" => "\u{2295}",
+    "otimes" => "\u{2297}",
+    "{" | "lbrace" => "{",
+    "}" | "rbrace" => "}",
+    "|" => "\u{2016}",
+    "%" | "$" | "&" | "#" | "_" => name,
+    _ => "",
+  };
+  if !operator.is_empty() {
+    return Some(Node::new(mo(operator)));
+  }
+
+  // Large operators take their limits above and below in display math
+  let large = match name {
+    "sum" => "\u{2211}",
+    "prod" => "\u{220f}",
+    "coprod" => "\u{2210}",
+    "bigcup" => "\u{22c3}",
+    "bigcap" => "\u{22c2}",
+    "int" => "\u{222b}",
+    "iint" => "\u{222c}",
+    "iiint" => "\u{222d}",
+    "oint" => "\u{222e}",
+    _ => "",
+  };
+  if !large.is_empty() {
+    let integral = matches!(name, "int" | "iint" | "iiint" | "oint");
+    return Some(Node {
+      mathml: format!("<mo largeop=\"true\">{}</mo>", large),
+      limits: !integral,
+    });
+  }
+
+  match name {
+    "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "arcsin" | "arccos"
+    | "arctan" | "sinh" | "cosh" | "tanh" | "log" | "ln" | "lg" | "exp"
+    | "det" | "dim" | "ker" | "deg" | "gcd" | "arg" | "hom" => {
+      Some(Node::new(mi(name)))
+    }
+    "lim" | "limsup" | "liminf" | "max" | "min" | "sup" | "inf" => {
+      let text = match name {
+        "limsup" => "lim sup",
+        "liminf" => "lim inf",
+        name => name,
+      };
+      Some(Node {
+        mathml: mi(text),
+        limits: true,
+      })
+    }
+    _ => None,
+  }
+}
+
+/// A letter in the mathematical alphanumeric style of a font command
+fn style(command: &str, c: char) -> char {
+  let (upper, lower) = match command {
+    "mathbf" => (0x1d400, Some(0x1d41a)),
+    "mathit" => (0x1d434, Some(0x1d44e)),
+    "mathbb" => (0x1d538, None),
+    _ => (0x1d49c, None),
+  };
+  // Letters that were in Unicode before the rest of their style
+  let exception = match (command, c) {
+    ("mathbb", 'C') => Some('\u{2102}'),
+    ("mathbb", 'H') => Some('\u{210d}'),
+    ("mathbb", 'N') => Some('\u{2115}'),
+    ("mathbb", 'P') => Some('\u{2119}'),
+    ("mathbb", 'Q') => Some('\u{211a}'),
+    ("mathbb", 'R') => Some('\u{211d}'),
+    ("mathbb", 'Z') => Some('\u{2124}'),
+    ("mathcal", 'B') => Some('\u{212c}'),
+    ("mathcal", 'E') => Some('\u{2130}'),
+    ("mathcal", 'F') => Some('\u{2131}'),
+    ("mathcal", 'H') => Some('\u{210b}'),
+    ("mathcal", 'I') => Some('\u{2110}'),
+    ("mathcal", 'L') => Some('\u{2112}'),
+    ("mathcal", 'M') => Some('\u{2133}'),
+    ("mathcal", 'R') => Some('\u{211b}'),
+    ("mathit", 'h') => Some('\u{210e}'),
+    _ => None,
+  };
+  if let Some(exception) = exception {
+    return exception;
+  }
+  let code = match (c, lower) {
+    ('A'..='Z', _) => upper + (c as u32 - 'A' as u32),
+    ('a'..='z', Some(lower)) => lower + (c as u32 - 'a' as u32),
+    _ => return c,
+  };
+  char::from_u32(code).unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tex_is_rendered_as_mathml() {
+    assert_eq!(
+      render("x^2", false),
+      "<math><semantics><msup><mi>x</mi><mn>2</mn></msup>\
+       <annotation encoding=\"application/x-tex\">x&#94;2</annotation>\
+       </semantics></math>"
+    );
+    assert_eq!(
+      render("\\frac{a}{b}", true),
+      "<math display=\"block\"><semantics><mfrac><mrow><mi>a</mi></mrow>\
+       <mrow><mi>b</mi></mrow></mfrac>\
+       <annotation encoding=\"application/x-tex\">\
+       &#92;frac&#123;a&#125;&#123;b&#125;</annotation></semantics></math>"
+    );
+  }
+
+  #[test]
+  fn unknown_commands_and_stray_braces_are_shown_as_errors() {
+    let mathml = render("\\alpha + \\foo }", false);
+    assert!(mathml.contains("<mi>α</mi><mo>&#43;</mo>"));
+    assert!(mathml.contains("<merror><mtext>&#92;foo</mtext></merror>"));
+    assert!(mathml.contains("<merror><mtext>&#125;</mtext></merror>"));
+  }
+
+  #[test]
+  fn display_math_is_expanded_outside_of_code() {
+    let input = "$$x$$\n\n`$$y$$`\n\n```\n$$z$$\n```\n";
+    assert_eq!(
+      expand(input, None).unwrap(),
+      format!(
+        "\n\n<div class=\"math\">{}</div>\n\n\n\n`$$y$$`\n\n```\n$$z$$\n```\n",
+        render("x", true)
+      )
+    );
+  }
+
+  #[test]
+  fn inline_math_leaves_amounts_and_escaped_dollars_alone() {
+    let output = expand("$x$ costs $5, not \\$x\\$\n", None).unwrap();
+    assert_eq!(
+      output,
+      format!("{} costs $5, not \\$x\\$\n", render("x", false))
+    );
+  }
+}
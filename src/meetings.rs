@@ -0,0 +1,261 @@
+use crate::code;
+use crate::frontmatter;
+use crate::listing;
+use crate::people;
+use crate::util::slugify;
+use crate::vfs::Source;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The file name of the page listing everyone's action items
+pub const PAGE: &str = "actions.html";
+
+/// The meeting a decision or action item was recorded in
+#[derive(Clone)]
+struct Meeting {
+  title: String,
+  date: String,
+  project: Option<String>,
+  path: PathBuf,
+}
+
+/// A task list item from a meeting, owned by the first person it mentions
+struct Action {
+  text: String,
+  done: bool,
+  owner: Option<String>,
+  meeting: Meeting,
+}
+
+/// A list item under a meeting's `Decisions` heading
+struct Decision {
+  text: String,
+  meeting: Meeting,
+}
+
+/// The decisions and action items of the meeting notes of a notebook
+///
+/// Meeting notes are those with `type: meeting` in their front matter, which
+/// may also give the meeting's `date`, `attendees` and `project`.
+pub struct Meetings {
+  root: PathBuf,
+  attendees: BTreeMap<String, usize>,
+  actions: Vec<Action>,
+  decisions: Vec<Decision>,
+}
+
+impl Meetings {
+  pub fn collect(source: &dyn Source, root: &Path) -> Result<Meetings> {
+    let mut meetings = Meetings {
+      root: root.to_path_buf(),
+      attendees: BTreeMap::new(),
+      actions: vec![],
+      decisions: vec![],
+    };
+    for path in source.files(root)? {
+      if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+        continue;
+      }
+      let text = source.read_to_string(&path)?;
+      let (meta, body) = frontmatter::split(&text);
+      if meta.get("type").and_then(Value::as_str) != Some("meeting") {
+        continue;
+      }
+      let key = |key: &str| meta.get(key).and_then(Value::as_str);
+      let meeting = Meeting {
+        title: listing::title(&meta, body).unwrap_or_else(|| {
+          let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+          stem.unwrap_or_default().into_owned()
+        }),
+        date: key("date").unwrap_or("").into(),
+        project: key("project").map(String::from),
+        path: path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+      };
+      let attendees = meta.get("attendees").and_then(Value::as_array);
+      for attendee in attendees.into_iter().flatten().filter_map(Value::as_str)
+      {
+        let attendee = attendee.trim_start_matches('@').to_string();
+        *meetings.attendees.entry(attendee).or_default() += 1;
+      }
+      meetings.parse(body, &meeting);
+    }
+    meetings
+      .actions
+      .sort_by(|a, b| b.meeting.date.cmp(&a.meeting.date));
+    meetings
+      .decisions
+      .sort_by(|a, b| b.meeting.date.cmp(&a.meeting.date));
+    Ok(meetings)
+  }
+
+  fn parse(&mut self, body: &str, meeting: &Meeting) {
+    let mut decisions = false;
+    for (code, line) in code::lines(body) {
+      if code {
+        continue;
+      }
+      let trimmed = line.trim();
+      if trimmed.starts_with('#') {
+        let heading = trimmed.trim_start_matches('#').trim();
+        decisions = heading.eq_ignore_ascii_case("decisions");
+        continue;
+      }
+
+      let item = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| trimmed.strip_prefix(marker));
+      let item = match item {
+        Some(item) => item.trim(),
+        None => continue,
+      };
+      let task = match item.get(..3) {
+        Some("[ ]") => Some(false),
+        Some("[x]") | Some("[X]") => Some(true),
+        _ => None,
+      };
+      match task {
+        Some(done) => {
+          let text = item[3..].trim().to_string();
+          self.actions.push(Action {
+            owner: people::mentions(&text).into_iter().next(),
+            text,
+            done,
+            meeting: meeting.clone(),
+          });
+        }
+        None if decisions => self.decisions.push(Decision {
+          text: item.into(),
+          meeting: meeting.clone(),
+        }),
+        None => {}
+      }
+    }
+  }
+
+  /// The pages of action items, the index first, as file names and markdown
+  ///
+  /// The index links to a page for each owner and each project, listing their
+  /// open and completed actions, and the decisions of each project.
+  pub fn pages(&self) -> Vec<(String, String)> {
+    let mut owners: BTreeMap<&str, Vec<&Action>> = BTreeMap::new();
+    let mut projects: BTreeMap<&str, Vec<&Action>> = BTreeMap::new();
+    for action in &self.actions {
+      let owner = action.owner.as_deref().unwrap_or("");
+      owners.entry(owner).or_default().push(action);
+      if let Some(project) = &action.meeting.project {
+        projects.entry(project).or_default().push(action);
+      }
+    }
+    for decision in &self.decisions {
+      if let Some(project) = &decision.meeting.project {
+        projects.entry(project).or_default();
+      }
+    }
+
+    let mut index = String::from("# Action items\n\n");
+    let mut pages = vec![];
+    if !owners.is_empty() {
+      index.push_str("## People\n\n");
+    }
+    for (owner, actions) in &owners {
+      let (name, label, title) = match *owner {
+        "" => (
+          "actions-unassigned.html".into(),
+          "Unassigned",
+          "Unassigned".into(),
+        ),
+        _ => (
+          format!("actions-{}.html", slugify(owner)),
+          *owner,
+          format!("@{}", owner),
+        ),
+      };
+      let meetings = self.attendees.get(*owner).copied().unwrap_or(0);
+      index.push_str(&format!(
+        "- [{}]({}) {} open, attended {} meetings\n",
+        label,
+        name,
+        actions.iter().filter(|action| !action.done).count(),
+        meetings
+      ));
+      let mut page = format!("# Actions for {}\n\n", title);
+      page.push_str(&self.actions_markdown(actions));
+      pages.push((name, page));
+    }
+
+    if !projects.is_empty() {
+      index.push_str("\n## Projects\n\n");
+    }
+    for (project, actions) in &projects {
+      let name = format!("actions-project-{}.html", slugify(project));
+      index.push_str(&format!(
+        "- [{}]({}) {} open\n",
+        project,
+        name,
+        actions.iter().filter(|action| !action.done).count()
+      ));
+      let mut page = format!("# {}\n\n", project);
+      page.push_str(&self.actions_markdown(actions));
+      let decisions = self
+        .decisions
+        .iter()
+        .filter(|decision| decision.meeting.project.as_deref() == Some(project))
+        .collect::<Vec<_>>();
+      if !decisions.is_empty() {
+        page.push_str("\n## Decisions\n\n");
+        for decision in decisions {
+          page.push_str(&format!(
+            "- {} ({})\n",
+            decision.text,
+            self.meeting_link(&decision.meeting)
+          ));
+        }
+      }
+      pages.push((name, page));
+    }
+
+    pages.insert(0, (PAGE.to_string(), index));
+    pages
+  }
+
+  /// Open actions, then completed ones, as task list items
+  fn actions_markdown(&self, actions: &[&Action]) -> String {
+    let mut markdown = String::new();
+    for done in [false, true] {
+      let actions = actions.iter().filter(|action| action.done == done);
+      let mut actions = actions.peekable();
+      if actions.peek().is_none() {
+        continue;
+      }
+      markdown.push_str(match done {
+        false => "## Open\n\n",
+        true => "\n## Done\n\n",
+      });
+      for action in actions {
+        markdown.push_str(&format!(
+          "- [{}] {} ({})\n",
+          if done { "x" } else { " " },
+          action.text,
+          self.meeting_link(&action.meeting)
+        ));
+      }
+    }
+    markdown
+  }
+
+  fn meeting_link(&self, meeting: &Meeting) -> String {
+    let href = meeting.path.with_extension("html");
+    let href = href.to_string_lossy().replace('\\', "/");
+    match meeting.date.as_str() {
+      "" => format!("[{}]({})", meeting.title, href),
+      date => format!("[{}]({}), {}", meeting.title, href, date),
+    }
+  }
+
+  /// The path the pages are rendered as, at the root of the notebook
+  pub fn document(&self) -> PathBuf {
+    self.root.join(PAGE).with_extension("md")
+  }
+}
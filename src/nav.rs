@@ -0,0 +1,136 @@
+use crate::frontmatter;
+use crate::headings::HeadingTransformer;
+use crate::publish::Schedule;
+use crate::vfs::Source;
+use anyhow::Result;
+use pulldown_cmark::Parser;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// A heading within a note, with the headings nested beneath it
+pub struct Heading {
+  pub text: String,
+  pub id: String,
+  pub children: Vec<Heading>,
+}
+
+/// A folder or note in the structure of a notebook
+pub enum Node {
+  Folder {
+    name: String,
+    children: Vec<Node>,
+  },
+  Note {
+    /// The path of the note, relative to the root of the tree
+    path: PathBuf,
+    title: String,
+    headings: Vec<Heading>,
+  },
+}
+
+/// The structure of the published notes under a directory, or of a single
+/// note
+pub fn tree(
+  source: &dyn Source,
+  root: &Path,
+  schedule: &Schedule,
+) -> Result<Vec<Node>> {
+  let files = match root.extension().and_then(|ext| ext.to_str()) {
+    Some("md") => vec![root.to_path_buf()],
+    _ => source.files(root)?,
+  };
+  let base = match files.as_slice() {
+    [file] if file == root => root.parent().unwrap_or(Path::new("")),
+    _ => root,
+  };
+
+  let mut nodes = vec![];
+  for file in files {
+    if file.extension().and_then(|ext| ext.to_str()) != Some("md") {
+      continue;
+    }
+    let text = source.read_to_string(&file)?;
+    if !schedule.is_published(&frontmatter::split(&text).0)? {
+      continue;
+    }
+    let relative = file.strip_prefix(base).unwrap_or(&file).to_path_buf();
+    let note = note(&text, relative.clone())?;
+
+    // Descend into the folders of the note, creating them as needed
+    let mut children = &mut nodes;
+    let folders = relative.parent().into_iter().flat_map(Path::components);
+    for folder in folders {
+      let name = folder.as_os_str().to_string_lossy().into_owned();
+      let found = children.iter().position(|node| match node {
+        Node::Folder { name: other, .. } => *other == name,
+        _ => false,
+      });
+      let index = match found {
+        Some(index) => index,
+        None => {
+          children.push(Node::Folder {
+            name,
+            children: vec![],
+          });
+          children.len() - 1
+        }
+      };
+      children = match &mut children[index] {
+        Node::Folder { children, .. } => children,
+        Node::Note { .. } => unreachable!("Only folders are searched"),
+      };
+    }
+    children.push(note);
+  }
+  Ok(nodes)
+}
+
+/// The title and nested headings of a note
+fn note(text: &str, path: PathBuf) -> Result<Node> {
+  let (meta, body) = frontmatter::split(text);
+
+  let mut transformer = HeadingTransformer::new(Parser::new(body));
+  transformer.by_ref().for_each(drop);
+
+  // Nest each heading beneath the closest preceding shallower heading
+  let mut headings: Vec<(u32, Heading)> = vec![];
+  let mut roots = vec![];
+  for heading in transformer.headings {
+    let level = heading.level;
+    while headings.last().is_some_and(|(other, _)| *other >= level) {
+      let (_, heading) = headings.pop().expect("A heading");
+      match headings.last_mut() {
+        Some((_, parent)) => parent.children.push(heading),
+        None => roots.push(heading),
+      }
+    }
+    let (text, id) = (heading.text, heading.id);
+    let children = vec![];
+    headings.push((level, Heading { text, id, children }));
+  }
+  while let Some((_, heading)) = headings.pop() {
+    match headings.last_mut() {
+      Some((_, parent)) => parent.children.push(heading),
+      None => roots.push(heading),
+    }
+  }
+
+  // A single top level heading is the note's title rather than a section
+  let title = meta.get("title").and_then(Value::as_str).map(String::from);
+  let (title, headings) = match (title, roots.len()) {
+    (Some(title), _) => (title, roots),
+    (None, 1) => {
+      let root = roots.pop().expect("A heading");
+      (root.text, root.children)
+    }
+    (None, _) => {
+      let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+      (stem.unwrap_or_default().into_owned(), roots)
+    }
+  };
+  Ok(Node::Note {
+    path,
+    title,
+    headings,
+  })
+}
@@ -0,0 +1,31 @@
+var CACHE = "notebook-{version}";
+
+self.addEventListener("install", function (event) {{
+  event.waitUntil(
+    fetch("{precache}")
+      .then(function (response) {{ return response.json(); }})
+      .then(function (manifest) {{
+        return caches.open(CACHE).then(function (cache) {{
+          return cache.addAll(manifest.files);
+        }});
+      }})
+  );
+  self.skipWaiting();
+}});
+
+self.addEventListener("activate", function (event) {{
+  event.waitUntil(
+    caches.keys().then(function (keys) {{
+      var old = keys.filter(function (key) {{ return key !== CACHE; }});
+      return Promise.all(old.map(function (key) {{ return caches.delete(key); }}));
+    }})
+  );
+}});
+
+self.addEventListener("fetch", function (event) {{
+  event.respondWith(
+    caches.match(event.request, {{ ignoreSearch: true }}).then(function (cached) {{
+      return cached || fetch(event.request);
+    }})
+  );
+}});
@@ -0,0 +1,37 @@
+use crate::assets::hash;
+use serde_json::{json, Value};
+
+/// The file name of the service worker, at the root of the output
+pub const WORKER: &str = "sw.js";
+
+/// The file name of the list of files the service worker caches
+pub const PRECACHE: &str = "precache.json";
+
+/// Script registering the service worker
+pub fn register() -> String {
+  format!(
+    "if (\"serviceWorker\" in navigator) {{\n  \
+     navigator.serviceWorker.register(\"{}\");\n}}\n",
+    WORKER
+  )
+}
+
+/// The precache manifest and service worker caching every written file
+///
+/// The worker is named after a hash of the files, so browsers replace their
+/// cache whenever any file changes.
+pub fn files(written: &[Value]) -> (Vec<u8>, Vec<u8>) {
+  let files = written
+    .iter()
+    .filter_map(|file| file.get("path")?.as_str())
+    .collect::<Vec<_>>();
+  let version = hash(Value::from(written).to_string().as_bytes());
+  let precache = json!({ "version": version, "files": files });
+  let worker = format!(
+    include_str!("offline.js"),
+    version = version,
+    precache = PRECACHE
+  );
+  let precache = serde_json::to_vec_pretty(&precache).expect("Precache");
+  (precache, worker.into_bytes())
+}
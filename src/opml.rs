@@ -0,0 +1,69 @@
+use crate::nav::{Heading, Node};
+use crate::util::escape;
+
+/// An OPML outline of a notebook's folders, notes and headings
+pub fn render(title: &str, nodes: &[Node]) -> String {
+  let mut opml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  opml.push_str("<opml version=\"2.0\">\n");
+  opml.push_str(&format!("<head><title>{}</title></head>\n", escape(title)));
+  opml.push_str("<body>\n");
+  for node in nodes {
+    outline_node(&mut opml, node, 1);
+  }
+  opml.push_str("</body>\n</opml>\n");
+  opml
+}
+
+fn outline_node(opml: &mut String, node: &Node, depth: usize) {
+  let indent = "  ".repeat(depth);
+  match node {
+    Node::Folder { name, children } => {
+      opml.push_str(&format!(
+        "{}<outline text=\"{}\">\n",
+        indent,
+        escape(name)
+      ));
+      for child in children {
+        outline_node(opml, child, depth + 1);
+      }
+      opml.push_str(&format!("{}</outline>\n", indent));
+    }
+    Node::Note {
+      path,
+      title,
+      headings,
+    } => {
+      let url = path
+        .with_extension("html")
+        .to_string_lossy()
+        .replace('\\', "/");
+      outline(opml, title, &url, headings, depth);
+    }
+  }
+}
+
+fn outline(
+  opml: &mut String,
+  text: &str,
+  url: &str,
+  headings: &[Heading],
+  depth: usize,
+) {
+  let indent = "  ".repeat(depth);
+  let attributes = format!(
+    "text=\"{}\" type=\"link\" url=\"{}\"",
+    escape(text),
+    escape(url)
+  );
+  if headings.is_empty() {
+    opml.push_str(&format!("{}<outline {}/>\n", indent, attributes));
+    return;
+  }
+  opml.push_str(&format!("{}<outline {}>\n", indent, attributes));
+  let page = url.split('#').next().unwrap_or(url);
+  for heading in headings {
+    let url = format!("{}#{}", page, heading.id);
+    outline(opml, &heading.text, &url, &heading.children, depth + 1);
+  }
+  opml.push_str(&format!("{}</outline>\n", indent));
+}
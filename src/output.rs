@@ -0,0 +1,259 @@
+use crate::assets::hash;
+use crate::util::crc32;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A destination for the files produced by a build
+pub trait Writer {
+  /// Write a file at a path relative to the root of the output
+  fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()>;
+
+  /// Complete the output once every file has been written
+  fn finish(&mut self) -> Result<()> {
+    Ok(())
+  }
+}
+
+/// Writes files into a directory
+pub struct Filesystem {
+  root: PathBuf,
+}
+
+impl Filesystem {
+  pub fn new(root: &Path) -> Filesystem {
+    Filesystem { root: root.into() }
+  }
+}
+
+impl Writer for Filesystem {
+  fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+    let path = self.root.join(path);
+    if let Some(dir) = path.parent().filter(|dir| dir != &Path::new("")) {
+      fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+  }
+}
+
+/// Writes the contents of every file to standard output
+pub struct Stdout;
+
+impl Writer for Stdout {
+  fn write(&mut self, _: &Path, contents: &[u8]) -> Result<()> {
+    io::stdout().write_all(contents)?;
+    Ok(())
+  }
+}
+
+/// Keeps files in memory, shared between its clones, as serve mode keeps the
+/// assets of the documents it renders
+#[derive(Clone, Default)]
+pub struct Memory {
+  files: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+}
+
+impl Memory {
+  /// The path and contents of the file a path ends with, as assets are found
+  /// relative to any page
+  pub fn ending(&self, path: &str) -> Option<(String, Vec<u8>)> {
+    let files = self.files.lock().expect("Memory output lock");
+    files
+      .iter()
+      .find(|(file, _)| path.ends_with(file.as_str()))
+      .map(|(file, contents)| (file.clone(), contents.clone()))
+  }
+}
+
+impl Writer for Memory {
+  fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut files = self.files.lock().expect("Memory output lock");
+    files.insert(path, contents.to_vec());
+    Ok(())
+  }
+}
+
+/// Writes files into an uncompressed zip archive
+///
+/// Names are marked as UTF-8, and archives past the 4 GiB or 65,535 files of
+/// a zip without its zip64 extensions are refused.
+pub struct Zip {
+  file: fs::File,
+  offset: u32,
+  entries: Vec<(String, u32, u32, u32)>,
+}
+
+impl Zip {
+  pub fn create(path: &Path) -> Result<Zip> {
+    Ok(Zip {
+      file: fs::File::create(path)?,
+      offset: 0,
+      entries: vec![],
+    })
+  }
+}
+
+impl Writer for Zip {
+  fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+    let name = path.to_string_lossy().replace('\\', "/");
+    let too_large = || anyhow!("Too large for a zip archive '{}'", name);
+    let crc = crc32(contents);
+    let size = u32::try_from(contents.len()).map_err(|_| too_large())?;
+    let length = u16::try_from(name.len()).map_err(|_| too_large())?;
+    if self.entries.len() == u16::MAX as usize {
+      return Err(anyhow!("Too many files for a zip archive '{}'", name));
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+    // Bit 11 of the flags marks the name as UTF-8
+    header.extend_from_slice(&[20, 0, 0, 0x08, 0, 0, 0, 0, 0x21, 0]);
+    header.extend_from_slice(&crc.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&length.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(name.as_bytes());
+    let end = u32::try_from(header.len())
+      .ok()
+      .and_then(|header| self.offset.checked_add(header)?.checked_add(size))
+      .ok_or_else(too_large)?;
+    self.file.write_all(&header)?;
+    self.file.write_all(contents)?;
+
+    self.entries.push((name, crc, size, self.offset));
+    self.offset = end;
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<()> {
+    let mut directory = vec![];
+    for (name, crc, size, offset) in &self.entries {
+      directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+      directory
+        .extend_from_slice(&[20, 0, 20, 0, 0, 0x08, 0, 0, 0, 0, 0x21, 0]);
+      directory.extend_from_slice(&crc.to_le_bytes());
+      directory.extend_from_slice(&size.to_le_bytes());
+      directory.extend_from_slice(&size.to_le_bytes());
+      directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+      directory.extend_from_slice(&[0; 12]);
+      directory.extend_from_slice(&offset.to_le_bytes());
+      directory.extend_from_slice(name.as_bytes());
+    }
+
+    let size = u32::try_from(directory.len())
+      .map_err(|_| anyhow!("Too many files for a zip archive"))?;
+    let count = (self.entries.len() as u16).to_le_bytes();
+    let mut end = vec![];
+    end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    end.extend_from_slice(&[0; 4]);
+    end.extend_from_slice(&count);
+    end.extend_from_slice(&count);
+    end.extend_from_slice(&size.to_le_bytes());
+    end.extend_from_slice(&self.offset.to_le_bytes());
+    end.extend_from_slice(&[0; 2]);
+
+    self.file.write_all(&directory)?;
+    self.file.write_all(&end)?;
+    Ok(())
+  }
+}
+
+/// Writes the files of a build through a writer, keeping a manifest of them
+pub struct Output {
+  writer: Box<dyn Writer>,
+  manifest: Vec<Value>,
+}
+
+impl Output {
+  /// The writer for an output path, a zip archive for a `.zip` path
+  ///
+  /// Returns the path of the document within the output alongside it.
+  pub fn open(path: Option<&str>) -> Result<(Output, PathBuf)> {
+    let (writer, document): (Box<dyn Writer>, PathBuf) = match path {
+      None => (Box::new(Stdout), PathBuf::new()),
+      Some(path) if path.ends_with(".zip") => {
+        let path = Path::new(path);
+        let document = Path::new(path.file_stem().unwrap_or_default());
+        (
+          Box::new(Zip::create(path)?),
+          document.with_extension("html"),
+        )
+      }
+      Some(path) => {
+        let path = Path::new(path);
+        let root = path.parent().unwrap_or(Path::new(""));
+        let document = path.file_name().unwrap_or_default();
+        (Box::new(Filesystem::new(root)), document.into())
+      }
+    };
+    let output = Output {
+      writer,
+      manifest: vec![],
+    };
+    Ok((output, document))
+  }
+
+  /// The writer for an output directory, or a `.zip` archive
+  pub fn directory(path: &str) -> Result<Output> {
+    let writer: Box<dyn Writer> = match path.ends_with(".zip") {
+      true => Box::new(Zip::create(Path::new(path))?),
+      false => Box::new(Filesystem::new(Path::new(path))),
+    };
+    Ok(Output {
+      writer,
+      manifest: vec![],
+    })
+  }
+
+  pub fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+    self.writer.write(path, contents)?;
+    self.manifest.push(json!({
+      "path": path.to_string_lossy().replace('\\', "/"),
+      "bytes": contents.len(),
+      "hash": hash(contents),
+    }));
+    Ok(())
+  }
+
+  /// The manifest entries of the files written so far
+  pub fn written(&self) -> &[Value] {
+    &self.manifest
+  }
+
+  /// Finish writing, returning the manifest of written files
+  pub fn finish(mut self) -> Result<Value> {
+    self.writer.finish()?;
+    Ok(Value::Array(self.manifest))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn zip_archives_store_files_with_their_checksums() {
+    let path = std::env::temp_dir()
+      .join(format!("notebook-output-{}.zip", std::process::id()));
+    let mut zip = Zip::create(&path).unwrap();
+    zip.write(Path::new("notes/a.txt"), b"hello").unwrap();
+    zip.finish().unwrap();
+    let data = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(&data[..4], b"PK\x03\x04");
+    assert_eq!(data[14..18], 0x3610a686u32.to_le_bytes());
+    assert_eq!(&data[30..46], b"notes/a.txthello");
+    let end = &data[data.len() - 22..];
+    assert_eq!(&end[..4], b"PK\x05\x06");
+    assert_eq!(end[10..12], 1u16.to_le_bytes());
+  }
+}
@@ -0,0 +1,66 @@
+use serde_json::{json, Map, Value};
+
+/// The page types with a built in layout and structured data
+pub const TYPES: [&str; 3] = ["recipe", "book", "meeting"];
+
+/// JSON-LD structured data describing a typed page
+///
+/// Recipes, books and meetings map their front matter onto the schema.org
+/// `Recipe`, `Book` and `Event` types.
+pub fn json_ld(meta: &Value, title: &str) -> Option<Value> {
+  let field = |key: &str| meta.get(key).filter(|value| !value.is_null());
+  let mut data = Map::new();
+  data.insert("@context".into(), json!("https://schema.org"));
+  data.insert("name".into(), json!(title));
+
+  let fields: &[(&str, &str)] = match meta.get("type")?.as_str()? {
+    "recipe" => {
+      data.insert("@type".into(), json!("Recipe"));
+      if let Some(steps) = field("steps").and_then(Value::as_array) {
+        let steps = steps
+          .iter()
+          .map(|step| json!({"@type": "HowToStep", "text": step}))
+          .collect();
+        data.insert("recipeInstructions".into(), Value::Array(steps));
+      }
+      &[
+        ("ingredients", "recipeIngredient"),
+        ("yield", "recipeYield"),
+        ("prep_time", "prepTime"),
+        ("cook_time", "cookTime"),
+        ("description", "description"),
+      ]
+    }
+    "book" => {
+      data.insert("@type".into(), json!("Book"));
+      if let Some(author) = field("author") {
+        data
+          .insert("author".into(), json!({"@type": "Person", "name": author}));
+      }
+      &[
+        ("isbn", "isbn"),
+        ("published", "datePublished"),
+        ("description", "description"),
+      ]
+    }
+    "meeting" => {
+      data.insert("@type".into(), json!("Event"));
+      if let Some(attendees) = field("attendees").and_then(Value::as_array) {
+        let attendees = attendees
+          .iter()
+          .map(|name| json!({"@type": "Person", "name": name}))
+          .collect();
+        data.insert("attendee".into(), Value::Array(attendees));
+      }
+      &[("date", "startDate"), ("location", "location")]
+    }
+    _ => return None,
+  };
+
+  for (key, property) in fields {
+    if let Some(value) = field(key) {
+      data.insert(property.to_string(), value.clone());
+    }
+  }
+  Some(Value::Object(data))
+}
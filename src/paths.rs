@@ -0,0 +1,191 @@
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Whether the filesystems of the platform usually ignore the case of names,
+/// as on Windows and macOS
+pub const CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Whether a link's destination is somewhere other than a file beside the
+/// note, as a URL with a scheme, an absolute path, a Windows drive path such
+/// as `C:\notes` or a UNC path such as `\\server\share`
+pub fn is_external(dest: &str) -> bool {
+  dest.starts_with(['/', '\\'])
+    || dest
+      .split(['/', '\\'])
+      .next()
+      .is_some_and(|first| first.contains(':'))
+}
+
+/// A relative link's destination with Windows separators as `/`, as they are
+/// in URLs
+pub fn slashes(dest: &str) -> Cow<'_, str> {
+  match dest.contains('\\') {
+    true => dest.replace('\\', "/").into(),
+    false => dest.into(),
+  }
+}
+
+/// A path without an extension, if it has one, ignoring its case
+pub fn strip_extension<'a>(path: &'a str, extension: &str) -> Option<&'a str> {
+  let (stem, ext) = path.rsplit_once('.')?;
+  ext.eq_ignore_ascii_case(extension).then_some(stem)
+}
+
+/// A path without `.` or `..` components and with `/` separators, in the case
+/// of its filesystem, for comparing paths
+pub fn key(path: &Path) -> String {
+  let mut parts: Vec<String> = vec![];
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir if parts.last().is_some_and(|part| part != "..") => {
+        parts.pop();
+      }
+      component => parts.push(
+        component
+          .as_os_str()
+          .to_string_lossy()
+          .replace('\\', "/")
+          .trim_end_matches('/')
+          .to_string(),
+      ),
+    }
+  }
+  let key = parts.join("/");
+  match CASE_INSENSITIVE {
+    true => key.to_lowercase(),
+    false => key,
+  }
+}
+
+/// Whether two paths name the same file, without touching the filesystem
+pub fn same(a: &Path, b: &Path) -> bool {
+  key(a) == key(b)
+}
+
+/// Whether a path is a directory or within it, without touching the
+/// filesystem, as when one is relative and the other absolute
+pub fn within(path: &Path, dir: &Path) -> bool {
+  let absolute = |path: &Path| match env::current_dir() {
+    Ok(current) => key(&current.join(path)),
+    Err(_) => key(path),
+  };
+  let (path, dir) = (absolute(path), absolute(dir));
+  path == dir || path.starts_with(&format!("{}/", dir.trim_end_matches('/')))
+}
+
+/// A canonical path without the `\\?\` prefix of canonical paths on
+/// Windows, which other programs and joined `/` separators don't understand
+pub fn canonicalize(path: &Path) -> Option<PathBuf> {
+  fs::canonicalize(path).ok().map(strip_verbatim)
+}
+
+/// A path without a `\\?\` prefix, as a UNC path for a `\\?\UNC\` share or
+/// a drive path, and otherwise as it is
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+  let text = path.to_string_lossy();
+  if let Some(share) = text.strip_prefix(r"\\?\UNC\") {
+    return PathBuf::from(format!(r"\\{}", share));
+  }
+  match text.strip_prefix(r"\\?\") {
+    Some(disk) if disk.get(1..2) == Some(":") => PathBuf::from(disk),
+    _ => path,
+  }
+}
+
+/// Whether a file is hidden, by a name starting with `.` or, on Windows, by
+/// its attributes
+pub fn is_hidden(path: &Path) -> bool {
+  let dotted = path
+    .file_name()
+    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+  dotted || has_hidden_attribute(path)
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(path: &Path) -> bool {
+  use std::os::windows::fs::MetadataExt;
+  const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+  fs::metadata(path)
+    .is_ok_and(|meta| meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_path: &Path) -> bool {
+  false
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn external_destinations_have_schemes_roots_or_drives() {
+    assert!(is_external("https://example.com/a.md"));
+    assert!(is_external("mailto:someone@example.com"));
+    assert!(is_external("/notes/a.md"));
+    assert!(is_external(r"C:\notes\a.md"));
+    assert!(is_external("C:/notes/a.md"));
+    assert!(is_external(r"\\server\share\a.md"));
+    assert!(!is_external("a.md"));
+    assert!(!is_external("dir/a.md#b:c"));
+    assert!(!is_external(r"..\dir\a.md"));
+  }
+
+  #[test]
+  fn keys_drop_dots_and_use_forward_slashes() {
+    assert_eq!(key(Path::new("./a/./b/../c.md")), "a/c.md");
+    assert_eq!(key(Path::new("../../a.md")), "../../a.md");
+    assert_eq!(key(Path::new("a/../../b.md")), "../b.md");
+    assert_eq!(key(Path::new("/a/b/")), "/a/b");
+  }
+
+  #[test]
+  fn same_paths_ignore_case_only_where_the_filesystem_does() {
+    assert!(same(Path::new("a/b.md"), Path::new("./a/c/../b.md")));
+    assert!(!same(Path::new("a/b.md"), Path::new("b/b.md")));
+    assert_eq!(
+      same(Path::new("Notes/A.md"), Path::new("notes/a.md")),
+      CASE_INSENSITIVE
+    );
+  }
+
+  #[test]
+  fn within_a_directory_compares_whole_components() {
+    assert!(within(Path::new("out/a.html"), Path::new("out")));
+    assert!(within(Path::new("out"), Path::new("./out/")));
+    assert!(!within(Path::new("output/a.html"), Path::new("out")));
+    assert!(!within(Path::new("a.html"), Path::new("out")));
+    let current = env::current_dir().unwrap();
+    assert!(within(&current.join("out/a.html"), Path::new("out")));
+    assert!(within(Path::new("a.md"), &current));
+  }
+
+  #[test]
+  fn verbatim_prefixes_are_stripped_from_drives_and_shares() {
+    let strip = |path: &str| strip_verbatim(PathBuf::from(path));
+    assert_eq!(strip(r"\\?\C:\notes"), PathBuf::from(r"C:\notes"));
+    assert_eq!(
+      strip(r"\\?\UNC\server\share\notes"),
+      PathBuf::from(r"\\server\share\notes")
+    );
+    // Other verbatim paths have no form without the prefix
+    assert_eq!(
+      strip(r"\\?\Volume{1234}\notes"),
+      PathBuf::from(r"\\?\Volume{1234}\notes")
+    );
+    assert_eq!(strip("/home/notes"), PathBuf::from("/home/notes"));
+  }
+
+  #[test]
+  fn canonical_paths_are_absolute_and_exist() {
+    let current = env::current_dir().unwrap();
+    let canonical = canonicalize(Path::new(".")).unwrap();
+    assert!(canonical.is_absolute());
+    assert!(same(&canonical, &canonicalize(&current).unwrap()));
+    assert!(!canonical.to_string_lossy().starts_with(r"\\?\"));
+    assert_eq!(canonicalize(Path::new("no/such/file.md")), None);
+  }
+}
@@ -0,0 +1,71 @@
+use crate::diagram::pipe_bytes;
+use crate::util::escape;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+/// Converts rendered documents to PDF with an external program
+///
+/// WeasyPrint and wkhtmltopdf are given their own arguments for the page
+/// size, margins and where relative paths are found from. Any other program
+/// is run as it is written, reading HTML on standard input and writing PDF to
+/// standard output.
+pub struct Pdf {
+  engine: Vec<String>,
+  size: String,
+  margin: String,
+}
+
+impl Pdf {
+  pub fn new(
+    engine: Option<String>,
+    size: Option<String>,
+    margin: Option<String>,
+  ) -> Result<Pdf> {
+    let engine = engine.unwrap_or_else(|| "weasyprint".into());
+    let engine = engine
+      .split_whitespace()
+      .map(String::from)
+      .collect::<Vec<_>>();
+    if engine.is_empty() {
+      return Err(anyhow!("Invalid PDF engine ''"));
+    }
+    Ok(Pdf {
+      engine,
+      size: size.unwrap_or_else(|| "A4".into()),
+      margin: margin.unwrap_or_else(|| "2cm".into()),
+    })
+  }
+
+  /// A stylesheet setting the size and margins of pages
+  pub fn style(&self) -> String {
+    format!(
+      "<style>@page {{ size: {}; margin: {}; }}</style>\n",
+      escape(&self.size),
+      escape(&self.margin)
+    )
+  }
+
+  /// Convert a document, run in the directory relative paths are found from
+  pub fn convert(&self, html: Vec<u8>, dir: &Path) -> Result<Vec<u8>> {
+    let program = &self.engine[0];
+    let mut args = self.engine[1..].to_vec();
+    let name = Path::new(program).file_stem().map(|s| s.to_string_lossy());
+    match name.as_deref() {
+      Some("weasyprint") => {
+        args.extend(["--base-url", "./", "-", "-"].map(String::from));
+      }
+      Some("wkhtmltopdf") => {
+        args
+          .extend(["--quiet", "--enable-local-file-access"].map(String::from));
+        args.extend(["--page-size".into(), self.size.clone()]);
+        for side in ["-T", "-B", "-L", "-R"] {
+          args.extend([side.into(), self.margin.clone()]);
+        }
+        args.extend(["-", "-"].map(String::from));
+      }
+      _ => {}
+    }
+
+    pipe_bytes(program, &args, dir, html)
+  }
+}
@@ -0,0 +1,383 @@
+use crate::code;
+use crate::frontmatter;
+use crate::listing;
+use crate::util::escape;
+use crate::vfs::Source;
+use anyhow::Result;
+use pulldown_cmark::{Event, LinkType, Tag};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::iter::Peekable;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The directory of person notes
+pub const DIR: &str = "people";
+
+/// A person described by the front matter of a note in `people/`
+struct Person {
+  name: String,
+  email: Option<String>,
+  /// Labelled links, such as a homepage or profiles
+  links: Vec<(String, String)>,
+  path: PathBuf,
+}
+
+/// The people of a notebook, mentioned in notes as `@id` after their file name
+#[derive(Default)]
+pub struct People {
+  /// The directory containing `people/`
+  root: PathBuf,
+  people: BTreeMap<String, Person>,
+}
+
+/// The people of each directory's notes, collected once for a build of many
+/// notes
+#[derive(Clone, Default)]
+pub struct Cache {
+  collected: Arc<Mutex<BTreeMap<PathBuf, Arc<People>>>>,
+}
+
+impl Cache {
+  /// The people in the nearest `people/` directory to a document
+  pub fn collect(
+    &self,
+    source: &dyn Source,
+    dir: &Path,
+  ) -> Result<Arc<People>> {
+    let mut collected = self.collected.lock().expect("People lock");
+    if let Some(people) = collected.get(dir) {
+      return Ok(people.clone());
+    }
+    let people = Arc::new(People::collect(source, dir)?);
+    collected.insert(dir.into(), people.clone());
+    Ok(people)
+  }
+}
+
+impl People {
+  /// Collect the people in the nearest `people/` directory to a document
+  pub fn collect(source: &dyn Source, dir: &Path) -> Result<People> {
+    let mut root = PathBuf::from(".");
+    let mut files = vec![];
+    for ancestor in dir.ancestors() {
+      let ancestor = match ancestor == Path::new("") {
+        true => Path::new("."),
+        false => ancestor,
+      };
+      // Notebooks without a people directory have no people
+      if let Ok(found) = source.files(&ancestor.join(DIR)) {
+        if !found.is_empty() {
+          root = ancestor.to_path_buf();
+          files = found;
+          break;
+        }
+      }
+    }
+
+    let mut people = BTreeMap::new();
+    for path in files {
+      if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+        continue;
+      }
+      // The directory's index page lists people rather than being one
+      let id = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some("_index") | None => continue,
+        Some(id) => id.to_string(),
+      };
+      let text = source.read_to_string(&path)?;
+      let (meta, body) = frontmatter::split(&text);
+      let name = listing::title(&meta, body).unwrap_or_else(|| id.clone());
+      let email = meta.get("email").and_then(Value::as_str).map(String::from);
+      let links = match meta.get("links") {
+        Some(Value::Array(links)) => links
+          .iter()
+          .filter_map(Value::as_str)
+          .map(|url| (url.to_string(), url.to_string()))
+          .collect(),
+        Some(Value::Object(links)) => links
+          .iter()
+          .filter_map(|(label, url)| {
+            Some((label.clone(), url.as_str()?.into()))
+          })
+          .collect(),
+        _ => vec![],
+      };
+      let person = Person {
+        name,
+        email,
+        links,
+        path,
+      };
+      people.insert(id, person);
+    }
+    Ok(People { root, people })
+  }
+
+  /// The name of a person and a link to their page, relative to a document
+  pub fn href(&self, document: &Path, id: &str) -> Option<(String, String)> {
+    let person = self.people.get(id)?;
+    let dir = document.parent().unwrap_or(Path::new(""));
+    let href = relative(dir, &person.path.with_extension("html"));
+    Some((person.name.clone(), href))
+  }
+
+  /// HTML for the profile at the top of a person's page, if it is one
+  pub fn profile(&self, document: &Path) -> Option<String> {
+    let person = self.person(document)?.1;
+    let mut html = String::from("<div class=\"person\">\n");
+    html.push_str(&format!(
+      "<p class=\"person-name\">{}</p>\n",
+      escape(&person.name)
+    ));
+    if let Some(email) = &person.email {
+      html.push_str(&format!(
+        "<p class=\"person-email\"><a href=\"mailto:{}\">{}</a></p>\n",
+        escape(email),
+        escape(email)
+      ));
+    }
+    if !person.links.is_empty() {
+      html.push_str("<ul class=\"person-links\">\n");
+      for (label, url) in &person.links {
+        html.push_str(&format!(
+          "<li><a href=\"{}\">{}</a></li>\n",
+          escape(url),
+          escape(label)
+        ));
+      }
+      html.push_str("</ul>\n");
+    }
+    html.push_str("</div>\n");
+    Some(html)
+  }
+
+  /// HTML listing the notes that mention a person, if the document is theirs
+  pub fn backlinks(
+    &self,
+    source: &dyn Source,
+    document: &Path,
+  ) -> Result<Option<String>> {
+    let (id, person) = match self.person(document) {
+      Some(person) => person,
+      None => return Ok(None),
+    };
+    let dir = document.parent().unwrap_or(Path::new(""));
+
+    let mut links = vec![];
+    for path in source.files(&self.root)? {
+      let is_markdown =
+        path.extension().and_then(|ext| ext.to_str()) == Some("md");
+      if !is_markdown || same(&path, &person.path) {
+        continue;
+      }
+      let text = source.read_to_string(&path)?;
+      let mut mentioned = false;
+      replace_mentions(&text, |other| {
+        mentioned |= other == id;
+        None
+      });
+      if !mentioned {
+        continue;
+      }
+      let (meta, body) = frontmatter::split(&text);
+      let title = listing::title(&meta, body).unwrap_or_else(|| {
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+        stem.unwrap_or_default().into_owned()
+      });
+      let href = relative(dir, &path.with_extension("html"));
+      links.push(format!(
+        "<li><a href=\"{}\">{}</a></li>\n",
+        escape(&href),
+        escape(&title)
+      ));
+    }
+
+    if links.is_empty() {
+      return Ok(None);
+    }
+    let mut html = String::from("<section class=\"mentions\">\n");
+    html.push_str("<h2>Mentioned in</h2>\n<ul>\n");
+    html.push_str(&links.concat());
+    html.push_str("</ul>\n</section>\n");
+    Ok(Some(html))
+  }
+
+  fn person(&self, document: &Path) -> Option<(&str, &Person)> {
+    self
+      .people
+      .iter()
+      .find(|(_, person)| same(&person.path, document))
+      .map(|(id, person)| (id.as_str(), person))
+  }
+}
+
+/// The ids of the `@id` mentions in some text, in order
+pub fn mentions(input: &str) -> Vec<String> {
+  let mut ids = vec![];
+  replace_mentions(input, |id| {
+    if !id.is_empty() {
+      ids.push(id.to_string());
+    }
+    None
+  });
+  ids
+}
+
+/// Links `@id` mentions of people to their pages, relative to a document,
+/// outside of code and links
+pub struct MentionTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: Peekable<T>,
+  people: &'a People,
+  document: &'a Path,
+  buffer: VecDeque<Event<'a>>,
+  skip: usize,
+}
+
+impl<'a, T> MentionTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T, people: &'a People, document: &'a Path) -> Self {
+    MentionTransformer {
+      iter: iter.peekable(),
+      people,
+      document,
+      buffer: VecDeque::new(),
+      skip: 0,
+    }
+  }
+}
+
+impl<'a, T> Iterator for MentionTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(event) = self.buffer.pop_front() {
+      return Some(event);
+    }
+
+    let event = self.iter.next()?;
+    let text = match event {
+      Event::Start(Tag::Link(..))
+      | Event::Start(Tag::Image(..))
+      | Event::Start(Tag::CodeBlock(_)) => {
+        self.skip += 1;
+        return Some(event);
+      }
+      Event::End(Tag::Link(..))
+      | Event::End(Tag::Image(..))
+      | Event::End(Tag::CodeBlock(_)) => {
+        self.skip -= 1;
+        return Some(event);
+      }
+      Event::Text(text) if self.skip == 0 => text,
+      _ => return Some(event),
+    };
+
+    // Text may be split into several events, even within a mention
+    let mut text = text.to_string();
+    while let Some(Event::Text(next)) = self.iter.peek() {
+      text.push_str(next);
+      self.iter.next();
+    }
+
+    let mut written = 0;
+    for (start, id) in mentions_in(&text, None) {
+      let (name, href) = match self.people.href(self.document, id) {
+        Some(person) => person,
+        None => continue,
+      };
+      if start > written {
+        let before = text[written..start].to_string();
+        self.buffer.push_back(Event::Text(before.into()));
+      }
+      let link = Tag::Link(LinkType::Inline, href.into(), "".into());
+      self.buffer.push_back(Event::Start(link.clone()));
+      self.buffer.push_back(Event::Text(name.into()));
+      self.buffer.push_back(Event::End(link));
+      written = start + 1 + id.len();
+    }
+    if written < text.len() {
+      let rest = text[written..].to_string();
+      self.buffer.push_back(Event::Text(rest.into()));
+    }
+    self.buffer.pop_front()
+  }
+}
+
+/// Replace each `@id` mention outside of code that `replace` resolves
+fn replace_mentions<F>(input: &str, mut replace: F) -> String
+where
+  F: FnMut(&str) -> Option<String>,
+{
+  let mut previous = None;
+  code::map_prose(input, |text| {
+    let mut output = String::with_capacity(text.len());
+    let mut written = 0;
+    for (start, id) in mentions_in(text, previous) {
+      if let Some(link) = replace(id) {
+        output.push_str(&text[written..start]);
+        output.push_str(&link);
+        written = start + 1 + id.len();
+      }
+    }
+    output.push_str(&text[written..]);
+    previous = text.chars().last().or(previous);
+    output
+  })
+}
+
+/// The position and id of each `@id` mention in some text, following a
+/// character when the text continues other text
+fn mentions_in(text: &str, previous: Option<char>) -> Vec<(usize, &str)> {
+  let is_id = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+  let mut mentions = vec![];
+  let mut from = 0;
+  while let Some(found) = text[from..].find('@') {
+    let start = from + found;
+    let before = text[..start].chars().last().or(previous);
+    let after = &text[start + 1..];
+    let end = after.find(|c| !is_id(c)).unwrap_or(after.len());
+    let id = after[..end].trim_end_matches('-');
+    // Mentions are not part of a word, such as an email address
+    if before.is_none_or(|c| !is_id(c) && c != '.') && !id.is_empty() {
+      mentions.push((start, id));
+    }
+    from = start + 1 + id.len();
+  }
+  mentions
+}
+
+/// Whether two paths name the same file, ignoring `.` components
+fn same(a: &Path, b: &Path) -> bool {
+  let normal = |path: &Path| {
+    path
+      .components()
+      .filter(|c| !matches!(c, Component::CurDir))
+      .collect::<PathBuf>()
+  };
+  normal(a) == normal(b)
+}
+
+/// The path of a file relative to a directory, for links between pages
+pub fn relative(dir: &Path, path: &Path) -> String {
+  let normal = |path: &Path| {
+    path
+      .components()
+      .filter(|c| !matches!(c, Component::CurDir))
+      .map(|c| c.as_os_str().to_string_lossy().into_owned())
+      .collect::<Vec<_>>()
+  };
+  let (dir, path) = (normal(dir), normal(path));
+  let common = dir.iter().zip(&path).take_while(|(a, b)| a == b).count();
+  let mut parts = vec!["..".to_string(); dir.len() - common];
+  parts.extend(path[common..].iter().cloned());
+  parts.join("/")
+}
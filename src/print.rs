@@ -0,0 +1,78 @@
+use pulldown_cmark::{CowStr, Event, LinkType, Tag};
+use std::collections::VecDeque;
+
+/// Follows external links with a footnote containing their URL
+pub struct PrintLinkTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  urls: Vec<CowStr<'a>>,
+  buffer: VecDeque<Event<'a>>,
+  done: bool,
+}
+
+impl<'a, T> PrintLinkTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T) -> Self {
+    PrintLinkTransformer {
+      iter,
+      urls: vec![],
+      buffer: VecDeque::new(),
+      done: false,
+    }
+  }
+
+  fn label(index: usize) -> CowStr<'a> {
+    format!("link-{}", index + 1).into()
+  }
+}
+
+impl<'a, T> Iterator for PrintLinkTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(event) = self.buffer.pop_front() {
+      return Some(event);
+    }
+
+    let event = match self.iter.next() {
+      Some(event) => event,
+      None if self.done => return None,
+      None => {
+        // Footnotes listing each URL follow the end of the document
+        self.done = true;
+        for (index, url) in self.urls.drain(..).enumerate() {
+          let label = Self::label(index);
+          self.buffer.extend(vec![
+            Event::Start(Tag::FootnoteDefinition(label.clone())),
+            Event::Start(Tag::Paragraph),
+            Event::Text(url),
+            Event::End(Tag::Paragraph),
+            Event::End(Tag::FootnoteDefinition(label)),
+          ]);
+        }
+        return self.buffer.pop_front();
+      }
+    };
+
+    match event {
+      Event::End(Tag::Link(LinkType::Autolink, ..))
+      | Event::End(Tag::Link(LinkType::Email, ..)) => Some(event),
+      Event::End(Tag::Link(_, ref url, _))
+        if url.starts_with("http://") || url.starts_with("https://") =>
+      {
+        let label = Self::label(self.urls.len());
+        self.urls.push(url.clone());
+        self.buffer.push_back(Event::FootnoteReference(label));
+        Some(event)
+      }
+      _ => Some(event),
+    }
+  }
+}
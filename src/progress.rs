@@ -0,0 +1,94 @@
+use crate::util::escape;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Route that reading positions are posted to, and listed at
+pub const PATH: &str = "progress";
+
+/// Reading positions and read state of served notes, persisted as JSON
+#[derive(Clone)]
+pub struct Progress {
+  path: PathBuf,
+  notes: Arc<Mutex<Map<String, Value>>>,
+}
+
+impl Progress {
+  /// Load the store kept in the `.notebook` directory beside a document
+  pub fn load(dir: &Path) -> Result<Progress> {
+    let path = dir.join(".notebook").join("progress.json");
+    let notes = match fs::read_to_string(&path) {
+      Ok(text) => match serde_json::from_str(&text)? {
+        Value::Object(notes) => notes,
+        _ => return Err(anyhow!("Invalid progress file")),
+      },
+      Err(_) => Map::new(),
+    };
+    Ok(Progress {
+      path,
+      notes: Arc::new(Mutex::new(notes)),
+    })
+  }
+
+  /// Record an update of `{"path", "position", "read"}` sent by a browser,
+  /// ignoring a position that isn't a number or a read state that isn't a
+  /// boolean
+  pub fn update(&self, update: &Value) -> Result<()> {
+    let note = match update.get("path") {
+      Some(Value::String(note)) => note.clone(),
+      _ => return Err(anyhow!("Missing path in progress update")),
+    };
+    let position = update.get("position").filter(|value| value.is_number());
+    let read = update.get("read").filter(|value| value.is_boolean());
+    let mut notes = self.notes.lock().expect("Progress lock");
+    let entry = notes.entry(note).or_insert_with(|| json!({}));
+    for (key, value) in [("position", position), ("read", read)] {
+      if let (Some(value), Value::Object(entry)) = (value, &mut *entry) {
+        entry.insert(key.into(), value.clone());
+      }
+    }
+
+    if let Some(dir) = self.path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+    fs::write(&self.path, serde_json::to_string_pretty(&*notes)?)?;
+    Ok(())
+  }
+
+  /// Script restoring and reporting the reading position of a note
+  pub fn script(&self, note: &str) -> String {
+    let notes = self.notes.lock().expect("Progress lock");
+    let state = notes.get(note).cloned().unwrap_or_else(|| json!({}));
+    format!(
+      include_str!("progress.html"),
+      path = PATH,
+      note = Value::String(note.into()),
+      state = state
+    )
+  }
+
+  /// Markdown for a page listing every note with its progress
+  pub fn page(&self) -> String {
+    let notes = self.notes.lock().expect("Progress lock");
+    let mut page = String::from("# Reading progress\n\n");
+    if notes.is_empty() {
+      page.push_str("Nothing has been read yet.\n");
+      return page;
+    }
+    page.push_str("| Note | Position | Read |\n|---|---|---|\n");
+    for (note, state) in notes.iter() {
+      let position = state.get("position").and_then(Value::as_f64);
+      let read = state.get("read").and_then(Value::as_bool) == Some(true);
+      page.push_str(&format!(
+        "| <a href=\"/{}\">{}</a> | {:.0}% | {} |\n",
+        escape(note.trim_start_matches('/')),
+        escape(note),
+        position.unwrap_or(0.0) * 100.0,
+        if read { "✓" } else { "" }
+      ));
+    }
+    page
+  }
+}
@@ -0,0 +1,152 @@
+use crate::people::People;
+use crate::tags;
+use crate::util::escape;
+use serde_json::Value;
+use std::path::Path;
+
+/// Front matter that configures a note rather than describing it, left out
+/// of tables of all its properties
+const HIDDEN: [&str; 20] = [
+  "title",
+  "name",
+  "type",
+  "layout",
+  "lang",
+  "aliases",
+  "summary",
+  "description",
+  "styles",
+  "scripts",
+  "preload",
+  "publish_at",
+  "sort",
+  "order",
+  "group",
+  "paginate",
+  "pinned",
+  "weight",
+  "macros",
+  "properties",
+];
+
+/// Which front matter fields are shown in a table at the top of notes
+///
+/// The fields are given in order, as in `--properties date,tags,author`, or
+/// are all of those describing a note when none are given. A note's own
+/// `properties` list takes the place of the fields given.
+#[derive(Clone)]
+pub struct Properties {
+  fields: Vec<String>,
+}
+
+impl Properties {
+  pub fn new(fields: &str) -> Properties {
+    let fields = fields.split(',').map(str::trim);
+    let fields = fields.filter(|field| !field.is_empty());
+    Properties {
+      fields: fields.map(String::from).collect(),
+    }
+  }
+
+  /// HTML for the table of a note's properties, if it has any
+  ///
+  /// Tags link to the tag index and `@id` mentions to people's pages.
+  pub fn table(
+    &self,
+    meta: &Value,
+    document: &Path,
+    people: &People,
+  ) -> Option<String> {
+    let object = meta.as_object()?;
+    let fields: Vec<String> = match meta.get("properties") {
+      Some(Value::Array(fields)) => fields
+        .iter()
+        .filter_map(Value::as_str)
+        .map(String::from)
+        .collect(),
+      _ if !self.fields.is_empty() => self.fields.clone(),
+      _ => object
+        .keys()
+        .filter(|key| !HIDDEN.contains(&key.as_str()))
+        .cloned()
+        .collect(),
+    };
+
+    let mut rows = String::new();
+    for field in fields {
+      let value = match object.get(&field) {
+        Some(Value::Null) | None => continue,
+        Some(value) => value,
+      };
+      let html = match field.as_str() {
+        "tags" => tags::of(meta)
+          .iter()
+          .map(|tag| {
+            format!(
+              "<a class=\"tag\" href=\"{}\">{}</a>",
+              escape(&tags::href(tag)),
+              escape(tag)
+            )
+          })
+          .collect::<Vec<_>>()
+          .join(", "),
+        _ => format_value(value, document, people),
+      };
+      rows.push_str(&format!(
+        "<tr><th scope=\"row\">{}</th><td>{}</td></tr>\n",
+        escape(&label(&field)),
+        html
+      ));
+    }
+    match rows.is_empty() {
+      true => None,
+      false => Some(format!(
+        "<table class=\"properties\">\n<tbody>\n{}</tbody>\n</table>\n",
+        rows
+      )),
+    }
+  }
+}
+
+/// A field name as a label, `due_date` as `Due date`
+fn label(field: &str) -> String {
+  let words = field.replace(['_', '-'], " ");
+  let mut chars = words.chars();
+  match chars.next() {
+    Some(first) => first.to_uppercase().chain(chars).collect(),
+    None => words,
+  }
+}
+
+fn format_value(value: &Value, document: &Path, people: &People) -> String {
+  match value {
+    Value::Array(values) => values
+      .iter()
+      .map(|value| format_value(value, document, people))
+      .collect::<Vec<_>>()
+      .join(", "),
+    Value::Object(object) => object
+      .iter()
+      .map(|(key, value)| {
+        let value = format_value(value, document, people);
+        format!("{}: {}", escape(key), value)
+      })
+      .collect::<Vec<_>>()
+      .join(", "),
+    Value::String(text) => {
+      let person = text
+        .strip_prefix('@')
+        .and_then(|id| people.href(document, id));
+      if let Some((name, href)) = person {
+        format!("<a href=\"{}\">{}</a>", escape(&href), escape(&name))
+      } else if text.starts_with("https://") || text.starts_with("http://") {
+        format!("<a href=\"{}\">{}</a>", escape(text), escape(text))
+      } else {
+        escape(text)
+      }
+    }
+    Value::Bool(true) => "Yes".into(),
+    Value::Bool(false) => "No".into(),
+    value => escape(&value.to_string()),
+  }
+}
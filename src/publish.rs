@@ -0,0 +1,92 @@
+use crate::util::days;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which notes are published, from the `publish_at` times of their front matter
+///
+/// Times are a date, `2024-03-05`, or a date and time, `2024-03-05T09:00`,
+/// in UTC unless followed by an offset such as `+01:00`.
+#[derive(Clone)]
+pub struct Schedule {
+  /// The time notes are published by, in seconds since the Unix epoch
+  now: i64,
+  /// Whether notes are shown before they are published
+  preview: bool,
+}
+
+impl Schedule {
+  /// The schedule as of a time, or now
+  pub fn new(at: Option<&str>) -> Result<Schedule> {
+    let now = match at {
+      Some(at) => {
+        timestamp(at).ok_or_else(|| anyhow!("Invalid time '{}'", at))?
+      }
+      None => SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0),
+    };
+    Ok(Schedule {
+      now,
+      preview: false,
+    })
+  }
+
+  /// This schedule, showing notes that are not yet published
+  pub fn preview(&self) -> Schedule {
+    Schedule {
+      preview: true,
+      ..self.clone()
+    }
+  }
+
+  pub fn is_published(&self, meta: &Value) -> Result<bool> {
+    let at = match meta.get("publish_at") {
+      Some(Value::String(at)) => at,
+      Some(Value::Null) | None => return Ok(true),
+      Some(at) => return Err(anyhow!("Invalid publish_at '{}'", at)),
+    };
+    let at =
+      timestamp(at).ok_or_else(|| anyhow!("Invalid publish_at '{}'", at))?;
+    Ok(self.preview || at <= self.now)
+  }
+}
+
+/// Seconds since the Unix epoch of a date or a date and time
+fn timestamp(text: &str) -> Option<i64> {
+  let text = text.trim();
+  let day = days(text.get(..10)?)?;
+  let rest = text.get(10..)?;
+  let rest = match rest.strip_prefix('T').or_else(|| rest.strip_prefix(' ')) {
+    Some(rest) => rest,
+    None if rest.is_empty() => return Some(day * 86400),
+    None => return None,
+  };
+
+  // The time runs until the zone, if it has one
+  let end = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+  let (time, zone) = rest.split_at(end);
+  let mut parts = time.split(':');
+  let mut part = |max: i64| {
+    let part = parts.next().map(|part| part.parse::<i64>().ok());
+    match part {
+      Some(Some(part)) if (0..max).contains(&part) => Some(part),
+      Some(_) => None,
+      None => Some(0),
+    }
+  };
+  let seconds = part(24)? * 3600 + part(60)? * 60 + part(61)?;
+  let offset = match zone {
+    "" | "Z" | "z" => 0,
+    zone => {
+      let sign = if zone.starts_with('-') { -1 } else { 1 };
+      let (hours, minutes) =
+        zone[1..].split_once(':').unwrap_or((&zone[1..], "0"));
+      sign
+        * (hours.parse::<i64>().ok()? * 3600
+          + minutes.parse::<i64>().ok()? * 60)
+    }
+  };
+  Some(day * 86400 + seconds - offset)
+}
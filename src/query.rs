@@ -0,0 +1,464 @@
+use crate::code::Blocks;
+use crate::frontmatter;
+use crate::listing;
+use crate::publish::Schedule;
+use crate::tags;
+use crate::util::{self, escape};
+use crate::vfs::Source;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Route that notes are queried at, under `/api`
+pub const PATH: &str = "notes";
+
+/// A note that queries are matched against
+struct Note {
+  path: PathBuf,
+  title: String,
+  meta: Value,
+  body: String,
+  /// The date the note was last modified, as `YYYY-MM-DD`
+  modified: Option<String>,
+  /// Whether the note's `#tag` tags are among its tags
+  inline_tags: bool,
+}
+
+impl Note {
+  /// The values of a field of the note, lowercased
+  ///
+  /// `tag` is the note's `tags` and `#tag` tags, `title`, `path` and
+  /// `modified` are those of the note and other fields are looked up in its
+  /// front matter.
+  fn values(&self, field: &str) -> Vec<String> {
+    let values = match field {
+      "title" => vec![self.title.clone()],
+      "path" => vec![self.path.to_string_lossy().replace('\\', "/")],
+      "modified" => self.modified.clone().into_iter().collect(),
+      "tag" => tags::all(&self.meta, &self.body, self.inline_tags),
+      field => strings(self.meta.get(field)),
+    };
+    values.iter().map(|value| value.to_lowercase()).collect()
+  }
+
+  fn contains(&self, text: &str) -> bool {
+    self.title.to_lowercase().contains(text)
+      || self.body.to_lowercase().contains(text)
+  }
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+  match value {
+    Some(Value::Array(values)) => values
+      .iter()
+      .flat_map(|value| strings(Some(value)))
+      .collect(),
+    Some(Value::String(value)) => vec![value.clone()],
+    Some(Value::Number(value)) => vec![value.to_string()],
+    Some(Value::Bool(value)) => vec![value.to_string()],
+    _ => vec![],
+  }
+}
+
+enum Comparison {
+  Equal,
+  Less,
+  LessOrEqual,
+  Greater,
+  GreaterOrEqual,
+}
+
+/// A condition on notes
+enum Filter {
+  /// Notes containing some text in their title or body
+  Text(String),
+  Field(String, Comparison, String),
+  Not(Box<Filter>),
+  And(Box<Filter>, Box<Filter>),
+  Or(Box<Filter>, Box<Filter>),
+  All,
+}
+
+impl Filter {
+  fn matches(&self, note: &Note) -> bool {
+    match self {
+      Filter::Text(text) => note.contains(text),
+      Filter::Field(field, comparison, value) => {
+        note.values(field).iter().any(|found| {
+          let ordering = compare(found, value);
+          match comparison {
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+          }
+        })
+      }
+      Filter::Not(filter) => !filter.matches(note),
+      Filter::And(a, b) => a.matches(note) && b.matches(note),
+      Filter::Or(a, b) => a.matches(note) || b.matches(note),
+      Filter::All => true,
+    }
+  }
+}
+
+/// Compare values as numbers when they both are, otherwise as text, which
+/// orders `YYYY-MM-DD` dates
+fn compare(a: &str, b: &str) -> Ordering {
+  match (a.parse::<f64>(), b.parse::<f64>()) {
+    (Ok(a), Ok(b)) => a.total_cmp(&b),
+    _ => a.cmp(b),
+  }
+}
+
+/// A query for notes, as in `tag:project AND modified:>2024-01-01`
+///
+/// Terms are `field:value` conditions, with `<`, `<=`, `>` or `>=` before the
+/// value to compare it, or text to search titles and bodies for. Terms are
+/// combined with `AND` (the default), `OR` and `NOT` or `-`, and grouped with
+/// parentheses. `sort:field` orders the notes, newest first for `date` and
+/// `modified`, with a `-` prefix reversing the order, and `limit:N` keeps the
+/// first of them.
+pub struct Query {
+  filter: Filter,
+  sort: Option<String>,
+  limit: Option<usize>,
+}
+
+impl Query {
+  pub fn parse(text: &str) -> Result<Query> {
+    let mut sort = None;
+    let mut limit = None;
+    let mut tokens = vec![];
+    for token in tokenize(text)? {
+      if let Some(field) = token.strip_prefix("sort:") {
+        sort = Some(field.to_lowercase());
+      } else if let Some(count) = token.strip_prefix("limit:") {
+        let count = count
+          .parse()
+          .map_err(|_| anyhow!("Invalid limit '{}'", count))?;
+        limit = Some(count);
+      } else {
+        tokens.push(token);
+      }
+    }
+    let mut parser = Parser { tokens, at: 0 };
+    let filter = match parser.tokens.is_empty() {
+      true => Filter::All,
+      false => parser.or()?,
+    };
+    if let Some(token) = parser.tokens.get(parser.at) {
+      return Err(anyhow!("Unexpected '{}' in query", token));
+    }
+    Ok(Query {
+      filter,
+      sort,
+      limit,
+    })
+  }
+
+  /// A query from the parameters of a URL, as in `?tag=x&sort=modified`
+  ///
+  /// `q` is a query as it is written, `sort` and `limit` order and limit the
+  /// notes, and any other parameter is a `field:value` condition.
+  pub fn from_params(params: &BTreeMap<String, String>) -> Result<Query> {
+    let mut text = params.get("q").cloned().unwrap_or_default();
+    for (key, value) in params {
+      let value = value.replace('"', "");
+      match key.as_str() {
+        "q" => continue,
+        "sort" | "limit" => text.push_str(&format!(" {}:{}", key, value)),
+        field => text.push_str(&format!(" {}:\"{}\"", field, value)),
+      }
+    }
+    Query::parse(&text)
+  }
+
+  fn compare(&self, a: &Note, b: &Note) -> Ordering {
+    let sort = self.sort.as_deref().unwrap_or("path");
+    let field = sort.trim_start_matches('-');
+    let value = |note: &Note| note.values(field).into_iter().next();
+    let ordering = match field {
+      "path" => a.path.cmp(&b.path),
+      "title" => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+      "date" | "modified" => value(b).cmp(&value(a)),
+      _ => match (value(a), value(b)) {
+        (Some(a), Some(b)) => compare(&a, &b),
+        // Notes without the field come last
+        (a, b) => b.is_some().cmp(&a.is_some()),
+      },
+    };
+    match sort.starts_with('-') {
+      true => ordering.reverse(),
+      false => ordering,
+    }
+  }
+}
+
+/// Split a query into words, quoted text and parentheses
+fn tokenize(text: &str) -> Result<Vec<String>> {
+  let mut tokens = vec![];
+  let mut token = String::new();
+  let mut chars = text.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' => loop {
+        match chars.next() {
+          Some('"') => break,
+          Some(c) => token.push(c),
+          None => return Err(anyhow!("Unclosed quote in query")),
+        }
+      },
+      '(' | ')' if token.is_empty() || c == ')' => {
+        if !token.is_empty() {
+          tokens.push(std::mem::take(&mut token));
+        }
+        tokens.push(c.to_string());
+      }
+      c if c.is_whitespace() => {
+        if !token.is_empty() {
+          tokens.push(std::mem::take(&mut token));
+        }
+      }
+      c => token.push(c),
+    }
+  }
+  if !token.is_empty() {
+    tokens.push(token);
+  }
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<String>,
+  at: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&str> {
+    self.tokens.get(self.at).map(String::as_str)
+  }
+
+  fn or(&mut self) -> Result<Filter> {
+    let mut filter = self.and()?;
+    while self.peek() == Some("OR") {
+      self.at += 1;
+      filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+    }
+    Ok(filter)
+  }
+
+  /// Terms next to each other must all match
+  fn and(&mut self) -> Result<Filter> {
+    let mut filter = self.not()?;
+    loop {
+      match self.peek() {
+        Some("AND") => self.at += 1,
+        Some("OR") | Some(")") | None => return Ok(filter),
+        Some(_) => {}
+      }
+      filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+    }
+  }
+
+  fn not(&mut self) -> Result<Filter> {
+    match self.peek() {
+      Some("NOT") => {
+        self.at += 1;
+        Ok(Filter::Not(Box::new(self.not()?)))
+      }
+      Some(token) if token.len() > 1 && token.starts_with('-') => {
+        self.tokens[self.at] = token[1..].to_string();
+        Ok(Filter::Not(Box::new(self.term()?)))
+      }
+      _ => self.term(),
+    }
+  }
+
+  fn term(&mut self) -> Result<Filter> {
+    let token = match self.peek() {
+      Some(token) => token.to_string(),
+      None => return Err(anyhow!("Query ends too soon")),
+    };
+    self.at += 1;
+    if token == "(" {
+      let filter = self.or()?;
+      if self.peek() != Some(")") {
+        return Err(anyhow!("Unclosed parenthesis in query"));
+      }
+      self.at += 1;
+      return Ok(filter);
+    }
+    let (field, value) = match token.split_once(':') {
+      Some((field, value)) if !field.is_empty() => (field, value),
+      _ => return Ok(Filter::Text(token.to_lowercase())),
+    };
+    let (comparison, value) = if let Some(value) = value.strip_prefix(">=") {
+      (Comparison::GreaterOrEqual, value)
+    } else if let Some(value) = value.strip_prefix("<=") {
+      (Comparison::LessOrEqual, value)
+    } else if let Some(value) = value.strip_prefix('>') {
+      (Comparison::Greater, value)
+    } else if let Some(value) = value.strip_prefix('<') {
+      (Comparison::Less, value)
+    } else {
+      (Comparison::Equal, value)
+    };
+    Ok(Filter::Field(
+      field.to_lowercase(),
+      comparison,
+      value.to_lowercase(),
+    ))
+  }
+}
+
+/// The published notes of a directory and its children, other than a document
+fn notes(
+  source: &dyn Source,
+  dir: &Path,
+  document: &Path,
+  schedule: &Schedule,
+  inline_tags: bool,
+) -> Result<Vec<Note>> {
+  let document = document.strip_prefix(dir).unwrap_or(document);
+  let mut notes = vec![];
+  for path in source.files(dir)? {
+    let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+    let is_markdown =
+      path.extension().and_then(|ext| ext.to_str()) == Some("md");
+    if !is_markdown || relative == document {
+      continue;
+    }
+    let text = source.read_to_string(&path)?;
+    let (meta, body) = frontmatter::split(&text);
+    if !schedule.is_published(&meta)? {
+      continue;
+    }
+    let modified = source.modified(&path).and_then(|modified| {
+      let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+      Some(util::date(seconds as i64 / 86400))
+    });
+    notes.push(Note {
+      title: listing::title(&meta, body)
+        .unwrap_or_else(|| listing::stem(&relative)),
+      body: body.to_string(),
+      path: relative,
+      meta,
+      modified,
+      inline_tags,
+    });
+  }
+  Ok(notes)
+}
+
+/// The published notes of a directory and its children matching a query, as
+/// JSON with their `path`, `url`, `title`, `modified` date and front matter
+pub fn find(
+  source: &dyn Source,
+  dir: &Path,
+  schedule: &Schedule,
+  inline_tags: bool,
+  query: &Query,
+) -> Result<Value> {
+  let notes = notes(source, dir, Path::new(""), schedule, inline_tags)?;
+  let mut found = notes
+    .iter()
+    .filter(|note| query.filter.matches(note))
+    .collect::<Vec<_>>();
+  found.sort_by(|a, b| query.compare(a, b));
+  found.truncate(query.limit.unwrap_or(usize::MAX));
+  let found = found.into_iter().map(|note| {
+    let path = note.path.to_string_lossy().replace('\\', "/");
+    let url = note.path.with_extension("html");
+    json!({
+      "path": path,
+      "url": url.to_string_lossy().replace('\\', "/"),
+      "title": note.title,
+      "modified": note.modified,
+      "meta": note.meta,
+    })
+  });
+  Ok(Value::Array(found.collect()))
+}
+
+/// HTML listing the notes matching a query
+fn render(query: &Query, notes: &[Note]) -> String {
+  let mut found = notes
+    .iter()
+    .filter(|note| query.filter.matches(note))
+    .collect::<Vec<_>>();
+  found.sort_by(|a, b| query.compare(a, b));
+  found.truncate(query.limit.unwrap_or(usize::MAX));
+  if found.is_empty() {
+    return "<p class=\"query empty\">No matching notes</p>\n".into();
+  }
+  let mut html = String::from("<nav class=\"query\">\n<ul>\n");
+  for note in found {
+    let href = note.path.with_extension("html");
+    let href = href.to_string_lossy().replace('\\', "/");
+    html.push_str(&format!(
+      "<li><a href=\"{}\">{}</a></li>\n",
+      escape(&href),
+      escape(&note.title)
+    ));
+  }
+  html.push_str("</ul>\n</nav>\n");
+  html
+}
+
+/// Replace ` ```query ` fenced blocks with lists of the notes they match,
+/// from the directory of a document and its children
+pub fn expand(
+  source: &dyn Source,
+  dir: &Path,
+  document: &Path,
+  schedule: &Schedule,
+  inline_tags: bool,
+  input: &str,
+) -> Result<String> {
+  let mut output = String::with_capacity(input.len());
+  let mut notes = None;
+  let mut blocks = Blocks::default();
+  let mut query: Option<String> = None;
+
+  for line in input.split_inclusive('\n') {
+    let fenced = blocks.is_fenced();
+    blocks.is_code(line);
+    if let Some(text) = &mut query {
+      if blocks.is_fenced() {
+        text.push_str(line);
+        continue;
+      }
+      if notes.is_none() {
+        notes =
+          Some(self::notes(source, dir, document, schedule, inline_tags)?);
+      }
+      let html = match Query::parse(text) {
+        Ok(query) => render(&query, notes.as_deref().unwrap_or(&[])),
+        // Like diagrams, errors are displayed in the output document
+        Err(err) => format!(
+          "<pre class=\"query error\">{}</pre>\n",
+          escape(&err.to_string())
+        ),
+      };
+      output.push_str(&format!("\n{}\n", html));
+      query = None;
+      continue;
+    }
+    if !fenced && blocks.is_fenced() && line.trim() == "```query" {
+      query = Some(String::new());
+      continue;
+    }
+    output.push_str(line);
+  }
+  // An unclosed query is left as it was
+  if let Some(text) = query {
+    output.push_str("```query\n");
+    output.push_str(&text);
+  }
+  Ok(output)
+}
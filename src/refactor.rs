@@ -0,0 +1,576 @@
+use crate::code;
+use crate::frontmatter;
+use crate::headings::HeadingTransformer;
+use crate::paths;
+use crate::people::relative;
+use crate::util::{escape, markdown_files, slugify};
+use crate::vfs;
+use crate::wikilink::{self, WikiLinks};
+use anyhow::{anyhow, Result};
+use pulldown_cmark::Parser;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Restructure notes, pointing the links of other notes at where their
+/// sections end up
+pub fn refactor(args: &[String]) -> Result<()> {
+  match args.first().map(String::as_str) {
+    Some("split") => split(&args[1..]),
+    Some("merge") => merge(&args[1..]),
+    _ => {
+      println!(
+        "Usage: notebook refactor split FILE [--by h1|h2] [--dir DIR]
+       notebook refactor merge A B -o C [--dir DIR]"
+      );
+      Ok(())
+    }
+  }
+}
+
+/// Move each section of a note into a note of its own, leaving the original
+/// as a list of links to them
+fn split(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optopt(
+    "",
+    "by",
+    "level of heading to split at, defaults to h1",
+    "h1",
+  );
+  opts.optopt(
+    "d",
+    "dir",
+    "notes to relink, defaults to the current directory",
+    "DIR",
+  );
+  let matches = opts.parse(args)?;
+  let file = match matches.free.as_slice() {
+    [file] => normal(Path::new(file)),
+    _ => {
+      let brief = "Usage: notebook refactor split FILE [--by h1|h2]";
+      print!("{}", opts.usage(brief));
+      return Ok(());
+    }
+  };
+  let level = match matches.opt_str("by").as_deref() {
+    None | Some("h1") => 1,
+    Some(by) => match by.strip_prefix('h').and_then(|n| n.parse().ok()) {
+      Some(level) if (1..=6).contains(&level) => level,
+      _ => return Err(anyhow!("Unknown heading level '{}'", by)),
+    },
+  };
+  let dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+
+  let input = fs::read_to_string(&file)?;
+  let (_, body) = frontmatter::split(&input);
+  let front = &input[..input.len() - body.len()];
+
+  // Ids are those the sections' headings are rendered with
+  let mut headings = HeadingTransformer::new(Parser::new(body));
+  headings.by_ref().for_each(drop);
+  let headings = headings
+    .headings
+    .into_iter()
+    .filter(|heading| heading.level == level)
+    .collect::<Vec<_>>();
+
+  let mut preamble = String::new();
+  let mut sections: Vec<String> = vec![];
+  for (fenced, line) in code::lines(body) {
+    if !fenced && heading_level(line) == Some(level) {
+      sections.push(String::new());
+    }
+    // Headings are raised so that each section's heading is its title
+    let line = match (fenced, heading_level(line)) {
+      (false, Some(n)) if n >= level => &line[level as usize - 1..],
+      _ => line,
+    };
+    match sections.last_mut() {
+      Some(section) => section.push_str(line),
+      None => preamble.push_str(line),
+    }
+  }
+  if sections.is_empty() {
+    return Err(anyhow!(
+      "No h{} headings to split '{}' at",
+      level,
+      file.display()
+    ));
+  }
+  if sections.len() != headings.len() {
+    return Err(anyhow!(
+      "Headings of '{}' could not be matched",
+      file.display()
+    ));
+  }
+
+  let mut moved: Vec<(Option<String>, PathBuf)> = vec![];
+  for heading in &headings {
+    // A section named as the note, which stays as the index, is numbered
+    let mut path = dir.join(format!("{}.md", heading.id));
+    let mut n = 0;
+    while paths::same(&path, &file) || moved.iter().any(|(_, p)| *p == path) {
+      n += 1;
+      path = dir.join(format!("{}-{}.md", heading.id, n));
+    }
+    if path.exists() {
+      return Err(anyhow!("Note '{}' already exists", path.display()));
+    }
+    moved.push((Some(heading.id.clone()), path));
+  }
+
+  let mut index = format!("{}{}", front, preamble);
+  if !index.is_empty() && !index.ends_with("\n\n") {
+    index.push('\n');
+  }
+  for ((section, heading), (_, path)) in
+    sections.iter().zip(&headings).zip(&moved)
+  {
+    let href = relative(&dir, path);
+    index.push_str(&format!("- [{}]({})\n", heading.text, href));
+    fs::write(path, section)?;
+    println!("Wrote '{}'", path.display());
+  }
+  fs::write(&file, index)?;
+
+  let roots = match matches.opt_str("dir") {
+    Some(dir) => vec![PathBuf::from(dir)],
+    None => roots(&[&file]),
+  };
+  relink(&roots, &[&file], |path, fragment| {
+    if path != file {
+      return None;
+    }
+    let (_, to) = moved.iter().find(|(id, _)| id.as_deref() == fragment)?;
+    Some((to.clone(), None))
+  })
+}
+
+/// Combine two notes into one, which is given the notes' old pages as
+/// `aliases` so that they redirect to it
+fn merge(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optopt("o", "output", "the merged note", "FILE");
+  opts.optopt(
+    "d",
+    "dir",
+    "notes to relink, defaults to the current directory",
+    "DIR",
+  );
+  let matches = opts.parse(args)?;
+  let (a, b, to) = match (matches.free.as_slice(), matches.opt_str("output")) {
+    ([a, b], Some(to)) => (
+      normal(Path::new(a)),
+      normal(Path::new(b)),
+      normal(Path::new(&to)),
+    ),
+    _ => {
+      let brief = "Usage: notebook refactor merge A B -o C";
+      print!("{}", opts.usage(brief));
+      return Ok(());
+    }
+  };
+  if to.exists() && to != a && to != b {
+    return Err(anyhow!("Note '{}' already exists", to.display()));
+  }
+  let dir = to.parent().unwrap_or(Path::new("")).to_path_buf();
+
+  let first = fs::read_to_string(&a)?;
+  let second = fs::read_to_string(&b)?;
+  let (mut meta, first) = frontmatter::split(&first);
+  let (second_meta, second) = frontmatter::split(&second);
+
+  let mut aliases = match meta.get("aliases") {
+    Some(Value::Array(aliases)) => aliases.clone(),
+    _ => vec![],
+  };
+  for path in [&a, &b] {
+    if *path != to {
+      let page = relative(&dir, &path.with_extension("html"));
+      aliases.push(page.into());
+    }
+  }
+  if let Value::Object(meta) = &mut meta {
+    meta.insert("aliases".into(), Value::Array(aliases));
+  }
+
+  // A note titled only in its front matter keeps its title as a heading
+  let mut part = String::new();
+  let title = second_meta.get("title").and_then(Value::as_str);
+  if let (Some(title), false) = (title, second.trim_start().starts_with("# ")) {
+    part.push_str(&format!("# {}\n\n", title));
+  }
+  part.push_str(second.trim_start());
+  let body = format!("{}\n\n{}", first.trim_end(), part);
+  let merged = format!("---\n{}---\n\n{}", serde_yaml::to_string(&meta)?, body);
+
+  // Headings of the second note follow those of the first, which may renumber
+  // the ids of headings with the same text
+  let (first_ids, part_ids) = (heading_ids(first), heading_ids(&part));
+  let merged_ids = heading_ids(&body);
+  let part_heading = part.lines().next().and_then(heading_level).is_some();
+
+  fs::write(&to, merged)?;
+  println!("Wrote '{}'", to.display());
+  for path in [&a, &b] {
+    if *path != to {
+      fs::remove_file(path)?;
+    }
+  }
+
+  let roots = match matches.opt_str("dir") {
+    Some(dir) => vec![PathBuf::from(dir)],
+    None => roots(&[&a, &b, &to]),
+  };
+  relink(&roots, &[&a, &b], |path, fragment| {
+    if paths::same(path, &a) {
+      return Some((to.clone(), fragment.map(String::from)));
+    }
+    if !paths::same(path, &b) {
+      return None;
+    }
+    // Links to the second note go to the heading its part starts with
+    let index = match fragment {
+      Some(fragment) => part_ids.iter().position(|id| id == fragment),
+      None => part_heading.then_some(0),
+    };
+    let fragment = match index {
+      Some(index) => merged_ids.get(first_ids.len() + index).cloned(),
+      None => fragment.map(String::from),
+    };
+    Some((to.clone(), fragment))
+  })
+}
+
+/// The ids of the headings of some markdown, as they are rendered
+fn heading_ids(markdown: &str) -> Vec<String> {
+  let mut headings = HeadingTransformer::new(Parser::new(markdown));
+  headings.by_ref().for_each(drop);
+  headings
+    .headings
+    .into_iter()
+    .map(|heading| heading.id)
+    .collect()
+}
+
+/// Move a note, pointing the links to it and its own links at their new
+/// places, with its old page added to its `aliases`
+pub fn mv(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optopt(
+    "d",
+    "dir",
+    "notes to relink, defaults to the current directory",
+    "DIR",
+  );
+  let matches = opts.parse(args)?;
+  let (from, to) = match matches.free.as_slice() {
+    [from, to] => (normal(Path::new(from)), normal(Path::new(to))),
+    _ => {
+      print!("{}", opts.usage("Usage: notebook mv FILE DESTINATION"));
+      return Ok(());
+    }
+  };
+  if to.exists() {
+    return Err(anyhow!("Note '{}' already exists", to.display()));
+  }
+  let from_dir = from.parent().unwrap_or(Path::new("")).to_path_buf();
+  let to_dir = to.parent().unwrap_or(Path::new("")).to_path_buf();
+
+  // Links are kept pointing at the same files from the new directory
+  let rebase = |dest: &str| {
+    let (path, fragment) = match dest.split_once('#') {
+      Some((path, fragment)) => (path, Some(fragment)),
+      None => (dest, None),
+    };
+    if path.is_empty() || paths::is_external(path) {
+      return None;
+    }
+    let path = paths::slashes(path);
+    let mut dest = relative(&to_dir, &normal(&from_dir.join(path.as_ref())));
+    if let Some(fragment) = fragment {
+      dest.push('#');
+      dest.push_str(fragment);
+    }
+    Some(dest)
+  };
+  let input = fs::read_to_string(&from)?;
+  let input = rewrite_links(&input, rebase);
+  // Wiki links only reach the notes beside a note, so those to the notes it
+  // leaves behind become relative links
+  let input = match paths::same(&from_dir, &to_dir) {
+    true => input,
+    false => {
+      let dir = match from_dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => from_dir.as_path(),
+      };
+      let notes = WikiLinks::collect(&vfs::Filesystem::default(), dir)?;
+      wikilink::replace_links(&input, |target| {
+        let (page, heading, label) = wikilink::parts(target);
+        let stem = notes.find(page).filter(|_| !page.is_empty())?;
+        let to = from_dir.join(format!("{}.md", stem));
+        let label = label.unwrap_or_else(|| target.trim());
+        let fragment = heading.map(slugify);
+        Some(link(label, &relative(&to_dir, &to), fragment.as_deref()))
+      })
+    }
+  };
+
+  let (mut meta, body) = frontmatter::split(&input);
+  let aliases = match meta.get("aliases") {
+    Some(Value::Array(aliases)) => aliases.clone(),
+    _ => vec![],
+  };
+  let mut aliases = aliases
+    .into_iter()
+    .map(|alias| match alias.as_str() {
+      Some(page) if page.ends_with(".html") => {
+        rebase(page).map(Value::from).unwrap_or(alias)
+      }
+      _ => alias,
+    })
+    .collect::<Vec<_>>();
+  aliases.push(relative(&to_dir, &from.with_extension("html")).into());
+  if let Value::Object(meta) = &mut meta {
+    meta.insert("aliases".into(), Value::Array(aliases));
+  }
+  let output = format!("---\n{}---\n{}", serde_yaml::to_string(&meta)?, body);
+
+  if !to_dir.as_os_str().is_empty() {
+    fs::create_dir_all(&to_dir)?;
+  }
+  fs::write(&to, output)?;
+  fs::remove_file(&from)?;
+  println!("Moved '{}' to '{}'", from.display(), to.display());
+
+  let roots = match matches.opt_str("dir") {
+    Some(dir) => vec![PathBuf::from(dir)],
+    None => roots(&[&from, &to]),
+  };
+  relink(&roots, &[&from], |path, fragment| {
+    paths::same(path, &from).then(|| (to.clone(), fragment.map(String::from)))
+  })
+}
+
+/// The directories where notes are relinked, the current directory when the
+/// notes are all within it, so that notes anywhere in the notebook are, or
+/// else the directories of the notes
+fn roots(paths: &[&Path]) -> Vec<PathBuf> {
+  if paths.iter().all(|path| paths::within(path, Path::new("."))) {
+    return vec![PathBuf::from(".")];
+  }
+  let mut roots: Vec<PathBuf> = vec![];
+  for path in paths {
+    let dir = match path.parent().unwrap_or(Path::new("")) {
+      dir if dir.as_os_str().is_empty() => Path::new("."),
+      dir => dir,
+    };
+    if !roots.iter().any(|root| paths::same(root, dir)) {
+      roots.push(dir.to_path_buf());
+    }
+  }
+  roots
+}
+
+/// The level of an ATX heading line
+fn heading_level(line: &str) -> Option<u32> {
+  let hashes = line.len() - line.trim_start_matches('#').len();
+  let rest = &line[hashes..];
+  let heading = (1..=6).contains(&hashes)
+    && (rest.starts_with(' ') || rest.trim().is_empty());
+  heading.then_some(hashes as u32)
+}
+
+/// Remove `.` and resolve `..` components of a path without touching the
+/// filesystem
+fn normal(path: &Path) -> PathBuf {
+  let mut normal = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir if normal.file_name().is_some() => {
+        normal.pop();
+      }
+      component => normal.push(component),
+    }
+  }
+  normal
+}
+
+/// Rewrite the links of the notes under a directory to the notes that have
+/// moved, given the markdown file and fragment each link points at, and the
+/// notes that were moved from
+fn relink<F>(roots: &[PathBuf], sources: &[&Path], moved: F) -> Result<()>
+where
+  F: Fn(&Path, Option<&str>) -> Option<(PathBuf, Option<String>)>,
+{
+  let mut seen = BTreeSet::new();
+  for root in roots {
+    for file in markdown_files(root)? {
+      let file = normal(&file);
+      if seen.insert(paths::key(&file)) {
+        relink_note(&file, sources, &moved)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Rewrite the links of a note to the notes that have moved, if it links to
+/// any of them
+fn relink_note<F>(file: &Path, sources: &[&Path], moved: &F) -> Result<()>
+where
+  F: Fn(&Path, Option<&str>) -> Option<(PathBuf, Option<String>)>,
+{
+  let input = fs::read_to_string(file)?;
+  let dir = file.parent().unwrap_or(Path::new(""));
+  let output = rewrite_links(&input, |dest| retarget(file, dir, dest, moved));
+  let output = wikilink::replace_links(&output, |target| {
+    retarget_wiki(dir, sources, target, moved)
+  });
+  if output != input {
+    fs::write(file, output)?;
+    println!("Relinked '{}'", file.display());
+  }
+  Ok(())
+}
+
+/// Replace the destinations of the inline links of some markdown, outside of
+/// code
+fn rewrite_links<F>(input: &str, mut replace: F) -> String
+where
+  F: FnMut(&str) -> Option<String>,
+{
+  code::map_prose(input, |text| {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+      let (before, after) = rest.split_at(start + 2);
+      output.push_str(before);
+      let end = after.find([')', ' ']).unwrap_or(after.len());
+      let dest = &after[..end];
+      output.push_str(&replace(dest).unwrap_or_else(|| dest.to_string()));
+      rest = &after[end..];
+    }
+    output.push_str(rest);
+    output
+  })
+}
+
+/// The new form of a wiki link from a note in a directory, if it links to a
+/// note that has moved
+///
+/// Wiki links find notes beside their note by the slugs of their names, and
+/// stay wiki links while the note they link to stays beside it, and become
+/// relative links otherwise.
+fn retarget_wiki<F>(
+  dir: &Path,
+  sources: &[&Path],
+  target: &str,
+  moved: &F,
+) -> Option<String>
+where
+  F: Fn(&Path, Option<&str>) -> Option<(PathBuf, Option<String>)>,
+{
+  let (page, heading, label) = wikilink::parts(target);
+  if page.is_empty() {
+    return None;
+  }
+  let slug = slugify(page.trim_end_matches(".md"));
+  let source = sources.iter().find(|source| {
+    let stem = source.file_stem().map(|stem| stem.to_string_lossy());
+    paths::same(source.parent().unwrap_or(Path::new("")), dir)
+      && stem.is_some_and(|stem| slugify(&stem) == slug)
+  })?;
+  let fragment = heading.map(slugify);
+  let (to, new_fragment) = moved(source, fragment.as_deref())?;
+  let label = label.unwrap_or_else(|| target.trim());
+  let beside = paths::same(to.parent().unwrap_or(Path::new("")), dir);
+  let stem = to.file_stem().map(|stem| stem.to_string_lossy())?;
+  match (beside, new_fragment == fragment) {
+    (true, true) => {
+      let heading = heading.map(|heading| format!("#{}", heading));
+      Some(format!(
+        "[[{}{}|{}]]",
+        stem,
+        heading.unwrap_or_default(),
+        label
+      ))
+    }
+    (true, false) if new_fragment.is_none() => {
+      Some(format!("[[{}|{}]]", stem, label))
+    }
+    _ => Some(link(label, &relative(dir, &to), new_fragment.as_deref())),
+  }
+}
+
+/// A markdown link to a note, at a fragment of it
+fn link(label: &str, href: &str, fragment: Option<&str>) -> String {
+  let fragment = fragment.map(|fragment| format!("#{}", fragment));
+  format!(
+    "[{}]({}{})",
+    label.replace('[', "\\[").replace(']', "\\]"),
+    href.replace(' ', "%20"),
+    fragment.unwrap_or_default()
+  )
+}
+
+/// The new destination of a link from a note, if it points at a note that
+/// has moved
+fn retarget<F>(file: &Path, dir: &Path, dest: &str, moved: &F) -> Option<String>
+where
+  F: Fn(&Path, Option<&str>) -> Option<(PathBuf, Option<String>)>,
+{
+  let (path, fragment) = match dest.split_once('#') {
+    Some((path, fragment)) => (path, Some(fragment)),
+    None => (dest, None),
+  };
+  if paths::is_external(path) {
+    return None;
+  }
+  let path = paths::slashes(path);
+  let extension = Path::new(path.as_ref())
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .map(str::to_lowercase);
+  let target = match (path.as_ref(), extension.as_deref()) {
+    ("", _) => file.to_path_buf(),
+    (_, Some("md")) => normal(&dir.join(path.as_ref())),
+    (_, Some("html")) => normal(&dir.join(path.as_ref())).with_extension("md"),
+    _ => return None,
+  };
+  let (to, fragment) = moved(&target, fragment)?;
+  let extension = extension.as_deref().unwrap_or("html");
+  let mut dest = relative(dir, &to.with_extension(extension));
+  if let Some(fragment) = fragment {
+    dest.push('#');
+    dest.push_str(&fragment);
+  }
+  Some(dest)
+}
+
+/// Pages redirecting to a note from each of the `.html` pages among its
+/// `aliases`, as paths relative to the note's page and their HTML
+pub fn redirects(meta: &Value, page: &Path) -> Vec<(PathBuf, String)> {
+  let aliases = meta.get("aliases").and_then(Value::as_array);
+  let aliases = aliases.into_iter().flatten().filter_map(Value::as_str);
+  let dir = page.parent().unwrap_or(Path::new(""));
+  aliases
+    .filter(|alias| alias.ends_with(".html"))
+    .map(|alias| {
+      let from = normal(&dir.join(alias));
+      let href = relative(from.parent().unwrap_or(Path::new("")), page);
+      let html = format!(
+        "<!doctype html>\n<meta charset=\"utf-8\">\n<title>Redirecting</title>\n\
+         <link rel=\"canonical\" href=\"{0}\">\n\
+         <meta http-equiv=\"refresh\" content=\"0; url={0}\">\n",
+        escape(&href)
+      );
+      (from, html)
+    })
+    .filter(|(from, _)| from != page)
+    .collect()
+}
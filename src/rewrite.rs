@@ -0,0 +1,166 @@
+use crate::util::slugify;
+use anyhow::{anyhow, Result};
+use lol_html::html_content::{ContentType, Element, EndTag, TextChunk};
+use lol_html::{ElementContentHandlers, HtmlRewriter, Selector, Settings};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// A rewrite rule applied to the final HTML, written as `SELECTOR@ACTION`
+#[derive(Clone, Debug)]
+pub struct Rule {
+  selector: String,
+  action: Action,
+}
+
+#[derive(Clone, Debug)]
+enum Action {
+  /// `class=NAMES` adds space separated class names
+  Class(String),
+  /// `attr=NAME=VALUE` sets an attribute
+  Attr(String, String),
+  /// `wrap=TAG` wraps the element in another element
+  Wrap(String),
+  /// `anchor` appends a self-link to the element
+  Anchor,
+  /// `remove` removes the element along with its content
+  Remove,
+}
+
+impl Rule {
+  /// Returns the rule for one of the built in transforms
+  pub fn builtin(name: &str) -> Result<Rule> {
+    let rule = match name {
+      "tables" => "table@class=table",
+      "sortable" => "table@class=sortable",
+      "images" => "img@wrap=figure",
+      "anchors" => "h1, h2, h3, h4, h5, h6@anchor",
+      _ => return Err(anyhow!("Unknown transform '{}'", name)),
+    };
+    rule.parse()
+  }
+}
+
+impl FromStr for Rule {
+  type Err = anyhow::Error;
+
+  fn from_str(rule: &str) -> Result<Rule> {
+    // Selectors never contain an '@' so the first one separates the action
+    let (selector, action) = match rule.find('@') {
+      Some(i) => (rule[..i].trim(), &rule[i + 1..]),
+      None => return Err(anyhow!("Missing action in rule '{}'", rule)),
+    };
+
+    selector
+      .parse::<Selector>()
+      .map_err(|err| anyhow!("Invalid selector '{}': {}", selector, err))?;
+
+    let (name, value) = match action.find('=') {
+      Some(i) => (&action[..i], Some(&action[i + 1..])),
+      None => (action, None),
+    };
+
+    let action = match (name, value) {
+      ("class", Some(names)) => Action::Class(names.into()),
+      ("attr", Some(attr)) => match attr.find('=') {
+        Some(i) => Action::Attr(attr[..i].into(), attr[i + 1..].into()),
+        None => Action::Attr(attr.into(), "".into()),
+      },
+      ("wrap", Some(tag)) if is_tag_name(tag) => Action::Wrap(tag.into()),
+      ("anchor", None) => Action::Anchor,
+      ("remove", None) => Action::Remove,
+      _ => return Err(anyhow!("Invalid action '{}' in rule", action)),
+    };
+
+    Ok(Rule {
+      selector: selector.into(),
+      action,
+    })
+  }
+}
+
+fn is_tag_name(tag: &str) -> bool {
+  !tag.is_empty() && tag.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Apply rewrite rules to an HTML document
+pub fn apply(html: &str, rules: &[Rule]) -> Result<String> {
+  let mut settings = Settings::new();
+  for rule in rules {
+    let selector = rule.selector.parse::<Selector>()?;
+    let handlers = rule.action.handlers();
+    settings =
+      settings.append_element_content_handler((Cow::Owned(selector), handlers));
+  }
+
+  let mut output = vec![];
+  let mut rewriter =
+    HtmlRewriter::new(settings, |chunk: &[u8]| output.extend_from_slice(chunk));
+  rewriter.write(html.as_bytes())?;
+  rewriter.end()?;
+  Ok(String::from_utf8(output)?)
+}
+
+impl Action {
+  fn handlers(&self) -> ElementContentHandlers<'_> {
+    let handlers = ElementContentHandlers::default();
+    match self {
+      Action::Class(names) => handlers.element(move |el: &mut Element| {
+        let class = match el.get_attribute("class") {
+          Some(class) => format!("{} {}", class, names),
+          None => names.clone(),
+        };
+        el.set_attribute("class", &class)?;
+        Ok(())
+      }),
+      Action::Attr(name, value) => handlers.element(move |el: &mut Element| {
+        el.set_attribute(name, value)?;
+        Ok(())
+      }),
+      Action::Wrap(tag) => handlers.element(move |el: &mut Element| {
+        el.before(&format!("<{}>", tag), ContentType::Html);
+        el.after(&format!("</{}>", tag), ContentType::Html);
+        Ok(())
+      }),
+      Action::Remove => handlers.element(|el: &mut Element| {
+        el.remove();
+        Ok(())
+      }),
+      Action::Anchor => anchor_handlers(handlers),
+    }
+  }
+}
+
+/// Link to an element by its id, or by an id derived from its text content
+fn anchor_handlers(
+  handlers: ElementContentHandlers<'_>,
+) -> ElementContentHandlers<'_> {
+  let text = Rc::new(RefCell::new(String::new()));
+  let text_handler = text.clone();
+
+  handlers
+    .element(move |el: &mut Element| {
+      text.borrow_mut().clear();
+      let id = el.get_attribute("id");
+      let text = text.clone();
+      if let Some(end_tag_handlers) = el.end_tag_handlers() {
+        end_tag_handlers.push(Box::new(move |end: &mut EndTag| {
+          let anchor = match id {
+            Some(id) => format!(r##"<a class="anchor" href="#{}">#</a>"##, id),
+            None => {
+              let id = slugify(&text.borrow());
+              format!(r##"<a class="anchor" id="{}" href="#{}">#</a>"##, id, id)
+            }
+          };
+          end.before(&anchor, ContentType::Html);
+          Ok(())
+        }));
+      }
+      Ok(())
+    })
+    .text(move |chunk: &mut TextChunk| {
+      text_handler.borrow_mut().push_str(chunk.as_str());
+      Ok(())
+    })
+}
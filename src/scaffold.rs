@@ -0,0 +1,157 @@
+use crate::clip::available;
+use crate::frontmatter;
+use crate::listing;
+use crate::util::{slugify, today};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// The template of notes made without one of their own
+const TEMPLATE: &str = "---\ntitle: \"{{title}}\"\ndate: \"{{date}}\"\n---\n\n";
+
+/// Make a note, `notebook new NAME`, or a note from a template note,
+/// `notebook new --from TEMPLATE.md`
+///
+/// Placeholders such as `{{env}}`, in the front matter or the body, are filled
+/// from `--vars env=prod`, or else from the defaults of the template's `vars`
+/// front matter, which a `null` default makes required. `{{date}}` is today
+/// and `{{title}}` is the name given.
+pub fn new(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optopt("", "from", "template note to make the note from", "FILE");
+  opts.optmulti("", "vars", "value of a placeholder", "NAME=VALUE");
+  opts.optopt("d", "dir", "directory to write the note in", "DIR");
+  opts.optopt("o", "output", "file to write the note to", "FILE");
+  let matches = opts.parse(args)?;
+  let name = matches.free.first().cloned();
+  let (template, input) = match (matches.opt_str("from"), &name) {
+    (Some(template), _) => {
+      let input = fs::read_to_string(&template)?;
+      (PathBuf::from(template), input)
+    }
+    (None, Some(name)) => (PathBuf::from(name), TEMPLATE.to_string()),
+    (None, None) => {
+      let brief = "Usage: notebook new [NAME] [--from TEMPLATE.md] [options]";
+      print!("{}", opts.usage(brief));
+      return Ok(());
+    }
+  };
+
+  let (meta, _) = frontmatter::split(&input);
+  let mut vars = BTreeMap::new();
+  vars.insert("date".to_string(), Some(today()));
+  if let Some(name) = name {
+    vars.insert("title".to_string(), Some(name));
+  }
+  if let Some(Value::Object(defaults)) = meta.get("vars") {
+    for (name, value) in defaults {
+      let value = match value {
+        Value::Null => None,
+        Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+      };
+      vars.insert(name.clone(), value);
+    }
+  }
+  for definition in matches.opt_strs("vars") {
+    match definition.split_once('=') {
+      Some((name, value)) => {
+        vars.insert(name.trim().into(), Some(value.into()))
+      }
+      None => return Err(anyhow!("Invalid variable '{}'", definition)),
+    };
+  }
+
+  let note = fill(&without_vars(&input), &vars)?;
+  let (meta, body) = frontmatter::split(&note);
+  let path = match matches.opt_str("output") {
+    Some(path) => PathBuf::from(path),
+    None => {
+      let dir = PathBuf::from(matches.opt_str("dir").unwrap_or(".".into()));
+      let title =
+        listing::title(&meta, body).unwrap_or_else(|| listing::stem(&template));
+      fs::create_dir_all(&dir)?;
+      available(&dir, &slugify(&title))
+    }
+  };
+  if path.exists() {
+    return Err(anyhow!("Note '{}' already exists", path.display()));
+  }
+  fs::write(&path, note)?;
+  println!("{}", path.display());
+  Ok(())
+}
+
+/// Replace each `{{name}}` placeholder with its value
+///
+/// Anything else in braces, such as a `{{< shortcode >}}`, is left as it is.
+fn fill(
+  input: &str,
+  vars: &BTreeMap<String, Option<String>>,
+) -> Result<String> {
+  let mut output = String::with_capacity(input.len());
+  let mut rest = input;
+  while let Some(start) = rest.find(OPEN) {
+    output.push_str(&rest[..start]);
+    let after = &rest[start + OPEN.len()..];
+    let end = match after.find(CLOSE) {
+      Some(end) => end,
+      None => {
+        rest = &rest[start..];
+        break;
+      }
+    };
+    let name = after[..end].trim();
+    let is_name = !name.is_empty()
+      && name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if !is_name {
+      output.push_str(OPEN);
+      rest = after;
+      continue;
+    }
+    match vars.get(name) {
+      Some(Some(value)) => output.push_str(value),
+      _ => {
+        return Err(anyhow!(
+          "No value for '{}', given with --vars {}=VALUE",
+          name,
+          name
+        ))
+      }
+    }
+    rest = &after[end + CLOSE.len()..];
+  }
+  output.push_str(rest);
+  Ok(output)
+}
+
+/// A template note without the `vars` of its front matter, which only
+/// describe the template
+fn without_vars(input: &str) -> String {
+  let mut output = String::with_capacity(input.len());
+  let mut front = false;
+  let mut skipping = false;
+  for (i, line) in input.split_inclusive('\n').enumerate() {
+    let trimmed = line.trim_end();
+    if i == 0 && trimmed == "---" {
+      front = true;
+    } else if front && matches!(trimmed, "---" | "...") {
+      front = false;
+      skipping = false;
+    } else if front {
+      let nested = line.starts_with([' ', '\t']) || trimmed.is_empty();
+      skipping = line.starts_with("vars:") || (skipping && nested);
+    }
+    if !skipping {
+      output.push_str(line);
+    }
+  }
+  output
+}
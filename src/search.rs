@@ -0,0 +1,188 @@
+use crate::cache::Cache;
+use crate::diagram::pipe;
+use crate::frontmatter;
+use crate::listing;
+use crate::publish::Schedule;
+use crate::util::escape;
+use crate::vfs::Source;
+use anyhow::{anyhow, Result};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+/// Route of the search page of the web server
+pub const PATH: &str = "search";
+
+/// Notes found by meaning, after those found by their words
+const SIMILAR: usize = 10;
+
+/// A program writing the embedding vector of text given on standard input,
+/// as numbers separated by whitespace or commas, such as
+/// `--embed "llm embed -m 3-small"`
+///
+/// Vectors are kept in the cache directory, so notes are only embedded again
+/// once changed.
+#[derive(Clone)]
+pub struct Embedder {
+  command: Vec<String>,
+}
+
+impl Embedder {
+  pub fn new(command: &str) -> Result<Embedder> {
+    let words = command.split_whitespace().map(String::from).collect();
+    match words {
+      words if Vec::is_empty(&words) => {
+        Err(anyhow!("Invalid embedding program '{}'", command))
+      }
+      command => Ok(Embedder { command }),
+    }
+  }
+
+  /// The vector of some text
+  fn embed(&self, text: &str, cache: &Cache) -> Result<Vec<f32>> {
+    let kind = format!("embed {}", self.command.join(" "));
+    let output = cache.get(&kind, text, || {
+      pipe(&self.command[0], &self.command[1..], text)
+    })?;
+    let vector = output
+      .split(|c: char| c.is_whitespace() || matches!(c, ',' | '[' | ']'))
+      .filter(|number| !number.is_empty())
+      .map(str::parse)
+      .collect::<Result<Vec<f32>, _>>()
+      .map_err(|_| anyhow!("Invalid embedding from '{}'", self.command[0]))?;
+    match vector.is_empty() {
+      true => Err(anyhow!("Empty embedding from '{}'", self.command[0])),
+      false => Ok(vector),
+    }
+  }
+}
+
+/// A note that may be found
+struct Note {
+  path: PathBuf,
+  title: String,
+  body: String,
+}
+
+/// A page of the notes under a directory found by a search, those containing
+/// each of its words, and with an embedder, those closest in meaning
+pub fn page(
+  source: &dyn Source,
+  dir: &Path,
+  schedule: &Schedule,
+  query: &str,
+  embedder: Option<&Embedder>,
+  cache: &Cache,
+) -> Result<String> {
+  let mut page = String::from("# Search\n\n");
+  page.push_str(&format!(
+    "<form class=\"search\" action=\"{}\"><input type=\"search\" name=\"q\" \
+     value=\"{}\"></form>\n\n",
+    PATH,
+    escape(query)
+  ));
+  let query = query.trim();
+  if query.is_empty() {
+    return Ok(page);
+  }
+
+  let mut notes = vec![];
+  for path in source.files(dir)? {
+    let is_markdown =
+      path.extension().and_then(|ext| ext.to_str()) == Some("md");
+    if !is_markdown {
+      continue;
+    }
+    let text = source.read_to_string(&path)?;
+    let (meta, body) = frontmatter::split(&text);
+    if !schedule.is_published(&meta)? {
+      continue;
+    }
+    let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+    notes.push(Note {
+      title: listing::title(&meta, body)
+        .unwrap_or_else(|| listing::stem(&relative)),
+      body: body.to_string(),
+      path: relative,
+    });
+  }
+
+  // Words in a title count for more than those in the body
+  let words = query
+    .split_whitespace()
+    .map(str::to_lowercase)
+    .collect::<Vec<_>>();
+  let mut matches = notes
+    .iter()
+    .filter_map(|note| {
+      let title = note.title.to_lowercase();
+      let body = note.body.to_lowercase();
+      let mut score = 0;
+      for word in &words {
+        let count = title.matches(word.as_str()).count() * 10
+          + body.matches(word.as_str()).count();
+        if count == 0 {
+          return None;
+        }
+        score += count;
+      }
+      Some((score, note))
+    })
+    .collect::<Vec<_>>();
+  matches.sort_by(|(a, x), (b, y)| b.cmp(a).then(x.title.cmp(&y.title)));
+  page.push_str("## Matches\n\n");
+  page.push_str(&list(matches.iter().map(|(_, note)| *note)));
+
+  if let Some(embedder) = embedder {
+    let wanted = embedder.embed(query, cache)?;
+    let mut similar = vec![];
+    for note in &notes {
+      if matches.iter().any(|(_, found)| found.path == note.path) {
+        continue;
+      }
+      let text = format!("{}\n\n{}", note.title, note.body);
+      let vector = embedder.embed(&text, cache)?;
+      similar.push((cosine(&wanted, &vector), note));
+    }
+    similar
+      .sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    similar.truncate(SIMILAR);
+    page.push_str("## Similar notes\n\n");
+    page.push_str(&list(similar.iter().map(|(_, note)| *note)));
+  }
+  Ok(page)
+}
+
+/// HTML listing some notes
+fn list<'a>(notes: impl Iterator<Item = &'a Note>) -> String {
+  let mut html = String::from("<ul class=\"search-results\">\n");
+  let mut empty = true;
+  for note in notes {
+    let href = note.path.with_extension("html");
+    let href = href.to_string_lossy().replace('\\', "/");
+    html.push_str(&format!(
+      "<li><a href=\"{}\">{}</a></li>\n",
+      escape(&href),
+      escape(&note.title)
+    ));
+    empty = false;
+  }
+  html.push_str("</ul>\n\n");
+  match empty {
+    true => "<p class=\"search-results empty\">No notes found</p>\n\n".into(),
+    false => html,
+  }
+}
+
+/// The cosine similarity of two vectors, zero for vectors of different
+/// lengths, as from different models
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+  if a.len() != b.len() {
+    return 0.0;
+  }
+  let dot = a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+  let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+  match norm(a) * norm(b) {
+    norms if norms > 0.0 => dot / norms,
+    _ => 0.0,
+  }
+}
@@ -0,0 +1,204 @@
+use crate::events::Events;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+const MASK: &str = "[REDACTED]";
+
+/// What to do when a secret is found
+#[derive(Clone, Copy)]
+pub enum Mode {
+  Fail,
+  Warn,
+  Mask,
+}
+
+#[derive(Clone)]
+enum Rule {
+  /// Tokens starting with a known prefix, such as `ghp_`
+  Prefix(String),
+  /// Email addresses
+  Email,
+  /// Long random looking tokens
+  Entropy,
+}
+
+/// Scans documents for credentials and other secrets before publishing
+#[derive(Clone)]
+pub struct Scanner {
+  mode: Mode,
+  rules: Vec<(String, Rule)>,
+}
+
+impl Scanner {
+  /// Create a scanner with the built in rules and any `NAME=PREFIX` rules
+  pub fn new(mode: &str, rules: &[String]) -> Result<Scanner> {
+    let mode = match mode {
+      "fail" => Mode::Fail,
+      "warn" => Mode::Warn,
+      "mask" => Mode::Mask,
+      _ => return Err(anyhow!("Unknown secrets mode '{}'", mode)),
+    };
+
+    let mut builtin = vec![
+      ("aws-access-key", Rule::Prefix("AKIA".into())),
+      ("github-token", Rule::Prefix("ghp_".into())),
+      ("github-token", Rule::Prefix("github_pat_".into())),
+      ("slack-token", Rule::Prefix("xoxb-".into())),
+      ("slack-token", Rule::Prefix("xoxp-".into())),
+      ("api-key", Rule::Prefix("sk-".into())),
+      ("email", Rule::Email),
+      ("high-entropy", Rule::Entropy),
+    ]
+    .into_iter()
+    .map(|(name, rule)| (name.to_string(), rule))
+    .collect::<Vec<_>>();
+
+    for rule in rules {
+      match rule.find('=') {
+        Some(i) => builtin.push((
+          rule[..i].trim().into(),
+          Rule::Prefix(rule[i + 1..].trim().into()),
+        )),
+        None => return Err(anyhow!("Invalid secret rule '{}'", rule)),
+      }
+    }
+
+    Ok(Scanner {
+      mode,
+      rules: builtin,
+    })
+  }
+
+  /// Scan a document, failing, warning or masking each secret found
+  ///
+  /// Private key blocks are always treated as secrets.
+  pub fn apply(
+    &self,
+    document: &str,
+    input: &str,
+    events: &Events,
+  ) -> Result<String> {
+    let (output, found) = self.scan(input);
+    for (line, name) in &found {
+      let message =
+        format!("Possible {} in {} on line {}", name, document, line);
+      match self.mode {
+        Mode::Fail => return Err(anyhow!("{}", message)),
+        _ => events.warn(&message),
+      }
+    }
+    Ok(output)
+  }
+
+  /// Some text with its secrets masked when masking, without warning of them,
+  /// for the other outputs of a document that [`Scanner::apply`] scanned
+  pub fn mask(&self, input: &str) -> String {
+    self.scan(input).0
+  }
+
+  /// Some text, masked when masking, and the lines and kinds of its secrets
+  fn scan(&self, input: &str) -> (String, Vec<(usize, String)>) {
+    let mut output = String::with_capacity(input.len());
+    let mut found = vec![];
+    let mut private_key = false;
+
+    for (number, line) in input.split_inclusive('\n').enumerate() {
+      if line.contains("-----BEGIN") && line.contains("PRIVATE KEY-----") {
+        found.push((number + 1, "private-key".to_string()));
+        private_key = true;
+      }
+      if private_key {
+        private_key = !line.contains("-----END");
+        if let Mode::Mask = self.mode {
+          if !private_key {
+            output.push_str(MASK);
+            output.push('\n');
+          }
+          continue;
+        }
+        output.push_str(line);
+        continue;
+      }
+
+      let mut rest = line;
+      while let Some(start) = rest.find(is_token) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_token(c)).unwrap_or(rest.len());
+        // Trailing punctuation ends sentences rather than tokens
+        let token = rest[..end].trim_end_matches(['.', '-']);
+        let end = token.len().max(1);
+        let token = &rest[..end];
+        match self.matches(token) {
+          Some(name) => {
+            found.push((number + 1, name.to_string()));
+            match self.mode {
+              Mode::Mask => output.push_str(MASK),
+              _ => output.push_str(token),
+            }
+          }
+          None => output.push_str(token),
+        }
+        rest = &rest[end..];
+      }
+      output.push_str(rest);
+    }
+    (output, found)
+  }
+
+  fn matches(&self, token: &str) -> Option<&str> {
+    self
+      .rules
+      .iter()
+      .find(|(_, rule)| match rule {
+        Rule::Prefix(prefix) => {
+          token.starts_with(prefix.as_str()) && token.len() >= prefix.len() + 8
+        }
+        Rule::Email => is_email(token),
+        Rule::Entropy => is_random(token),
+      })
+      .map(|(name, _)| name.as_str())
+  }
+}
+
+fn is_token(c: char) -> bool {
+  c.is_ascii_alphanumeric() || "_-+/.@".contains(c)
+}
+
+fn is_email(token: &str) -> bool {
+  match token.split_once('@') {
+    Some((user, domain)) => {
+      !user.is_empty()
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.contains('@')
+    }
+    None => false,
+  }
+}
+
+/// Whether a token is long, mixes letters and digits and has high entropy
+fn is_random(token: &str) -> bool {
+  if token.len() < 24 || token.contains(['.', '@']) {
+    return false;
+  }
+  let digits = token.chars().any(|c| c.is_ascii_digit());
+  let letters = token.chars().any(|c| c.is_ascii_alphabetic());
+  if !digits || !letters {
+    return false;
+  }
+
+  let mut counts = HashMap::new();
+  for c in token.chars() {
+    *counts.entry(c).or_insert(0) += 1;
+  }
+  let len = token.len() as f64;
+  let entropy: f64 = counts
+    .values()
+    .map(|count| {
+      let p = *count as f64 / len;
+      -p * p.log2()
+    })
+    .sum();
+  entropy >= 4.0
+}
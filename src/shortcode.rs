@@ -0,0 +1,183 @@
+use crate::code;
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+
+const OPEN: &str = "{{<";
+const CLOSE: &str = ">}}";
+
+/// Handlebars partials expanded from `{{< name key="value" >}}` shortcodes
+#[derive(Clone)]
+pub struct Shortcodes {
+  templates: BTreeMap<String, String>,
+}
+
+impl Shortcodes {
+  /// Load the built in shortcodes along with any `.hbs` files in a directory
+  pub fn load(dir: Option<&str>) -> Result<Shortcodes> {
+    let mut templates = BTreeMap::new();
+    templates.insert(
+      "figure".into(),
+      include_str!("shortcodes/figure.hbs").into(),
+    );
+
+    if let Some(dir) = dir {
+      for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("hbs") {
+          continue;
+        }
+        if let Some(name) = path.file_stem() {
+          let name = name.to_string_lossy().to_string();
+          templates.insert(name, fs::read_to_string(&path)?);
+        }
+      }
+    }
+
+    Ok(Shortcodes { templates })
+  }
+
+  /// Expand all shortcodes in the markdown input
+  ///
+  /// Shortcodes are either self closing, or paired with a `{{< /name >}}`
+  /// closing tag, in which case the enclosed text is available to the partial
+  /// as `inner`. Writing `{{</* name */>}}` outputs the shortcode literally.
+  /// Shortcodes in code blocks and code spans are left as they are.
+  pub fn expand(&self, input: &str) -> Result<String> {
+    let mut registry = handlebars::Handlebars::new();
+    for (name, template) in &self.templates {
+      registry.register_template_string(name, template)?;
+    }
+    let (prose, code) = code::set_aside(input);
+    let output = self.expand_with(&registry, &prose, &code)?;
+    Ok(code::restore(&output, &code))
+  }
+
+  fn expand_with(
+    &self,
+    registry: &handlebars::Handlebars,
+    input: &str,
+    code: &[String],
+  ) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find(OPEN) {
+      output.push_str(&rest[..start]);
+      rest = &rest[start + OPEN.len()..];
+
+      if rest.starts_with("/*") {
+        let end = match rest.find(&format!("*/{}", CLOSE)) {
+          Some(end) => end,
+          None => return Err(anyhow!("Unterminated shortcode comment")),
+        };
+        output.push_str(OPEN);
+        output.push_str(&rest[2..end]);
+        output.push_str(CLOSE);
+        rest = &rest[end + 2 + CLOSE.len()..];
+        continue;
+      }
+
+      let end = match rest.find(CLOSE) {
+        Some(end) => end,
+        None => return Err(anyhow!("Unterminated shortcode")),
+      };
+      let (name, mut context) = parse_tag(&code::restore(&rest[..end], code))?;
+      rest = &rest[end + CLOSE.len()..];
+
+      if !self.templates.contains_key(&name) {
+        return Err(anyhow!("Unknown shortcode '{}'", name));
+      }
+
+      if let Some((inner_end, close_end)) = find_closing(rest, &name) {
+        let inner = self.expand_with(registry, &rest[..inner_end], code)?;
+        let inner = code::restore(&inner, code);
+        context.insert("inner".into(), Value::String(inner));
+        rest = &rest[close_end..];
+      }
+
+      let rendered = registry.render(&name, &Value::Object(context))?;
+      output.push_str(rendered.trim_end());
+    }
+
+    output.push_str(rest);
+    Ok(output)
+  }
+}
+
+/// Find the byte range of a `{{< /name >}}` closing tag
+fn find_closing(input: &str, name: &str) -> Option<(usize, usize)> {
+  let mut offset = 0;
+  while let Some(start) = input[offset..].find(OPEN) {
+    let start = offset + start;
+    let tag = &input[start + OPEN.len()..];
+    let end = tag.find(CLOSE)?;
+    let close_end = start + OPEN.len() + end + CLOSE.len();
+    match tag[..end].trim().strip_prefix('/') {
+      Some(closing) if closing.trim() == name => {
+        return Some((start, close_end));
+      }
+      _ => offset = close_end,
+    }
+  }
+  None
+}
+
+/// Parse the name and `key="value"` arguments of a shortcode tag
+pub fn parse_tag(tag: &str) -> Result<(String, Map<String, Value>)> {
+  let tag = tag.trim();
+  let (name, mut rest) = match tag.find(char::is_whitespace) {
+    Some(i) => (&tag[..i], tag[i..].trim_start()),
+    None => (tag, ""),
+  };
+
+  if name.is_empty() || name.starts_with('/') {
+    return Err(anyhow!("Unexpected shortcode '{}'", tag));
+  }
+
+  let mut args = Map::new();
+  while !rest.is_empty() {
+    let eq = match rest.find('=') {
+      Some(eq) => eq,
+      None => return Err(anyhow!("Invalid argument in shortcode '{}'", tag)),
+    };
+    let key = rest[..eq].trim();
+    let value = &rest[eq + 1..];
+    let (value, remaining) = match value.strip_prefix('"') {
+      Some(value) => match value.find('"') {
+        Some(end) => (&value[..end], &value[end + 1..]),
+        None => return Err(anyhow!("Unterminated string in '{}'", tag)),
+      },
+      None => match value.find(char::is_whitespace) {
+        Some(end) => (&value[..end], &value[end..]),
+        None => (value, ""),
+      },
+    };
+    args.insert(key.into(), Value::String(value.into()));
+    rest = remaining.trim_start();
+  }
+
+  Ok((name.into(), args))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn shortcodes_in_code_are_left_alone() {
+    let shortcodes = Shortcodes::load(None).unwrap();
+    let input = "```\n{{< youtube id=\"abc\" >}}\n```\n\n\
+                 Use `{{< figure src=\"a.png\" >}}` for figures.\n";
+    assert_eq!(shortcodes.expand(input).unwrap(), input);
+  }
+
+  #[test]
+  fn shortcodes_in_prose_are_expanded() {
+    let shortcodes = Shortcodes::load(None).unwrap();
+    let output = shortcodes.expand("{{< figure src=\"a.png\" >}}\n").unwrap();
+    assert!(output.contains("a.png"));
+    assert!(!output.contains("{{<"));
+  }
+}
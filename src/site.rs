@@ -0,0 +1,31 @@
+use crate::paths;
+use pulldown_cmark::{CowStr, Event, Tag};
+
+/// Point links between notes at the pages they are rendered to, replacing
+/// the `.md` extension of relative links with `.html`, and any Windows `\`
+/// separators with `/`
+pub fn link(event: Event) -> Event {
+  match event {
+    Event::Start(Tag::Link(kind, dest, title)) => {
+      Event::Start(Tag::Link(kind, href(dest), title))
+    }
+    Event::End(Tag::Link(kind, dest, title)) => {
+      Event::End(Tag::Link(kind, href(dest), title))
+    }
+    event => event,
+  }
+}
+
+fn href(dest: CowStr) -> CowStr {
+  let end = dest.find(['#', '?']).unwrap_or(dest.len());
+  let (path, rest) = dest.split_at(end);
+  if paths::is_external(path) {
+    return dest;
+  }
+  match paths::strip_extension(path, "md") {
+    Some(stem) if !stem.is_empty() => {
+      format!("{}.html{}", paths::slashes(stem), rest).into()
+    }
+    _ => dest,
+  }
+}
@@ -0,0 +1,55 @@
+use crate::events::Events;
+use crate::fixture::diff;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Component, Path};
+
+/// Compare a document's rendered content with its snapshot in a directory
+///
+/// Missing snapshots are written, as are changed ones when `update` is set.
+/// Otherwise a change fails the build with a diff of the content.
+pub fn check(
+  dir: &str,
+  document: &str,
+  content: &str,
+  update: bool,
+  events: &Events,
+) -> Result<()> {
+  let name = Path::new(document)
+    .with_extension("html")
+    .components()
+    .filter_map(|component| match component {
+      Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+      _ => None,
+    })
+    .collect::<Vec<_>>()
+    .join("__");
+  let path = Path::new(dir).join(name);
+  let actual = canonical(content);
+
+  match fs::read_to_string(&path) {
+    Ok(expected) if expected == actual => Ok(()),
+    Ok(expected) if !update => Err(anyhow!(
+      "Snapshot {} changed:\n{}",
+      path.display(),
+      diff(&expected, &actual)
+    )),
+    _ => {
+      fs::create_dir_all(dir)?;
+      fs::write(&path, actual)?;
+      events.info(&format!("Wrote snapshot {}", path.display()));
+      Ok(())
+    }
+  }
+}
+
+/// Content without trailing whitespace, so that snapshots are stable
+fn canonical(content: &str) -> String {
+  let mut canonical = content
+    .lines()
+    .map(str::trim_end)
+    .collect::<Vec<_>>()
+    .join("\n");
+  canonical.push('\n');
+  canonical
+}
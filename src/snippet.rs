@@ -0,0 +1,184 @@
+use crate::code;
+use crate::util::is_markdown;
+use crate::vfs::Source;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const OPEN: &str = "{{snippet ";
+const CLOSE: &str = "}}";
+
+/// Named snippets defined with `:::snippet id=NAME` containers
+#[derive(Default)]
+pub struct Snippets {
+  /// The definitions of each id, with the notes defining them
+  bodies: BTreeMap<String, Vec<(PathBuf, String)>>,
+}
+
+/// The snippets of the notes of each directory, collected once for a build
+/// of many notes, and only once a note refers to one
+#[derive(Clone, Default)]
+pub struct Cache {
+  collected: Arc<Mutex<BTreeMap<PathBuf, Arc<Snippets>>>>,
+}
+
+impl Cache {
+  /// Remove snippet markers from a document and expand its references to
+  /// the snippets of the notes in a directory and its children
+  pub fn expand(
+    &self,
+    source: &dyn Source,
+    dir: &Path,
+    path: &Path,
+    input: &str,
+  ) -> Result<String> {
+    let snippets = match input.contains(OPEN) {
+      true => self.collect(source, dir)?,
+      false => Default::default(),
+    };
+    snippets.expand(path, input)
+  }
+
+  fn collect(&self, source: &dyn Source, dir: &Path) -> Result<Arc<Snippets>> {
+    let mut collected = self.collected.lock().expect("Snippets lock");
+    if let Some(snippets) = collected.get(dir) {
+      return Ok(snippets.clone());
+    }
+    let snippets = Arc::new(Snippets::collect(source, dir)?);
+    collected.insert(dir.into(), snippets.clone());
+    Ok(snippets)
+  }
+}
+
+impl Snippets {
+  /// Collect the snippets defined by notes in a directory and its children
+  pub fn collect(source: &dyn Source, dir: &Path) -> Result<Snippets> {
+    let mut bodies: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    let files = source.files(dir)?;
+    for path in files.iter().filter(|path| is_markdown(path)) {
+      let text = source.read_to_string(path)?;
+      for (id, body) in parse(&text).0 {
+        bodies.entry(id).or_default().push((path.clone(), body));
+      }
+    }
+    Ok(Snippets { bodies })
+  }
+
+  /// Remove snippet markers from a document and expand its references
+  ///
+  /// Snippets defined by the document itself take precedence over the
+  /// collected ones.
+  pub fn expand(&self, path: &Path, input: &str) -> Result<String> {
+    let (local, stripped) = parse(input);
+    if !stripped.contains(OPEN) {
+      return Ok(stripped);
+    }
+    let mut bodies = self.bodies.clone();
+    for (id, body) in local {
+      bodies.insert(id, vec![(path.into(), body)]);
+    }
+    let snippets = Snippets { bodies };
+    snippets.expand_with(&stripped, &mut vec![])
+  }
+
+  fn expand_with(
+    &self,
+    input: &str,
+    stack: &mut Vec<String>,
+  ) -> Result<String> {
+    let mut error = None;
+    let output = code::map_prose(input, |text| {
+      let mut output = String::with_capacity(text.len());
+      let mut rest = text;
+      while let Some(start) = rest.find(OPEN).filter(|_| error.is_none()) {
+        output.push_str(&rest[..start]);
+        let reference = &rest[start + OPEN.len()..];
+        let end = match reference.find(CLOSE) {
+          Some(end) => end,
+          None => {
+            error = Some(anyhow!("Unterminated snippet reference"));
+            break;
+          }
+        };
+        let id = reference[..end].trim();
+        match self.resolve(id, stack) {
+          Ok(body) => output.push_str(&body),
+          Err(err) => error = Some(err),
+        }
+        rest = &reference[end + CLOSE.len()..];
+      }
+      output.push_str(rest);
+      output
+    });
+    match error {
+      Some(error) => Err(error),
+      None => Ok(output),
+    }
+  }
+
+  fn resolve(&self, id: &str, stack: &mut Vec<String>) -> Result<String> {
+    if stack.iter().any(|other| other == id) {
+      stack.push(id.into());
+      return Err(anyhow!("Snippet cycle: {}", stack.join(" -> ")));
+    }
+    let body = match self.bodies.get(id).map(Vec::as_slice) {
+      Some([(_, body)]) => body,
+      Some([(first, _), (second, _), ..]) => {
+        return Err(anyhow!(
+          "Snippet '{}' is defined in both {} and {}",
+          id,
+          first.display(),
+          second.display()
+        ))
+      }
+      _ => return Err(anyhow!("Unknown snippet '{}'", id)),
+    };
+    stack.push(id.into());
+    let expanded = self.expand_with(body, stack)?;
+    stack.pop();
+    Ok(expanded.trim_end().into())
+  }
+}
+
+/// Extract snippet definitions, returning them with the markers removed
+fn parse(input: &str) -> (BTreeMap<String, String>, String) {
+  let mut snippets = BTreeMap::new();
+  let mut stripped = String::with_capacity(input.len());
+  // Each open container, with the id of the snippet it defines
+  let mut frames: Vec<Option<(String, String)>> = vec![];
+
+  for (code, line) in code::lines(input) {
+    let directive = match line.trim().strip_prefix(":::") {
+      Some(directive) if !code => Some(directive.trim()),
+      _ => None,
+    };
+
+    match directive {
+      Some(directive) if directive.starts_with("snippet ") => {
+        let id = directive
+          .split_whitespace()
+          .find_map(|arg| arg.strip_prefix("id="))
+          .unwrap_or("")
+          .trim_matches('"');
+        frames.push(Some((id.into(), String::new())));
+        continue;
+      }
+      Some("") => {
+        if let Some(Some((id, body))) = frames.pop() {
+          snippets.insert(id, body);
+          continue;
+        }
+      }
+      Some(_) => frames.push(None),
+      None => {}
+    }
+
+    for frame in frames.iter_mut().flatten() {
+      frame.1.push_str(line);
+    }
+    stripped.push_str(line);
+  }
+
+  (snippets, stripped)
+}
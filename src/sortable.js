@@ -0,0 +1,40 @@
+(function () {
+  var text = function (row, index) {
+    var cell = row.cells[index];
+    return cell ? cell.textContent.trim() : "";
+  };
+  var compare = function (a, b) {
+    var x = parseFloat(a), y = parseFloat(b);
+    if (!isNaN(x) && !isNaN(y)) return x - y;
+    return a.localeCompare(b);
+  };
+  document.querySelectorAll("table.sortable").forEach(function (table) {
+    var body = table.tBodies[0];
+    if (!body || !table.tHead) return;
+    var rows = table.tHead.rows;
+    Array.prototype.forEach.call(rows[rows.length - 1].cells, function (th, index) {
+      var ascending = true;
+      th.style.cursor = "pointer";
+      th.addEventListener("click", function () {
+        var sorted = Array.prototype.slice.call(body.rows).sort(function (a, b) {
+          var order = compare(text(a, index), text(b, index));
+          return ascending ? order : -order;
+        });
+        ascending = !ascending;
+        sorted.forEach(function (row) { body.appendChild(row); });
+      });
+    });
+    var filter = document.createElement("input");
+    filter.type = "search";
+    filter.className = "table-filter";
+    filter.placeholder = "Filter";
+    filter.addEventListener("input", function () {
+      var query = filter.value.toLowerCase();
+      Array.prototype.forEach.call(body.rows, function (row) {
+        var match = row.textContent.toLowerCase().indexOf(query) !== -1;
+        row.style.display = match ? "" : "none";
+      });
+    });
+    table.parentNode.insertBefore(filter, table);
+  });
+})();
@@ -0,0 +1,126 @@
+use crate::assets::{self, Assets};
+use crate::events::Events;
+use crate::util::encode_base64;
+use crate::vfs::Source;
+use anyhow::Result;
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{ElementContentHandlers, HtmlRewriter, Selector, Settings};
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Make a rendered document self contained, for emailing or archiving
+///
+/// Local images, media, icons, stylesheets and scripts, whether assets of the
+/// document or files beside it, are inlined as data URIs, `<style>` and
+/// `<script>` elements. Files that can't be read, and anything from another
+/// site, are left as they are with a warning.
+pub fn inline(
+  html: &str,
+  source: &dyn Source,
+  dir: &Path,
+  assets: &Assets,
+  events: &Events,
+) -> Result<String> {
+  let read = |url: &str| -> Option<Vec<u8>> {
+    if !is_local(url) {
+      events.warn(&format!("'{}' is not inlined", url));
+      return None;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let asset = assets.files().find(|(asset, _)| path.ends_with(asset));
+    let contents = match asset {
+      Some((_, contents)) => Some(contents.to_vec()),
+      None => source.read(&dir.join(path)).ok(),
+    };
+    if contents.is_none() {
+      events.warn(&format!("'{}' could not be inlined", url));
+    }
+    contents
+  };
+  let data_uri = |el: &mut Element, attr: &str| {
+    let url = el.get_attribute(attr).unwrap_or_default();
+    if url.starts_with("data:") {
+      return Ok(());
+    }
+    if let Some(contents) = read(&url) {
+      let kind = assets::content_type(&url);
+      let uri = format!("data:{};base64,{}", kind, encode_base64(&contents));
+      el.set_attribute(attr, &uri)?;
+      // Other sizes of an image would be fetched in its place
+      el.remove_attribute("srcset");
+    }
+    Ok(())
+  };
+
+  let handlers = vec![
+    (
+      "img[src], source[src], video[src], audio[src]",
+      ElementContentHandlers::default()
+        .element(|el: &mut Element| data_uri(el, "src")),
+    ),
+    (
+      "video[poster]",
+      ElementContentHandlers::default()
+        .element(|el: &mut Element| data_uri(el, "poster")),
+    ),
+    (
+      "link[rel~=icon][href], link[rel=manifest][href]",
+      ElementContentHandlers::default()
+        .element(|el: &mut Element| data_uri(el, "href")),
+    ),
+    (
+      "link[rel~=stylesheet][href]",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        let href = el.get_attribute("href").unwrap_or_default();
+        if let Some(css) = read(&href) {
+          let css = String::from_utf8_lossy(&css).replace("</", "<\\/");
+          el.replace(&format!("<style>\n{}</style>", css), ContentType::Html);
+        }
+        Ok(())
+      }),
+    ),
+    // Nothing is fetched early when there is nothing to fetch
+    (
+      "link[rel=preload]",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        el.remove();
+        Ok(())
+      }),
+    ),
+    (
+      "script[src]",
+      ElementContentHandlers::default().element(|el: &mut Element| {
+        let src = el.get_attribute("src").unwrap_or_default();
+        if let Some(code) = read(&src) {
+          let code = String::from_utf8_lossy(&code).replace("</", "<\\/");
+          el.remove_attribute("src");
+          el.remove_attribute("defer");
+          el.set_inner_content(&code, ContentType::Html);
+        }
+        Ok(())
+      }),
+    ),
+  ];
+
+  let mut settings = Settings::new();
+  for (selector, handler) in handlers {
+    let selector = selector.parse::<Selector>()?;
+    settings =
+      settings.append_element_content_handler((Cow::Owned(selector), handler));
+  }
+  let mut output = vec![];
+  let mut rewriter =
+    HtmlRewriter::new(settings, |chunk: &[u8]| output.extend_from_slice(chunk));
+  rewriter.write(html.as_bytes())?;
+  rewriter.end()?;
+  Ok(String::from_utf8(output)?)
+}
+
+/// Whether a URL names a file of the notebook rather than another site
+fn is_local(url: &str) -> bool {
+  let scheme = url
+    .split('/')
+    .next()
+    .is_some_and(|first| first.contains(':'));
+  !url.is_empty() && !url.starts_with("//") && !scheme
+}
@@ -0,0 +1,118 @@
+use crate::data;
+use anyhow::{anyhow, Result};
+use handlebars::{
+  Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext,
+};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The language strings are looked up in when a document's is missing one
+const FALLBACK: &str = "en";
+
+/// Text for templates in each language, used with `{{t "key"}}`
+///
+/// Each file in a strings directory holds the strings of the language it is
+/// named after, such as `fr.toml` or `pt-br.yaml`. Keys may be nested and are
+/// then looked up with dots, as in `{{t "recipe.method"}}`.
+#[derive(Clone)]
+pub struct Strings {
+  languages: BTreeMap<String, Map<String, Value>>,
+}
+
+impl Strings {
+  /// Load the built in English strings, then the files of each directory
+  /// over them
+  pub fn load(dirs: &[String]) -> Result<Strings> {
+    let mut languages = BTreeMap::new();
+    let english = toml::from_str(include_str!("strings/en.toml"))?;
+    languages.insert(FALLBACK.to_string(), english);
+
+    for dir in dirs {
+      for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let lang = match path.file_stem() {
+          Some(stem) => stem.to_string_lossy().to_lowercase(),
+          None => continue,
+        };
+        let strings = match data::parse(&path)? {
+          Value::Object(strings) => strings,
+          _ => return Err(anyhow!("Invalid strings '{}'", path.display())),
+        };
+        let existing: &mut Map<String, Value> =
+          languages.entry(lang).or_default();
+        existing.extend(strings);
+      }
+    }
+    Ok(Strings { languages })
+  }
+
+  /// The strings directory shipped with a theme's layouts, if it has one
+  pub fn theme(layouts: &str) -> Option<String> {
+    let dir = Path::new(layouts).join("strings");
+    match dir.is_dir() {
+      true => Some(dir.to_string_lossy().to_string()),
+      false => None,
+    }
+  }
+
+  /// Register the `t` helper, looking strings up in a language, then in the
+  /// language without its region, then in English
+  pub fn register(&self, registry: &mut Handlebars, lang: &str) {
+    let lang = lang.to_lowercase();
+    let mut chain = vec![lang.clone()];
+    if let Some((base, _)) = lang.split_once('-') {
+      chain.push(base.to_string());
+    }
+    chain.push(FALLBACK.to_string());
+    let chain = chain
+      .iter()
+      .filter_map(|lang| self.languages.get(lang).cloned())
+      .collect();
+    registry.register_helper("t", Box::new(Translate { chain }));
+  }
+}
+
+/// The string of a key, with any `{name}` replaced by the `name=value`
+/// parameters of the helper, or the key itself when it has no string
+struct Translate {
+  chain: Vec<Map<String, Value>>,
+}
+
+impl Translate {
+  fn lookup(&self, key: &str) -> Option<String> {
+    self.chain.iter().find_map(|strings| {
+      let mut parts = key.split('.');
+      let first = strings.get(parts.next()?)?;
+      let value = parts.try_fold(first, |value, part| value.get(part))?;
+      value.as_str().map(String::from)
+    })
+  }
+}
+
+impl HelperDef for Translate {
+  fn call<'reg: 'rc, 'rc>(
+    &self,
+    h: &Helper<'reg, 'rc>,
+    _: &'reg Handlebars<'reg>,
+    _: &'rc Context,
+    _: &mut RenderContext<'reg, 'rc>,
+    out: &mut dyn Output,
+  ) -> HelperResult {
+    let key = h
+      .param(0)
+      .and_then(|key| key.value().as_str())
+      .unwrap_or("");
+    let mut text = self.lookup(key).unwrap_or_else(|| key.to_string());
+    for (name, value) in h.hash() {
+      let value = match value.value() {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+      };
+      text = text.replace(&format!("{{{}}}", name), &value);
+    }
+    out.write(&handlebars::html_escape(&text))?;
+    Ok(())
+  }
+}
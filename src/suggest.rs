@@ -0,0 +1,254 @@
+use crate::code;
+use crate::frontmatter;
+use crate::listing;
+use crate::people::relative;
+use crate::util::markdown_files;
+use crate::vfs::Source;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Titles shorter than this are too likely to be ordinary words
+const SHORTEST: usize = 3;
+
+/// A link proposed for some text of a note
+struct Suggestion {
+  line: usize,
+  text: String,
+  href: String,
+}
+
+/// Propose links to other notes whose titles or `aliases` appear as plain
+/// text in a note, adding them to the note with `--apply`
+pub fn suggest(args: &[String]) -> Result<()> {
+  let mut opts = getopts::Options::new();
+  opts.optopt(
+    "d",
+    "dir",
+    "notes to link to, defaults to the note's",
+    "DIR",
+  );
+  opts.optflag("", "apply", "add the suggested links to the note");
+  let matches = opts.parse(args)?;
+
+  let file = match matches.free.as_slice() {
+    [file] => Path::new(file),
+    _ => {
+      let brief = "Usage: notebook suggest FILE [--dir DIR] [--apply]";
+      print!("{}", opts.usage(brief));
+      return Ok(());
+    }
+  };
+  let here = file.parent().unwrap_or(Path::new(""));
+  let dir = match matches.opt_str("dir") {
+    Some(dir) => PathBuf::from(dir),
+    None if here.as_os_str().is_empty() => PathBuf::from("."),
+    None => here.to_path_buf(),
+  };
+
+  let input = fs::read_to_string(file)?;
+  let (_, body) = frontmatter::split(&input);
+  let start = input.len() - body.len();
+  let titles = titles(&dir, file)?;
+  let (linked, suggestions) = link(body, &titles, here);
+
+  let skipped = input[..start].lines().count();
+  for suggestion in &suggestions {
+    println!(
+      "{}:{}: {} -> {}",
+      file.display(),
+      skipped + suggestion.line,
+      suggestion.text,
+      suggestion.href
+    );
+  }
+  if matches.opt_present("apply") && !suggestions.is_empty() {
+    fs::write(file, format!("{}{}", &input[..start], linked))?;
+    eprintln!("Linked {} notes in '{}'", suggestions.len(), file.display());
+  }
+  Ok(())
+}
+
+/// The titles and aliases of the notes in a directory other than a document,
+/// longest first so that longer titles are matched before those inside them
+fn titles(dir: &Path, document: &Path) -> Result<Vec<(String, PathBuf)>> {
+  let mut titles = vec![];
+  for path in markdown_files(dir)? {
+    if same_file(&path, document) || listing::is_index(&path.to_string_lossy())
+    {
+      continue;
+    }
+    let input = fs::read_to_string(&path)?;
+    let (meta, body) = frontmatter::split(&input);
+    for title in note_titles(&meta, body) {
+      titles.push((title, path.clone()));
+    }
+  }
+  titles.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+  Ok(titles)
+}
+
+/// The title and aliases of a note that are long enough to look for
+fn note_titles(meta: &Value, body: &str) -> Vec<String> {
+  let aliases = match meta.get("aliases") {
+    Some(Value::Array(aliases)) => aliases.clone(),
+    Some(Value::String(alias)) => vec![alias.as_str().into()],
+    _ => vec![],
+  };
+  // Aliases that are old pages of the note are not titles
+  let aliases = aliases.into_iter().filter_map(|alias| match alias {
+    Value::String(alias) if !alias.ends_with(".html") => Some(alias),
+    _ => None,
+  });
+  listing::title(meta, body)
+    .into_iter()
+    .chain(aliases)
+    .map(|title| title.trim().to_string())
+    .filter(|title| title.chars().count() >= SHORTEST)
+    .collect()
+}
+
+/// The other notes under a directory that mention a document's title or
+/// aliases as plain text without linking to it, as `title` and `href`
+pub fn unlinked_mentions(
+  source: &dyn Source,
+  root: &Path,
+  document: &Path,
+  meta: &Value,
+  body: &str,
+) -> Result<Vec<Value>> {
+  let mut titles = note_titles(meta, body)
+    .into_iter()
+    .map(|title| (title, document.to_path_buf()))
+    .collect::<Vec<_>>();
+  titles.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+  if titles.is_empty() {
+    return Ok(vec![]);
+  }
+  let here = document.parent().unwrap_or(Path::new(""));
+
+  let mut mentions = vec![];
+  for path in source.files(root)? {
+    let is_markdown =
+      path.extension().and_then(|ext| ext.to_str()) == Some("md");
+    if !is_markdown || same_file(&path, document) {
+      continue;
+    }
+    let text = source.read_to_string(&path)?;
+    let (note, other) = frontmatter::split(&text);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let (_, suggestions) = link(other, &titles, dir);
+    if suggestions.is_empty() {
+      continue;
+    }
+    let title =
+      listing::title(&note, other).unwrap_or_else(|| listing::stem(&path));
+    mentions.push(json!({
+      "title": title,
+      "href": relative(here, &path.with_extension("html")),
+    }));
+  }
+  Ok(mentions)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+  match (a.canonicalize(), b.canonicalize()) {
+    (Ok(a), Ok(b)) => a == b,
+    _ => a == b,
+  }
+}
+
+/// Link the first plain text occurrence of each note's titles in some
+/// markdown, leaving out headings, code, links and notes already linked to
+fn link(
+  body: &str,
+  titles: &[(String, PathBuf)],
+  dir: &Path,
+) -> (String, Vec<Suggestion>) {
+  let mut linked: Vec<PathBuf> = titles
+    .iter()
+    .filter(|(_, path)| {
+      let href = relative(dir, &path.with_extension("html"));
+      body.contains(&format!("]({}", href))
+    })
+    .map(|(_, path)| path.clone())
+    .collect();
+  let mut suggestions = vec![];
+  let mut output = String::with_capacity(body.len());
+
+  for (number, (code, line)) in code::lines(body).enumerate() {
+    if code || line.trim_start().starts_with('#') {
+      output.push_str(line);
+      continue;
+    }
+
+    let protected = protected(line);
+    let mut rest = 0;
+    let mut boundary = true;
+    for (start, c) in line.char_indices() {
+      let is_word = c.is_alphanumeric();
+      let starts_word = boundary && is_word && start >= rest;
+      boundary = !is_word;
+      if !starts_word
+        || protected.iter().any(|(a, b)| (*a..*b).contains(&start))
+      {
+        continue;
+      }
+      let found = titles.iter().find(|(title, path)| {
+        let end = start + title.len();
+        let ends_word = line
+          .get(end..)
+          .and_then(|after| after.chars().next())
+          .is_none_or(|c| !c.is_alphanumeric());
+        !linked.contains(path)
+          && ends_word
+          && line
+            .get(start..end)
+            .is_some_and(|text| text.to_lowercase() == title.to_lowercase())
+          && !protected.iter().any(|(a, b)| start < *b && end > *a)
+      });
+      if let Some((title, path)) = found {
+        let end = start + title.len();
+        let text = &line[start..end];
+        let href = relative(dir, &path.with_extension("html"));
+        output.push_str(&line[rest..start]);
+        output.push_str(&format!("[{}]({})", text, href));
+        suggestions.push(Suggestion {
+          line: number + 1,
+          text: text.to_string(),
+          href,
+        });
+        linked.push(path.clone());
+        rest = end;
+      }
+    }
+    output.push_str(&line[rest..]);
+  }
+  (output, suggestions)
+}
+
+/// Byte ranges of a line that are code, links, autolinks or HTML tags
+fn protected(line: &str) -> Vec<(usize, usize)> {
+  let mut ranges = vec![];
+  let mut at = 0;
+  while let Some(offset) = line[at..].find(['`', '[', '<']) {
+    let start = at + offset;
+    let end = match line.as_bytes()[start] {
+      b'`' => line[start + 1..].find('`').map(|end| start + end + 2),
+      b'<' => line[start..].find('>').map(|end| start + end + 1),
+      _ => line[start..].find("](").and_then(|middle| {
+        let close = line[start + middle..].find(')')?;
+        Some(start + middle + close + 1)
+      }),
+    };
+    match end {
+      Some(end) => {
+        ranges.push((start, end));
+        at = end;
+      }
+      None => at = start + 1,
+    }
+  }
+  ranges
+}
@@ -0,0 +1,94 @@
+use crate::cache::Cache;
+use crate::code;
+use crate::diagram::pipe;
+use crate::util::escape;
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{html, Options, Parser};
+use serde_json::Value;
+
+/// Marks the end of a note's summary
+pub const MARKER: &str = "<!-- more -->";
+
+/// The front matter field marking a summary as generated
+pub const GENERATED: &str = "summary_generated";
+
+/// HTML for the summary of a note, used by listings and feeds
+///
+/// The summary is either the `summary` in the note's front matter, or the
+/// note up to a `<!-- more -->` marker without its title. Notes with neither
+/// have no summary. Generated summaries are given the `generated` class.
+pub fn summary(meta: &Value, body: &str) -> Option<String> {
+  if let Some(summary) = meta.get("summary").and_then(Value::as_str) {
+    let generated = meta.get(GENERATED) == Some(&Value::Bool(true));
+    return match generated {
+      true => Some(format!(
+        "<p class=\"generated\" title=\"Generated summary\">{}</p>\n",
+        escape(summary.trim())
+      )),
+      false => Some(format!("<p>{}</p>\n", escape(summary.trim()))),
+    };
+  }
+
+  let mut teaser = String::new();
+  for (code, line) in code::lines(body) {
+    if !code && line.trim() == MARKER {
+      let mut html = String::new();
+      let parser = Parser::new_ext(&teaser, Options::ENABLE_SMART_PUNCTUATION);
+      html::push_html(&mut html, parser);
+      return Some(html);
+    }
+    if !line.starts_with("# ") {
+      teaser.push_str(line);
+    }
+  }
+  None
+}
+
+/// A program writing the summary of a note given on standard input, for notes
+/// without a summary of their own, such as `--summarize "llm -s Summarize"`
+#[derive(Clone)]
+pub struct Summarizer {
+  command: Vec<String>,
+}
+
+impl Summarizer {
+  pub fn new(command: &str) -> Result<Summarizer> {
+    let words = command.split_whitespace().map(String::from).collect();
+    match words {
+      words if Vec::is_empty(&words) => {
+        Err(anyhow!("Invalid summarizer '{}'", command))
+      }
+      command => Ok(Summarizer { command }),
+    }
+  }
+
+  /// Give a note without a `summary`, `description` or `<!-- more -->` marker
+  /// a generated `summary` and `description`, marked `summary_generated`
+  ///
+  /// Summaries are cached, so notes are only summarized again once changed.
+  pub fn fill(
+    &self,
+    meta: &mut Value,
+    body: &str,
+    cache: &Cache,
+  ) -> Result<()> {
+    let written = meta.get("summary").is_some()
+      || meta.get("description").is_some()
+      || body.contains(MARKER);
+    let meta = match meta {
+      Value::Object(meta) if !written && !body.trim().is_empty() => meta,
+      _ => return Ok(()),
+    };
+    let kind = format!("summarize {}", self.command.join(" "));
+    let summary = cache.get(&kind, body, || {
+      pipe(&self.command[0], &self.command[1..], body)
+    })?;
+    let summary = summary.trim();
+    if !summary.is_empty() {
+      meta.insert("summary".into(), summary.into());
+      meta.insert("description".into(), summary.into());
+      meta.insert(GENERATED.into(), true.into());
+    }
+    Ok(())
+  }
+}
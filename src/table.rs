@@ -0,0 +1,290 @@
+use pulldown_cmark::{html, Alignment, Event, Tag};
+use std::collections::VecDeque;
+
+const CAPTION: &str = "Table:";
+
+/// A cell, with its rendered content and the spans it has been merged into
+struct Cell {
+  html: String,
+  text: String,
+  colspan: usize,
+  rowspan: usize,
+  merged: bool,
+}
+
+/// Extends tables with captions, spanning cells, header rows and widths
+///
+/// * A `Table: CAPTION` paragraph after a table becomes its caption
+/// * A cell containing only `<` merges into the cell to its left, and `^`
+///   into the cell above
+/// * Rows above a row of `===` cells are part of the header
+/// * A row of `{width=N}` cells sets relative column widths
+pub struct TableTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  buffer: VecDeque<Event<'a>>,
+}
+
+impl<'a, T> TableTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T) -> Self {
+    TableTransformer {
+      iter,
+      buffer: VecDeque::new(),
+    }
+  }
+
+  /// Read the rest of a table, returning its events
+  fn table(&mut self, start: Event<'a>) -> Vec<Event<'a>> {
+    let mut events = vec![start];
+    for event in &mut self.iter {
+      let end = matches!(event, Event::End(Tag::Table(_)));
+      events.push(event);
+      if end {
+        break;
+      }
+    }
+    events
+  }
+
+  /// Read a caption paragraph following a table, if there is one
+  fn caption(&mut self) -> Option<String> {
+    match self.iter.next() {
+      Some(Event::Start(Tag::Paragraph)) => {}
+      Some(event) => {
+        self.buffer.push_back(event);
+        return None;
+      }
+      None => return None,
+    }
+
+    let mut events = vec![];
+    for event in &mut self.iter {
+      if let Event::End(Tag::Paragraph) = event {
+        break;
+      }
+      events.push(event);
+    }
+
+    if let Some(Event::Text(text)) = events.first_mut() {
+      if let Some(rest) = text.strip_prefix(CAPTION) {
+        *text = rest.trim_start().to_string().into();
+        let mut caption = String::new();
+        html::push_html(&mut caption, events.into_iter());
+        return Some(caption);
+      }
+    }
+
+    self.buffer.push_back(Event::Start(Tag::Paragraph));
+    self.buffer.extend(events);
+    self.buffer.push_back(Event::End(Tag::Paragraph));
+    None
+  }
+}
+
+impl<'a, T> Iterator for TableTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(event) = self.buffer.pop_front() {
+      return Some(event);
+    }
+
+    let event = self.iter.next()?;
+    let alignments = match event {
+      Event::Start(Tag::Table(ref alignments)) => alignments.clone(),
+      _ => return Some(event),
+    };
+
+    let events = self.table(event);
+    let caption = self.caption();
+    let (head, mut rows) = cells(&events);
+
+    let widths = rows
+      .iter()
+      .position(|row| row.iter().all(|cell| width(&cell.text).is_some()))
+      .map(|index| rows.remove(index))
+      .map(|row| {
+        row
+          .iter()
+          .filter_map(|cell| width(&cell.text))
+          .collect::<Vec<_>>()
+      });
+    let separator = rows.iter().position(|row| {
+      row.iter().all(|cell| {
+        !cell.text.is_empty() && cell.text.chars().all(|c| c == '=')
+      })
+    });
+    let spans = std::iter::once(&head)
+      .chain(rows.iter())
+      .flatten()
+      .any(|cell| cell.text == "<" || cell.text == "^");
+
+    if caption.is_none() && widths.is_none() && separator.is_none() && !spans {
+      // The table is passed through unchanged, ahead of anything read after it
+      let mut events = events.into_iter();
+      let first = events.next();
+      for event in events.rev() {
+        self.buffer.push_front(event);
+      }
+      return first;
+    }
+
+    let mut grid = vec![head];
+    grid.extend(rows);
+    let head_rows = match separator {
+      Some(index) => {
+        grid.remove(index + 1);
+        index + 1
+      }
+      None => 1,
+    };
+    merge(&mut grid);
+
+    let mut output = String::from("<table>");
+    if let Some(caption) = caption {
+      output.push_str(&format!("<caption>{}</caption>", caption));
+    }
+    if let Some(widths) = widths {
+      let total: f64 = widths.iter().sum();
+      output.push_str("<colgroup>");
+      for width in &widths {
+        output.push_str(&format!(
+          "<col style=\"width: {:.1}%\">",
+          width / total * 100.0
+        ));
+      }
+      output.push_str("</colgroup>");
+    }
+    for (index, row) in grid.iter().enumerate() {
+      if index == 0 {
+        output.push_str("<thead>");
+      }
+      if index == head_rows {
+        output.push_str("</thead><tbody>\n");
+      }
+      let tag = if index < head_rows { "th" } else { "td" };
+      output.push_str("<tr>");
+      for (column, cell) in row.iter().enumerate() {
+        if cell.merged {
+          continue;
+        }
+        output.push('<');
+        output.push_str(tag);
+        let align = match alignments.get(column) {
+          Some(Alignment::Left) => Some("left"),
+          Some(Alignment::Center) => Some("center"),
+          Some(Alignment::Right) => Some("right"),
+          _ => None,
+        };
+        if let Some(align) = align {
+          output.push_str(&format!(" style=\"text-align: {}\"", align));
+        }
+        if cell.colspan > 1 {
+          output.push_str(&format!(" colspan=\"{}\"", cell.colspan));
+        }
+        if cell.rowspan > 1 {
+          output.push_str(&format!(" rowspan=\"{}\"", cell.rowspan));
+        }
+        output.push_str(&format!(">{}</{}>", cell.html, tag));
+      }
+      output.push_str("</tr>");
+      if index >= head_rows {
+        output.push('\n');
+      }
+    }
+    if grid.len() <= head_rows {
+      output.push_str("</thead><tbody>\n");
+    }
+    output.push_str("</tbody></table>\n");
+
+    Some(Event::Html(output.into()))
+  }
+}
+
+/// Split the events of a table into its header and body cells
+fn cells(events: &[Event]) -> (Vec<Cell>, Vec<Vec<Cell>>) {
+  let mut head = vec![];
+  let mut rows: Vec<Vec<Cell>> = vec![];
+  let mut in_head = false;
+  let mut cell: Option<Vec<Event>> = None;
+
+  for event in events {
+    match event {
+      Event::Start(Tag::TableHead) => in_head = true,
+      Event::End(Tag::TableHead) => in_head = false,
+      Event::Start(Tag::TableRow) => rows.push(vec![]),
+      Event::Start(Tag::TableCell) => cell = Some(vec![]),
+      Event::End(Tag::TableCell) => {
+        let events = cell.take().unwrap_or_default();
+        let text = events
+          .iter()
+          .filter_map(|event| match event {
+            Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+            _ => None,
+          })
+          .collect::<String>();
+        let mut html = String::new();
+        html::push_html(&mut html, events.into_iter());
+        let cell = Cell {
+          html,
+          text: text.trim().into(),
+          colspan: 1,
+          rowspan: 1,
+          merged: false,
+        };
+        match (in_head, rows.last_mut()) {
+          (true, _) | (false, None) => head.push(cell),
+          (false, Some(row)) => row.push(cell),
+        }
+      }
+      event => {
+        if let Some(cell) = &mut cell {
+          cell.push(event.clone());
+        }
+      }
+    }
+  }
+
+  (head, rows)
+}
+
+/// Parse a `{width=N}` attribute cell
+fn width(text: &str) -> Option<f64> {
+  let value = text.strip_prefix("{width=")?.strip_suffix('}')?;
+  value.trim_end_matches('%').parse().ok()
+}
+
+/// Merge `<` and `^` cells into their neighbours, updating the spans
+fn merge(grid: &mut [Vec<Cell>]) {
+  for row in 0..grid.len() {
+    for column in 0..grid[row].len() {
+      let (mut r, mut c) = (row, column);
+      match grid[row][column].text.as_str() {
+        "<" if column > 0 => {
+          c -= 1;
+          while c > 0 && grid[r][c].merged {
+            c -= 1;
+          }
+          grid[r][c].colspan += 1;
+        }
+        "^" if row > 0 => {
+          r -= 1;
+          while r > 0 && grid[r][c].merged {
+            r -= 1;
+          }
+          grid[r][c].rowspan += 1;
+        }
+        _ => continue,
+      }
+      grid[row][column].merged = true;
+    }
+  }
+}
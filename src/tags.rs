@@ -0,0 +1,263 @@
+use crate::code;
+use crate::frontmatter;
+use crate::listing;
+use crate::publish::Schedule;
+use crate::transformer::{EventTransformer, Events};
+use crate::util::{escape, slugify};
+use crate::vfs::Source;
+use anyhow::Result;
+use pulldown_cmark::{Event, Tag};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+
+/// The file name of the tag index
+pub const PAGE: &str = "tags.html";
+
+/// The id of a tag's section of the tag index
+pub fn anchor(tag: &str) -> String {
+  format!("tag-{}", slugify(tag))
+}
+
+/// A link to a tag's section of the tag index
+pub fn href(tag: &str) -> String {
+  format!("{}#{}", PAGE, anchor(tag))
+}
+
+/// The `tags` of a note's front matter, a list or a single tag
+pub fn of(meta: &Value) -> Vec<String> {
+  let tags = match meta.get("tags") {
+    Some(Value::Array(tags)) => tags.iter().filter_map(Value::as_str).collect(),
+    Some(Value::String(tag)) => vec![tag.as_str()],
+    _ => vec![],
+  };
+  tags.into_iter().map(|tag| tag.trim().to_string()).collect()
+}
+
+/// The `#tag` tags in the text of a note, in order
+pub fn inline(input: &str) -> Vec<String> {
+  let mut tags = vec![];
+  replace_tags(input, |tag| {
+    tags.push(tag.to_string());
+    None
+  });
+  tags
+}
+
+/// The front matter tags of a note, and its inline tags when they are used,
+/// each only once
+pub fn all(meta: &Value, body: &str, inline_tags: bool) -> Vec<String> {
+  let mut tags = of(meta);
+  if !inline_tags {
+    return tags;
+  }
+  for tag in inline(body) {
+    if !tags.iter().any(|known| known.eq_ignore_ascii_case(&tag)) {
+      tags.push(tag);
+    }
+  }
+  tags
+}
+
+/// Links `#tag` tags in the text of a document to their sections of the tag
+/// index, outside of headings, code and links
+pub struct TagTransformer;
+
+impl EventTransformer for TagTransformer {
+  fn transform<'a>(&self, events: Events<'a>) -> Events<'a> {
+    Box::new(TagEvents {
+      iter: events.peekable(),
+      buffer: VecDeque::new(),
+      skip: 0,
+      previous: None,
+    })
+  }
+}
+
+struct TagEvents<'a> {
+  iter: Peekable<Events<'a>>,
+  buffer: VecDeque<Event<'a>>,
+  skip: usize,
+  /// The character before the next text, if it doesn't start a line
+  previous: Option<char>,
+}
+
+impl<'a> Iterator for TagEvents<'a> {
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(event) = self.buffer.pop_front() {
+      return Some(event);
+    }
+
+    let event = self.iter.next()?;
+    let text = match event {
+      Event::Text(text) if self.skip == 0 => text,
+      event => {
+        match &event {
+          // Headings are left alone, so that their ids don't change
+          Event::Start(Tag::Heading(_))
+          | Event::Start(Tag::Link(..))
+          | Event::Start(Tag::Image(..))
+          | Event::Start(Tag::CodeBlock(_)) => self.skip += 1,
+          Event::End(Tag::Heading(_))
+          | Event::End(Tag::Link(..))
+          | Event::End(Tag::Image(..))
+          | Event::End(Tag::CodeBlock(_)) => self.skip -= 1,
+          _ => {}
+        }
+        // Tags start lines and blocks, but don't follow other inline markup
+        self.previous = match &event {
+          Event::SoftBreak | Event::HardBreak => None,
+          Event::Start(tag) | Event::End(tag) if !is_inline(tag) => None,
+          _ => Some('`'),
+        };
+        return Some(event);
+      }
+    };
+
+    // Text may be split into several events, even within a tag
+    let mut text = text.to_string();
+    while let Some(Event::Text(next)) = self.iter.peek() {
+      text.push_str(next);
+      self.iter.next();
+    }
+
+    let mut written = 0;
+    for (start, tag) in tags_in(&text, self.previous) {
+      if start > written {
+        let before = text[written..start].to_string();
+        self.buffer.push_back(Event::Text(before.into()));
+      }
+      let link = format!(
+        "<a class=\"tag\" href=\"{}\">#{}</a>",
+        escape(&href(tag)),
+        escape(tag)
+      );
+      self.buffer.push_back(Event::Html(link.into()));
+      written = start + 1 + tag.len();
+    }
+    if written < text.len() {
+      let rest = text[written..].to_string();
+      self.buffer.push_back(Event::Text(rest.into()));
+    }
+    self.previous = text.chars().last().or(self.previous);
+    self.buffer.pop_front()
+  }
+}
+
+fn is_inline(tag: &Tag) -> bool {
+  matches!(
+    tag,
+    Tag::Emphasis
+      | Tag::Strong
+      | Tag::Strikethrough
+      | Tag::Link(..)
+      | Tag::Image(..)
+  )
+}
+
+/// Replace each `#tag` outside of code that `replace` resolves
+fn replace_tags<F>(input: &str, mut replace: F) -> String
+where
+  F: FnMut(&str) -> Option<String>,
+{
+  let mut previous = None;
+  code::map_prose(input, |text| {
+    let mut output = String::with_capacity(text.len());
+    let mut written = 0;
+    for (start, tag) in tags_in(text, previous) {
+      if let Some(link) = replace(tag) {
+        output.push_str(&text[written..start]);
+        output.push_str(&link);
+        written = start + 1 + tag.len();
+      }
+    }
+    output.push_str(&text[written..]);
+    // Pieces after code spans continue their line
+    previous = match text.ends_with('\n') {
+      true => None,
+      false => Some('`'),
+    };
+    output
+  })
+}
+
+/// The position and name of each `#tag` in some text, after a character when
+/// the text doesn't start a line
+///
+/// Tags follow whitespace or start a line, begin with a letter and continue
+/// with letters, digits, `-`, `_` or `/`, so that headings, `{#id}` attributes
+/// and `#1` are not tags.
+fn tags_in(text: &str, previous: Option<char>) -> Vec<(usize, &str)> {
+  let is_tag = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '/');
+  let mut tags = vec![];
+  let mut from = 0;
+  while let Some(found) = text[from..].find('#') {
+    let start = from + found;
+    from = start + 1;
+    let before = text[..start].chars().last().or(previous);
+    let after = &text[start + 1..];
+    if before.is_some_and(|c| !c.is_whitespace())
+      || !after.chars().next().is_some_and(char::is_alphabetic)
+    {
+      continue;
+    }
+    let end = after.find(|c| !is_tag(c)).unwrap_or(after.len());
+    let tag = after[..end].trim_end_matches(['-', '/']);
+    tags.push((start, tag));
+    from = start + 1 + tag.len();
+  }
+  tags
+}
+
+/// The published notes under a directory, by their tags
+pub struct Tags {
+  tags: BTreeMap<String, Vec<(String, PathBuf)>>,
+}
+
+impl Tags {
+  pub fn collect(
+    source: &dyn Source,
+    root: &Path,
+    schedule: &Schedule,
+    inline_tags: bool,
+  ) -> Result<Tags> {
+    let mut tags: BTreeMap<String, Vec<(String, PathBuf)>> = BTreeMap::new();
+    for path in source.files(root)? {
+      if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+        continue;
+      }
+      let text = source.read_to_string(&path)?;
+      let (meta, body) = frontmatter::split(&text);
+      if !schedule.is_published(&meta)? {
+        continue;
+      }
+      let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+      let title =
+        listing::title(&meta, body).unwrap_or_else(|| listing::stem(&relative));
+      // Tags differing only in case are the same tag
+      for tag in all(&meta, body, inline_tags) {
+        let notes = tags.entry(tag.to_lowercase()).or_default();
+        notes.push((title.clone(), relative.clone()));
+      }
+    }
+    Ok(Tags { tags })
+  }
+
+  /// An index of the tags and their notes, as markdown
+  pub fn page(&self) -> String {
+    let mut page = String::from("# Tags\n\n");
+    for (tag, notes) in &self.tags {
+      page.push_str(&format!("## {} {{#{}}}\n\n", tag, anchor(tag)));
+      for (title, path) in notes {
+        let href = path.with_extension("html");
+        let href = href.to_string_lossy().replace('\\', "/");
+        page.push_str(&format!("* [{}]({})\n", title, href));
+      }
+      page.push('\n');
+    }
+    page
+  }
+}
@@ -0,0 +1,291 @@
+use crate::frontmatter;
+use crate::publish::Schedule;
+use crate::util::{date, days, escape};
+use crate::vfs::Source;
+use anyhow::Result;
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The file name of the dashboard of tracked metrics
+pub const PAGE: &str = "dashboard.html";
+
+/// The metric of lines that name none, in a `track` block without a name
+const DEFAULT: &str = "value";
+
+/// A value recorded in a `track` block
+pub struct Record {
+  pub metric: String,
+  pub date: String,
+  pub value: f64,
+}
+
+/// The date of a daily note, from its `date` front matter or file name
+pub fn note_date(meta: &Value, path: &Path) -> Option<String> {
+  let stem = path.file_stem().and_then(|stem| stem.to_str());
+  meta
+    .get("date")
+    .and_then(Value::as_str)
+    .into_iter()
+    .chain(stem)
+    .find_map(|text| days(text).map(date))
+}
+
+/// Parse the lines of a ` ```track ` block
+///
+/// Each line is an optional date, an optional metric and a value, such as
+/// `2024-01-02 sleep 7.5`. Lines without a date are for the date of the note,
+/// and lines without a metric are for the metric named by the block's info
+/// string, ` ```track sleep `. Habits are recorded with `yes` or `no`.
+pub fn parse(info: &str, text: &str, note: Option<&str>) -> Vec<Record> {
+  let name = info.trim_start_matches("track").trim();
+  let name = if name.is_empty() { DEFAULT } else { name };
+  let mut records = vec![];
+  for line in text.lines() {
+    let mut words = line
+      .split(|c: char| c.is_whitespace() || c == ':' || c == '\u{2192}')
+      .filter(|word| !word.is_empty() && *word != "->")
+      .collect::<Vec<_>>();
+    let value = match words.pop().and_then(value) {
+      Some(value) => value,
+      None => continue,
+    };
+    let date = match words.first().and_then(|word| days(word)) {
+      Some(day) => {
+        words.remove(0);
+        date(day)
+      }
+      None => match note {
+        Some(note) => note.into(),
+        None => continue,
+      },
+    };
+    let metric = match words.is_empty() {
+      true => name.to_string(),
+      false => words.join(" "),
+    };
+    records.push(Record {
+      metric,
+      date,
+      value,
+    });
+  }
+  records
+}
+
+fn value(word: &str) -> Option<f64> {
+  match word.to_lowercase().as_str() {
+    "yes" | "y" | "done" | "x" => Some(1.0),
+    "no" | "n" => Some(0.0),
+    word => word.parse().ok().filter(|value: &f64| value.is_finite()),
+  }
+}
+
+/// Transforms `track` fenced code blocks into tables of their records
+pub struct TrackTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  date: Option<String>,
+}
+
+impl<'a, T> TrackTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  /// Records without a date are for the `date` of the note
+  pub fn new(iter: T, date: Option<String>) -> Self {
+    TrackTransformer { iter, date }
+  }
+}
+
+impl<'a, T> Iterator for TrackTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+    let info = match event {
+      Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info)))
+        if info.split_whitespace().next() == Some("track") =>
+      {
+        info.to_string()
+      }
+      _ => return Some(event),
+    };
+
+    let mut text = String::new();
+    for event in &mut self.iter {
+      match event {
+        Event::Text(chunk) => text.push_str(&chunk),
+        _ => break,
+      }
+    }
+
+    let mut output = String::from("<table class=\"track\">\n");
+    output.push_str("<tr><th>Date</th><th>Metric</th><th>Value</th></tr>\n");
+    for record in parse(&info, &text, self.date.as_deref()) {
+      output.push_str(&format!(
+        "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+        escape(&record.date),
+        escape(&record.metric),
+        record.value
+      ));
+    }
+    output.push_str("</table>\n");
+    Some(Event::Html(output.into()))
+  }
+}
+
+/// The values of each metric tracked in the notes of a notebook, by date
+pub struct Tracker {
+  metrics: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl Tracker {
+  /// The metrics of the published notes under a directory
+  pub fn collect(
+    source: &dyn Source,
+    root: &Path,
+    schedule: &Schedule,
+  ) -> Result<Tracker> {
+    let mut metrics: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+    for path in source.files(root)? {
+      if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+        continue;
+      }
+      let text = source.read_to_string(&path)?;
+      let (meta, body) = frontmatter::split(&text);
+      if !schedule.is_published(&meta)? {
+        continue;
+      }
+      let note = note_date(&meta, &path);
+      for (info, block) in blocks(body) {
+        // Values recorded more than once a day are summed
+        for record in parse(info, &block, note.as_deref()) {
+          let metric = metrics.entry(record.metric).or_default();
+          *metric.entry(record.date).or_default() += record.value;
+        }
+      }
+    }
+    Ok(Tracker { metrics })
+  }
+
+  /// A dashboard with a chart and a summary of each metric, as markdown
+  pub fn page(&self) -> String {
+    let mut page = String::from("# Dashboard\n\n");
+    for (metric, values) in &self.metrics {
+      let total = values.values().sum::<f64>();
+      let (current, longest) = streaks(values);
+      page.push_str(&format!("## {}\n\n", metric));
+      page.push_str(&chart(values));
+      page.push_str(&format!(
+        "\n<p class=\"track-summary\">{} entries, total {}, average {:.2}, \
+         current streak {} days, longest streak {} days</p>\n\n",
+        values.len(),
+        total,
+        total / values.len() as f64,
+        current,
+        longest
+      ));
+    }
+    page
+  }
+}
+
+/// The info string and text of each `track` block outside other code blocks
+fn blocks(body: &str) -> Vec<(&str, String)> {
+  let mut blocks = vec![];
+  let mut current: Option<(&str, String)> = None;
+  let mut fence: Option<&str> = None;
+  for line in body.lines() {
+    let trimmed = line.trim_start();
+    let marker = ["```", "~~~"]
+      .iter()
+      .find(|marker| trimmed.starts_with(**marker));
+    match (fence, marker) {
+      (None, Some(marker)) => {
+        fence = Some(marker);
+        let info = trimmed.trim_start_matches(*marker).trim();
+        if info.split_whitespace().next() == Some("track") {
+          current = Some((info, String::new()));
+        }
+        continue;
+      }
+      (Some(open), Some(marker)) if open == *marker => {
+        fence = None;
+        blocks.extend(current.take());
+        continue;
+      }
+      _ => {}
+    }
+    if let Some((_, text)) = &mut current {
+      text.push_str(line);
+      text.push('\n');
+    }
+  }
+  blocks
+}
+
+/// The current and longest runs of consecutive days with a positive value
+///
+/// The current streak ends on the latest recorded day.
+fn streaks(values: &BTreeMap<String, f64>) -> (usize, usize) {
+  let mut longest = 0;
+  let mut run = 0;
+  let mut previous = None;
+  for (date, value) in values {
+    let day = days(date);
+    if *value <= 0.0 {
+      run = 0;
+    } else if day.is_some() && previous.map(|previous| previous + 1) == day {
+      run += 1;
+    } else {
+      run = 1;
+    }
+    longest = longest.max(run);
+    previous = day;
+  }
+  (run, longest)
+}
+
+/// An SVG line chart of a metric over the days it was recorded
+fn chart(values: &BTreeMap<String, f64>) -> String {
+  let (width, height) = (600.0, 120.0);
+  let points = values
+    .iter()
+    .filter_map(|(date, value)| Some((days(date)?, *value)))
+    .collect::<Vec<_>>();
+  let first = points.first().map(|(day, _)| *day).unwrap_or(0);
+  let last = points.last().map(|(day, _)| *day).unwrap_or(0);
+  let max = points.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+  let min = points.iter().map(|(_, value)| *value).fold(0.0, f64::min);
+  let span = (last - first).max(1) as f64;
+  let range = if max > min { max - min } else { 1.0 };
+
+  let coordinates = points
+    .iter()
+    .map(|(day, value)| {
+      let x = (day - first) as f64 / span * width;
+      let y = height - (value - min) / range * height;
+      format!("{:.1},{:.1}", x, y)
+    })
+    .collect::<Vec<_>>()
+    .join(" ");
+  let label =
+    format!("{} to {}, from {} to {}", date(first), date(last), min, max);
+  format!(
+    "<svg class=\"track-chart\" viewBox=\"0 0 {} {}\" role=\"img\" \
+     aria-label=\"{}\">\n\
+     <polyline fill=\"none\" stroke=\"currentColor\" points=\"{}\"/>\n\
+     </svg>\n",
+    width,
+    height,
+    escape(&label),
+    coordinates
+  )
+}
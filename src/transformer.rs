@@ -0,0 +1,13 @@
+use pulldown_cmark::Event;
+
+/// The events of a document as they pass through the rendering pipeline
+pub type Events<'a> = Box<dyn Iterator<Item = Event<'a>> + 'a>;
+
+/// A step of the rendering pipeline, such as drawing diagrams from fenced code
+/// blocks, that turns the events of a document into other events
+///
+/// Steps are given to [`Params`](crate::Params) in the order they run, after
+/// the built in steps for code blocks.
+pub trait EventTransformer: Send + Sync {
+  fn transform<'a>(&self, events: Events<'a>) -> Events<'a>;
+}
@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{Event, Tag};
+
+const NBSP: char = '\u{a0}';
+const NARROW_NBSP: char = '\u{202f}';
+const HAIR_SPACE: char = '\u{200a}';
+
+const UNITS: [&str; 24] = [
+  "%", "‰", "°", "°C", "°F", "K", "mm", "cm", "m", "km", "mg", "g", "kg", "t",
+  "ms", "s", "min", "h", "B", "KB", "MB", "GB", "TB", "Hz",
+];
+
+/// Language specific typographic conventions
+#[derive(Clone, Copy)]
+pub enum Language {
+  English,
+  French,
+  German,
+}
+
+impl Language {
+  pub fn parse(lang: &str) -> Result<Language> {
+    match lang.split(['-', '_']).next().unwrap_or("") {
+      "en" => Ok(Language::English),
+      "fr" => Ok(Language::French),
+      "de" => Ok(Language::German),
+      _ => Err(anyhow!("Unsupported typography language '{}'", lang)),
+    }
+  }
+
+  fn titles(self) -> &'static [&'static str] {
+    match self {
+      Language::English => &["Mr.", "Mrs.", "Ms.", "Dr.", "Prof.", "St."],
+      Language::French => &["M.", "Mme", "Mlle", "Dr", "Pr"],
+      Language::German => &["Hr.", "Fr.", "Dr.", "Prof."],
+    }
+  }
+}
+
+/// Typeset text, beyond what smart punctuation covers
+///
+/// Numbers are kept together with their units, and titles with the following
+/// name. Spaced hyphens become en dashes, numeric ranges use an en dash and
+/// em dashes get hair spaces in English. French text gets narrow no-break
+/// spaces before `;:!?` and inside guillemets.
+pub fn typeset(text: &str, language: Language) -> String {
+  let text = text.replace(". . .", "…").replace("...", "…");
+  let words = text.split(' ').collect::<Vec<_>>();
+
+  let mut output = String::with_capacity(text.len());
+  for (i, word) in words.iter().enumerate() {
+    output.push_str(word);
+    let next = match words.get(i + 1) {
+      Some(next) => next,
+      None => break,
+    };
+    let unit = next.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    let number = word.chars().last().is_some_and(|c| c.is_ascii_digit());
+    let binds = (number && UNITS.contains(&unit))
+      || language.titles().contains(word)
+      || (matches!(language, Language::French)
+        && (matches!(*next, ";" | ":" | "!" | "?" | "»") || *word == "«"));
+    if *next == "-" {
+      output.push(' ');
+    } else if *word == "-" {
+      output.pop();
+      output.push_str("– ");
+      continue;
+    } else if binds {
+      output.push(match language {
+        Language::French => NARROW_NBSP,
+        _ => NBSP,
+      });
+    } else {
+      output.push(' ');
+    }
+  }
+
+  let mut typeset = String::with_capacity(output.len());
+  let chars = output.chars().collect::<Vec<_>>();
+  for (i, c) in chars.iter().enumerate() {
+    let before = i.checked_sub(1).and_then(|i| chars.get(i));
+    let after = chars.get(i + 1);
+    let digits = before.is_some_and(char::is_ascii_digit)
+      && after.is_some_and(char::is_ascii_digit);
+    match c {
+      '-' if digits => typeset.push('–'),
+      '—' if matches!(language, Language::English) => {
+        if typeset.ends_with(' ') {
+          typeset.pop();
+        }
+        typeset.push(HAIR_SPACE);
+        typeset.push('—');
+        typeset.push(HAIR_SPACE);
+      }
+      ' ' if typeset.ends_with(HAIR_SPACE) => {}
+      c => typeset.push(*c),
+    }
+  }
+  typeset
+}
+
+/// Applies typographic improvements to text outside of code
+pub struct TypographyTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: T,
+  language: Language,
+  code: bool,
+}
+
+impl<'a, T> TypographyTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T, language: Language) -> Self {
+    TypographyTransformer {
+      iter,
+      language,
+      code: false,
+    }
+  }
+}
+
+impl<'a, T> Iterator for TypographyTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+    match event {
+      Event::Start(Tag::CodeBlock(_)) => self.code = true,
+      Event::End(Tag::CodeBlock(_)) => self.code = false,
+      Event::Text(text) if !self.code => {
+        return Some(Event::Text(typeset(&text, self.language).into()))
+      }
+      _ => {}
+    }
+    Some(event)
+  }
+}
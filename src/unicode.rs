@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{Event, Tag};
+use std::iter::Peekable;
+use unicode_normalization::UnicodeNormalization;
+
+const GLYPHS: [(&str, &str); 17] = [
+  ("<->", "↔"),
+  ("<=>", "⇔"),
+  ("->", "→"),
+  ("<-", "←"),
+  ("=>", "⇒"),
+  ("<=", "≤"),
+  (">=", "≥"),
+  ("!=", "≠"),
+  ("+-", "±"),
+  ("(c)", "©"),
+  ("(r)", "®"),
+  ("(tm)", "™"),
+  ("1/2", "½"),
+  ("1/4", "¼"),
+  ("3/4", "¾"),
+  ("1/3", "⅓"),
+  ("2/3", "⅔"),
+];
+
+/// Unicode clean ups applied to the text of a document
+#[derive(Clone, Copy, Default)]
+pub struct Unicode {
+  /// Normalize text to NFC
+  nfc: bool,
+  /// Replace ASCII arrows, fractions and symbols with their glyphs
+  glyphs: bool,
+  /// Remove emoji
+  strip_emoji: bool,
+}
+
+impl Unicode {
+  /// Parse a comma separated list of `nfc`, `glyphs` and `strip-emoji`
+  pub fn parse(options: &[String]) -> Result<Unicode> {
+    let mut unicode = Unicode::default();
+    for option in options.iter().flat_map(|option| option.split(',')) {
+      match option.trim() {
+        "nfc" => unicode.nfc = true,
+        "glyphs" => unicode.glyphs = true,
+        "strip-emoji" => unicode.strip_emoji = true,
+        option => return Err(anyhow!("Unknown unicode option '{}'", option)),
+      }
+    }
+    Ok(unicode)
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.nfc || self.glyphs || self.strip_emoji
+  }
+
+  fn apply(&self, text: &str) -> String {
+    let mut text = match self.nfc {
+      true => text.nfc().collect::<String>(),
+      false => text.into(),
+    };
+    if self.glyphs {
+      text = replace_glyphs(&text);
+    }
+    if self.strip_emoji {
+      text = text.chars().filter(|c| !is_emoji(*c)).collect();
+      text = text.replace("  ", " ");
+    }
+    text
+  }
+}
+
+/// Replace glyph sequences, with fractions only when they stand alone
+fn replace_glyphs(text: &str) -> String {
+  let mut output = String::with_capacity(text.len());
+  let mut rest = text;
+  'outer: while let Some(c) = rest.chars().next() {
+    for (ascii, glyph) in GLYPHS.iter() {
+      if !rest.starts_with(ascii) {
+        continue;
+      }
+      let fraction = ascii.contains('/');
+      let before = output.chars().last().is_some_and(|c| c.is_alphanumeric());
+      let after = rest[ascii.len()..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '/');
+      if fraction && (before || after) {
+        continue;
+      }
+      output.push_str(glyph);
+      rest = &rest[ascii.len()..];
+      continue 'outer;
+    }
+    output.push(c);
+    rest = &rest[c.len_utf8()..];
+  }
+  output
+}
+
+fn is_emoji(c: char) -> bool {
+  matches!(c as u32,
+    0x1f000..=0x1faff
+    | 0x2600..=0x27bf
+    | 0x2b00..=0x2bff
+    | 0xfe0f
+    | 0x200d
+    | 0xe0020..=0xe007f)
+}
+
+/// Applies the enabled Unicode clean ups to text outside of code
+pub struct UnicodeTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  iter: Peekable<T>,
+  unicode: Unicode,
+  code: bool,
+}
+
+impl<'a, T> UnicodeTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  pub fn new(iter: T, unicode: Unicode) -> Self {
+    UnicodeTransformer {
+      iter: iter.peekable(),
+      unicode,
+      code: false,
+    }
+  }
+}
+
+impl<'a, T> Iterator for UnicodeTransformer<'a, T>
+where
+  T: Iterator<Item = Event<'a>>,
+{
+  type Item = Event<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let event = self.iter.next()?;
+    match event {
+      Event::Start(Tag::CodeBlock(_)) => self.code = true,
+      Event::End(Tag::CodeBlock(_)) => self.code = false,
+      Event::Text(text) if !self.code => {
+        // Text is often split into several events, which could split a glyph
+        let mut text = text.to_string();
+        while let Some(Event::Text(next)) = self.iter.peek() {
+          text.push_str(next);
+          self.iter.next();
+        }
+        return Some(Event::Text(self.unicode.apply(&text).into()));
+      }
+      _ => {}
+    }
+    Some(event)
+  }
+}
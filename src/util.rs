@@ -0,0 +1,199 @@
+use crate::code;
+use crate::vfs::{self, Source};
+use anyhow::Result;
+use pulldown_cmark::escape;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BASE64: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Escape text for use in HTML content or attributes
+pub fn escape(text: &str) -> String {
+  let mut escaped = String::new();
+  escape::escape_html(&mut escaped, text).expect("Writing to a string");
+  escaped
+}
+
+/// Whether two secrets are the same, comparing every byte so that the time
+/// taken doesn't tell how much of a guess was right
+pub fn same_secret(a: &[u8], b: &[u8]) -> bool {
+  let differences = a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b));
+  a.len() == b.len() && differences == 0
+}
+
+/// Percent encode text for use as a query parameter or a path segment
+pub fn encode(text: &str) -> String {
+  text
+    .bytes()
+    .map(|byte| match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        (byte as char).to_string()
+      }
+      _ => format!("%{:02X}", byte),
+    })
+    .collect()
+}
+
+/// Percent encode each segment of a `/` separated path
+pub fn encode_path(path: &str) -> String {
+  path.split('/').map(encode).collect::<Vec<_>>().join("/")
+}
+
+/// Convert text into a lowercase, hyphen separated identifier
+pub fn slugify(text: &str) -> String {
+  let mut slug = String::new();
+  for c in text.chars() {
+    if c.is_alphanumeric() {
+      slug.extend(c.to_lowercase());
+    } else if !slug.is_empty() && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  }
+  slug.trim_end_matches('-').into()
+}
+
+/// The text of the first level one heading of some markdown, outside of code
+pub fn heading(markdown: &str) -> Option<&str> {
+  code::lines(markdown)
+    .filter(|(code, _)| !code)
+    .find_map(|(_, line)| line.strip_prefix("# "))
+    .map(str::trim)
+}
+
+/// List the markdown files in a directory tree, skipping hidden entries
+pub fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>> {
+  let files = vfs::Filesystem::default().files(dir)?;
+  Ok(files.into_iter().filter(|path| is_markdown(path)).collect())
+}
+
+/// Whether a path is of a markdown file, by its `.md` extension
+pub fn is_markdown(path: &Path) -> bool {
+  path.extension().and_then(|ext| ext.to_str()) == Some("md")
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`
+pub fn today() -> String {
+  let seconds = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs())
+    .unwrap_or(0);
+  date(seconds as i64 / 86400)
+}
+
+/// Convert days since the Unix epoch to a `YYYY-MM-DD` date
+pub fn date(days: i64) -> String {
+  // Howard Hinnant's civil_from_days algorithm
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Convert a `YYYY-MM-DD` date to days since the Unix epoch
+pub fn days(date: &str) -> Option<i64> {
+  // Howard Hinnant's days_from_civil algorithm
+  let mut parts = date.get(..10)?.splitn(3, '-');
+  let year: i64 = parts.next()?.parse().ok()?;
+  let month: i64 = parts.next()?.parse().ok()?;
+  let day: i64 = parts.next()?.parse().ok()?;
+  if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+    return None;
+  }
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let yoe = year.rem_euclid(400);
+  let mp = if month > 2 { month - 3 } else { month + 9 };
+  let doy = (153 * mp + 2) / 5 + day - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  Some(era * 146097 + doe - 719468)
+}
+
+/// Encode bytes as standard, padded base64
+pub fn encode_base64(bytes: &[u8]) -> String {
+  let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      match i <= chunk.len() {
+        true => output.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char),
+        false => output.push('='),
+      }
+    }
+  }
+  output
+}
+
+/// Decode base64, standard or URL safe, ignoring padding and line breaks
+pub fn decode_base64(text: &str) -> Vec<u8> {
+  let value = |c: u8| match c {
+    b'A'..=b'Z' => Some(c - b'A'),
+    b'a'..=b'z' => Some(c - b'a' + 26),
+    b'0'..=b'9' => Some(c - b'0' + 52),
+    b'+' | b'-' => Some(62),
+    b'/' | b'_' => Some(63),
+    _ => None,
+  };
+  let mut bytes = vec![];
+  let mut buffer = 0u32;
+  let mut bits = 0;
+  for sextet in text.bytes().filter_map(value) {
+    buffer = (buffer << 6) | sextet as u32;
+    bits += 6;
+    if bits >= 8 {
+      bits -= 8;
+      bytes.push((buffer >> bits) as u8);
+    }
+  }
+  bytes
+}
+
+/// The CRC-32 checksum of some bytes, as zip and gzip use
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut crc = !0u32;
+  for byte in data {
+    crc ^= *byte as u32;
+    for _ in 0..8 {
+      let mask = (!(crc & 1)).wrapping_add(1);
+      crc = (crc >> 1) ^ (0xedb88320 & mask);
+    }
+  }
+  !crc
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn crc32_matches_its_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    assert_eq!(crc32(b""), 0);
+  }
+
+  #[test]
+  fn decode_base64_reads_standard_and_url_safe_text() {
+    assert_eq!(decode_base64("Zm9vYmFy"), b"foobar");
+    assert_eq!(decode_base64("Zm9vYg==\r\n"), b"foob");
+    assert_eq!(decode_base64("+/-_"), [0xfb, 0xff, 0xbf]);
+    assert_eq!(decode_base64(""), b"");
+  }
+
+  #[test]
+  fn encode_base64_matches_known_vectors() {
+    assert_eq!(encode_base64(b""), "");
+    assert_eq!(encode_base64(b"f"), "Zg==");
+    assert_eq!(encode_base64(b"fo"), "Zm8=");
+    assert_eq!(encode_base64(b"foo"), "Zm9v");
+    assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+  }
+}
@@ -0,0 +1,490 @@
+use crate::compress;
+use crate::paths;
+use crate::util::crc32;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where the sources of a build are read from
+pub trait Source: Send + Sync {
+  /// Read the contents of a file
+  fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+  /// List the files in a directory and its children, skipping hidden entries
+  fn files(&self, dir: &Path) -> Result<Vec<PathBuf>>;
+
+  fn read_to_string(&self, path: &Path) -> Result<String> {
+    Ok(String::from_utf8(self.read(path)?)?)
+  }
+
+  /// When a file was last modified, if known
+  fn modified(&self, _path: &Path) -> Option<SystemTime> {
+    None
+  }
+}
+
+/// How symlinks met when reading a directory of notes are treated
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Symlinks {
+  /// Followed, while they lead to somewhere within the vault
+  #[default]
+  Follow,
+  /// Left out, as if they weren't there
+  Skip,
+  /// Refused, failing the build
+  Error,
+}
+
+impl Symlinks {
+  pub fn parse(policy: &str) -> Result<Symlinks> {
+    match policy {
+      "follow" => Ok(Symlinks::Follow),
+      "skip" => Ok(Symlinks::Skip),
+      "error" => Ok(Symlinks::Error),
+      _ => Err(anyhow!("Unknown symlinks policy '{}'", policy)),
+    }
+  }
+}
+
+/// Reads sources from the real filesystem
+///
+/// With a vault, the directory of a notebook, nothing is read from outside
+/// of it, whether by `..` in the paths of links and embeds or by symlinks.
+#[derive(Default)]
+pub struct Filesystem {
+  vault: Option<PathBuf>,
+  symlinks: Symlinks,
+}
+
+impl Filesystem {
+  pub fn new(vault: &Path, symlinks: Symlinks) -> Result<Filesystem> {
+    let vault = paths::canonicalize(vault)
+      .ok_or_else(|| anyhow!("Unknown vault '{}'", vault.display()))?;
+    Ok(Filesystem {
+      vault: Some(vault),
+      symlinks,
+    })
+  }
+
+  /// Fail for a path leading outside of the vault, leaving paths that don't
+  /// exist to fail when they are read
+  fn confine(&self, path: &Path) -> Result<()> {
+    let (vault, path) = match (&self.vault, paths::canonicalize(path)) {
+      (Some(vault), Some(path)) => (vault, path),
+      _ => return Ok(()),
+    };
+    match path.starts_with(vault) {
+      true => Ok(()),
+      false => Err(anyhow!(
+        "'{}' is outside of the vault '{}'",
+        path.display(),
+        vault.display()
+      )),
+    }
+  }
+
+  fn walk(
+    &self,
+    dir: &Path,
+    parents: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+  ) -> Result<()> {
+    // Symlinks to a directory above themselves are not followed in a loop
+    let canonical = paths::canonicalize(dir);
+    if let Some(canonical) = &canonical {
+      if parents.contains(canonical) {
+        return Ok(());
+      }
+      parents.push(canonical.clone());
+    }
+    let mut entries = fs::read_dir(dir)?
+      .map(|entry| entry.map(|entry| entry.path()))
+      .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for path in entries {
+      if paths::is_hidden(&path) {
+        continue;
+      }
+      let symlink = fs::symlink_metadata(&path)
+        .is_ok_and(|meta| meta.file_type().is_symlink());
+      if symlink {
+        match self.symlinks {
+          Symlinks::Follow if self.confine(&path).is_ok() => {}
+          Symlinks::Follow | Symlinks::Skip => continue,
+          Symlinks::Error => {
+            return Err(anyhow!("Symlink '{}' is refused", path.display()))
+          }
+        }
+      }
+      match path.is_dir() {
+        true => self.walk(&path, parents, files)?,
+        false => files.push(path),
+      }
+    }
+    if canonical.is_some() {
+      parents.pop();
+    }
+    Ok(())
+  }
+}
+
+impl Source for Filesystem {
+  fn read(&self, path: &Path) -> Result<Vec<u8>> {
+    self.confine(path)?;
+    Ok(fs::read(path)?)
+  }
+
+  fn modified(&self, path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+  }
+
+  fn files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+    self.confine(dir)?;
+    let mut files = vec![];
+    self.walk(dir, &mut vec![], &mut files)?;
+    Ok(files)
+  }
+}
+
+/// The input path that reads a document from standard input
+pub const STDIN: &str = "-";
+
+/// A document read from standard input, as the file `-`, with every other
+/// file read from another source
+pub struct Stdin {
+  contents: Vec<u8>,
+  source: Arc<dyn Source>,
+}
+
+impl Stdin {
+  /// Read all of standard input
+  pub fn read(source: Arc<dyn Source>) -> Result<Stdin> {
+    let mut contents = vec![];
+    std::io::stdin().read_to_end(&mut contents)?;
+    Ok(Stdin { contents, source })
+  }
+}
+
+impl Source for Stdin {
+  fn read(&self, path: &Path) -> Result<Vec<u8>> {
+    match path == Path::new(STDIN) {
+      true => Ok(self.contents.clone()),
+      false => self.source.read(path),
+    }
+  }
+
+  fn files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+    self.source.files(dir)
+  }
+
+  fn modified(&self, path: &Path) -> Option<SystemTime> {
+    self.source.modified(path)
+  }
+}
+
+/// The most bytes the files of an archive may have, once decompressed
+const ARCHIVE_LIMIT: usize = 1 << 30;
+
+/// Sources held in memory, such as the contents of an archive
+#[derive(Default)]
+pub struct Memory {
+  files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Memory {
+  pub fn insert(&mut self, path: &Path, contents: Vec<u8>) {
+    self.files.insert(normalize(path), contents);
+  }
+
+  /// Load the files of a `.zip` or `.tar` archive
+  pub fn archive(path: &Path) -> Result<Memory> {
+    let data = fs::read(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some("zip") => Memory::zip(&data),
+      Some("tar") => Memory::tar(&data),
+      _ => Err(anyhow!("Unknown archive format '{}'", path.display())),
+    }
+  }
+
+  /// Read the entries of a zip archive, stored or deflated, by its central
+  /// directory, which has their sizes even when they were streamed with data
+  /// descriptors after them
+  fn zip(data: &[u8]) -> Result<Memory> {
+    let u16_at = |at: usize| {
+      data
+        .get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let u32_at = |at: usize| {
+      data
+        .get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let invalid = || anyhow!("Invalid zip archive");
+
+    // The end of the central directory is last, before any comment
+    let end = (0..data.len().saturating_sub(21))
+      .rev()
+      .take(65536)
+      .find(|&at| u32_at(at) == Some(0x06054b50))
+      .ok_or_else(invalid)?;
+    let entries = u16_at(end + 10).ok_or_else(invalid)?;
+    let mut at = u32_at(end + 16).ok_or_else(invalid)?;
+    if entries == 0xffff || at == 0xffffffff {
+      return Err(anyhow!("Zip64 archives are unsupported"));
+    }
+
+    let mut memory = Memory::default();
+    let mut total = 0;
+    for _ in 0..entries {
+      if u32_at(at) != Some(0x02014b50) {
+        return Err(invalid());
+      }
+      let flags = u16_at(at + 8).ok_or_else(invalid)?;
+      let method = u16_at(at + 10).ok_or_else(invalid)?;
+      let crc = u32_at(at + 16).ok_or_else(invalid)?;
+      let size = u32_at(at + 20).ok_or_else(invalid)?;
+      let length = u32_at(at + 24).ok_or_else(invalid)?;
+      let name_len = u16_at(at + 28).ok_or_else(invalid)?;
+      let extra_len = u16_at(at + 30).ok_or_else(invalid)?;
+      let comment_len = u16_at(at + 32).ok_or_else(invalid)?;
+      let local = u32_at(at + 42).ok_or_else(invalid)?;
+      let name = data.get(at + 46..at + 46 + name_len).ok_or_else(invalid)?;
+      let name = String::from_utf8_lossy(name).into_owned();
+      at += 46 + name_len + extra_len + comment_len;
+
+      if size == 0xffffffff || length == 0xffffffff || local == 0xffffffff {
+        return Err(anyhow!("Zip64 entry '{}' is unsupported", name));
+      }
+      if flags & 1 != 0 {
+        return Err(anyhow!("Encrypted zip entry '{}' is unsupported", name));
+      }
+      if name.ends_with('/') {
+        continue;
+      }
+      if u32_at(local) != Some(0x04034b50) {
+        return Err(invalid());
+      }
+      let start = local
+        + 30
+        + u16_at(local + 26).ok_or_else(invalid)?
+        + u16_at(local + 28).ok_or_else(invalid)?;
+      // Entries are read only up to their sizes, within the archive's limit
+      total += length;
+      if total > ARCHIVE_LIMIT {
+        return Err(anyhow!(
+          "Zip archive is over {} bytes at '{}'",
+          ARCHIVE_LIMIT,
+          name
+        ));
+      }
+      let contents = data.get(start..start + size).ok_or_else(invalid)?;
+      let contents = match method {
+        0 => contents.to_vec(),
+        8 => compress::inflate(contents, length)
+          .map_err(|err| anyhow!("Zip entry '{}': {}", name, err))?,
+        _ => {
+          return Err(anyhow!(
+            "Zip entry '{}' is compressed with an unsupported method",
+            name
+          ))
+        }
+      };
+      if contents.len() != length || crc32(&contents) as usize != crc {
+        return Err(anyhow!("Zip entry '{}' is corrupt", name));
+      }
+      memory.insert(Path::new(&name), contents);
+    }
+    Ok(memory)
+  }
+
+  /// Read the regular files of a tar archive
+  fn tar(data: &[u8]) -> Result<Memory> {
+    let field = |header: &[u8], start: usize, len: usize| {
+      let bytes = &header[start..start + len];
+      let end = bytes.iter().position(|b| *b == 0).unwrap_or(len);
+      String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+    };
+
+    let mut memory = Memory::default();
+    let mut at = 0;
+    while let Some(header) = data.get(at..at + 512) {
+      if header.iter().all(|b| *b == 0) {
+        break;
+      }
+      let mut name = field(header, 0, 100);
+      let prefix = field(header, 345, 155);
+      if !prefix.is_empty() {
+        name = format!("{}/{}", prefix, name);
+      }
+      let size = usize::from_str_radix(&field(header, 124, 12), 8)
+        .map_err(|_| anyhow!("Invalid tar archive"))?;
+      let start = at + 512;
+      let contents = data
+        .get(start..start + size)
+        .ok_or_else(|| anyhow!("Invalid tar archive"))?;
+
+      // Regular files only, skipping directories and links
+      if matches!(header[156], b'0' | 0) {
+        memory.insert(Path::new(&name), contents.to_vec());
+      }
+      at = start + size.div_ceil(512) * 512;
+    }
+    Ok(memory)
+  }
+}
+
+impl Source for Memory {
+  fn read(&self, path: &Path) -> Result<Vec<u8>> {
+    match self.files.get(&normalize(path)) {
+      Some(contents) => Ok(contents.clone()),
+      None => Err(anyhow!("File not found '{}'", path.display())),
+    }
+  }
+
+  fn files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+    let dir = normalize(dir);
+    let files = self
+      .files
+      .keys()
+      .filter(|path| path.starts_with(&dir))
+      .filter(|path| {
+        let hidden =
+          |c: Component| c.as_os_str().to_string_lossy().starts_with('.');
+        !path
+          .strip_prefix(&dir)
+          .unwrap_or(path)
+          .components()
+          .any(hidden)
+      })
+      .cloned()
+      .collect();
+    Ok(files)
+  }
+}
+
+/// Reads sources from a git revision, without checking it out
+pub struct Git {
+  rev: String,
+}
+
+impl Git {
+  pub fn new(rev: &str) -> Result<Git> {
+    if rev.is_empty() || rev.starts_with('-') {
+      return Err(anyhow!("Invalid revision '{}'", rev));
+    }
+    Ok(Git { rev: rev.into() })
+  }
+
+  fn run(&self, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+      .args(args)
+      .output()
+      .map_err(|err| anyhow!("Failed to run 'git': {}", err))?;
+    if !output.status.success() {
+      let stderr = String::from_utf8_lossy(&output.stderr);
+      return Err(anyhow!("'git' failed: {}", stderr.trim()));
+    }
+    Ok(output.stdout)
+  }
+}
+
+impl Source for Git {
+  fn read(&self, path: &Path) -> Result<Vec<u8>> {
+    // Paths starting with `./` are relative to the working directory
+    let path = Path::new(".").join(normalize(path));
+    let object = format!("{}:{}", self.rev, path.to_string_lossy());
+    self.run(&["show", &object])
+  }
+
+  fn modified(&self, path: &Path) -> Option<SystemTime> {
+    // The time of the last commit to change the file
+    let path = Path::new(".").join(normalize(path));
+    let path = path.to_string_lossy();
+    let args = ["log", "-1", "--format=%ct", &self.rev, "--", &path];
+    let output = String::from_utf8(self.run(&args).ok()?).ok()?;
+    let seconds = output.trim().parse().ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+  }
+
+  fn files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+    let dir = Path::new(".").join(normalize(dir));
+    let dir = dir.to_string_lossy();
+    let args = ["ls-tree", "-r", "--name-only", &self.rev, "--", &dir];
+    let listing = String::from_utf8(self.run(&args)?)?;
+    let files = listing
+      .lines()
+      .map(PathBuf::from)
+      .filter(|path| !path.components().any(hidden))
+      .collect();
+    Ok(files)
+  }
+}
+
+/// Whether a path component is hidden
+fn hidden(component: Component) -> bool {
+  match component {
+    Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+    _ => false,
+  }
+}
+
+/// Remove `.` components so that `./a.md` and `a.md` are the same file
+fn normalize(path: &Path) -> PathBuf {
+  path
+    .components()
+    .filter(|component| !matches!(component, Component::CurDir))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn zip_archives_read_back_their_files() {
+    use crate::output::{Writer, Zip};
+
+    let path = std::env::temp_dir()
+      .join(format!("notebook-vfs-{}.zip", std::process::id()));
+    let mut zip = Zip::create(&path).unwrap();
+    zip.write(Path::new("notes/a.md"), b"# A\n").unwrap();
+    zip.write(Path::new("b.md"), b"").unwrap();
+    zip.finish().unwrap();
+    let data = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let memory = Memory::zip(&data).unwrap();
+    assert_eq!(memory.read(Path::new("notes/a.md")).unwrap(), b"# A\n");
+    assert_eq!(memory.read(Path::new("b.md")).unwrap(), b"");
+  }
+
+  #[test]
+  fn tar_archives_read_back_their_regular_files() {
+    let entry = |name: &str, kind: u8, contents: &[u8]| {
+      let mut header = vec![0; 512];
+      header[..name.len()].copy_from_slice(name.as_bytes());
+      let size = format!("{:011o}", contents.len());
+      header[124..135].copy_from_slice(size.as_bytes());
+      header[156] = kind;
+      header.extend_from_slice(contents);
+      header.resize(512 + contents.len().div_ceil(512) * 512, 0);
+      header
+    };
+    let mut data = entry("notes/", b'5', b"");
+    data.extend(entry("notes/a.md", b'0', &[b'a'; 600]));
+    data.extend(entry("b.md", b'0', b"# B\n"));
+    data.extend([0; 1024]);
+
+    let memory = Memory::tar(&data).unwrap();
+    assert_eq!(memory.read(Path::new("notes/a.md")).unwrap(), [b'a'; 600]);
+    assert_eq!(memory.read(Path::new("b.md")).unwrap(), b"# B\n");
+    assert_eq!(memory.files.len(), 2);
+  }
+}
@@ -0,0 +1,115 @@
+use crate::util::{date, days, escape, today};
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Route that visit counts are listed at
+pub const PATH: &str = "stats";
+
+/// Counts of the pages served each day, kept in memory or persisted as JSON
+///
+/// Only the path and the day of a visit are recorded, never anything about
+/// the visitor.
+#[derive(Clone)]
+pub struct Visits {
+  path: Option<PathBuf>,
+  /// Counts by day, then by path
+  days: Arc<Mutex<BTreeMap<String, BTreeMap<String, u64>>>>,
+}
+
+impl Visits {
+  /// Count visits in `memory`, or in a `file` in the `.notebook` directory
+  /// beside a document
+  pub fn new(mode: &str, dir: &Path) -> Result<Visits> {
+    let path = match mode {
+      "memory" => None,
+      "file" => Some(dir.join(".notebook").join("visits.json")),
+      _ => return Err(anyhow!("Unknown visit counting '{}'", mode)),
+    };
+    let mut days = BTreeMap::new();
+    if let Some(Ok(text)) = path.as_ref().map(fs::read_to_string) {
+      let counts: Map<String, Value> = match serde_json::from_str(&text)? {
+        Value::Object(counts) => counts,
+        _ => return Err(anyhow!("Invalid visits file")),
+      };
+      for (day, paths) in counts {
+        let paths = paths.as_object().into_iter().flatten();
+        let paths = paths
+          .filter_map(|(path, count)| Some((path.clone(), count.as_u64()?)));
+        days.insert(day, paths.collect());
+      }
+    }
+    Ok(Visits {
+      path,
+      days: Arc::new(Mutex::new(days)),
+    })
+  }
+
+  /// Count a visit to a path today
+  pub fn record(&self, path: &str) -> Result<()> {
+    let mut days = self.days.lock().expect("Visits lock");
+    let day = days.entry(today()).or_default();
+    *day.entry(format!("/{}", path)).or_default() += 1;
+
+    if let Some(file) = &self.path {
+      if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+      }
+      fs::write(file, serde_json::to_string_pretty(&*days)?)?;
+    }
+    Ok(())
+  }
+
+  /// Markdown for a page of the visits to each path and on recent days
+  pub fn page(&self) -> String {
+    let visits = self.days.lock().expect("Visits lock");
+    let mut page = String::from("# Visits\n\n");
+    if visits.is_empty() {
+      page.push_str("Nothing has been visited yet.\n");
+      return page;
+    }
+
+    let today = today();
+    let recent = days(&today)
+      .map(|today| date(today - 6))
+      .unwrap_or_default();
+    let mut paths: BTreeMap<&str, (u64, u64, u64)> = BTreeMap::new();
+    for (day, counts) in visits.iter() {
+      for (path, count) in counts {
+        let totals = paths.entry(path).or_default();
+        if *day == today {
+          totals.0 += count;
+        }
+        if *day >= recent {
+          totals.1 += count;
+        }
+        totals.2 += count;
+      }
+    }
+    page
+      .push_str("| Path | Today | Last 7 days | Total |\n|---|---|---|---|\n");
+    for (path, (today, week, total)) in paths {
+      page.push_str(&format!(
+        "| <a href=\"{}\">{}</a> | {} | {} | {} |\n",
+        escape(path),
+        escape(path),
+        today,
+        week,
+        total
+      ));
+    }
+
+    page.push_str("\n| Day | Visits |\n|---|---|\n");
+    for (day, counts) in visits.iter().rev().take(30) {
+      page.push_str(&format!(
+        "| {} | {} |\n",
+        day,
+        counts.values().sum::<u64>()
+      ));
+    }
+    page
+  }
+}
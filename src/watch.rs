@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Route that browsers listen on for changes, as server-sent events
+pub const PATH: &str = "_reload";
+
+/// Script reloading a page when the server sends a change event
+pub fn script() -> String {
+  format!(
+    "<script>\nnew EventSource(\"/{}\").onmessage = function () {{ \
+     location.reload(); }};\n</script>\n",
+    PATH
+  )
+}
+
+/// Polls the modification times and sizes of the files a document is
+/// rendered from
+///
+/// Polling works alike on every platform and filesystem, including network
+/// shares, and sizes catch changes within the coarse modification times of
+/// filesystems such as FAT.
+pub struct Watcher {
+  files: Vec<(PathBuf, Option<Stamp>)>,
+}
+
+/// When a file was last modified, and its size
+type Stamp = (SystemTime, u64);
+
+impl Watcher {
+  pub fn new(paths: Vec<PathBuf>) -> Watcher {
+    let files = paths
+      .into_iter()
+      .map(|path| {
+        let modified = modified(&path);
+        (path, modified)
+      })
+      .collect();
+    Watcher { files }
+  }
+
+  /// Whether any file has changed since the last check
+  pub fn changed(&mut self) -> bool {
+    let mut changed = false;
+    for (path, last) in &mut self.files {
+      let modified = modified(path);
+      if modified != *last {
+        *last = modified;
+        changed = true;
+      }
+    }
+    changed
+  }
+}
+
+fn modified(path: &PathBuf) -> Option<Stamp> {
+  let meta = fs::metadata(path).ok()?;
+  Some((meta.modified().ok()?, meta.len()))
+}
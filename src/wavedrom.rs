@@ -0,0 +1,220 @@
+use crate::util::escape;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+const CYCLE: f64 = 20.0;
+const ROW: f64 = 30.0;
+const SIGNAL: f64 = 20.0;
+const LABELS: f64 = 80.0;
+
+/// Colours of the data states `2` to `9`, with `=` using the first
+const FILLS: [&str; 8] = [
+  "#ffffff", "#ffffb4", "#ffe0b9", "#b9e0ff", "#ccfdfe", "#cdfdc5", "#f0c1fb",
+  "#f5c2c0",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+  Low,
+  High,
+  Clock { positive: bool, marked: bool },
+  Undefined,
+  HighImpedance,
+  Data(usize),
+}
+
+/// Render a WaveDrom timing diagram, written as WaveJSON, to SVG
+///
+/// Only `signal` lanes with the `wave`, `name`, `data` and `period` fields are
+/// supported. Groups and the `edge` and `config` sections are ignored.
+pub fn render(text: &str) -> Result<String> {
+  // WaveJSON is usually written with unquoted keys, which YAML accepts
+  let diagram: Value = serde_yaml::from_str(text)?;
+  let lanes = match diagram.get("signal") {
+    Some(Value::Array(lanes)) => lanes,
+    _ => return Err(anyhow!("WaveDrom diagram is missing a 'signal' list")),
+  };
+
+  let waves = lanes
+    .iter()
+    .filter_map(|lane| Some((lane, lane.get("wave")?.as_str()?)))
+    .collect::<Vec<_>>();
+  let cycles = waves
+    .iter()
+    .map(|(lane, wave)| wave.chars().count() * period(lane))
+    .max()
+    .unwrap_or(0);
+
+  let width = LABELS + cycles as f64 * CYCLE + 10.0;
+  let height = ROW * lanes.len() as f64 + 10.0;
+  let mut svg = format!(
+    "<svg class=\"wavedrom\" xmlns=\"http://www.w3.org/2000/svg\" \
+     width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" \
+     font-family=\"sans-serif\" font-size=\"11\">\n",
+    width, height, width, height
+  );
+
+  for (row, lane) in lanes.iter().enumerate() {
+    let top = 5.0 + row as f64 * ROW;
+    if let Some(name) = lane.get("name").and_then(Value::as_str) {
+      svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n",
+        LABELS - 8.0,
+        top + SIGNAL / 2.0 + 4.0,
+        escape(name)
+      ));
+    }
+    if let Some(wave) = lane.get("wave").and_then(Value::as_str) {
+      lane_svg(&mut svg, lane, wave, top);
+    }
+  }
+
+  svg.push_str("</svg>\n");
+  Ok(svg)
+}
+
+fn period(lane: &Value) -> usize {
+  lane
+    .get("period")
+    .and_then(Value::as_u64)
+    .unwrap_or(1)
+    .max(1) as usize
+}
+
+fn lane_svg(svg: &mut String, lane: &Value, wave: &str, top: f64) {
+  let data = match lane.get("data") {
+    Some(Value::Array(data)) => data
+      .iter()
+      .map(|value| match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+      })
+      .collect(),
+    Some(Value::String(data)) => {
+      data.split_whitespace().map(String::from).collect()
+    }
+    _ => vec![],
+  };
+
+  // Consecutive cycles in the same data state are drawn as a single shape
+  let mut states: Vec<(State, usize)> = vec![];
+  for c in wave.chars() {
+    let state = match (c, states.last()) {
+      ('.', Some((state, _))) | ('|', Some((state, _))) => *state,
+      ('p', _) | ('P', _) => State::Clock {
+        positive: true,
+        marked: c == 'P',
+      },
+      ('n', _) | ('N', _) => State::Clock {
+        positive: false,
+        marked: c == 'N',
+      },
+      ('0', _) | ('l', _) | ('L', _) => State::Low,
+      ('1', _) | ('h', _) | ('H', _) => State::High,
+      ('z', _) => State::HighImpedance,
+      ('=', _) => State::Data(0),
+      (c @ '2'..='9', _) => State::Data(c as usize - '2' as usize),
+      _ => State::Undefined,
+    };
+    match states.last_mut() {
+      Some((_, cycles)) if c == '.' || c == '|' => *cycles += 1,
+      _ => states.push((state, 1)),
+    }
+  }
+
+  let cycle = CYCLE * period(lane) as f64;
+  let (high, low) = (top, top + SIGNAL);
+  let mut x = LABELS;
+  let mut level: Option<f64> = None;
+  let mut labels = data.iter();
+
+  for (state, cycles) in states {
+    let end = x + cycle * cycles as f64;
+    match state {
+      State::Clock { positive, marked } => {
+        let (first, second) = if positive { (high, low) } else { (low, high) };
+        let mut points = vec![];
+        if let Some(level) = level {
+          points.push((x, level));
+        }
+        for i in 0..cycles {
+          let start = x + cycle * i as f64;
+          points.push((start, first));
+          points.push((start + cycle / 2.0, first));
+          points.push((start + cycle / 2.0, second));
+          points.push((start + cycle, second));
+        }
+        polyline(svg, &points);
+        if marked {
+          svg.push_str(&format!(
+            "<path d=\"M{},{} l-3,5 h6 z\" fill=\"#000\"/>\n",
+            if positive { x } else { x + cycle / 2.0 },
+            top + SIGNAL / 2.0 - 2.0
+          ));
+        }
+        level = Some(second);
+      }
+      State::Low | State::High => {
+        let y = if state == State::Low { low } else { high };
+        let mut points = vec![];
+        if let Some(level) = level {
+          points.push((x, level));
+        }
+        points.push((x, y));
+        points.push((end, y));
+        polyline(svg, &points);
+        level = Some(y);
+      }
+      State::HighImpedance => {
+        let y = top + SIGNAL / 2.0;
+        polyline(svg, &[(x, y), (end, y)]);
+        level = Some(y);
+      }
+      State::Undefined | State::Data(_) => {
+        let fill = match state {
+          State::Data(index) => FILLS[index],
+          _ => "#cccccc",
+        };
+        let slope = 3.0;
+        svg.push_str(&format!(
+          "<path d=\"M{},{} L{},{} L{},{} L{},{} L{},{} L{},{} z\" \
+           fill=\"{}\" stroke=\"#000\"/>\n",
+          x,
+          top + SIGNAL / 2.0,
+          x + slope,
+          high,
+          end - slope,
+          high,
+          end,
+          top + SIGNAL / 2.0,
+          end - slope,
+          low,
+          x + slope,
+          low,
+          fill
+        ));
+        if let (State::Data(_), Some(label)) = (state, labels.next()) {
+          svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+            (x + end) / 2.0,
+            top + SIGNAL / 2.0 + 4.0,
+            escape(label)
+          ));
+        }
+        level = None;
+      }
+    }
+    x = end;
+  }
+}
+
+fn polyline(svg: &mut String, points: &[(f64, f64)]) {
+  let points = points
+    .iter()
+    .map(|(x, y)| format!("{},{}", x, y))
+    .collect::<Vec<_>>();
+  svg.push_str(&format!(
+    "<polyline points=\"{}\" fill=\"none\" stroke=\"#000\"/>\n",
+    points.join(" ")
+  ));
+}
@@ -0,0 +1,166 @@
+use crate::events::Events;
+use crate::links::resolve;
+use crate::util::encode;
+use anyhow::{anyhow, Result};
+use lol_html::html_content::Element;
+use lol_html::{ElementContentHandlers, HtmlRewriter, Selector, Settings};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The file, in a notebook's `.notebook` directory, of webmentions sent
+const SENT: &str = "webmentions.json";
+
+/// Fetch the mentions of a page from a JSON endpoint, such as webmention.io
+///
+/// Any `{url}` in the endpoint is replaced with the page's URL. Endpoints
+/// that can not be fetched give no mentions, with a warning.
+pub fn mentions(endpoint: &str, url: Option<&str>, events: &Events) -> Value {
+  let endpoint = match (endpoint.contains("{url}"), url) {
+    (true, Some(url)) => endpoint.replace("{url}", &encode(url)),
+    (true, None) => return Value::Null,
+    (false, _) => endpoint.to_string(),
+  };
+  let result =
+    curl(&["-sSfL", "--max-time", "10", &endpoint]).and_then(|body| {
+      serde_json::from_slice(&body)
+        .map_err(|err| anyhow!("Invalid mentions from '{}': {}", endpoint, err))
+    });
+  match result {
+    Ok(mentions) => mentions,
+    Err(err) => {
+      events.warn(&err.to_string());
+      Value::Null
+    }
+  }
+}
+
+/// Send webmentions for the links of a page published at `source`
+///
+/// Targets are sent one mention each, recorded in `.notebook/webmentions.json`
+/// so later builds only mention links that are new.
+pub fn send(
+  dir: &Path,
+  source: &str,
+  html: &str,
+  events: &Events,
+) -> Result<()> {
+  let path = dir.join(".notebook").join(SENT);
+  let mut sent: Map<String, Value> = fs::read_to_string(&path)
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default();
+  let done = sent.get(source).and_then(Value::as_array).cloned();
+  let mut done = done.unwrap_or_default();
+
+  for target in links(html, "a[href]", "href") {
+    let target = resolve(source, &target);
+    let external =
+      target.starts_with("https://") || target.starts_with("http://");
+    if !external
+      || target == source
+      || done.contains(&Value::from(target.as_str()))
+    {
+      continue;
+    }
+    let endpoint = match endpoint(&target) {
+      Some(endpoint) => endpoint,
+      None => continue,
+    };
+    let result = curl(&[
+      "-sSf",
+      "--max-time",
+      "10",
+      "--data-urlencode",
+      &format!("source={}", source),
+      "--data-urlencode",
+      &format!("target={}", target),
+      &endpoint,
+    ]);
+    match result {
+      Ok(_) => {
+        events.info(&format!("Sent webmention for '{}'", target));
+        done.push(target.into());
+      }
+      Err(err) => events.warn(&err.to_string()),
+    }
+  }
+
+  sent.insert(source.into(), Value::Array(done));
+  fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+  fs::write(path, serde_json::to_string_pretty(&sent)?)?;
+  Ok(())
+}
+
+/// Discover the webmention endpoint of a page, from its `Link` headers or
+/// its `rel="webmention"` links
+fn endpoint(target: &str) -> Option<String> {
+  let response = curl(&["-sSL", "--max-time", "10", "-i", target]).ok()?;
+  let response = String::from_utf8_lossy(&response);
+  // Redirects give several sets of headers, the last set before the body
+  let (head, body) = match response.rfind("\r\n\r\nHTTP/") {
+    Some(start) => response[start + 4..].split_once("\r\n\r\n"),
+    None => response.split_once("\r\n\r\n"),
+  }
+  .unwrap_or((&response, ""));
+
+  let header = head.lines().find_map(|line| {
+    let (name, value) = line.split_once(':')?;
+    if !name.eq_ignore_ascii_case("link") {
+      return None;
+    }
+    value.split(',').find_map(|link| {
+      let (url, params) = link.split_once(';')?;
+      let rel = params.split(';').any(|param| {
+        let param = param.trim().replace('"', "");
+        param.strip_prefix("rel=").is_some_and(|rel| {
+          rel.split_whitespace().any(|rel| rel == "webmention")
+        })
+      });
+      let url = url.trim().trim_start_matches('<').trim_end_matches('>');
+      rel.then(|| url.to_string())
+    })
+  });
+  let endpoint = header.or_else(|| {
+    let selector = "link[rel~=\"webmention\"], a[rel~=\"webmention\"]";
+    links(body, selector, "href").into_iter().next()
+  })?;
+  Some(resolve(target, &endpoint))
+}
+
+/// The values of an attribute of the elements matching a selector
+fn links(html: &str, selector: &str, attribute: &str) -> Vec<String> {
+  let found = RefCell::new(vec![]);
+  let selector = selector.parse::<Selector>().expect("Valid selector");
+  let handlers =
+    ElementContentHandlers::default().element(|el: &mut Element| {
+      if let Some(value) = el.get_attribute(attribute) {
+        found.borrow_mut().push(value);
+      }
+      Ok(())
+    });
+  let settings = Settings::new()
+    .append_element_content_handler((Cow::Owned(selector), handlers));
+  let mut rewriter = HtmlRewriter::new(settings, |_: &[u8]| {});
+  match rewriter.write(html.as_bytes()) {
+    Ok(()) => drop(rewriter.end()),
+    Err(_) => drop(rewriter),
+  }
+  found.into_inner()
+}
+
+fn curl(args: &[&str]) -> Result<Vec<u8>> {
+  let output = Command::new("curl")
+    .args(args)
+    .output()
+    .map_err(|err| anyhow!("Failed to run 'curl': {}", err))?;
+  if !output.status.success() {
+    let url = args.last().unwrap_or(&"");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(anyhow!("Request to '{}' failed: {}", url, stderr.trim()));
+  }
+  Ok(output.stdout)
+}
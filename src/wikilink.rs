@@ -0,0 +1,139 @@
+use crate::code;
+use crate::util::{encode, escape, slugify};
+use crate::vfs::Source;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const OPEN: &str = "[[";
+const CLOSE: &str = "]]";
+
+/// Wiki style links between the notes of a directory, `[[Page Name]]`,
+/// `[[Page Name|label]]` or `[[Page Name#Heading]]`
+///
+/// Page names are matched to notes by their slug, so `[[Page Name]]` links to
+/// `page-name.md` as `page-name.html`. Links to notes that don't exist yet are
+/// given the `missing` class.
+pub struct WikiLinks {
+  /// The file stems of notes by their slugs
+  notes: BTreeMap<String, String>,
+}
+
+impl WikiLinks {
+  /// Index the notes of a directory, not those of its children
+  pub fn collect(source: &dyn Source, dir: &Path) -> Result<WikiLinks> {
+    let mut notes = BTreeMap::new();
+    for path in source.files(dir)? {
+      let is_markdown =
+        path.extension().and_then(|ext| ext.to_str()) == Some("md");
+      if !is_markdown || path.parent() != Some(dir) {
+        continue;
+      }
+      if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+        notes
+          .entry(slugify(stem))
+          .or_insert_with(|| stem.to_string());
+      }
+    }
+    Ok(WikiLinks { notes })
+  }
+
+  /// Replace wiki links outside of code with links to their notes
+  pub fn link(&self, input: &str) -> String {
+    replace_links(input, |target| Some(self.anchor(target)))
+  }
+
+  /// The file names of the notes that the wiki links of some text link to
+  pub fn targets(&self, input: &str) -> Vec<String> {
+    let mut targets = vec![];
+    replace_links(input, |target| {
+      let target = target.split('|').next().unwrap_or_default();
+      let page = target.split('#').next().unwrap_or_default().trim();
+      if let Some(stem) = self.find(page) {
+        targets.push(format!("{}.md", stem));
+      }
+      None
+    });
+    targets
+  }
+
+  /// The file stem of the note a page name links to, if it exists
+  pub fn find(&self, page: &str) -> Option<&str> {
+    let slug = slugify(page.trim_end_matches(".md"));
+    self.notes.get(&slug).map(String::as_str)
+  }
+
+  /// The HTML link of a wiki link's target
+  fn anchor(&self, target: &str) -> String {
+    let (page, heading, label) = parts(target);
+    let label = match (label, page) {
+      (Some(label), _) => label,
+      (None, "") => heading.unwrap_or_default(),
+      (None, _) => target.split('|').next().unwrap_or_default().trim(),
+    };
+    // `[[#Heading]]` links to a section of the same note
+    let (class, mut href) = match page {
+      "" => ("wikilink", String::new()),
+      page => match self.find(page) {
+        Some(stem) => ("wikilink", format!("{}.html", encode(stem))),
+        None => {
+          let slug = slugify(page.trim_end_matches(".md"));
+          ("wikilink missing", format!("{}.html", slug))
+        }
+      },
+    };
+    if let Some(heading) = heading {
+      href.push('#');
+      href.push_str(&slugify(heading));
+    }
+    format!(
+      "<a class=\"{}\" href=\"{}\">{}</a>",
+      class,
+      escape(&href),
+      escape(label)
+    )
+  }
+}
+
+/// Replace each `[[target]]` outside of code that `replace` resolves
+pub fn replace_links<F>(input: &str, mut replace: F) -> String
+where
+  F: FnMut(&str) -> Option<String>,
+{
+  code::map_prose(input, |text| {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+      let reference = &rest[start + OPEN.len()..];
+      let end = match reference.find(CLOSE) {
+        Some(end) if !reference[..end].trim().is_empty() => end,
+        _ => {
+          output.push_str(&rest[..start + OPEN.len()]);
+          rest = reference;
+          continue;
+        }
+      };
+      let after = &reference[end + CLOSE.len()..];
+      output.push_str(&rest[..start]);
+      match replace(&reference[..end]) {
+        Some(link) => output.push_str(&link),
+        None => output.push_str(&rest[start..rest.len() - after.len()]),
+      }
+      rest = after;
+    }
+    output.push_str(rest);
+    output
+  })
+}
+
+/// The page, heading and label of a wiki link's target, `Page#Heading|label`
+pub fn parts(target: &str) -> (&str, Option<&str>, Option<&str>) {
+  let (target, label) = match target.split_once('|') {
+    Some((target, label)) => (target.trim(), Some(label.trim())),
+    None => (target.trim(), None),
+  };
+  match target.split_once('#') {
+    Some((page, heading)) => (page.trim(), Some(heading.trim()), label),
+    None => (target, None, label),
+  }
+}